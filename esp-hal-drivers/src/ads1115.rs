@@ -0,0 +1,499 @@
+//! # ads1115
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Texas Instruments
+//! ADS1115 16-bit and ADS1015 12-bit ADCs over I2C.
+//!
+//! The ADCs have four inputs, measured either against ground or differentially
+//! through a multiplexer, a programmable gain amplifier and a comparator that
+//! can assert the ALERT/RDY pin when a conversion crosses a threshold.
+//!
+//! Like the [MCP3428](crate::mcp3428), the ADC implements
+//! [AsyncAdcChannel], so that helpers such as
+//! [BatteryMonitor](crate::battery::BatteryMonitor) work with either.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let config = Config::new()
+//!     .with_mux(Mux::Single0)
+//!     .with_gain(Gain::Fsr4_096)
+//!     .with_data_rate(DataRate::Sps250);
+//! let mut adc = Ads1115::new(i2c, ADDRESS_GND, Model::Ads1115, config);
+//!
+//! // Take a single measurement
+//! let voltage = adc.one_shot_measurement().await?;
+//! println!("{} mV", voltage.0);
+//!
+//! // Read another input through the same abstraction as the MCP3428
+//! let mut battery = BatteryMonitor::new(adc.channel(Mux::Single2), 5.545, Chemistry::LiIon);
+//! let reading = battery.read().await?;
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use crate::{units::Millivolts, AsyncAdcChannel};
+
+/// I2C address of the ADC when ADDR is tied to ground
+pub const ADDRESS_GND: u8 = 0x48;
+
+/// I2C address of the ADC when ADDR is tied to the supply
+pub const ADDRESS_VDD: u8 = 0x49;
+
+/// I2C address of the ADC when ADDR is tied to SDA
+pub const ADDRESS_SDA: u8 = 0x4A;
+
+/// I2C address of the ADC when ADDR is tied to SCL
+pub const ADDRESS_SCL: u8 = 0x4B;
+
+/// Extra time allowed for a conversion before giving up
+const TIMEOUT_MARGIN: Duration = Duration::from_millis(10);
+
+/// Time between two polls of a conversion
+const POLL_INTERVAL: Duration = Duration::from_micros(200);
+
+/// Registers of the ADC. See datasheet section 8.6 for more details.
+struct Register;
+
+impl Register {
+    const CONVERSION: u8 = 0x00;
+    const CONFIG: u8 = 0x01;
+    const LOW_THRESHOLD: u8 = 0x02;
+    const HIGH_THRESHOLD: u8 = 0x03;
+}
+
+/// Flag of the configuration register starting a conversion when written, or
+/// set when no conversion is in progress when read
+const CONFIG_OS: u16 = 1 << 15;
+
+/// Flag of the configuration register selecting the single-shot mode
+const CONFIG_SINGLE_SHOT: u16 = 1 << 8;
+
+/// Model of the ADC
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Model {
+    /// 12-bit, up to 3300 samples per second
+    Ads1015,
+    /// 16-bit, up to 860 samples per second
+    Ads1115,
+}
+
+/// Configuration of the ADC
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    mux: Mux,
+    gain: Gain,
+    data_rate: DataRate,
+    comparator: Comparator,
+}
+
+impl Config {
+    /// Create a new configuration with the power-on defaults of the ADC.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_mux(mut self, mux: Mux) -> Self {
+        self.mux = mux;
+        self
+    }
+
+    pub fn with_gain(mut self, gain: Gain) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    pub fn with_data_rate(mut self, data_rate: DataRate) -> Self {
+        self.data_rate = data_rate;
+        self
+    }
+
+    pub fn with_comparator(mut self, comparator: Comparator) -> Self {
+        self.comparator = comparator;
+        self
+    }
+
+    /// Value of the configuration register in a given mode
+    fn bits(&self, mode: Mode) -> u16 {
+        let mode = match mode {
+            Mode::Continuous => 0,
+            Mode::SingleShot => CONFIG_SINGLE_SHOT,
+        };
+        ((self.mux.bits() as u16) << 12)
+            | ((self.gain.bits() as u16) << 9)
+            | mode
+            | ((self.data_rate.bits() as u16) << 5)
+            | self.comparator.bits() as u16
+    }
+}
+
+/// Input multiplexer configuration
+///
+/// Defaults to `Differential0_1`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mux {
+    /// AIN0 against AIN1
+    #[default]
+    Differential0_1 = 0b000,
+    /// AIN0 against AIN3
+    Differential0_3 = 0b001,
+    /// AIN1 against AIN3
+    Differential1_3 = 0b010,
+    /// AIN2 against AIN3
+    Differential2_3 = 0b011,
+    /// AIN0 against ground
+    Single0 = 0b100,
+    /// AIN1 against ground
+    Single1 = 0b101,
+    /// AIN2 against ground
+    Single2 = 0b110,
+    /// AIN3 against ground
+    Single3 = 0b111,
+}
+
+impl Mux {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Full-scale range of the programmable gain amplifier, in V
+///
+/// The inputs must stay within the supply regardless of the range.
+/// Defaults to `Fsr2_048`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Gain {
+    Fsr6_144 = 0b000,
+    Fsr4_096 = 0b001,
+    #[default]
+    Fsr2_048 = 0b010,
+    Fsr1_024 = 0b011,
+    Fsr0_512 = 0b100,
+    Fsr0_256 = 0b101,
+}
+
+impl Gain {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Return the full-scale range in µV.
+    pub fn full_scale_uv(&self) -> i64 {
+        match self {
+            Gain::Fsr6_144 => 6_144_000,
+            Gain::Fsr4_096 => 4_096_000,
+            Gain::Fsr2_048 => 2_048_000,
+            Gain::Fsr1_024 => 1_024_000,
+            Gain::Fsr0_512 => 512_000,
+            Gain::Fsr0_256 => 256_000,
+        }
+    }
+}
+
+/// Data rate in samples per second
+///
+/// The variants are named after the rates of the ADS1115. The ADS1015 runs
+/// the same setting at 128, 250, 490, 920, 1600, 2400, 3300 and 3300 samples
+/// per second respectively. Defaults to `Sps128`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DataRate {
+    Sps8 = 0b000,
+    Sps16 = 0b001,
+    Sps32 = 0b010,
+    Sps64 = 0b011,
+    #[default]
+    Sps128 = 0b100,
+    Sps250 = 0b101,
+    Sps475 = 0b110,
+    Sps860 = 0b111,
+}
+
+impl DataRate {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Return the number of samples per second on a given model.
+    pub fn samples_per_second(&self, model: Model) -> u32 {
+        const ADS1015: [u32; 8] = [128, 250, 490, 920, 1600, 2400, 3300, 3300];
+        const ADS1115: [u32; 8] = [8, 16, 32, 64, 128, 250, 475, 860];
+        match model {
+            Model::Ads1015 => ADS1015[self.bits() as usize],
+            Model::Ads1115 => ADS1115[self.bits() as usize],
+        }
+    }
+}
+
+/// Configuration of the comparator driving the ALERT/RDY pin
+///
+/// Disabled by default.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Comparator {
+    /// Whether the comparator checks a window between the thresholds rather
+    /// than hysteresis between them
+    pub window: bool,
+    /// Whether the ALERT/RDY pin is active high
+    pub active_high: bool,
+    /// Whether the ALERT/RDY pin stays asserted until the conversion is read
+    pub latching: bool,
+    /// Number of consecutive conversions crossing a threshold before the pin
+    /// is asserted
+    pub queue: ComparatorQueue,
+}
+
+impl Comparator {
+    fn bits(&self) -> u8 {
+        ((self.window as u8) << 4)
+            | ((self.active_high as u8) << 3)
+            | ((self.latching as u8) << 2)
+            | self.queue.bits()
+    }
+}
+
+/// Number of conversions crossing a threshold before the comparator asserts
+/// the ALERT/RDY pin
+///
+/// Defaults to `Disabled`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ComparatorQueue {
+    One = 0b00,
+    Two = 0b01,
+    Four = 0b10,
+    #[default]
+    Disabled = 0b11,
+}
+
+impl ComparatorQueue {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Conversion mode of the ADC
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Mode {
+    Continuous,
+    SingleShot,
+}
+
+/// An ADS1115 or ADS1015 on an I2C bus
+pub struct Ads1115<I2C> {
+    address: u8,
+    i2c: I2C,
+    model: Model,
+    config: Config,
+    mode: Mode,
+}
+
+impl<I2C: I2c> Ads1115<I2C> {
+    /// Create a new ADC. The configuration is written on the next conversion.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the ADC is on.
+    /// - `address`: The I2C address of the ADC, e.g. [ADDRESS_GND].
+    /// - `model`: The model of the ADC.
+    /// - `config`: The configuration of the ADC.
+    pub fn new(i2c: I2C, address: u8, model: Model, config: Config) -> Self {
+        Self {
+            address,
+            i2c,
+            model,
+            config,
+            mode: Mode::SingleShot,
+        }
+    }
+
+    /// Get the current configuration of the ADC
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Set the configuration of the ADC. Written immediately in continuous
+    /// mode, or on the next conversion otherwise.
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
+        self.config = config;
+        if self.mode == Mode::Continuous {
+            self.write_config(0).await?;
+        }
+        Ok(())
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Start a single conversion and wait for its result. The ADC powers
+    /// down once the conversion is complete.
+    pub async fn one_shot_measurement(&mut self) -> Result<Millivolts, Error<I2C::Error>> {
+        self.mode = Mode::SingleShot;
+        self.write_config(CONFIG_OS).await?;
+
+        let rate = self.config.data_rate.samples_per_second(self.model);
+        let conversion_time = Duration::from_micros(1_000_000 / rate as u64);
+        Timer::after(conversion_time).await;
+
+        // Poll until the conversion is complete
+        let poll = async {
+            loop {
+                if self.read_register(Register::CONFIG).await? & CONFIG_OS != 0 {
+                    return Ok(());
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        };
+        with_timeout(conversion_time + TIMEOUT_MARGIN, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))?;
+
+        self.get_measurement().await
+    }
+
+    /// Let the ADC convert continuously at its data rate.
+    pub async fn start_continuous(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.mode = Mode::Continuous;
+        self.write_config(0).await
+    }
+
+    /// Read the result of the last conversion.
+    pub async fn get_measurement(&mut self) -> Result<Millivolts, Error<I2C::Error>> {
+        let raw = self.read_register(Register::CONVERSION).await? as i16;
+        Ok(Millivolts(self.code_millivolts(raw)))
+    }
+
+    /// Set the thresholds of the comparator.
+    ///
+    /// The thresholds are expressed at the input, so they must be set again
+    /// when the gain changes.
+    pub async fn set_thresholds(
+        &mut self,
+        low: Millivolts,
+        high: Millivolts,
+    ) -> Result<(), Error<I2C::Error>> {
+        let low = self.millivolts_code(low);
+        let high = self.millivolts_code(high);
+        self.write_register(Register::LOW_THRESHOLD, low as u16)
+            .await?;
+        self.write_register(Register::HIGH_THRESHOLD, high as u16)
+            .await
+    }
+
+    /// Configure the ALERT/RDY pin to pulse at the end of every conversion
+    /// instead of acting as a comparator output.
+    pub async fn enable_ready_pin(&mut self) -> Result<(), Error<I2C::Error>> {
+        // The pin signals conversions when the MSB of the high threshold is
+        // set and the MSB of the low threshold is cleared
+        self.write_register(Register::LOW_THRESHOLD, 0x0000).await?;
+        self.write_register(Register::HIGH_THRESHOLD, 0x8000)
+            .await?;
+        if let ComparatorQueue::Disabled = self.config.comparator.queue {
+            self.config.comparator.queue = ComparatorQueue::One;
+        }
+        if self.mode == Mode::Continuous {
+            self.write_config(0).await?;
+        }
+        Ok(())
+    }
+
+    /// Get a handle to a single input of the ADC, implementing
+    /// [AsyncAdcChannel].
+    pub fn channel(&mut self, mux: Mux) -> Ads1115Channel<'_, I2C> {
+        Ads1115Channel { adc: self, mux }
+    }
+
+    /// Convert a conversion code into mV at the input. The 12-bit results of
+    /// the ADS1015 are left-justified, so both models share the same scale.
+    fn code_millivolts(&self, code: i16) -> i32 {
+        (code as i64 * self.config.gain.full_scale_uv() / 32_768 / 1000) as i32
+    }
+
+    fn millivolts_code(&self, voltage: Millivolts) -> i16 {
+        let code = voltage.0 as i64 * 1000 * 32_768 / self.config.gain.full_scale_uv();
+        code.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+    }
+
+    async fn write_config(&mut self, flags: u16) -> Result<(), Error<I2C::Error>> {
+        let bits = self.config.bits(self.mode) | flags;
+        self.write_register(Register::CONFIG, bits).await
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u16, Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.i2c
+            .write_read(self.address, &[register], &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    async fn write_register(&mut self, register: u8, value: u16) -> Result<(), Error<I2C::Error>> {
+        let [msb, lsb] = value.to_be_bytes();
+        self.i2c
+            .write(self.address, &[register, msb, lsb])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+impl<I2C: I2c> AsyncAdcChannel for Ads1115<I2C> {
+    type Error = Error<I2C::Error>;
+
+    /// Read the last conversion in continuous mode, or trigger a single
+    /// conversion otherwise.
+    async fn read_millivolts(&mut self) -> Result<Millivolts, Self::Error> {
+        match self.mode {
+            Mode::Continuous => self.get_measurement().await,
+            Mode::SingleShot => self.one_shot_measurement().await,
+        }
+    }
+}
+
+/// A single input of an [Ads1115]
+///
+/// Created by [Ads1115::channel].
+pub struct Ads1115Channel<'a, I2C> {
+    adc: &'a mut Ads1115<I2C>,
+    mux: Mux,
+}
+
+impl<I2C: I2c> AsyncAdcChannel for Ads1115Channel<'_, I2C> {
+    type Error = Error<I2C::Error>;
+
+    /// Trigger a single conversion on the input. The input previously
+    /// selected on the ADC is restored afterwards, but continuous mode is
+    /// left.
+    async fn read_millivolts(&mut self) -> Result<Millivolts, Self::Error> {
+        let mux = self.adc.config.mux;
+        self.adc.config.mux = self.mux;
+        let voltage = self.adc.one_shot_measurement().await;
+        self.adc.config.mux = mux;
+        voltage
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The ADC did not complete a conversion in time.
+    Timeout,
+}
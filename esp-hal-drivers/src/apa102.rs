@@ -0,0 +1,140 @@
+//! # apa102
+//!
+//! ## Overview
+//!
+//! This driver sends colors to APA102 (DotStar) and SK9822 addressable LEDs
+//! over an SPI bus, with the data line on MOSI and the clock line on SCK.
+//!
+//! Each LED has a 5-bit global brightness in hardware, applied by dimming the
+//! LED with a slow PWM. It is kept separate from the colors, so dimming the
+//! strip does not lose color resolution. Colors can also be gamma-corrected
+//! before they are sent.
+//!
+//! The strip shares the [LedStrip] trait of the [WS2812](crate::ws2812), so a
+//! [PixelBuffer](crate::led::PixelBuffer) shows on either.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let spi = Spi::new(peripherals.SPI2, Config::default().with_frequency(4.MHz()))
+//!     .unwrap()
+//!     .with_sck(peripherals.GPIO6)
+//!     .with_mosi(peripherals.GPIO7)
+//!     .into_async();
+//! let mut strip = Apa102::new(spi).with_brightness(8);
+//!
+//! strip.write(&[Rgb8::new(255, 0, 0), Rgb8::new(0, 255, 0)]).await?;
+//!
+//! // Or keep the colors of the strip in a buffer
+//! let mut pixels = PixelBuffer::<16>::new();
+//! pixels.fill(Rgb8::new(0, 0, 255));
+//! pixels.show(&mut strip).await?;
+//! ```
+
+use embedded_hal_async::spi::SpiBus;
+
+use crate::led::{LedStrip, Rgb8};
+
+/// Maximum global brightness
+pub const MAX_BRIGHTNESS: u8 = 31;
+
+/// Marker of the first byte of a LED frame, followed by the brightness
+const LED_FRAME: u8 = 0xE0;
+
+/// Frame preceding the LED frames
+const START_FRAME: [u8; 4] = [0x00; 4];
+
+/// Zeros following the LED frames to latch the colors of the SK9822
+const RESET_LEN: usize = 4;
+
+/// LEDs encoded per SPI write
+const CHUNK: usize = 16;
+
+/// A strip of APA102 or SK9822 LEDs on an SPI bus
+pub struct Apa102<SPI> {
+    spi: SPI,
+    brightness: u8,
+    gamma: bool,
+}
+
+impl<SPI: SpiBus> Apa102<SPI> {
+    /// Create a new strip, at full brightness.
+    ///
+    /// # Arguments
+    ///
+    /// - `spi`: The SPI bus the strip is on, in mode 0. The LEDs handle
+    ///   clocks up to a few MHz over short strips.
+    pub fn new(spi: SPI) -> Self {
+        Self {
+            spi,
+            brightness: MAX_BRIGHTNESS,
+            gamma: true,
+        }
+    }
+
+    /// Set the global brightness, from 0 to [MAX_BRIGHTNESS].
+    pub fn with_brightness(mut self, brightness: u8) -> Self {
+        self.set_brightness(brightness);
+        self
+    }
+
+    /// Enable or disable gamma correction, enabled by default.
+    pub fn with_gamma(mut self, gamma: bool) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Set the global brightness, from 0 to [MAX_BRIGHTNESS].
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness.min(MAX_BRIGHTNESS);
+    }
+
+    /// Release the underlying SPI bus
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Send colors to the strip, starting from its first LED.
+    pub async fn write(&mut self, pixels: &[Rgb8]) -> Result<(), SPI::Error> {
+        self.spi.write(&START_FRAME).await?;
+
+        let mut frames = [0u8; 4 * CHUNK];
+        for chunk in pixels.chunks(CHUNK) {
+            for (pixel, frame) in chunk.iter().zip(frames.as_chunks_mut::<4>().0) {
+                frame.copy_from_slice(&self.encode(*pixel));
+            }
+            self.spi.write(&frames[..4 * chunk.len()]).await?;
+        }
+
+        // Each LED delays the data by half a clock, so the last LEDs need an
+        // extra clock per two LEDs. Zeros are sent rather than ones, so that
+        // LEDs beyond the strip are not lit.
+        frames.fill(0);
+        let mut end = RESET_LEN + pixels.len().div_ceil(16);
+        while end > 0 {
+            let len = end.min(frames.len());
+            self.spi.write(&frames[..len]).await?;
+            end -= len;
+        }
+
+        self.spi.flush().await
+    }
+
+    /// Encode a color in a LED frame, blue first.
+    fn encode(&self, pixel: Rgb8) -> [u8; 4] {
+        let pixel = if self.gamma {
+            pixel.gamma_corrected()
+        } else {
+            pixel
+        };
+        [LED_FRAME | self.brightness, pixel.b, pixel.g, pixel.r]
+    }
+}
+
+impl<SPI: SpiBus> LedStrip for Apa102<SPI> {
+    type Error = SPI::Error;
+
+    async fn write(&mut self, pixels: &[Rgb8]) -> Result<(), Self::Error> {
+        Apa102::write(self, pixels).await
+    }
+}
@@ -0,0 +1,653 @@
+//! # apds9960
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Broadcom APDS9960
+//! proximity, color and gesture sensor over I2C.
+//!
+//! - The proximity engine measures the infrared light of its LED reflected by
+//!   a nearby object, from 0 (far) to 255 (close).
+//! - The color engine measures the clear, red, green and blue light.
+//! - The gesture engine starts when an object comes close, and tracks its
+//!   reflection on four photodiodes to tell in which direction it swiped.
+//!
+//! The sensor pulls its INT pin low when the proximity or the clear light
+//! leaves a range, or when a gesture is in progress. Once a GPIO is attached
+//! with [Apds9960::with_interrupt_pin], [Apds9960::wait_for_event] awaits
+//! these on a GPIO interrupt and returns them as a stream of [Event]s.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut apds9960 = Apds9960::new(i2c, Config::new())
+//!     .await?
+//!     .with_interrupt_pin(Input::new(peripherals.GPIO5, Pull::Up));
+//!
+//! apds9960.enable_gesture(true).await?;
+//! // Also report when something comes close
+//! apds9960.set_proximity_interrupt(Some((0, 200))).await?;
+//!
+//! loop {
+//!     match apds9960.wait_for_event().await? {
+//!         Event::Gesture(gesture) => println!("Swiped {:?}", gesture),
+//!         Event::Proximity(proximity) => println!("Proximity {}", proximity),
+//!         Event::Color(color) => println!("{:?}", color),
+//!     }
+//! }
+//! ```
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::{digital::Wait, i2c::I2c};
+
+/// I2C address of the sensor
+pub const ADDRESS: u8 = 0x39;
+
+/// Values of the ID register, the first one being the genuine part
+const DEVICE_IDS: [u8; 3] = [0xAB, 0x9C, 0xA8];
+
+/// Time of one cycle of the color engine, in µs
+const COLOR_CYCLE_US: u64 = 2780;
+
+/// Time between two reads of the gesture FIFO while a gesture is in
+/// progress
+const GESTURE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Size of a gesture FIFO dataset, the up, down, left and right photodiodes
+const GESTURE_DATASET_SIZE: usize = 4;
+
+/// Number of datasets in the gesture FIFO
+const GESTURE_FIFO_LEN: usize = 32;
+
+/// Minimum value of every photodiode for a dataset to be tracked
+const GESTURE_MIN_LEVEL: u8 = 10;
+
+/// Minimum change of the balance between two opposite photodiodes, in
+/// percent, to count as a swipe
+const GESTURE_SENSITIVITY: i32 = 50;
+
+/// Registers of the sensor. See datasheet section "Register Set" for more
+/// details.
+struct Register;
+
+impl Register {
+    const ENABLE: u8 = 0x80;
+    const ATIME: u8 = 0x81;
+    const WTIME: u8 = 0x83;
+    const AILTL: u8 = 0x84;
+    const PILT: u8 = 0x89;
+    const PERS: u8 = 0x8C;
+    const CONFIG1: u8 = 0x8D;
+    const PPULSE: u8 = 0x8E;
+    const CONTROL: u8 = 0x8F;
+    const CONFIG2: u8 = 0x90;
+    const ID: u8 = 0x92;
+    const STATUS: u8 = 0x93;
+    const CDATAL: u8 = 0x94;
+    const PDATA: u8 = 0x9C;
+    const GPENTH: u8 = 0xA0;
+    const GEXTH: u8 = 0xA1;
+    const GCONF1: u8 = 0xA2;
+    const GCONF2: u8 = 0xA3;
+    const GPULSE: u8 = 0xA6;
+    const GCONF4: u8 = 0xAB;
+    const GFLVL: u8 = 0xAE;
+    const GSTATUS: u8 = 0xAF;
+    const PICLEAR: u8 = 0xE5;
+    const CICLEAR: u8 = 0xE6;
+    const AICLEAR: u8 = 0xE7;
+    const GFIFO_U: u8 = 0xFC;
+}
+
+/// Flags of the enable register
+const ENABLE_POWER_ON: u8 = 1 << 0;
+const ENABLE_COLOR: u8 = 1 << 1;
+const ENABLE_PROXIMITY: u8 = 1 << 2;
+const ENABLE_COLOR_INTERRUPT: u8 = 1 << 4;
+const ENABLE_PROXIMITY_INTERRUPT: u8 = 1 << 5;
+const ENABLE_GESTURE: u8 = 1 << 6;
+
+/// Flags of the status register
+const STATUS_GESTURE_INTERRUPT: u8 = 1 << 2;
+const STATUS_COLOR_INTERRUPT: u8 = 1 << 4;
+const STATUS_PROXIMITY_INTERRUPT: u8 = 1 << 5;
+
+/// Flags of the gesture config 4 register
+const GCONF4_MODE: u8 = 1 << 0;
+const GCONF4_INTERRUPT: u8 = 1 << 1;
+const GCONF4_FIFO_CLEAR: u8 = 1 << 2;
+
+/// Flag of the gesture status register
+const GSTATUS_VALID: u8 = 1 << 0;
+
+/// Value of the config 1 register, without the long wait
+const CONFIG1: u8 = 0x60;
+
+/// Value of the config 2 register, without LED boost nor saturation
+/// interrupts
+const CONFIG2: u8 = 0x01;
+
+/// Value of the wait time register, 27.8 ms
+const WTIME: u8 = 0xF6;
+
+/// Value of the persistence register, interrupting on the first measurement
+/// out of range
+const PERS: u8 = 0x11;
+
+/// Value of the proximity pulse register, 8 pulses of 16 µs
+const PPULSE: u8 = (0b10 << 6) | 7;
+
+/// Value of the gesture pulse register, 10 pulses of 32 µs
+const GPULSE: u8 = (0b11 << 6) | 9;
+
+/// Proximity above which the gesture engine starts
+const GESTURE_ENTER_THRESHOLD: u8 = 40;
+
+/// Level of the photodiodes below which the gesture engine stops
+const GESTURE_EXIT_THRESHOLD: u8 = 30;
+
+/// Value of the gesture config 1 register, interrupting once 4 datasets are
+/// in the FIFO
+const GCONF1: u8 = 0b01 << 6;
+
+/// Wait time between two gesture datasets, 2.8 ms
+const GESTURE_WAIT_TIME: u8 = 0b001;
+
+/// Configuration of the sensor
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    led_drive: LedDrive,
+    proximity_gain: ProximityGain,
+    gesture_gain: ProximityGain,
+    color_gain: ColorGain,
+    color_integration_cycles: u16,
+}
+
+impl Default for Config {
+    /// Default configuration of the sensor, with the settings of the
+    /// reference design
+    fn default() -> Self {
+        Self {
+            led_drive: LedDrive::default(),
+            proximity_gain: ProximityGain::default(),
+            gesture_gain: ProximityGain::default(),
+            color_gain: ColorGain::default(),
+            color_integration_cycles: 37,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_led_drive(mut self, led_drive: LedDrive) -> Self {
+        self.led_drive = led_drive;
+        self
+    }
+
+    pub fn with_proximity_gain(mut self, gain: ProximityGain) -> Self {
+        self.proximity_gain = gain;
+        self
+    }
+
+    pub fn with_gesture_gain(mut self, gain: ProximityGain) -> Self {
+        self.gesture_gain = gain;
+        self
+    }
+
+    pub fn with_color_gain(mut self, gain: ColorGain) -> Self {
+        self.color_gain = gain;
+        self
+    }
+
+    /// Set the integration time of the color engine, from 2.78 ms to 712 ms
+    /// in steps of 2.78 ms. Longer times are more sensitive in dim light.
+    pub fn with_color_integration_time(mut self, time: Duration) -> Self {
+        self.color_integration_cycles = (time.as_micros() / COLOR_CYCLE_US).clamp(1, 256) as u16;
+        self
+    }
+}
+
+/// Current through the infrared LED
+///
+/// Defaults to `Ma100`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LedDrive {
+    #[default]
+    Ma100 = 0b00,
+    Ma50 = 0b01,
+    Ma25 = 0b10,
+    Ma12_5 = 0b11,
+}
+
+impl LedDrive {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Gain of the proximity and gesture engines
+///
+/// Defaults to `X4`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProximityGain {
+    X1 = 0b00,
+    X2 = 0b01,
+    #[default]
+    X4 = 0b10,
+    X8 = 0b11,
+}
+
+impl ProximityGain {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Gain of the color engine
+///
+/// Defaults to `X4`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ColorGain {
+    X1 = 0b00,
+    #[default]
+    X4 = 0b01,
+    X16 = 0b10,
+    X64 = 0b11,
+}
+
+impl ColorGain {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// A measurement of the color engine, in counts
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Color {
+    pub clear: u16,
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+}
+
+/// Direction of a swipe over the sensor
+///
+/// The directions are given with the sensor seen from above, its LED at the
+/// top and its pins at the bottom.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Gesture {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// An event reported by the sensor
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The proximity left the range of the proximity interrupt.
+    Proximity(u8),
+    /// The clear light left the range of the color interrupt.
+    Color(Color),
+    /// An object swiped over the sensor.
+    Gesture(Gesture),
+}
+
+/// Tracks the datasets of a gesture to find its direction
+///
+/// The balance between two opposite photodiodes changes sign as an object
+/// swipes from one to the other.
+#[derive(Debug, Default)]
+struct GestureTracker {
+    first: Option<[u8; GESTURE_DATASET_SIZE]>,
+    last: [u8; GESTURE_DATASET_SIZE],
+}
+
+impl GestureTracker {
+    fn add(&mut self, dataset: &[u8]) {
+        if dataset.iter().all(|level| *level > GESTURE_MIN_LEVEL) {
+            let mut levels = [0; GESTURE_DATASET_SIZE];
+            levels.copy_from_slice(dataset);
+            self.first.get_or_insert(levels);
+            self.last = levels;
+        }
+    }
+
+    fn gesture(&self) -> Option<Gesture> {
+        let first = self.first?;
+        // Balances in percent, positive towards up and left
+        let balance = |a: u8, b: u8| (a as i32 - b as i32) * 100 / (a as i32 + b as i32);
+        let up_down = balance(self.last[0], self.last[1]) - balance(first[0], first[1]);
+        let left_right = balance(self.last[2], self.last[3]) - balance(first[2], first[3]);
+
+        if up_down.abs() >= left_right.abs() {
+            match up_down {
+                d if d <= -GESTURE_SENSITIVITY => Some(Gesture::Up),
+                d if d >= GESTURE_SENSITIVITY => Some(Gesture::Down),
+                _ => None,
+            }
+        } else {
+            match left_right {
+                d if d <= -GESTURE_SENSITIVITY => Some(Gesture::Left),
+                d if d >= GESTURE_SENSITIVITY => Some(Gesture::Right),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// An APDS9960 on an I2C bus
+///
+/// `INT` is the pin the interrupt output of the sensor is connected to, if
+/// any.
+pub struct Apds9960<I2C, INT = ()> {
+    i2c: I2C,
+    interrupt: INT,
+    config: Config,
+    /// Shadow of the enable register
+    enable: u8,
+}
+
+impl<I2C: I2c> Apds9960<I2C> {
+    /// Create a new sensor and power it on, with all engines disabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidDeviceId` if the device does not identify as an
+    /// APDS9960.
+    pub async fn new(i2c: I2C, config: Config) -> Result<Self, Error<I2C::Error>> {
+        let mut apds9960 = Self {
+            i2c,
+            interrupt: (),
+            config,
+            enable: 0,
+        };
+
+        let id = apds9960.read_register(Register::ID).await?;
+        if !DEVICE_IDS.contains(&id) {
+            return Err(Error::InvalidDeviceId(id));
+        }
+
+        apds9960.write_register(Register::ENABLE, 0).await?;
+        apds9960.write_register(Register::WTIME, WTIME).await?;
+        apds9960.write_register(Register::PERS, PERS).await?;
+        apds9960.write_register(Register::CONFIG1, CONFIG1).await?;
+        apds9960.write_register(Register::CONFIG2, CONFIG2).await?;
+        apds9960.write_register(Register::PPULSE, PPULSE).await?;
+        apds9960
+            .write_register(Register::GPENTH, GESTURE_ENTER_THRESHOLD)
+            .await?;
+        apds9960
+            .write_register(Register::GEXTH, GESTURE_EXIT_THRESHOLD)
+            .await?;
+        apds9960.write_register(Register::GCONF1, GCONF1).await?;
+        apds9960.write_register(Register::GPULSE, GPULSE).await?;
+        apds9960.set_config(config).await?;
+        apds9960.set_enable(ENABLE_POWER_ON, true).await?;
+        Ok(apds9960)
+    }
+
+    /// Attach the pin the interrupt output of the sensor is connected to.
+    ///
+    /// The output is open-drain and active low, so the pin needs a pull-up.
+    pub fn with_interrupt_pin<P: Wait>(self, interrupt: P) -> Apds9960<I2C, P> {
+        Apds9960 {
+            i2c: self.i2c,
+            interrupt,
+            config: self.config,
+            enable: self.enable,
+        }
+    }
+}
+
+impl<I2C: I2c, P: Wait> Apds9960<I2C, P> {
+    /// Wait for the next event of the enabled interrupts.
+    ///
+    /// Once a gesture starts, the FIFO is read until the object leaves. A
+    /// gesture whose direction is unclear is dropped, and the wait goes on.
+    pub async fn wait_for_event(&mut self) -> Result<Event, Error<I2C::Error>> {
+        loop {
+            self.interrupt
+                .wait_for_low()
+                .await
+                .map_err(|_| Error::Pin)?;
+
+            // Handle one source at a time, the pin stays low until all are
+            // cleared
+            let status = self.read_register(Register::STATUS).await?;
+            if status & STATUS_GESTURE_INTERRUPT != 0 {
+                if let Some(gesture) = self.read_gesture().await? {
+                    return Ok(Event::Gesture(gesture));
+                }
+            } else if status & STATUS_PROXIMITY_INTERRUPT != 0 {
+                let proximity = self.proximity().await?;
+                self.clear_interrupt(Register::PICLEAR).await?;
+                return Ok(Event::Proximity(proximity));
+            } else if status & STATUS_COLOR_INTERRUPT != 0 {
+                let color = self.color().await?;
+                self.clear_interrupt(Register::CICLEAR).await?;
+                return Ok(Event::Color(color));
+            } else {
+                // A source that was disabled in the meantime
+                self.clear_interrupt(Register::AICLEAR).await?;
+            }
+        }
+    }
+
+    /// Release the underlying I2C bus and interrupt pin
+    pub fn release_with_interrupt_pin(self) -> (I2C, P) {
+        (self.i2c, self.interrupt)
+    }
+
+    /// Read the gesture FIFO until the gesture engine stops, and return the
+    /// direction of the gesture.
+    async fn read_gesture(&mut self) -> Result<Option<Gesture>, Error<I2C::Error>> {
+        let mut tracker = GestureTracker::default();
+        let mut buf = [0u8; GESTURE_FIFO_LEN * GESTURE_DATASET_SIZE];
+        loop {
+            let status = self.read_register(Register::GSTATUS).await?;
+            if status & GSTATUS_VALID != 0 {
+                let len =
+                    (self.read_register(Register::GFLVL).await? as usize).min(GESTURE_FIFO_LEN);
+                let bytes = &mut buf[..len * GESTURE_DATASET_SIZE];
+                self.read_registers(Register::GFIFO_U, bytes).await?;
+                for dataset in bytes.as_chunks::<GESTURE_DATASET_SIZE>().0 {
+                    tracker.add(dataset);
+                }
+            }
+
+            let gconf4 = self.read_register(Register::GCONF4).await?;
+            if gconf4 & GCONF4_MODE == 0 {
+                // Clearing the FIFO releases the interrupt
+                self.write_register(Register::GCONF4, GCONF4_INTERRUPT | GCONF4_FIFO_CLEAR)
+                    .await?;
+                return Ok(tracker.gesture());
+            }
+            Timer::after(GESTURE_POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl<I2C: I2c, INT> Apds9960<I2C, INT> {
+    /// Get the current configuration of the sensor
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
+        self.config = config;
+        let atime = (256 - config.color_integration_cycles) as u8;
+        self.write_register(Register::ATIME, atime).await?;
+        let control = (config.led_drive.bits() << 6)
+            | (config.proximity_gain.bits() << 2)
+            | config.color_gain.bits();
+        self.write_register(Register::CONTROL, control).await?;
+        let gconf2 =
+            (config.gesture_gain.bits() << 5) | (config.led_drive.bits() << 3) | GESTURE_WAIT_TIME;
+        self.write_register(Register::GCONF2, gconf2).await
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Enable or disable the proximity engine.
+    pub async fn enable_proximity(&mut self, enabled: bool) -> Result<(), Error<I2C::Error>> {
+        self.set_enable(ENABLE_PROXIMITY, enabled).await
+    }
+
+    /// Enable or disable the color engine.
+    pub async fn enable_color(&mut self, enabled: bool) -> Result<(), Error<I2C::Error>> {
+        self.set_enable(ENABLE_COLOR, enabled).await
+    }
+
+    /// Enable or disable the gesture engine and its interrupt.
+    ///
+    /// The gesture engine starts from a proximity measurement, so enabling
+    /// it also enables the proximity engine.
+    pub async fn enable_gesture(&mut self, enabled: bool) -> Result<(), Error<I2C::Error>> {
+        let gconf4 = if enabled { GCONF4_INTERRUPT } else { 0 };
+        self.write_register(Register::GCONF4, gconf4 | GCONF4_FIFO_CLEAR)
+            .await?;
+        if enabled {
+            self.set_enable(ENABLE_PROXIMITY, true).await?;
+        }
+        self.set_enable(ENABLE_GESTURE, enabled).await
+    }
+
+    /// Set the range of the proximity outside of which the sensor
+    /// interrupts, or disable the interrupt with `None`.
+    ///
+    /// # Arguments
+    ///
+    /// - `range`: The low and high thresholds of the proximity.
+    pub async fn set_proximity_interrupt(
+        &mut self,
+        range: Option<(u8, u8)>,
+    ) -> Result<(), Error<I2C::Error>> {
+        if let Some((low, high)) = range {
+            self.write_registers(Register::PILT, &[low, high]).await?;
+            self.clear_interrupt(Register::PICLEAR).await?;
+        }
+        self.set_enable(ENABLE_PROXIMITY_INTERRUPT, range.is_some())
+            .await
+    }
+
+    /// Set the range of the clear light outside of which the sensor
+    /// interrupts, or disable the interrupt with `None`.
+    ///
+    /// # Arguments
+    ///
+    /// - `range`: The low and high thresholds of the clear channel.
+    pub async fn set_color_interrupt(
+        &mut self,
+        range: Option<(u16, u16)>,
+    ) -> Result<(), Error<I2C::Error>> {
+        if let Some((low, high)) = range {
+            let [low_l, low_h] = low.to_le_bytes();
+            let [high_l, high_h] = high.to_le_bytes();
+            self.write_registers(Register::AILTL, &[low_l, low_h, high_l, high_h])
+                .await?;
+            self.clear_interrupt(Register::CICLEAR).await?;
+        }
+        self.set_enable(ENABLE_COLOR_INTERRUPT, range.is_some())
+            .await
+    }
+
+    /// Read the latest proximity, from 0 (far) to 255 (close).
+    pub async fn proximity(&mut self) -> Result<u8, Error<I2C::Error>> {
+        self.read_register(Register::PDATA).await
+    }
+
+    /// Read the latest measurement of the color engine.
+    pub async fn color(&mut self) -> Result<Color, Error<I2C::Error>> {
+        let mut buf = [0u8; 8];
+        self.read_registers(Register::CDATAL, &mut buf).await?;
+        Ok(Color {
+            clear: u16::from_le_bytes([buf[0], buf[1]]),
+            red: u16::from_le_bytes([buf[2], buf[3]]),
+            green: u16::from_le_bytes([buf[4], buf[5]]),
+            blue: u16::from_le_bytes([buf[6], buf[7]]),
+        })
+    }
+
+    async fn set_enable(&mut self, flag: u8, enabled: bool) -> Result<(), Error<I2C::Error>> {
+        if enabled {
+            self.enable |= flag;
+        } else {
+            self.enable &= !flag;
+        }
+        self.write_register(Register::ENABLE, self.enable).await
+    }
+
+    /// Clear an interrupt by addressing one of the special function
+    /// registers
+    async fn clear_interrupt(&mut self, register: u8) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(ADDRESS, &[register])
+            .await
+            .map_err(Error::I2c)
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.read_registers(register, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn read_registers(
+        &mut self,
+        register: u8,
+        buf: &mut [u8],
+    ) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write_read(ADDRESS, &[register], buf)
+            .await
+            .map_err(Error::I2c)
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<I2C::Error>> {
+        self.write_registers(register, &[value]).await
+    }
+
+    async fn write_registers(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<(), Error<I2C::Error>> {
+        let mut buf = [0u8; 5];
+        buf[0] = register;
+        buf[1..=values.len()].copy_from_slice(values);
+        self.i2c
+            .write(ADDRESS, &buf[..=values.len()])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The interrupt pin could not be read.
+    Pin,
+    /// The device does not identify as an APDS9960.
+    InvalidDeviceId(u8),
+}
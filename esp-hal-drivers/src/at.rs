@@ -0,0 +1,459 @@
+//! # at
+//!
+//! ## Overview
+//!
+//! This module provides an engine to drive modems with AT commands over UART,
+//! such as the SIMCom SIM7000 and SIM800 cellular modems.
+//!
+//! - Commands are sent without their `AT` prefix and their response is read
+//!   until the final result code, with a timeout.
+//! - Information lines of a command are returned to the caller, while
+//!   unsolicited result codes (URC), such as `RING` or `+CMTI: "SM",1`, are
+//!   passed to a handler as they arrive.
+//! - Result codes arriving after the final `OK` of a command, such as
+//!   `CONNECT OK`, can be waited for with [At::wait_for].
+//!
+//! A line starting with `+` is a URC unless it answers the command being
+//! executed, e.g. `+CSQ: 20,0` for `+CSQ`. Lines not starting with `+` are
+//! URCs only when they are known ones, such as `RING` or `CLOSED`.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! fn handle_urc(urc: &[u8]) {
+//!     println!("URC {:?}", core::str::from_utf8(urc));
+//! }
+//!
+//! let mut at = At::new(uart).with_urc_handler(handle_urc);
+//! at.sync(10).await?;
+//! at.command("E0", Duration::from_secs(1)).await?;
+//!
+//! let mut response = [0u8; 32];
+//! let len = at.query("+CSQ", &mut response, Duration::from_secs(1)).await?;
+//! println!("{:?}", core::str::from_utf8(&response[..len]));
+//! ```
+
+use core::fmt::{self, Display, Write as _};
+
+use embassy_time::{with_timeout, Duration};
+use embedded_io_async::{Read, Write};
+
+/// Size of the buffer of received bytes, bounding the length of a line
+const RX_BUF_LEN: usize = 256;
+
+/// Size of the buffer of a command, with its `AT` prefix and terminator
+const TX_BUF_LEN: usize = 128;
+
+/// Time given to the modem to answer a synchronization attempt
+const SYNC_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Final result codes of a command
+const OK: &[u8] = b"OK";
+const ERROR: &[u8] = b"ERROR";
+const CME_ERROR: &[u8] = b"+CME ERROR:";
+const CMS_ERROR: &[u8] = b"+CMS ERROR:";
+
+/// Unsolicited result codes that do not start with `+`
+const URCS: [&[u8]; 9] = [
+    b"RING",
+    b"NO CARRIER",
+    b"CLOSED",
+    b"RDY",
+    b"Call Ready",
+    b"SMS Ready",
+    b"NORMAL POWER DOWN",
+    b"UNDER-VOLTAGE",
+    b"OVER-VOLTAGE",
+];
+
+/// An AT command engine on a UART
+pub struct At<UART> {
+    uart: UART,
+    rx: [u8; RX_BUF_LEN],
+    /// Range of the received bytes not consumed yet
+    start: usize,
+    end: usize,
+    tx: [u8; TX_BUF_LEN],
+    /// Length of the name of the command being executed, e.g. `+CSQ`, after
+    /// the `AT` prefix in `tx`
+    name_len: usize,
+    urc_handler: Option<fn(&[u8])>,
+}
+
+impl<UART: Read + Write> At<UART> {
+    pub fn new(uart: UART) -> Self {
+        Self {
+            uart,
+            rx: [0; RX_BUF_LEN],
+            start: 0,
+            end: 0,
+            tx: [0; TX_BUF_LEN],
+            name_len: 0,
+            urc_handler: None,
+        }
+    }
+
+    /// Set the handler of the URCs received while a command is executed.
+    ///
+    /// URCs are dropped if no handler is set.
+    pub fn with_urc_handler(mut self, handler: fn(&[u8])) -> Self {
+        self.urc_handler = Some(handler);
+        self
+    }
+
+    /// Release the underlying UART
+    pub fn release(self) -> UART {
+        self.uart
+    }
+
+    /// Send `AT` until the modem answers, letting modems with automatic baud
+    /// rate detection lock on the baud rate of the UART.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Timeout` if the modem did not answer any of the
+    /// attempts.
+    pub async fn sync(&mut self, attempts: u8) -> Result<(), Error<UART::Error>> {
+        for _ in 0..attempts {
+            match self.command("", SYNC_TIMEOUT).await {
+                Err(Error::Timeout) => continue,
+                result => return result,
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Execute a command, discarding its information lines.
+    ///
+    /// # Arguments
+    ///
+    /// - `command`: The command without its `AT` prefix, e.g. `"+CFUN=1"`, or
+    ///   `format_args!` to build it.
+    /// - `timeout`: The maximum time taken by the modem to answer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Command`, `Error::Cme` or `Error::Cms` if the modem
+    /// answered with an error.
+    pub async fn command(
+        &mut self,
+        command: impl Display,
+        timeout: Duration,
+    ) -> Result<(), Error<UART::Error>> {
+        self.execute(command, None, timeout).await.map(|_| ())
+    }
+
+    /// Execute a command and read its information lines into a buffer,
+    /// separated by `\n`, returning their length.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferTooSmall` if the information lines do not fit in
+    /// the buffer, and the errors of [At::command].
+    pub async fn query(
+        &mut self,
+        command: impl Display,
+        response: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, Error<UART::Error>> {
+        self.execute(command, Some(response), timeout).await
+    }
+
+    /// Send a command without waiting for its answer, for commands that do
+    /// not end with a final result code.
+    pub async fn send(&mut self, command: impl Display) -> Result<(), Error<UART::Error>> {
+        let mut cursor = Cursor {
+            buf: &mut self.tx,
+            len: 0,
+        };
+        write!(cursor, "AT{}\r", command).map_err(|_| Error::BufferTooSmall)?;
+        let len = cursor.len;
+
+        // Commands are named up to their parameters or the query mark
+        self.name_len = self.tx[2..len - 1]
+            .iter()
+            .position(|&c| c == b'=' || c == b'?')
+            .unwrap_or(len - 3);
+
+        self.uart
+            .write_all(&self.tx[..len])
+            .await
+            .map_err(Error::Uart)?;
+        self.uart.flush().await.map_err(Error::Uart)
+    }
+
+    /// Write raw data, e.g. after the `>` prompt of [At::wait_for_prompt].
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), Error<UART::Error>> {
+        self.uart.write_all(data).await.map_err(Error::Uart)?;
+        self.uart.flush().await.map_err(Error::Uart)
+    }
+
+    /// Wait for a line starting with one of the expected result codes, and
+    /// return the index of the one received.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Command` if the modem answered `ERROR` instead.
+    pub async fn wait_for(
+        &mut self,
+        expected: &[&str],
+        timeout: Duration,
+    ) -> Result<usize, Error<UART::Error>> {
+        let wait = async {
+            loop {
+                let line = self.next_line().await?;
+                let text = &self.rx[line.0..line.1];
+                if let Some(i) = expected
+                    .iter()
+                    .position(|code| text.starts_with(code.as_bytes()))
+                {
+                    return Ok(i);
+                }
+                if text == ERROR {
+                    return Err(Error::Command);
+                }
+                self.dispatch_urc(line);
+            }
+        };
+        with_timeout(timeout, wait)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    /// Read the next line that is not a URC into a buffer, and return its
+    /// length.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Command` if the modem answered `ERROR`, and
+    /// `Error::BufferTooSmall` if the line does not fit in the buffer.
+    pub async fn read_line(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, Error<UART::Error>> {
+        let read = async {
+            loop {
+                let line = self.next_line().await?;
+                if self.dispatch_urc(line) {
+                    continue;
+                }
+                let text = &self.rx[line.0..line.1];
+                if text == ERROR {
+                    return Err(Error::Command);
+                }
+                let dest = buf.get_mut(..text.len()).ok_or(Error::BufferTooSmall)?;
+                dest.copy_from_slice(text);
+                return Ok(text.len());
+            }
+        };
+        with_timeout(timeout, read)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    /// Wait for the `>` prompt of the modem asking for data.
+    pub async fn wait_for_prompt(&mut self, timeout: Duration) -> Result<(), Error<UART::Error>> {
+        let wait = async {
+            loop {
+                // The prompt is not followed by an end of line
+                let pending = &self.rx[self.start..self.end];
+                match pending.iter().position(|&c| c == b'>' || c == b'\n') {
+                    Some(i) if pending[i] == b'>' => {
+                        self.start += i + 1;
+                        return Ok(());
+                    }
+                    Some(_) => {
+                        let Some(line) = self.pop_line() else {
+                            continue;
+                        };
+                        if self.rx[line.0..line.1] == *ERROR {
+                            return Err(Error::Command);
+                        }
+                        self.dispatch_urc(line);
+                    }
+                    None => self.fill().await?,
+                }
+            }
+        };
+        with_timeout(timeout, wait)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    /// Wait for the next line sent by the modem while no command is executed,
+    /// such as a URC, read it into a buffer and return its length.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferTooSmall` if the line does not fit in the
+    /// buffer.
+    pub async fn wait_for_urc(&mut self, buf: &mut [u8]) -> Result<usize, Error<UART::Error>> {
+        let line = self.next_line().await?;
+        let text = &self.rx[line.0..line.1];
+        let dest = buf.get_mut(..text.len()).ok_or(Error::BufferTooSmall)?;
+        dest.copy_from_slice(text);
+        Ok(text.len())
+    }
+
+    async fn execute(
+        &mut self,
+        command: impl Display,
+        mut response: Option<&mut [u8]>,
+        timeout: Duration,
+    ) -> Result<usize, Error<UART::Error>> {
+        self.send(command).await?;
+
+        let answer = async {
+            let mut len = 0;
+            loop {
+                let line = self.next_line().await?;
+                let text = &self.rx[line.0..line.1];
+                if text == OK {
+                    return Ok(len);
+                }
+                if text == ERROR {
+                    return Err(Error::Command);
+                }
+                if let Some(code) = text.strip_prefix(CME_ERROR) {
+                    return Err(parse_code(code).map_or(Error::Command, Error::Cme));
+                }
+                if let Some(code) = text.strip_prefix(CMS_ERROR) {
+                    return Err(parse_code(code).map_or(Error::Command, Error::Cms));
+                }
+                // The echo of the command, if enabled
+                if text.starts_with(b"AT") || self.dispatch_urc(line) {
+                    continue;
+                }
+
+                let Some(response) = response.as_deref_mut() else {
+                    continue;
+                };
+                let text = &self.rx[line.0..line.1];
+                let separator = (len > 0) as usize;
+                let dest = response
+                    .get_mut(len..len + separator + text.len())
+                    .ok_or(Error::BufferTooSmall)?;
+                if separator == 1 {
+                    dest[0] = b'\n';
+                }
+                dest[separator..].copy_from_slice(text);
+                len += separator + text.len();
+            }
+        };
+        with_timeout(timeout, answer)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    /// Pass a line to the URC handler if it is a URC, and return whether it
+    /// is one.
+    fn dispatch_urc(&self, (start, end): (usize, usize)) -> bool {
+        let text = &self.rx[start..end];
+        let is_urc = match text.first() {
+            Some(b'+') => {
+                let name = &self.tx[2..2 + self.name_len];
+                !(text.starts_with(name) && text.get(name.len()) == Some(&b':'))
+            }
+            _ => URCS
+                .iter()
+                .any(|urc| text.starts_with(urc) || text.ends_with(urc)),
+        };
+        if let (true, Some(handler)) = (is_urc, self.urc_handler) {
+            handler(text);
+        }
+        is_urc
+    }
+
+    /// Read the next non-empty line, and return its range in the buffer of
+    /// received bytes, valid until the next read.
+    async fn next_line(&mut self) -> Result<(usize, usize), Error<UART::Error>> {
+        loop {
+            match self.pop_line() {
+                Some(line) => return Ok(line),
+                None if self.rx[self.start..self.end].contains(&b'\n') => continue,
+                None => self.fill().await?,
+            }
+        }
+    }
+
+    /// Consume the next line already received, and return its range in the
+    /// buffer of received bytes if it is not empty.
+    fn pop_line(&mut self) -> Option<(usize, usize)> {
+        let pending = &self.rx[self.start..self.end];
+        let i = pending.iter().position(|&c| c == b'\n')?;
+        let line = pending[..i].trim_ascii();
+        let start = self.start + (line.as_ptr() as usize - pending.as_ptr() as usize);
+        let end = start + line.len();
+        self.start += i + 1;
+        (start != end).then_some((start, end))
+    }
+
+    /// Read more bytes from the UART.
+    async fn fill(&mut self) -> Result<(), Error<UART::Error>> {
+        self.rx.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+
+        // Drop a line longer than the buffer
+        if self.end == RX_BUF_LEN {
+            self.end = 0;
+        }
+
+        let len = self
+            .uart
+            .read(&mut self.rx[self.end..])
+            .await
+            .map_err(Error::Uart)?;
+        if len == 0 {
+            return Err(Error::Eof);
+        }
+        self.end += len;
+        Ok(())
+    }
+}
+
+/// Parse the numeric code of an error result code.
+fn parse_code(code: &[u8]) -> Option<u16> {
+    core::str::from_utf8(code).ok()?.trim().parse().ok()
+}
+
+/// Writer of formatted text into a buffer
+struct Cursor<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for Cursor<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let dest = self
+            .buf
+            .get_mut(self.len..self.len + s.len())
+            .ok_or(fmt::Error)?;
+        dest.copy_from_slice(s.as_bytes());
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+/// All possible errors in this module
+///
+/// `E` is the error type of the underlying UART.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// UART error
+    Uart(E),
+    /// The UART has no more data.
+    Eof,
+    /// The modem did not answer in time.
+    Timeout,
+    /// The modem answered `ERROR`.
+    Command,
+    /// The modem answered with a mobile equipment error code.
+    Cme(u16),
+    /// The modem answered with a message service error code.
+    Cms(u16),
+    /// The command or its answer does not fit in its buffer.
+    BufferTooSmall,
+}
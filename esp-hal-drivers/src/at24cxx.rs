@@ -0,0 +1,315 @@
+//! # at24cxx
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Microchip
+//! AT24C02 to AT24C512 serial EEPROMs over I2C.
+//!
+//! - Writes are split along the pages of the EEPROM, since a write wraps
+//!   around within its page.
+//! - After each page, the EEPROM ignores the bus during its write cycle, so
+//!   the driver polls it until it acknowledges again.
+//! - The EEPROM implements the `embedded-storage-async`
+//!   [NorFlash](embedded_storage_async::nor_flash::NorFlash) traits, so that
+//!   it can back any storage built on them. EEPROMs need no erase, so erasing
+//!   writes `0xFF` and every byte can be rewritten at will.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut eeprom = At24cxx::new(i2c, ADDRESS, Model::At24c256);
+//!
+//! eeprom.write(0x0100, b"hello").await?;
+//! let mut buf = [0u8; 5];
+//! eeprom.read(0x0100, &mut buf).await?;
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+use embedded_storage_async::nor_flash::{
+    ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+/// I2C address of the EEPROM with its address pins low
+pub const ADDRESS: u8 = 0x50;
+
+/// Largest page of the supported EEPROMs
+const MAX_PAGE_SIZE: usize = 128;
+
+/// Time between two polls of the EEPROM during a write cycle
+const POLL_INTERVAL: Duration = Duration::from_micros(500);
+
+/// Maximum time taken by a write cycle, with some margin
+const WRITE_CYCLE_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// Model of the EEPROM
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Model {
+    /// 256 bytes
+    At24c02,
+    /// 512 bytes
+    At24c04,
+    /// 1 KiB
+    At24c08,
+    /// 2 KiB
+    At24c16,
+    /// 4 KiB
+    At24c32,
+    /// 8 KiB
+    At24c64,
+    /// 16 KiB
+    At24c128,
+    /// 32 KiB
+    At24c256,
+    /// 64 KiB
+    At24c512,
+}
+
+impl Model {
+    /// Size of the EEPROM in bytes
+    pub fn capacity(&self) -> usize {
+        match self {
+            Model::At24c02 => 256,
+            Model::At24c04 => 512,
+            Model::At24c08 => 1024,
+            Model::At24c16 => 2048,
+            Model::At24c32 => 4096,
+            Model::At24c64 => 8192,
+            Model::At24c128 => 16_384,
+            Model::At24c256 => 32_768,
+            Model::At24c512 => 65_536,
+        }
+    }
+
+    /// Size of a page in bytes
+    pub fn page_size(&self) -> usize {
+        match self {
+            Model::At24c02 => 8,
+            Model::At24c04 | Model::At24c08 | Model::At24c16 => 16,
+            Model::At24c32 | Model::At24c64 => 32,
+            Model::At24c128 | Model::At24c256 => 64,
+            Model::At24c512 => 128,
+        }
+    }
+
+    /// Whether the memory address is sent as two bytes, rather than one byte
+    /// and the high bits in the I2C address
+    fn has_wide_address(&self) -> bool {
+        self.capacity() > 2048
+    }
+}
+
+/// An AT24Cxx EEPROM on an I2C bus
+pub struct At24cxx<I2C> {
+    address: u8,
+    i2c: I2C,
+    model: Model,
+}
+
+impl<I2C: I2c> At24cxx<I2C> {
+    /// Create a new EEPROM.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the EEPROM is on.
+    /// - `address`: The I2C address of the EEPROM, [ADDRESS] plus its address
+    ///   pins. The AT24C04 to AT24C16 use some of these bits as memory
+    ///   address instead.
+    /// - `model`: The model of the EEPROM.
+    pub fn new(i2c: I2C, address: u8, model: Model) -> Self {
+        Self {
+            address,
+            i2c,
+            model,
+        }
+    }
+
+    /// Get the model of the EEPROM
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Read bytes starting at an offset.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::OutOfBounds` if the bytes do not fit in the EEPROM.
+    pub async fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Error<I2C::Error>> {
+        self.check_bounds(offset, buf.len())?;
+
+        // EEPROMs with a narrow address wrap around within the 256 bytes
+        // selected by the I2C address
+        let block_size = if self.model.has_wide_address() {
+            self.model.capacity()
+        } else {
+            256
+        };
+        let mut offset = offset as usize;
+        let mut buf = buf;
+        while !buf.is_empty() {
+            let len = buf.len().min(block_size - offset % block_size);
+            let (chunk, rest) = buf.split_at_mut(len);
+            let (address, memory_address, address_len) = self.addresses(offset);
+            self.i2c
+                .write_read(address, &memory_address[..address_len], chunk)
+                .await
+                .map_err(Error::I2c)?;
+            offset += len;
+            buf = rest;
+        }
+        Ok(())
+    }
+
+    /// Write bytes starting at an offset, one page at a time, and wait for
+    /// the write cycles to complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::OutOfBounds` if the bytes do not fit in the EEPROM,
+    /// and `Error::Timeout` if a write cycle does not complete.
+    pub async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Error<I2C::Error>> {
+        self.check_bounds(offset, data.len())?;
+
+        let page_size = self.model.page_size();
+        let mut offset = offset as usize;
+        let mut data = data;
+        while !data.is_empty() {
+            let len = data.len().min(page_size - offset % page_size);
+            let (chunk, rest) = data.split_at(len);
+            self.write_page(offset, chunk).await?;
+            offset += len;
+            data = rest;
+        }
+        Ok(())
+    }
+
+    /// Fill bytes starting at an offset with a value.
+    pub async fn fill(
+        &mut self,
+        offset: u32,
+        len: usize,
+        value: u8,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.check_bounds(offset, len)?;
+
+        let page_size = self.model.page_size();
+        let page = [value; MAX_PAGE_SIZE];
+        let end = offset as usize + len;
+        let mut offset = offset as usize;
+        while offset < end {
+            let len = (end - offset).min(page_size - offset % page_size);
+            self.write_page(offset, &page[..len]).await?;
+            offset += len;
+        }
+        Ok(())
+    }
+
+    /// Write bytes within a single page and wait for the write cycle.
+    async fn write_page(&mut self, offset: usize, data: &[u8]) -> Result<(), Error<I2C::Error>> {
+        let (address, memory_address, address_len) = self.addresses(offset);
+        let mut buf = [0u8; MAX_PAGE_SIZE + 2];
+        buf[..address_len].copy_from_slice(&memory_address[..address_len]);
+        buf[address_len..address_len + data.len()].copy_from_slice(data);
+        self.i2c
+            .write(address, &buf[..address_len + data.len()])
+            .await
+            .map_err(Error::I2c)?;
+
+        // The EEPROM does not acknowledge its address until the write cycle
+        // completes
+        let poll = async {
+            loop {
+                if self.i2c.read(address, &mut [0u8]).await.is_ok() {
+                    return;
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        };
+        with_timeout(WRITE_CYCLE_TIMEOUT, poll)
+            .await
+            .map_err(|_| Error::Timeout)
+    }
+
+    /// Compute the I2C address and the memory address bytes of an offset.
+    fn addresses(&self, offset: usize) -> (u8, [u8; 2], usize) {
+        if self.model.has_wide_address() {
+            (self.address, (offset as u16).to_be_bytes(), 2)
+        } else {
+            // The bits above the first 256 bytes replace the low bits of the
+            // I2C address
+            let block = (offset >> 8) as u8;
+            (self.address | block, [offset as u8, 0], 1)
+        }
+    }
+
+    fn check_bounds(&self, offset: u32, len: usize) -> Result<(), Error<I2C::Error>> {
+        match (offset as usize).checked_add(len) {
+            Some(end) if end <= self.model.capacity() => Ok(()),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+}
+
+impl<I2C: I2c> ErrorType for At24cxx<I2C> {
+    type Error = Error<I2C::Error>;
+}
+
+impl<I2C: I2c> ReadNorFlash for At24cxx<I2C> {
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        At24cxx::read(self, offset, bytes).await
+    }
+
+    fn capacity(&self) -> usize {
+        self.model.capacity()
+    }
+}
+
+impl<I2C: I2c> NorFlash for At24cxx<I2C> {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = 1;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from > to {
+            return Err(Error::OutOfBounds);
+        }
+        self.fill(from, (to - from) as usize, 0xFF).await
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        At24cxx::write(self, offset, bytes).await
+    }
+}
+
+impl<I2C: I2c> MultiwriteNorFlash for At24cxx<I2C> {}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The access does not fit in the EEPROM.
+    OutOfBounds,
+    /// A write cycle did not complete in time.
+    Timeout,
+}
+
+impl<E: core::fmt::Debug> NorFlashError for Error<E> {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Error::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! # battery
+//!
+//! ## Overview
+//!
+//! This module monitors a battery pack through a resistor divider connected
+//! to any [AsyncAdcChannel], e.g. a channel of the MCP3428 or the internal
+//! SAR ADC.
+//!
+//! The pack voltage is computed from the divider ratio, and the state of
+//! charge is estimated from the open-circuit voltage curve of the battery
+//! chemistry. The estimate is only meaningful when the battery is at rest.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! // 2S Li-ion pack behind a 100 kΩ / 22 kΩ divider
+//! let mut battery = BatteryMonitor::new(
+//!     adc.channel(Channel::Channel2),
+//!     (100.0 + 22.0) / 22.0,
+//!     Chemistry::LiIon,
+//! )
+//! .with_cells(2);
+//!
+//! let reading = battery.read().await?;
+//! println!("{} mV ({}%)", reading.voltage.0, reading.percent);
+//! ```
+
+use crate::{units::Millivolts, AsyncAdcChannel};
+
+/// Battery chemistry, used to estimate the state of charge
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Chemistry {
+    /// Lithium-ion or lithium-polymer, 3.0 V to 4.2 V per cell
+    LiIon,
+    /// Lithium iron phosphate, 2.5 V to 3.6 V per cell
+    LiFePo4,
+}
+
+impl Chemistry {
+    /// Open-circuit voltage in mV of a cell and the matching state of charge
+    /// in percent, by increasing voltage
+    fn curve(&self) -> &'static [(i32, u8)] {
+        match self {
+            Chemistry::LiIon => &[
+                (3_000, 0),
+                (3_300, 5),
+                (3_600, 20),
+                (3_700, 40),
+                (3_800, 60),
+                (3_900, 75),
+                (4_000, 85),
+                (4_100, 95),
+                (4_200, 100),
+            ],
+            Chemistry::LiFePo4 => &[
+                (2_500, 0),
+                (3_000, 10),
+                (3_200, 30),
+                (3_250, 50),
+                (3_300, 70),
+                (3_350, 90),
+                (3_400, 98),
+                (3_600, 100),
+            ],
+        }
+    }
+
+    /// Estimate the state of charge in percent from the voltage of a cell.
+    pub fn percent(&self, cell: Millivolts) -> u8 {
+        let curve = self.curve();
+
+        let Some(upper) = curve.iter().position(|&(mv, _)| cell.0 < mv) else {
+            return 100;
+        };
+        if upper == 0 {
+            return 0;
+        }
+
+        // Interpolate linearly between the surrounding points of the curve
+        let (low_mv, low_pct) = curve[upper - 1];
+        let (high_mv, high_pct) = curve[upper];
+        let pct =
+            low_pct as i32 + (cell.0 - low_mv) * (high_pct - low_pct) as i32 / (high_mv - low_mv);
+        pct as u8
+    }
+}
+
+/// Battery voltage and estimated state of charge
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BatteryReading {
+    /// Voltage of the whole pack
+    pub voltage: Millivolts,
+    /// Estimated state of charge in percent
+    pub percent: u8,
+}
+
+/// A battery pack measured through a resistor divider on an ADC channel
+pub struct BatteryMonitor<A> {
+    adc: A,
+    divider_ratio: f32,
+    chemistry: Chemistry,
+    cells: u8,
+}
+
+impl<A: AsyncAdcChannel> BatteryMonitor<A> {
+    /// Create a new battery monitor for a single cell.
+    ///
+    /// # Arguments
+    ///
+    /// - `adc`: The ADC channel measuring the divider.
+    /// - `divider_ratio`: The ratio of the pack voltage to the measured
+    ///   voltage, i.e. `(R_top + R_bottom) / R_bottom`.
+    /// - `chemistry`: The chemistry of the battery.
+    pub fn new(adc: A, divider_ratio: f32, chemistry: Chemistry) -> Self {
+        Self {
+            adc,
+            divider_ratio,
+            chemistry,
+            cells: 1,
+        }
+    }
+
+    /// Set the number of cells in series in the pack.
+    pub fn with_cells(mut self, cells: u8) -> Self {
+        self.cells = cells.max(1);
+        self
+    }
+
+    /// Measure the pack voltage and estimate its state of charge.
+    pub async fn read(&mut self) -> Result<BatteryReading, A::Error> {
+        let measured = self.adc.read_millivolts().await?;
+
+        let voltage = Millivolts((measured.0 as f32 * self.divider_ratio) as i32);
+        Ok(BatteryReading {
+            voltage,
+            percent: self.percent(voltage),
+        })
+    }
+
+    /// Estimate the state of charge in percent from the pack voltage.
+    pub fn percent(&self, voltage: Millivolts) -> u8 {
+        let cell = Millivolts(voltage.0 / self.cells as i32);
+        self.chemistry.percent(cell)
+    }
+
+    /// Release the ADC channel
+    pub fn release(self) -> A {
+        self.adc
+    }
+}
@@ -0,0 +1,568 @@
+//! # bme280
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Bosch BME280
+//! temperature, humidity and pressure sensor over I2C.
+//!
+//! The raw readings are compensated with the factory calibration of the
+//! sensor using the integer compensation formulas of the datasheet (section
+//! 4.2.3), so no floating point arithmetic is needed.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let config = Config::new()
+//!     .with_temperature_oversampling(Oversampling::X2)
+//!     .with_pressure_oversampling(Oversampling::X16)
+//!     .with_humidity_oversampling(Oversampling::X1)
+//!     .with_filter(IirFilter::X16);
+//! let mut bme280 = Bme280::new(i2c, ADDRESS_PRIMARY, config).await?;
+//!
+//! // Take a single measurement
+//! let measurement = bme280.measure().await?;
+//! println!(
+//!     "{} °C, {} hPa, {} %RH",
+//!     measurement.celsius().0,
+//!     measurement.hectopascals().0,
+//!     measurement.relative_humidity().0
+//! );
+//!
+//! // Or let the sensor measure continuously
+//! bme280.start_continuous().await?;
+//! let measurement = bme280.read().await?;
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use crate::units::{Celsius, Hectopascals, RelativeHumidity};
+
+/// I2C address of the sensor when SDO is tied to ground
+pub const ADDRESS_PRIMARY: u8 = 0x76;
+
+/// I2C address of the sensor when SDO is tied to the supply
+pub const ADDRESS_SECONDARY: u8 = 0x77;
+
+/// Value of the chip ID register of the BME280
+const CHIP_ID: u8 = 0x60;
+
+/// Value written to the reset register to trigger a soft reset
+const SOFT_RESET: u8 = 0xB6;
+
+/// Maximum time to wait for a measurement
+const MEASUREMENT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Registers of the sensor. See datasheet section 5.3 for more details.
+struct Register;
+
+impl Register {
+    const CALIBRATION_TP: u8 = 0x88;
+    const CHIP_ID: u8 = 0xD0;
+    const RESET: u8 = 0xE0;
+    const CALIBRATION_H: u8 = 0xE1;
+    const CTRL_HUM: u8 = 0xF2;
+    const STATUS: u8 = 0xF3;
+    const CTRL_MEAS: u8 = 0xF4;
+    const CONFIG: u8 = 0xF5;
+    const DATA: u8 = 0xF7;
+}
+
+/// Bit of the status register set while a conversion is running
+const STATUS_MEASURING: u8 = 0b0000_1000;
+
+/// Bit of the status register set while the calibration is copied
+const STATUS_IM_UPDATE: u8 = 0b0000_0001;
+
+/// Configuration of the BME280
+///
+/// Defaults to 1x oversampling of all quantities, no filter and 1 s between
+/// measurements in continuous mode.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    pub temperature: Oversampling,
+    pub pressure: Oversampling,
+    pub humidity: Oversampling,
+    pub filter: IirFilter,
+    pub standby: Standby,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            temperature: Oversampling::X1,
+            pressure: Oversampling::X1,
+            humidity: Oversampling::X1,
+            filter: IirFilter::Off,
+            standby: Standby::Ms1000,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_temperature_oversampling(mut self, oversampling: Oversampling) -> Self {
+        self.temperature = oversampling;
+        self
+    }
+
+    pub fn with_pressure_oversampling(mut self, oversampling: Oversampling) -> Self {
+        self.pressure = oversampling;
+        self
+    }
+
+    pub fn with_humidity_oversampling(mut self, oversampling: Oversampling) -> Self {
+        self.humidity = oversampling;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: IirFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Set the inactive time between measurements in continuous mode.
+    pub fn with_standby(mut self, standby: Standby) -> Self {
+        self.standby = standby;
+        self
+    }
+
+    /// Maximum time in µs taken by a measurement. See datasheet section 9.1
+    /// for more details.
+    fn measurement_time_us(&self) -> u64 {
+        let mut time = 1_250 + 2_300 * self.temperature.samples();
+        if self.pressure.samples() > 0 {
+            time += 2_300 * self.pressure.samples() + 575;
+        }
+        if self.humidity.samples() > 0 {
+            time += 2_300 * self.humidity.samples() + 575;
+        }
+        time
+    }
+
+    fn ctrl_meas(&self, mode: u8) -> u8 {
+        (self.temperature.bits() << 5) | (self.pressure.bits() << 2) | mode
+    }
+
+    fn config(&self) -> u8 {
+        (self.standby.bits() << 5) | (self.filter.bits() << 2)
+    }
+}
+
+/// Number of samples averaged for a quantity
+///
+/// A quantity measured with `Skip` reads as 0.
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Oversampling {
+    Skip = 0b000,
+    X1 = 0b001,
+    X2 = 0b010,
+    X4 = 0b011,
+    X8 = 0b100,
+    X16 = 0b101,
+}
+
+impl Oversampling {
+    /// Return the bitmask for this oversampling.
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Return the number of samples averaged.
+    pub fn samples(&self) -> u64 {
+        match self {
+            Oversampling::Skip => 0,
+            _ => 1 << (self.bits() - 1),
+        }
+    }
+}
+
+/// Coefficient of the IIR filter smoothing the pressure and temperature
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IirFilter {
+    Off = 0b000,
+    X2 = 0b001,
+    X4 = 0b010,
+    X8 = 0b011,
+    X16 = 0b100,
+}
+
+impl IirFilter {
+    /// Return the bitmask for this filter coefficient.
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Inactive time between measurements in continuous mode
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Standby {
+    Us500 = 0b000,
+    Ms10 = 0b110,
+    Ms20 = 0b111,
+    Ms62_5 = 0b001,
+    Ms125 = 0b010,
+    Ms250 = 0b011,
+    Ms500 = 0b100,
+    Ms1000 = 0b101,
+}
+
+impl Standby {
+    /// Return the bitmask for this standby time.
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Power mode written to the ctrl_meas register
+#[derive(Copy, Clone)]
+enum Mode {
+    Sleep = 0b00,
+    Forced = 0b01,
+    Normal = 0b11,
+}
+
+/// A compensated measurement
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    /// Temperature in 0.01 °C
+    pub temperature_centi_c: i32,
+    /// Pressure in Pa
+    pub pressure_pa: u32,
+    /// Relative humidity in 0.001 %RH
+    pub humidity_milli_pct: u32,
+}
+
+impl Measurement {
+    /// Return the temperature.
+    pub fn celsius(&self) -> Celsius {
+        Celsius(self.temperature_centi_c as f32 / 100.0)
+    }
+
+    /// Return the pressure.
+    pub fn hectopascals(&self) -> Hectopascals {
+        Hectopascals(self.pressure_pa as f32 / 100.0)
+    }
+
+    /// Return the relative humidity.
+    pub fn relative_humidity(&self) -> RelativeHumidity {
+        RelativeHumidity(self.humidity_milli_pct as f32 / 1000.0)
+    }
+}
+
+/// Factory calibration of the sensor. See datasheet section 4.2.2 for more
+/// details.
+#[derive(Debug, Default, Copy, Clone)]
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+impl Calibration {
+    fn from_registers(tp: &[u8; 26], h: &[u8; 7]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([tp[i], tp[i + 1]]);
+        let i16_at = |i: usize| i16::from_le_bytes([tp[i], tp[i + 1]]);
+
+        Self {
+            t1: u16_at(0),
+            t2: i16_at(2),
+            t3: i16_at(4),
+            p1: u16_at(6),
+            p2: i16_at(8),
+            p3: i16_at(10),
+            p4: i16_at(12),
+            p5: i16_at(14),
+            p6: i16_at(16),
+            p7: i16_at(18),
+            p8: i16_at(20),
+            p9: i16_at(22),
+            h1: tp[25],
+            h2: i16::from_le_bytes([h[0], h[1]]),
+            h3: h[2],
+            h4: ((h[3] as i8 as i16) << 4) | (h[4] & 0x0F) as i16,
+            h5: ((h[5] as i8 as i16) << 4) | (h[4] >> 4) as i16,
+            h6: h[6] as i8,
+        }
+    }
+
+    /// Compensate the raw temperature and return it in 0.01 °C along with
+    /// the fine temperature used by the other compensations.
+    fn temperature(&self, adc_t: i32) -> (i32, i32) {
+        let t1 = self.t1 as i32;
+        let var1 = (((adc_t >> 3) - (t1 << 1)) * self.t2 as i32) >> 11;
+        let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * self.t3 as i32) >> 14;
+        let t_fine = var1 + var2;
+        ((t_fine * 5 + 128) >> 8, t_fine)
+    }
+
+    /// Compensate the raw pressure and return it in Pa.
+    fn pressure(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let mut var1 = t_fine as i64 - 128_000;
+        let mut var2 = var1 * var1 * self.p6 as i64;
+        var2 += (var1 * self.p5 as i64) << 17;
+        var2 += (self.p4 as i64) << 35;
+        var1 = ((var1 * var1 * self.p3 as i64) >> 8) + ((var1 * self.p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * self.p1 as i64) >> 33;
+        if var1 == 0 {
+            // Avoid a division by zero
+            return 0;
+        }
+
+        let mut p = 1_048_576 - adc_p as i64;
+        p = (((p << 31) - var2) * 3125) / var1;
+        let var1 = (self.p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+        let var2 = (self.p8 as i64 * p) >> 19;
+        p = ((p + var1 + var2) >> 8) + ((self.p7 as i64) << 4);
+
+        // p is in Q24.8 Pa
+        (p >> 8) as u32
+    }
+
+    /// Compensate the raw humidity and return it in 0.001 %RH.
+    fn humidity(&self, adc_h: i32, t_fine: i32) -> u32 {
+        let mut v = t_fine - 76_800;
+        v = (((adc_h << 14) - ((self.h4 as i32) << 20) - (self.h5 as i32 * v) + 16_384) >> 15)
+            * (((((((v * self.h6 as i32) >> 10) * (((v * self.h3 as i32) >> 11) + 32_768))
+                >> 10)
+                + 2_097_152)
+                * self.h2 as i32
+                + 8_192)
+                >> 14);
+        v -= ((((v >> 15) * (v >> 15)) >> 7) * self.h1 as i32) >> 4;
+        v = v.clamp(0, 419_430_400);
+
+        // v >> 12 is in Q22.10 %RH
+        (((v >> 12) as u64 * 1000) >> 10) as u32
+    }
+}
+
+/// A BME280 sensor on an I2C bus
+pub struct Bme280<I2C> {
+    address: u8,
+    i2c: I2C,
+    config: Config,
+    calibration: Calibration,
+}
+
+impl<I2C: I2c> Bme280<I2C> {
+    /// Reset the sensor, check its identity and apply the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the sensor is on.
+    /// - `address`: The I2C address of the sensor, either [ADDRESS_PRIMARY]
+    ///   or [ADDRESS_SECONDARY].
+    /// - `config`: The configuration used for the measurements.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::InvalidChipId] if the device is not a BME280.
+    pub async fn new(i2c: I2C, address: u8, config: Config) -> Result<Self, Error<I2C::Error>> {
+        let mut bme280 = Self {
+            address,
+            i2c,
+            config,
+            calibration: Calibration::default(),
+        };
+
+        let chip_id = bme280.read_register(Register::CHIP_ID).await?;
+        if chip_id != CHIP_ID {
+            return Err(Error::InvalidChipId(chip_id));
+        }
+
+        bme280.reset().await?;
+        bme280.read_calibration().await?;
+        bme280.write_config(Mode::Sleep).await?;
+        Ok(bme280)
+    }
+
+    /// Get the configuration used for the measurements
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Set the configuration used for the measurements.
+    ///
+    /// The sensor is put to sleep, so continuous measurements must be
+    /// started again.
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
+        self.config = config;
+        self.write_config(Mode::Sleep).await
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Trigger a single measurement and return it once complete.
+    ///
+    /// The sensor goes back to sleep afterwards.
+    pub async fn measure(&mut self) -> Result<Measurement, Error<I2C::Error>> {
+        self.write_config(Mode::Forced).await?;
+        Timer::after(Duration::from_micros(self.config.measurement_time_us())).await;
+
+        // Poll until the measurement is complete
+        let poll = async {
+            loop {
+                let status = self.read_register(Register::STATUS).await?;
+                if status & STATUS_MEASURING == 0 {
+                    return Ok(());
+                }
+                Timer::after(Duration::from_millis(1)).await;
+            }
+        };
+        with_timeout(MEASUREMENT_TIMEOUT, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))?;
+
+        self.read().await
+    }
+
+    /// Let the sensor measure continuously, waiting for the configured
+    /// standby time between measurements.
+    pub async fn start_continuous(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write_config(Mode::Normal).await
+    }
+
+    /// Put the sensor to sleep, stopping continuous measurements.
+    pub async fn sleep(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write_config(Mode::Sleep).await
+    }
+
+    /// Read and compensate the latest measurement.
+    pub async fn read(&mut self) -> Result<Measurement, Error<I2C::Error>> {
+        let mut data = [0u8; 8];
+        self.read_registers(Register::DATA, &mut data).await?;
+
+        let adc_p = ((data[0] as i32) << 12) | ((data[1] as i32) << 4) | (data[2] as i32 >> 4);
+        let adc_t = ((data[3] as i32) << 12) | ((data[4] as i32) << 4) | (data[5] as i32 >> 4);
+        let adc_h = ((data[6] as i32) << 8) | data[7] as i32;
+
+        let (temperature, t_fine) = self.calibration.temperature(adc_t);
+        let pressure = match self.config.pressure {
+            Oversampling::Skip => 0,
+            _ => self.calibration.pressure(adc_p, t_fine),
+        };
+        let humidity = match self.config.humidity {
+            Oversampling::Skip => 0,
+            _ => self.calibration.humidity(adc_h, t_fine),
+        };
+
+        Ok(Measurement {
+            temperature_centi_c: temperature,
+            pressure_pa: pressure,
+            humidity_milli_pct: humidity,
+        })
+    }
+
+    /// Soft reset the sensor and wait for the calibration to be loaded.
+    async fn reset(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write_register(Register::RESET, SOFT_RESET).await?;
+        Timer::after(Duration::from_millis(2)).await;
+
+        let poll = async {
+            while self.read_register(Register::STATUS).await? & STATUS_IM_UPDATE != 0 {
+                Timer::after(Duration::from_millis(1)).await;
+            }
+            Ok(())
+        };
+        with_timeout(MEASUREMENT_TIMEOUT, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    async fn read_calibration(&mut self) -> Result<(), Error<I2C::Error>> {
+        let mut tp = [0u8; 26];
+        self.read_registers(Register::CALIBRATION_TP, &mut tp)
+            .await?;
+        let mut h = [0u8; 7];
+        self.read_registers(Register::CALIBRATION_H, &mut h).await?;
+
+        self.calibration = Calibration::from_registers(&tp, &h);
+        Ok(())
+    }
+
+    /// Write the configuration with the given power mode.
+    ///
+    /// The config register is only applied in sleep mode and ctrl_hum only
+    /// after a write to ctrl_meas, so the writes are ordered accordingly.
+    async fn write_config(&mut self, mode: Mode) -> Result<(), Error<I2C::Error>> {
+        self.write_register(
+            Register::CTRL_MEAS,
+            self.config.ctrl_meas(Mode::Sleep as u8),
+        )
+        .await?;
+        self.write_register(Register::CONFIG, self.config.config())
+            .await?;
+        self.write_register(Register::CTRL_HUM, self.config.humidity.bits())
+            .await?;
+        self.write_register(Register::CTRL_MEAS, self.config.ctrl_meas(mode as u8))
+            .await
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.read_registers(register, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn read_registers(
+        &mut self,
+        register: u8,
+        buf: &mut [u8],
+    ) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write_read(self.address, &[register], buf)
+            .await
+            .map_err(Error::I2c)
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The device answered with an unexpected chip ID, so it is not a BME280.
+    InvalidChipId(u8),
+    /// The sensor did not complete an operation in time.
+    Timeout,
+}
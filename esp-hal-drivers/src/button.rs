@@ -0,0 +1,183 @@
+//! # button
+//!
+//! ## Overview
+//!
+//! This driver reads push buttons wired to a GPIO, debouncing their contacts
+//! and classifying presses into clicks, double clicks and long presses.
+//!
+//! The pin is awaited on GPIO interrupts, so an idle button costs nothing.
+//! Calling [Button::wait_for_event] in a loop gives a stream of events.
+//!
+//! - A click is reported once the double click window elapsed without a
+//!   second press, so disabling double clicks makes clicks immediate.
+//! - A long press is reported while the button is still held, as soon as it
+//!   was held long enough.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let peripherals = esp_hal::init(esp_hal::Config::default());
+//! let mut button = Button::new(peripherals.GPIO9, Pull::Up, Level::Low)
+//!     .with_long_press(Duration::from_millis(1_000));
+//!
+//! loop {
+//!     match button.wait_for_event().await {
+//!         Event::Click => println!("Next"),
+//!         Event::DoubleClick => println!("Previous"),
+//!         Event::LongPress => println!("Menu"),
+//!     }
+//! }
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use esp_hal::{
+    gpio::{Input, InputPin, Level, Pull},
+    peripheral::Peripheral,
+};
+
+/// Default time for the contacts to settle
+const DEFAULT_DEBOUNCE_TIME: Duration = Duration::from_millis(20);
+
+/// Default time a button is held for a long press
+const DEFAULT_LONG_PRESS_TIME: Duration = Duration::from_millis(800);
+
+/// Default time between two clicks of a double click
+const DEFAULT_DOUBLE_CLICK_TIME: Duration = Duration::from_millis(300);
+
+/// A classified press of a [Button]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// A short press, not followed by a second one.
+    Click,
+    /// Two short presses in quick succession
+    DoubleClick,
+    /// A press held longer than the long press time
+    LongPress,
+}
+
+/// A push button on a GPIO
+pub struct Button<'a> {
+    input: Input<'a>,
+    active: Level,
+    debounce_time: Duration,
+    long_press_time: Duration,
+    double_click_time: Duration,
+    pressed: bool,
+}
+
+impl<'a> Button<'a> {
+    /// Create a new button.
+    ///
+    /// # Arguments
+    ///
+    /// - `pin`: The pin the button is connected to.
+    /// - `pull`: The internal pull resistor of the pin, [Pull::None] if the
+    ///   button has an external one.
+    /// - `active`: The level of the pin while the button is pressed, e.g.
+    ///   [Level::Low] for a button to ground with a pull-up.
+    pub fn new(pin: impl Peripheral<P = impl InputPin> + 'a, pull: Pull, active: Level) -> Self {
+        let input = Input::new(pin, pull);
+        let pressed = input.level() == active;
+        Self {
+            input,
+            active,
+            debounce_time: DEFAULT_DEBOUNCE_TIME,
+            long_press_time: DEFAULT_LONG_PRESS_TIME,
+            double_click_time: DEFAULT_DOUBLE_CLICK_TIME,
+            pressed,
+        }
+    }
+
+    /// Set the time for the contacts to settle.
+    ///
+    /// Defaults to 20ms.
+    pub fn with_debounce(mut self, time: Duration) -> Self {
+        self.debounce_time = time;
+        self
+    }
+
+    /// Set the time a button must be held for a long press.
+    ///
+    /// Defaults to 800ms.
+    pub fn with_long_press(mut self, time: Duration) -> Self {
+        self.long_press_time = time;
+        self
+    }
+
+    /// Set the maximum time between the release of a click and the second
+    /// press of a double click. A time of zero disables double clicks.
+    ///
+    /// Defaults to 300ms.
+    pub fn with_double_click(mut self, time: Duration) -> Self {
+        self.double_click_time = time;
+        self
+    }
+
+    /// Release the underlying input pin
+    pub fn release(self) -> Input<'a> {
+        self.input
+    }
+
+    /// Return whether the button is pressed, as of the last debounced change.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Wait for the button to be pressed.
+    ///
+    /// Returns immediately if it is already pressed.
+    pub async fn wait_for_press(&mut self) {
+        self.wait_for_state(true).await
+    }
+
+    /// Wait for the button to be released.
+    ///
+    /// Returns immediately if it is already released.
+    pub async fn wait_for_release(&mut self) {
+        self.wait_for_state(false).await
+    }
+
+    /// Wait for the next press of the button and classify it.
+    ///
+    /// A button still held after a long press must be released before the
+    /// next press is awaited.
+    pub async fn wait_for_event(&mut self) -> Event {
+        self.wait_for_release().await;
+        self.wait_for_press().await;
+
+        if with_timeout(self.long_press_time, self.wait_for_release())
+            .await
+            .is_err()
+        {
+            return Event::LongPress;
+        }
+
+        if self.double_click_time.as_ticks() == 0 {
+            return Event::Click;
+        }
+
+        match with_timeout(self.double_click_time, self.wait_for_press()).await {
+            Ok(()) => {
+                self.wait_for_release().await;
+                Event::DoubleClick
+            }
+            Err(_) => Event::Click,
+        }
+    }
+
+    /// Wait for the button to be debounced in a state.
+    async fn wait_for_state(&mut self, pressed: bool) {
+        let level = if pressed { self.active } else { !self.active };
+        while self.pressed != pressed {
+            match level {
+                Level::High => self.input.wait_for_high().await,
+                Level::Low => self.input.wait_for_low().await,
+            }
+
+            // Bounces are over by the end of the debounce time
+            Timer::after(self.debounce_time).await;
+            self.pressed = self.input.level() == self.active;
+        }
+    }
+}
@@ -0,0 +1,399 @@
+//! # ccs811
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the ScioSense (ams)
+//! CCS811 eCO2 and TVOC sensor over I2C.
+//!
+//! - The sensor boots into its bootloader, and [Ccs811::new] starts its
+//!   application firmware.
+//! - The drive mode sets how often the sensor measures, trading power for
+//!   response time.
+//! - The baseline is the resistance of the sensor in clean air. It drifts
+//!   slowly, and can be saved and restored to skip the burn-in after a
+//!   restart.
+//! - The humidity and the temperature around the sensor compensate its
+//!   measurements, e.g. from an [SHT4x](crate::sht4x).
+//!
+//! The sensor pulls its nINT pin low when a measurement is ready. Once a GPIO
+//! is attached with [Ccs811::with_interrupt_pin],
+//! [Ccs811::wait_for_measurement] awaits the measurement on a GPIO interrupt
+//! instead of polling the bus. The nWAKE pin of the sensor must be tied low.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut ccs811 = Ccs811::new(i2c, ADDRESS_LOW)
+//!     .await?
+//!     .with_interrupt_pin(Input::new(peripherals.GPIO5, Pull::Up));
+//! ccs811.set_drive_mode(DriveMode::EverySecond).await?;
+//! ccs811.set_baseline(saved_baseline).await?;
+//!
+//! loop {
+//!     let environment = sht4x.measure().await?;
+//!     ccs811
+//!         .set_environment(environment.temperature, environment.humidity)
+//!         .await?;
+//!     let measurement = ccs811.wait_for_measurement().await?;
+//!     println!("{} ppm eCO2, {} ppb TVOC", measurement.eco2, measurement.tvoc);
+//! }
+//! ```
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::{digital::Wait, i2c::I2c};
+
+use crate::units::{Celsius, RelativeHumidity};
+
+/// I2C address of the sensor with its ADDR pin low
+pub const ADDRESS_LOW: u8 = 0x5A;
+
+/// I2C address of the sensor with its ADDR pin high
+pub const ADDRESS_HIGH: u8 = 0x5B;
+
+/// Value of the hardware ID register
+const HARDWARE_ID: u8 = 0x81;
+
+/// Sequence written to the software reset register to reset the sensor
+const RESET_SEQUENCE: [u8; 4] = [0x11, 0xE5, 0x72, 0x8A];
+
+/// Time taken by the sensor to boot after a reset
+const RESET_TIME: Duration = Duration::from_millis(2);
+
+/// Time taken by the application to start
+const APP_START_TIME: Duration = Duration::from_millis(1);
+
+/// Registers of the sensor. See datasheet section "Sensor Interface" for more
+/// details.
+struct Register;
+
+impl Register {
+    const STATUS: u8 = 0x00;
+    const MEAS_MODE: u8 = 0x01;
+    const ALG_RESULT_DATA: u8 = 0x02;
+    const ENV_DATA: u8 = 0x05;
+    const BASELINE: u8 = 0x11;
+    const HW_ID: u8 = 0x20;
+    const FW_APP_VERSION: u8 = 0x24;
+    const ERROR_ID: u8 = 0xE0;
+    const APP_START: u8 = 0xF4;
+    const SW_RESET: u8 = 0xFF;
+}
+
+/// Flags of the status register
+const STATUS_ERROR: u8 = 1 << 0;
+const STATUS_DATA_READY: u8 = 1 << 3;
+const STATUS_APP_VALID: u8 = 1 << 4;
+const STATUS_FW_MODE_APP: u8 = 1 << 7;
+
+/// Flag of the measurement mode register enabling the data ready interrupt
+const MEAS_MODE_INTERRUPT: u8 = 1 << 3;
+
+/// How often the sensor measures
+///
+/// Defaults to `Idle`, as after a reset.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DriveMode {
+    /// No measurements, the heater is off.
+    #[default]
+    Idle = 0b000,
+    /// A measurement every second.
+    EverySecond = 0b001,
+    /// A measurement every 10 seconds, with a pulsed heater.
+    Every10Seconds = 0b010,
+    /// A measurement every 60 seconds, with a pulsed heater.
+    Every60Seconds = 0b011,
+    /// A raw measurement every 250 ms, without eCO2 nor TVOC.
+    Every250Ms = 0b100,
+}
+
+impl DriveMode {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// A measurement of the sensor
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    /// Equivalent CO2 in ppm, from 400 to 32768
+    pub eco2: u16,
+    /// Total volatile organic compounds in ppb, from 0 to 32768
+    pub tvoc: u16,
+}
+
+/// A CCS811 on an I2C bus
+///
+/// `INT` is the pin the nINT output of the sensor is connected to, if any.
+pub struct Ccs811<I2C, INT = ()> {
+    address: u8,
+    i2c: I2C,
+    interrupt: INT,
+    drive_mode: DriveMode,
+}
+
+impl<I2C: I2c> Ccs811<I2C> {
+    /// Create a new sensor, reset it and start its application firmware.
+    ///
+    /// The sensor is idle until a drive mode is set.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the sensor is on.
+    /// - `address`: The I2C address of the sensor, [ADDRESS_LOW] or
+    ///   [ADDRESS_HIGH].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidHardwareId` if the device does not identify as
+    /// a CCS811, and `Error::NoValidApp` if its bootloader holds no valid
+    /// application firmware.
+    pub async fn new(i2c: I2C, address: u8) -> Result<Self, Error<I2C::Error>> {
+        let mut ccs811 = Self {
+            address,
+            i2c,
+            interrupt: (),
+            drive_mode: DriveMode::default(),
+        };
+
+        let id = ccs811.read_register(Register::HW_ID).await?;
+        if id != HARDWARE_ID {
+            return Err(Error::InvalidHardwareId(id));
+        }
+
+        ccs811.reset().await?;
+        ccs811.start_app().await?;
+        Ok(ccs811)
+    }
+
+    /// Attach the pin the nINT output of the sensor is connected to.
+    ///
+    /// The output is open-drain and active low, so the pin needs a pull-up.
+    pub fn with_interrupt_pin<P: Wait>(self, interrupt: P) -> Ccs811<I2C, P> {
+        Ccs811 {
+            address: self.address,
+            i2c: self.i2c,
+            interrupt,
+            drive_mode: self.drive_mode,
+        }
+    }
+}
+
+impl<I2C: I2c, P: Wait> Ccs811<I2C, P> {
+    /// Wait for a new measurement and return it.
+    ///
+    /// Returns immediately if a measurement is ready since the last read.
+    pub async fn wait_for_measurement(&mut self) -> Result<Measurement, Error<I2C::Error>> {
+        self.interrupt
+            .wait_for_low()
+            .await
+            .map_err(|_| Error::Pin)?;
+
+        // Reading the measurement releases the interrupt
+        self.read().await
+    }
+
+    /// Release the underlying I2C bus and interrupt pin
+    pub fn release_with_interrupt_pin(self) -> (I2C, P) {
+        (self.i2c, self.interrupt)
+    }
+}
+
+impl<I2C: I2c, INT> Ccs811<I2C, INT> {
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Reset the sensor back into its bootloader.
+    pub async fn reset(&mut self) -> Result<(), Error<I2C::Error>> {
+        let [a, b, c, d] = RESET_SEQUENCE;
+        self.i2c
+            .write(self.address, &[Register::SW_RESET, a, b, c, d])
+            .await
+            .map_err(Error::I2c)?;
+        Timer::after(RESET_TIME).await;
+        self.drive_mode = DriveMode::default();
+        Ok(())
+    }
+
+    /// Start the application firmware from the bootloader. Does nothing if
+    /// it already runs.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoValidApp` if the bootloader holds no valid
+    /// application firmware.
+    pub async fn start_app(&mut self) -> Result<(), Error<I2C::Error>> {
+        let status = self.status().await?;
+        if status & STATUS_FW_MODE_APP != 0 {
+            return Ok(());
+        }
+        if status & STATUS_APP_VALID == 0 {
+            return Err(Error::NoValidApp);
+        }
+
+        // The command has no data
+        self.i2c
+            .write(self.address, &[Register::APP_START])
+            .await
+            .map_err(Error::I2c)?;
+        Timer::after(APP_START_TIME).await;
+
+        if self.status().await? & STATUS_FW_MODE_APP == 0 {
+            return Err(Error::NoValidApp);
+        }
+        Ok(())
+    }
+
+    /// Read the version of the application firmware, as major, minor and
+    /// trivial numbers.
+    pub async fn app_version(&mut self) -> Result<(u8, u8, u8), Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.read_registers(Register::FW_APP_VERSION, &mut buf)
+            .await?;
+        Ok((buf[0] >> 4, buf[0] & 0x0F, buf[1]))
+    }
+
+    /// Get the current drive mode of the sensor
+    pub fn drive_mode(&self) -> DriveMode {
+        self.drive_mode
+    }
+
+    /// Set how often the sensor measures, enabling the data ready interrupt.
+    ///
+    /// When lowering the rate, the sensor should first stay idle for 10
+    /// minutes.
+    pub async fn set_drive_mode(&mut self, mode: DriveMode) -> Result<(), Error<I2C::Error>> {
+        self.write_registers(
+            Register::MEAS_MODE,
+            &[(mode.bits() << 4) | MEAS_MODE_INTERRUPT],
+        )
+        .await?;
+        self.drive_mode = mode;
+        Ok(())
+    }
+
+    /// Check if a new measurement is ready since the last read.
+    pub async fn data_ready(&mut self) -> Result<bool, Error<I2C::Error>> {
+        Ok(self.status().await? & STATUS_DATA_READY != 0)
+    }
+
+    /// Read the latest measurement.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Device` if the sensor reports an error.
+    pub async fn read(&mut self) -> Result<Measurement, Error<I2C::Error>> {
+        // eCO2, TVOC, status and error ID
+        let mut buf = [0u8; 6];
+        self.read_registers(Register::ALG_RESULT_DATA, &mut buf)
+            .await?;
+        if buf[4] & STATUS_ERROR != 0 {
+            return Err(Error::Device(buf[5]));
+        }
+
+        Ok(Measurement {
+            eco2: u16::from_be_bytes([buf[0], buf[1]]),
+            tvoc: u16::from_be_bytes([buf[2], buf[3]]),
+        })
+    }
+
+    /// Read the current baseline of the sensor, to restore it later.
+    ///
+    /// The baseline is only meaningful after 20 minutes of measurements in
+    /// clean air.
+    pub async fn baseline(&mut self) -> Result<u16, Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.read_registers(Register::BASELINE, &mut buf).await?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    /// Restore a baseline previously read with [Ccs811::baseline].
+    pub async fn set_baseline(&mut self, baseline: u16) -> Result<(), Error<I2C::Error>> {
+        self.write_registers(Register::BASELINE, &baseline.to_be_bytes())
+            .await
+    }
+
+    /// Set the humidity and the temperature around the sensor, to compensate
+    /// its measurements. The sensor assumes 50 %RH and 25 °C until set.
+    pub async fn set_environment(
+        &mut self,
+        temperature: Celsius,
+        humidity: RelativeHumidity,
+    ) -> Result<(), Error<I2C::Error>> {
+        // Both in 1/512, the temperature offset by 25 °C. See datasheet
+        // section "ENV_DATA" for more details.
+        let humidity = (humidity.0.clamp(0.0, 100.0) * 512.0) as u16;
+        let temperature = ((temperature.0.clamp(-25.0, 100.0) + 25.0) * 512.0) as u16;
+        let [h_msb, h_lsb] = humidity.to_be_bytes();
+        let [t_msb, t_lsb] = temperature.to_be_bytes();
+        self.write_registers(Register::ENV_DATA, &[h_msb, h_lsb, t_msb, t_lsb])
+            .await
+    }
+
+    /// Read the status register, checking its error flag.
+    async fn status(&mut self) -> Result<u8, Error<I2C::Error>> {
+        let status = self.read_register(Register::STATUS).await?;
+        if status & STATUS_ERROR != 0 {
+            let error = self.read_register(Register::ERROR_ID).await?;
+            return Err(Error::Device(error));
+        }
+        Ok(status)
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.read_registers(register, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn read_registers(
+        &mut self,
+        register: u8,
+        buf: &mut [u8],
+    ) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write_read(self.address, &[register], buf)
+            .await
+            .map_err(Error::I2c)
+    }
+
+    async fn write_registers(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<(), Error<I2C::Error>> {
+        let mut buf = [0u8; 5];
+        buf[0] = register;
+        buf[1..=values.len()].copy_from_slice(values);
+        self.i2c
+            .write(self.address, &buf[..=values.len()])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The interrupt pin could not be read.
+    Pin,
+    /// The device does not identify as a CCS811.
+    InvalidHardwareId(u8),
+    /// The bootloader holds no valid application firmware, or could not
+    /// start it.
+    NoValidApp,
+    /// The sensor reported an error, with the flags of its error register:
+    /// invalid register write (bit 0), invalid register read (bit 1), invalid
+    /// drive mode (bit 2), resistance out of range (bit 3), heater current
+    /// out of range (bit 4) or heater voltage out of range (bit 5).
+    Device(u8),
+}
@@ -0,0 +1,196 @@
+//! # current
+//!
+//! ## Overview
+//!
+//! This module measures currents with analog sensors read through any
+//! [AsyncAdcChannel], such as the ACS712 Hall-effect sensor or the SCT-013
+//! split-core current transformers.
+//!
+//! - The RMS current of AC loads is computed over a window long enough to
+//!   cover whole cycles of the mains, 200ms by default. The DC bias of the
+//!   sensor is removed from the samples, and the square root is computed in
+//!   integer math.
+//! - The average current of DC loads is measured from the output of the
+//!   sensor at 0 A, measured with [CurrentSensor::calibrate_zero].
+//! - The [Sensitivity] converts the voltage to a current, from the
+//!   sensitivity of an ACS712 or the turns ratio and burden resistor of a
+//!   current transformer.
+//!
+//! The ADC should sample at least a few times per cycle, so slow ADCs only
+//! give a rough RMS current.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! // SCT-013-000 (100 A:50 mA) with a 33 Ω burden biased at mid-supply
+//! let sensitivity = Sensitivity::current_transformer(2_000, 33);
+//! let mut config = SarAdcConfig::new();
+//! let mut pin = config.enable_pin(peripherals.GPIO2, Attenuation::_11dB);
+//! let mut adc = SarAdc::new(peripherals.ADC1, config);
+//! let mut sensor = CurrentSensor::new(adc.channel(&mut pin), sensitivity)
+//!     .with_window(Duration::from_millis(400));
+//!
+//! let current = sensor.read_rms().await?;
+//! println!("{} mA RMS", current.0);
+//! ```
+
+use embassy_time::{Duration, Instant};
+
+use crate::{
+    units::{Milliamps, Millivolts},
+    AsyncAdcChannel,
+};
+
+/// Default measurement window, 10 cycles at 50 Hz or 12 cycles at 60 Hz
+const DEFAULT_WINDOW: Duration = Duration::from_millis(200);
+
+/// Conversion from the voltage at the output of a sensor to a current
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sensitivity {
+    /// Current per voltage in mA/V
+    milliamps_per_volt: u32,
+}
+
+impl Sensitivity {
+    /// Sensitivity of the 5 A ACS712
+    pub const ACS712_5A: Self = Self::hall(185);
+    /// Sensitivity of the 20 A ACS712
+    pub const ACS712_20A: Self = Self::hall(100);
+    /// Sensitivity of the 30 A ACS712
+    pub const ACS712_30A: Self = Self::hall(66);
+
+    /// Create a new sensitivity for a Hall-effect sensor, such as the
+    /// ACS712, from its sensitivity in mV/A.
+    pub const fn hall(millivolts_per_amp: u32) -> Self {
+        Self {
+            milliamps_per_volt: 1_000_000 / millivolts_per_amp,
+        }
+    }
+
+    /// Create a new sensitivity for a current transformer with a current
+    /// output, such as the SCT-013-000.
+    ///
+    /// # Arguments
+    ///
+    /// - `turns`: The turns ratio, e.g. 2000 for 100 A:50 mA.
+    /// - `burden`: The resistance of the burden resistor in Ω.
+    pub const fn current_transformer(turns: u32, burden: u32) -> Self {
+        Self {
+            milliamps_per_volt: turns * 1_000 / burden,
+        }
+    }
+
+    /// Create a new sensitivity for a current transformer with a voltage
+    /// output and a built-in burden, such as the SCT-013-030 (30 A/V).
+    pub const fn voltage_output(amps_per_volt: u32) -> Self {
+        Self {
+            milliamps_per_volt: amps_per_volt * 1_000,
+        }
+    }
+
+    /// Get the current in mA for a voltage in µV
+    fn current(&self, microvolts: i64) -> Milliamps {
+        Milliamps(microvolts as f32 * self.milliamps_per_volt as f32 / 1_000_000.0)
+    }
+}
+
+/// An analog current sensor on an ADC channel
+pub struct CurrentSensor<A> {
+    adc: A,
+    sensitivity: Sensitivity,
+    window: Duration,
+    /// Output of the sensor at 0 A
+    zero: Millivolts,
+}
+
+impl<A: AsyncAdcChannel> CurrentSensor<A> {
+    /// Create a new current sensor measuring over 200ms.
+    ///
+    /// The output at 0 A defaults to 2500 mV, the output of an ACS712 on a
+    /// 5 V supply.
+    pub fn new(adc: A, sensitivity: Sensitivity) -> Self {
+        Self {
+            adc,
+            sensitivity,
+            window: DEFAULT_WINDOW,
+            zero: Millivolts(2_500),
+        }
+    }
+
+    /// Set the measurement window, which should cover whole cycles of the
+    /// mains.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set the output of the sensor at 0 A.
+    pub fn with_zero(mut self, zero: Millivolts) -> Self {
+        self.zero = zero;
+        self
+    }
+
+    /// Get the output of the sensor at 0 A
+    pub fn zero(&self) -> Millivolts {
+        self.zero
+    }
+
+    /// Measure the output of the sensor at 0 A, averaged over the window.
+    ///
+    /// No current should flow through the sensor.
+    pub async fn calibrate_zero(&mut self) -> Result<Millivolts, A::Error> {
+        let (count, sum, _) = self.sample().await?;
+        self.zero = Millivolts((sum / count) as i32);
+        Ok(self.zero)
+    }
+
+    /// Measure the RMS current of an AC load over the window.
+    ///
+    /// The DC bias of the sensor is removed, so the output at 0 A does not
+    /// need to be calibrated.
+    pub async fn read_rms(&mut self) -> Result<Milliamps, A::Error> {
+        let (count, sum, sum_squares) = self.sample().await?;
+
+        // Variance of the samples in µV², without the DC bias
+        let count = count as i128;
+        let variance =
+            (count * sum_squares as i128 - sum as i128 * sum as i128) * 1_000_000 / (count * count);
+        let rms = (variance.max(0) as u128).isqrt();
+
+        Ok(self.sensitivity.current(rms as i64))
+    }
+
+    /// Measure the average current of a DC load over the window, negative
+    /// when flowing backwards through the sensor.
+    pub async fn read_dc(&mut self) -> Result<Milliamps, A::Error> {
+        let (count, sum, _) = self.sample().await?;
+        let microvolts = (sum - count * self.zero.0 as i64) * 1_000 / count;
+
+        Ok(self.sensitivity.current(microvolts))
+    }
+
+    /// Release the ADC channel
+    pub fn release(self) -> A {
+        self.adc
+    }
+
+    /// Sample the sensor over the window and return the number of samples,
+    /// their sum and the sum of their squares, in mV.
+    async fn sample(&mut self) -> Result<(i64, i64, i64), A::Error> {
+        let start = Instant::now();
+        let mut count = 0;
+        let mut sum = 0;
+        let mut sum_squares = 0;
+
+        // Take at least one sample, even with an empty window
+        while count == 0 || start.elapsed() < self.window {
+            let sample = self.adc.read_millivolts().await?.0 as i64;
+            count += 1;
+            sum += sample;
+            sum_squares += sample * sample;
+        }
+
+        Ok((count, sum, sum_squares))
+    }
+}
@@ -0,0 +1,215 @@
+//! # dht
+//!
+//! ## Overview
+//!
+//! This driver reads the Aosong DHT11 and DHT22 (AM2302) humidity and
+//! temperature sensors over their single-wire protocol.
+//!
+//! The sensor encodes each bit in the length of a pulse. Rather than
+//! busy-waiting on the pin, the driver awaits its falling edges and decodes
+//! the bits from the time elapsed between them, so that other tasks keep
+//! running during the 5 ms transfer.
+//!
+//! The sensors must not be read more often than their minimum interval, so
+//! the driver waits for it to elapse before starting a read.
+//!
+//! The pin must be configured as an open-drain output with its input enabled,
+//! and the data line needs a pull-up resistor, typically 4.7 kΩ to 10 kΩ.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut pin = Flex::new(peripherals.GPIO4);
+//! pin.set_as_open_drain(Pull::None);
+//! let mut dht = Dht::new(pin, Model::Dht22);
+//!
+//! loop {
+//!     let measurement = dht.read().await?;
+//!     println!(
+//!         "{} °C, {} %RH",
+//!         measurement.temperature.0,
+//!         measurement.humidity.0
+//!     );
+//! }
+//! ```
+
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::digital::Wait;
+
+use crate::units::{Celsius, RelativeHumidity};
+
+/// Number of data bits sent by the sensor
+const BITS: usize = 40;
+
+/// Number of falling edges in a transfer: the start of the response, the
+/// start of the first bit and the end of every bit
+const EDGES: usize = BITS + 2;
+
+/// Time between two falling edges above which a bit is a 1. A 0 lasts about
+/// 78 µs and a 1 about 120 µs.
+const ONE_THRESHOLD: Duration = Duration::from_micros(100);
+
+/// Maximum time taken by a transfer
+const TRANSFER_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Model of the sensor
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Model {
+    Dht11,
+    Dht22,
+}
+
+impl Model {
+    /// Time the data line is pulled low to wake the sensor up
+    fn start_time(&self) -> Duration {
+        match self {
+            Model::Dht11 => Duration::from_millis(18),
+            Model::Dht22 => Duration::from_millis(1),
+        }
+    }
+
+    /// Minimum time between two reads, which is also the time taken by the
+    /// sensor to start up
+    fn min_interval(&self) -> Duration {
+        match self {
+            Model::Dht11 => Duration::from_secs(1),
+            Model::Dht22 => Duration::from_secs(2),
+        }
+    }
+
+    fn decode(&self, data: &[u8; 5]) -> Measurement {
+        let (temperature, humidity) = match self {
+            Model::Dht11 => {
+                // The sign is carried by the most significant bit of the
+                // decimal part
+                let magnitude = data[2] as f32 + (data[3] & 0x7F) as f32 / 10.0;
+                let temperature = if data[3] & 0x80 != 0 {
+                    -magnitude
+                } else {
+                    magnitude
+                };
+                (temperature, data[0] as f32 + data[1] as f32 / 10.0)
+            }
+            Model::Dht22 => {
+                let magnitude = u16::from_be_bytes([data[2] & 0x7F, data[3]]) as f32 / 10.0;
+                let temperature = if data[2] & 0x80 != 0 {
+                    -magnitude
+                } else {
+                    magnitude
+                };
+                let humidity = u16::from_be_bytes([data[0], data[1]]) as f32 / 10.0;
+                (temperature, humidity)
+            }
+        };
+
+        Measurement {
+            temperature: Celsius(temperature),
+            humidity: RelativeHumidity(humidity),
+        }
+    }
+}
+
+/// A measurement of the sensor
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    pub temperature: Celsius,
+    pub humidity: RelativeHumidity,
+}
+
+/// A DHT sensor on a GPIO
+pub struct Dht<P> {
+    pin: P,
+    model: Model,
+    ready_at: Instant,
+}
+
+impl<P: OutputPin + Wait> Dht<P> {
+    /// Create a new sensor on an open-drain pin.
+    ///
+    /// The first read waits for the sensor to start up.
+    pub fn new(mut pin: P, model: Model) -> Self {
+        // Let the data line idle high
+        let _ = pin.set_high();
+        Self {
+            pin,
+            model,
+            ready_at: Instant::now() + model.min_interval(),
+        }
+    }
+
+    /// Release the underlying pin
+    pub fn release(self) -> P {
+        self.pin
+    }
+
+    /// Read the temperature and the relative humidity.
+    ///
+    /// Waits for the minimum interval since the last read to elapse first.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Timeout` if the sensor did not answer, and
+    /// `Error::Checksum` if the transfer was corrupted.
+    pub async fn read(&mut self) -> Result<Measurement, Error<P::Error>> {
+        Timer::at(self.ready_at).await;
+
+        let result = self.transfer().await;
+        self.ready_at = Instant::now() + self.model.min_interval();
+        let data = result?;
+
+        let checksum = data[..4]
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if checksum != data[4] {
+            return Err(Error::Checksum);
+        }
+
+        Ok(self.model.decode(&data))
+    }
+
+    /// Wake the sensor up and receive its 5 bytes of data.
+    async fn transfer(&mut self) -> Result<[u8; 5], Error<P::Error>> {
+        self.pin.set_low().map_err(Error::Pin)?;
+        Timer::after(self.model.start_time()).await;
+        self.pin.set_high().map_err(Error::Pin)?;
+
+        // Timestamp every falling edge of the transfer
+        let mut edges = [Instant::MIN; EDGES];
+        let receive = async {
+            for edge in edges.iter_mut() {
+                self.pin.wait_for_falling_edge().await.map_err(Error::Pin)?;
+                *edge = Instant::now();
+            }
+            Ok(())
+        };
+        with_timeout(TRANSFER_TIMEOUT, receive)
+            .await
+            .unwrap_or(Err(Error::Timeout))?;
+
+        // Each bit spans two consecutive falling edges, after the response
+        let mut data = [0u8; 5];
+        for (i, pair) in edges[1..].windows(2).enumerate() {
+            if pair[1] - pair[0] > ONE_THRESHOLD {
+                data[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying pin.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Pin error
+    Pin(E),
+    /// The sensor did not complete a transfer in time.
+    Timeout,
+    /// The received data failed its checksum.
+    Checksum,
+}
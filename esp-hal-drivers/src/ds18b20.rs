@@ -0,0 +1,339 @@
+//! # ds18b20
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Maxim DS18B20
+//! temperature sensor over a [1-Wire bus](crate::onewire).
+//!
+//! Several sensors can share a bus, so each sensor is only a handle holding
+//! its ROM and the bus is passed to every operation.
+//!
+//! Sensors powered parasitically from the data line cannot answer while they
+//! convert, so the driver waits for the worst-case conversion time instead of
+//! polling them. The bus must then be held high by a strong enough pull-up
+//! during the conversion.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut bus = OneWire::new(pin);
+//!
+//! // Find all sensors on the bus
+//! let mut roms = [Rom([0; 8]); 4];
+//! let count = Ds18b20::enumerate(&mut bus, &mut roms).await?;
+//!
+//! // Measure with each sensor in turn
+//! for rom in &roms[..count] {
+//!     let mut sensor = Ds18b20::new(&mut bus, *rom).await?;
+//!     sensor.set_resolution(&mut bus, Resolution::Bits11).await?;
+//!     println!("{} °C", sensor.measure(&mut bus).await?.0);
+//! }
+//!
+//! // Or convert on all sensors at once, then read them one by one
+//! let sensors = [
+//!     Ds18b20::new(&mut bus, roms[0]).await?,
+//!     Ds18b20::new(&mut bus, roms[1]).await?,
+//! ];
+//! Ds18b20::convert_all(&mut bus, &sensors).await?;
+//! for sensor in &sensors {
+//!     println!("{} °C", sensor.read_temperature(&mut bus).await?.0);
+//! }
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal::digital::{InputPin, OutputPin};
+
+use crate::{
+    onewire::{self, crc8, OneWire, Rom, RomSearch},
+    units::Celsius,
+};
+
+/// Family code of the DS18B20
+pub const FAMILY_CODE: u8 = 0x28;
+
+/// Function commands of the sensor. See datasheet section "DS18B20 Function
+/// Commands" for more details.
+struct Command;
+
+impl Command {
+    const CONVERT_T: u8 = 0x44;
+    const WRITE_SCRATCHPAD: u8 = 0x4E;
+    const READ_SCRATCHPAD: u8 = 0xBE;
+    const COPY_SCRATCHPAD: u8 = 0x48;
+    const READ_POWER_SUPPLY: u8 = 0xB4;
+}
+
+/// Resolution of the temperature in °C per LSB at 12 bits
+const CELSIUS_PER_LSB: f32 = 0.0625;
+
+/// Time taken to copy the scratchpad to the EEPROM
+const COPY_TIME: Duration = Duration::from_millis(10);
+
+/// Extra time allowed for a conversion before giving up
+const TIMEOUT_MARGIN: Duration = Duration::from_millis(100);
+
+/// Time between two polls of a conversion
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Resolution of the conversions
+///
+/// Defaults to `Bits12`, the power-on default of the sensor.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Resolution {
+    /// 0.5 °C, takes up to 93.75 ms.
+    Bits9 = 0b00,
+    /// 0.25 °C, takes up to 187.5 ms.
+    Bits10 = 0b01,
+    /// 0.125 °C, takes up to 375 ms.
+    Bits11 = 0b10,
+    /// 0.0625 °C, takes up to 750 ms.
+    #[default]
+    Bits12 = 0b11,
+}
+
+impl Resolution {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    fn from_config(config: u8) -> Self {
+        match (config >> 5) & 0b11 {
+            0b00 => Resolution::Bits9,
+            0b01 => Resolution::Bits10,
+            0b10 => Resolution::Bits11,
+            _ => Resolution::Bits12,
+        }
+    }
+
+    /// Value of the configuration register of the scratchpad
+    fn config(&self) -> u8 {
+        (self.bits() << 5) | 0x1F
+    }
+
+    /// Maximum time taken by a conversion
+    fn conversion_time(&self) -> Duration {
+        Duration::from_micros(750_000 >> (3 - self.bits()))
+    }
+}
+
+/// A DS18B20 sensor on a 1-Wire bus
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ds18b20 {
+    rom: Rom,
+    resolution: Resolution,
+    parasite: bool,
+}
+
+impl Ds18b20 {
+    /// Search the bus for DS18B20 sensors.
+    ///
+    /// Devices of other families are skipped. Returns the number of ROMs
+    /// written to `roms`; any sensor beyond its capacity is ignored.
+    pub async fn enumerate<P: InputPin + OutputPin>(
+        bus: &mut OneWire<P>,
+        roms: &mut [Rom],
+    ) -> Result<usize, Error<P::Error>> {
+        let mut search = RomSearch::new();
+        let mut count = 0;
+        while count < roms.len() {
+            match search.next(bus).await? {
+                Some(rom) if rom.family() == FAMILY_CODE => {
+                    roms[count] = rom;
+                    count += 1;
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        Ok(count)
+    }
+
+    /// Create a handle to a sensor, reading its resolution and whether it is
+    /// powered parasitically.
+    ///
+    /// # Arguments
+    ///
+    /// - `bus`: The 1-Wire bus the sensor is on.
+    /// - `rom`: The ROM of the sensor, e.g. found by [Ds18b20::enumerate].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidFamily` if the device is not a DS18B20.
+    pub async fn new<P: InputPin + OutputPin>(
+        bus: &mut OneWire<P>,
+        rom: Rom,
+    ) -> Result<Self, Error<P::Error>> {
+        if rom.family() != FAMILY_CODE {
+            return Err(Error::InvalidFamily(rom.family()));
+        }
+
+        let mut ds18b20 = Self {
+            rom,
+            resolution: Resolution::default(),
+            parasite: false,
+        };
+
+        // Parasitically powered sensors pull the bus low during the read slot
+        bus.select(Some(&rom)).await?;
+        bus.write_byte(Command::READ_POWER_SUPPLY)?;
+        ds18b20.parasite = !bus.read_bit()?;
+
+        let scratchpad = ds18b20.read_scratchpad(bus).await?;
+        ds18b20.resolution = Resolution::from_config(scratchpad[4]);
+        Ok(ds18b20)
+    }
+
+    pub fn rom(&self) -> &Rom {
+        &self.rom
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Return whether the sensor is powered parasitically from the bus.
+    pub fn is_parasite_powered(&self) -> bool {
+        self.parasite
+    }
+
+    /// Set the resolution of the conversions. The resolution is lost on power
+    /// loss; use [Ds18b20::save] to persist it.
+    pub async fn set_resolution<P: InputPin + OutputPin>(
+        &mut self,
+        bus: &mut OneWire<P>,
+        resolution: Resolution,
+    ) -> Result<(), Error<P::Error>> {
+        // The alarm thresholds share the write, so keep their current values
+        let scratchpad = self.read_scratchpad(bus).await?;
+
+        bus.select(Some(&self.rom)).await?;
+        bus.write_bytes(&[
+            Command::WRITE_SCRATCHPAD,
+            scratchpad[2],
+            scratchpad[3],
+            resolution.config(),
+        ])?;
+        self.resolution = resolution;
+        Ok(())
+    }
+
+    /// Copy the resolution and the alarm thresholds to the EEPROM of the
+    /// sensor, so that they are loaded on every power-up.
+    pub async fn save<P: InputPin + OutputPin>(
+        &self,
+        bus: &mut OneWire<P>,
+    ) -> Result<(), Error<P::Error>> {
+        bus.select(Some(&self.rom)).await?;
+        bus.write_byte(Command::COPY_SCRATCHPAD)?;
+        Timer::after(COPY_TIME).await;
+        Ok(())
+    }
+
+    /// Start a conversion on this sensor, wait for it to complete and return
+    /// its result.
+    pub async fn measure<P: InputPin + OutputPin>(
+        &self,
+        bus: &mut OneWire<P>,
+    ) -> Result<Celsius, Error<P::Error>> {
+        bus.select(Some(&self.rom)).await?;
+        bus.write_byte(Command::CONVERT_T)?;
+        wait_for_conversion(bus, self.resolution, self.parasite).await?;
+        self.read_temperature(bus).await
+    }
+
+    /// Start a conversion on all sensors of the bus at once and wait for the
+    /// slowest of `sensors` to complete. The results are then read with
+    /// [Ds18b20::read_temperature].
+    pub async fn convert_all<P: InputPin + OutputPin>(
+        bus: &mut OneWire<P>,
+        sensors: &[Ds18b20],
+    ) -> Result<(), Error<P::Error>> {
+        let resolution = sensors
+            .iter()
+            .map(|sensor| sensor.resolution)
+            .max()
+            .unwrap_or_default();
+        let parasite = sensors.iter().any(|sensor| sensor.parasite);
+
+        bus.select(None).await?;
+        bus.write_byte(Command::CONVERT_T)?;
+        wait_for_conversion(bus, resolution, parasite).await
+    }
+
+    /// Read the result of the last conversion of the sensor.
+    pub async fn read_temperature<P: InputPin + OutputPin>(
+        &self,
+        bus: &mut OneWire<P>,
+    ) -> Result<Celsius, Error<P::Error>> {
+        let scratchpad = self.read_scratchpad(bus).await?;
+
+        // The unused low bits are undefined at lower resolutions
+        let undefined = 3 - self.resolution.bits();
+        let raw = i16::from_le_bytes([scratchpad[0], scratchpad[1]]) >> undefined << undefined;
+        Ok(Celsius(raw as f32 * CELSIUS_PER_LSB))
+    }
+
+    async fn read_scratchpad<P: InputPin + OutputPin>(
+        &self,
+        bus: &mut OneWire<P>,
+    ) -> Result<[u8; 9], Error<P::Error>> {
+        bus.select(Some(&self.rom)).await?;
+        bus.write_byte(Command::READ_SCRATCHPAD)?;
+
+        let mut scratchpad = [0u8; 9];
+        bus.read_bytes(&mut scratchpad)?;
+        if crc8(&scratchpad[..8]) != scratchpad[8] {
+            return Err(Error::OneWire(onewire::Error::Crc));
+        }
+        Ok(scratchpad)
+    }
+}
+
+/// Wait for a conversion started on the bus to complete.
+async fn wait_for_conversion<P: InputPin + OutputPin>(
+    bus: &mut OneWire<P>,
+    resolution: Resolution,
+    parasite: bool,
+) -> Result<(), Error<P::Error>> {
+    // Read slots would starve parasitically powered sensors
+    if parasite {
+        Timer::after(resolution.conversion_time()).await;
+        return Ok(());
+    }
+
+    // The sensors hold the bus low in read slots until they are done
+    let poll = async {
+        loop {
+            if bus.read_bit()? {
+                return Ok(());
+            }
+            Timer::after(POLL_INTERVAL).await;
+        }
+    };
+    with_timeout(resolution.conversion_time() + TIMEOUT_MARGIN, poll)
+        .await
+        .unwrap_or(Err(Error::Timeout))
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying pin.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// 1-Wire bus error
+    OneWire(onewire::Error<E>),
+    /// The ROM has an unexpected family code, so the device is not a DS18B20.
+    InvalidFamily(u8),
+    /// The sensor did not complete a conversion in time.
+    Timeout,
+}
+
+impl<E> From<onewire::Error<E>> for Error<E> {
+    fn from(error: onewire::Error<E>) -> Self {
+        Error::OneWire(error)
+    }
+}
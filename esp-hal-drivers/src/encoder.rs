@@ -0,0 +1,287 @@
+//! # encoder
+//!
+//! ## Overview
+//!
+//! This driver decodes incremental quadrature rotary encoders, such as the
+//! mechanical encoders used for menu navigation or the optical and Hall
+//! encoders of motors.
+//!
+//! The ESP32-C3 has no pulse counter peripheral, so the driver awaits the
+//! edges of the A and B channels on GPIO interrupts and decodes them in
+//! software. It only counts while a method is awaited, so it should run in a
+//! dedicated task. Transitions skipping a level, e.g. after a missed edge,
+//! cannot be decoded and are dropped, so fast motors may lose counts.
+//!
+//! - Edges shorter than the glitch filter are ignored, which debounces
+//!   mechanical encoders.
+//! - The position counts every transition (x4), every other transition (x2)
+//!   or every full cycle (x1) of the channels. It only changes once the
+//!   channels moved a whole position away from the last one, in either
+//!   direction, so wiggling a knob around a detent does not report steps.
+//! - The push button of the encoder, if any, is reported along with the
+//!   rotations by [Encoder::wait_for_event].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let a = Input::new(peripherals.GPIO2, Pull::Up);
+//! let b = Input::new(peripherals.GPIO3, Pull::Up);
+//! let button = Input::new(peripherals.GPIO4, Pull::Up);
+//! let mut encoder = Encoder::new(a, b, Decoding::X1)?.with_button(button)?;
+//!
+//! loop {
+//!     match encoder.wait_for_event().await? {
+//!         Event::Rotated(steps) => println!("{} steps to {}", steps, encoder.position()),
+//!         Event::Pressed => println!("Select"),
+//!         Event::Released => {}
+//!     }
+//! }
+//! ```
+
+use embassy_futures::select::{select, select3, Either, Either3};
+use embassy_time::{Duration, Timer};
+use embedded_hal::digital::InputPin;
+use embedded_hal_async::digital::Wait;
+
+/// Default glitch filter, long enough to debounce mechanical encoders
+const DEFAULT_FILTER: Duration = Duration::from_micros(1_000);
+
+/// Time for the contacts of the push button to settle
+const BUTTON_DEBOUNCE_TIME: Duration = Duration::from_millis(10);
+
+/// Steps taken for each transition of the channels, indexed by the previous
+/// and current levels as `0bAB_AB`. Channel A leads channel B when turning
+/// forward. Transitions skipping a level are invalid and ignored.
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+/// Number of transitions counted per position
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Decoding {
+    /// One position per cycle, matching the detents of most mechanical
+    /// encoders.
+    X1,
+    /// Two positions per cycle
+    X2,
+    /// Four positions per cycle, the finest resolution.
+    X4,
+}
+
+impl Decoding {
+    fn transitions(&self) -> i32 {
+        match self {
+            Decoding::X1 => 4,
+            Decoding::X2 => 2,
+            Decoding::X4 => 1,
+        }
+    }
+}
+
+/// An event of an [Encoder] with a push button
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The position changed by the given number of steps.
+    Rotated(i32),
+    Pressed,
+    Released,
+}
+
+/// A quadrature encoder on two GPIOs
+///
+/// `BTN` is the pin the push button of the encoder is connected to, if any.
+pub struct Encoder<A, B, BTN = ()> {
+    a: A,
+    b: B,
+    button: BTN,
+    decoding: Decoding,
+    filter: Duration,
+    /// Levels of the channels as `0bAB`
+    levels: u8,
+    /// Transitions counted since the creation of the encoder
+    transitions: i32,
+    /// Position, reached when the transitions were a multiple of the decoding
+    position: i32,
+    pressed: bool,
+}
+
+impl<A: InputPin + Wait, B: InputPin + Wait> Encoder<A, B> {
+    /// Create a new encoder at position 0.
+    ///
+    /// # Arguments
+    ///
+    /// - `a`: The pin channel A is connected to.
+    /// - `b`: The pin channel B is connected to.
+    /// - `decoding`: The number of transitions counted per position.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::Pin] if the levels of the channels cannot be read.
+    pub fn new(a: A, b: B, decoding: Decoding) -> Result<Self, Error> {
+        let mut encoder = Self {
+            a,
+            b,
+            button: (),
+            decoding,
+            filter: DEFAULT_FILTER,
+            levels: 0,
+            transitions: 0,
+            position: 0,
+            pressed: false,
+        };
+        encoder.levels = encoder.read_levels()?;
+        Ok(encoder)
+    }
+
+    /// Attach the push button of the encoder.
+    ///
+    /// The button is expected to connect the pin to ground, so the pin needs
+    /// a pull-up.
+    pub fn with_button<P: InputPin + Wait>(self, mut button: P) -> Result<Encoder<A, B, P>, Error> {
+        let pressed = button.is_low().map_err(|_| Error::Pin)?;
+        Ok(Encoder {
+            a: self.a,
+            b: self.b,
+            button,
+            decoding: self.decoding,
+            filter: self.filter,
+            levels: self.levels,
+            transitions: self.transitions,
+            position: self.position,
+            pressed,
+        })
+    }
+}
+
+impl<A: InputPin + Wait, B: InputPin + Wait, P: InputPin + Wait> Encoder<A, B, P> {
+    /// Wait for the encoder to rotate or its button to change, and return
+    /// the event.
+    pub async fn wait_for_event(&mut self) -> Result<Event, Error> {
+        loop {
+            let edge = select3(
+                self.a.wait_for_any_edge(),
+                self.b.wait_for_any_edge(),
+                self.button.wait_for_any_edge(),
+            )
+            .await;
+
+            match edge {
+                Either3::First(result) => result.map_err(|_| Error::Pin)?,
+                Either3::Second(result) => result.map_err(|_| Error::Pin)?,
+                Either3::Third(result) => {
+                    result.map_err(|_| Error::Pin)?;
+                    Timer::after(BUTTON_DEBOUNCE_TIME).await;
+                    let pressed = self.button.is_low().map_err(|_| Error::Pin)?;
+                    if pressed != self.pressed {
+                        self.pressed = pressed;
+                        return Ok(if pressed {
+                            Event::Pressed
+                        } else {
+                            Event::Released
+                        });
+                    }
+                    continue;
+                }
+            }
+
+            let steps = self.on_edge().await?;
+            if steps != 0 {
+                return Ok(Event::Rotated(steps));
+            }
+        }
+    }
+
+    /// Return whether the push button is pressed, as of the last event.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Release the underlying pins
+    pub fn release_with_button(self) -> (A, B, P) {
+        (self.a, self.b, self.button)
+    }
+}
+
+impl<A: InputPin + Wait, B: InputPin + Wait, BTN> Encoder<A, B, BTN> {
+    /// Set the glitch filter. Changes of the channels that do not last
+    /// longer than the filter are ignored. Encoders without contacts can use
+    /// a shorter filter to follow faster rotations.
+    pub fn with_filter(mut self, filter: Duration) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Release the underlying channel pins
+    pub fn release(self) -> (A, B) {
+        (self.a, self.b)
+    }
+
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Set the position, e.g. to go back to 0 at a reference point.
+    pub fn set_position(&mut self, position: i32) {
+        // Keep the transitions counted since the current position
+        self.transitions += (position - self.position) * self.decoding.transitions();
+        self.position = position;
+    }
+
+    /// Wait for the position to change and return the number of steps it
+    /// changed by, negative when turning backwards.
+    pub async fn wait_for_change(&mut self) -> Result<i32, Error> {
+        loop {
+            match select(self.a.wait_for_any_edge(), self.b.wait_for_any_edge()).await {
+                Either::First(result) => result.map_err(|_| Error::Pin)?,
+                Either::Second(result) => result.map_err(|_| Error::Pin)?,
+            }
+
+            let steps = self.on_edge().await?;
+            if steps != 0 {
+                return Ok(steps);
+            }
+        }
+    }
+
+    /// Decode the levels of the channels after an edge and return the number
+    /// of steps the position changed by.
+    async fn on_edge(&mut self) -> Result<i32, Error> {
+        // A glitch is over by the end of the filter, leaving the levels as
+        // they were
+        if self.filter.as_ticks() > 0 {
+            Timer::after(self.filter).await;
+        }
+
+        let levels = self.read_levels()?;
+        let transition = (self.levels << 2) | levels;
+        self.levels = levels;
+
+        self.transitions += TRANSITIONS[transition as usize] as i32;
+
+        // Only move once a whole position away from the current one, so that
+        // the position behaves the same in both directions
+        let position = self.position;
+        let per_position = self.decoding.transitions();
+        if self.transitions >= (position + 1) * per_position {
+            self.position = self.transitions.div_euclid(per_position);
+        } else if self.transitions <= (position - 1) * per_position {
+            self.position = -(-self.transitions).div_euclid(per_position);
+        }
+        Ok(self.position - position)
+    }
+
+    /// Read the levels of the channels as `0bAB`.
+    fn read_levels(&mut self) -> Result<u8, Error> {
+        let a = self.a.is_high().map_err(|_| Error::Pin)?;
+        let b = self.b.is_high().map_err(|_| Error::Pin)?;
+        Ok(((a as u8) << 1) | b as u8)
+    }
+}
+
+/// All possible errors in this driver
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A pin could not be read.
+    Pin,
+}
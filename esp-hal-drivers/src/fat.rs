@@ -0,0 +1,713 @@
+//! # fat
+//!
+//! ## Overview
+//!
+//! This module provides a minimal FAT16 and FAT32 layer over any
+//! [BlockDevice], such as an [SdCard](crate::sdcard::SdCard), to append data
+//! logs to files.
+//!
+//! - The volume is either the first partition of the device, or the whole
+//!   device when it has no partition table.
+//! - Only the files of the root directory are supported, by their 8.3 short
+//!   name, e.g. `LOG0001.CSV`.
+//! - Files are opened for appending, and created when they do not exist.
+//! - Writes are buffered one block at a time, so a file must be flushed for
+//!   its data and size to reach the device.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut volume = Volume::mount(&mut card).await?;
+//! let mut file = volume.open_append("LOG0001.CSV").await?;
+//!
+//! file.write(b"time,temperature\n").await?;
+//! file.flush().await?;
+//! ```
+
+/// Size of a block of the device, and of a sector of the volume
+pub const BLOCK_SIZE: usize = 512;
+
+/// Size of a directory entry
+const DIR_ENTRY_SIZE: usize = 32;
+
+/// Attributes of a directory entry
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+/// Markers in the first byte of a directory entry
+const ENTRY_END: u8 = 0x00;
+const ENTRY_DELETED: u8 = 0xE5;
+
+/// Smallest number of clusters of the FAT types. Volumes with fewer clusters
+/// are FAT12, which is not supported.
+const FAT16_MIN_CLUSTERS: u32 = 4085;
+const FAT32_MIN_CLUSTERS: u32 = 65525;
+
+/// Partition types of an MBR holding a FAT16 or FAT32 volume
+const PARTITION_TYPES: [u8; 5] = [0x04, 0x06, 0x0E, 0x0B, 0x0C];
+
+/// Date of the files created, 1980-01-01, since there is no clock to date
+/// them
+const FAT_EPOCH_DATE: u16 = (1 << 5) | 1;
+
+/// Signatures of the FSInfo sector
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+
+/// Value of the FSInfo free count and next free cluster when unknown
+const FS_INFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// A device made of blocks of [BLOCK_SIZE] bytes
+#[allow(async_fn_in_trait)]
+pub trait BlockDevice {
+    /// Error returned by a failed access
+    type Error;
+
+    /// Read a block.
+    async fn read_block(
+        &mut self,
+        lba: u32,
+        block: &mut [u8; BLOCK_SIZE],
+    ) -> Result<(), Self::Error>;
+
+    /// Write a block.
+    async fn write_block(&mut self, lba: u32, block: &[u8; BLOCK_SIZE]) -> Result<(), Self::Error>;
+}
+
+impl<T: BlockDevice> BlockDevice for &mut T {
+    type Error = T::Error;
+
+    async fn read_block(
+        &mut self,
+        lba: u32,
+        block: &mut [u8; BLOCK_SIZE],
+    ) -> Result<(), Self::Error> {
+        T::read_block(self, lba, block).await
+    }
+
+    async fn write_block(&mut self, lba: u32, block: &[u8; BLOCK_SIZE]) -> Result<(), Self::Error> {
+        T::write_block(self, lba, block).await
+    }
+}
+
+/// Type of the file allocation table
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FatType {
+    Fat16,
+    Fat32,
+}
+
+impl FatType {
+    /// Size of an entry of the table in bytes
+    fn entry_size(&self) -> u32 {
+        match self {
+            FatType::Fat16 => 2,
+            FatType::Fat32 => 4,
+        }
+    }
+
+    /// Entry marking the end of a cluster chain
+    fn end_of_chain(&self) -> u32 {
+        match self {
+            FatType::Fat16 => 0xFFFF,
+            FatType::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+
+    /// Smallest entry marking the end of a cluster chain
+    fn min_end_of_chain(&self) -> u32 {
+        match self {
+            FatType::Fat16 => 0xFFF8,
+            FatType::Fat32 => 0x0FFF_FFF8,
+        }
+    }
+}
+
+/// Location of a directory entry
+#[derive(Debug, Copy, Clone)]
+struct Location {
+    lba: u32,
+    offset: usize,
+}
+
+/// Result of the search of a name in the root directory
+enum Lookup {
+    /// The entry of the name, with its first cluster and size
+    Found(Location, u32, u32),
+    /// A free entry, the name being absent
+    Free(Location),
+    /// No free entry, with the last cluster of the directory on FAT32
+    Full(Option<u32>),
+}
+
+/// A FAT16 or FAT32 volume on a block device
+pub struct Volume<D> {
+    device: D,
+    fat_type: FatType,
+    sectors_per_cluster: u32,
+    /// First sector of the first table
+    fat_start: u32,
+    /// Sectors of each table
+    fat_size: u32,
+    fat_count: u32,
+    /// First sector and sector count of the FAT16 root directory
+    root_start: u32,
+    root_sectors: u32,
+    /// First cluster of the FAT32 root directory
+    root_cluster: u32,
+    /// First sector of cluster 2
+    data_start: u32,
+    max_cluster: u32,
+    /// Sector of the FSInfo, until its free count is invalidated
+    fs_info: Option<u32>,
+    /// Cluster from which to look for a free cluster
+    next_free: u32,
+    /// Cache of the last table or directory sector accessed
+    buf: [u8; BLOCK_SIZE],
+    cached: Option<u32>,
+}
+
+impl<D: BlockDevice> Volume<D> {
+    /// Mount the volume of a device.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoFilesystem` if neither the device nor its first
+    /// partition holds a FAT volume, and `Error::Unsupported` if the volume
+    /// is FAT12.
+    pub async fn mount(mut device: D) -> Result<Self, Error<D::Error>> {
+        let mut buf = [0u8; BLOCK_SIZE];
+        device
+            .read_block(0, &mut buf)
+            .await
+            .map_err(Error::Device)?;
+
+        // Without a partition table, the device starts with the boot sector
+        let base = if is_boot_sector(&buf) {
+            0
+        } else if buf[510..512] == [0x55, 0xAA] {
+            let partition = &buf[446..462];
+            if !PARTITION_TYPES.contains(&partition[4]) {
+                return Err(Error::NoFilesystem);
+            }
+            let base = le32(partition, 8);
+            device
+                .read_block(base, &mut buf)
+                .await
+                .map_err(Error::Device)?;
+            if !is_boot_sector(&buf) {
+                return Err(Error::NoFilesystem);
+            }
+            base
+        } else {
+            return Err(Error::NoFilesystem);
+        };
+
+        // BIOS parameter block. See the Microsoft FAT specification section 3
+        // for more details.
+        let sectors_per_cluster = buf[13] as u32;
+        let reserved_sectors = le16(&buf, 14);
+        let fat_count = buf[16] as u32;
+        let root_entries = le16(&buf, 17);
+        let total_sectors = match le16(&buf, 19) {
+            0 => le32(&buf, 32),
+            sectors => sectors,
+        };
+        let fat_size = match le16(&buf, 22) {
+            0 => le32(&buf, 36),
+            sectors => sectors,
+        };
+        if !sectors_per_cluster.is_power_of_two() || fat_count == 0 || fat_size == 0 {
+            return Err(Error::NoFilesystem);
+        }
+
+        let root_sectors = (root_entries * DIR_ENTRY_SIZE as u32).div_ceil(BLOCK_SIZE as u32);
+        let data_offset = reserved_sectors + fat_count * fat_size + root_sectors;
+        if data_offset >= total_sectors {
+            return Err(Error::NoFilesystem);
+        }
+        let clusters = (total_sectors - data_offset) / sectors_per_cluster;
+        let fat_type = if clusters < FAT16_MIN_CLUSTERS {
+            return Err(Error::Unsupported);
+        } else if clusters < FAT32_MIN_CLUSTERS {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+
+        let (root_cluster, fs_info) = match fat_type {
+            FatType::Fat16 => (0, None),
+            FatType::Fat32 => {
+                let fs_info = match le16(&buf, 48) {
+                    0 | 0xFFFF => None,
+                    sector => Some(base + sector),
+                };
+                (le32(&buf, 44), fs_info)
+            }
+        };
+
+        let fat_start = base + reserved_sectors;
+        Ok(Self {
+            device,
+            fat_type,
+            sectors_per_cluster,
+            fat_start,
+            fat_size,
+            fat_count,
+            root_start: fat_start + fat_count * fat_size,
+            root_sectors,
+            root_cluster,
+            data_start: base + data_offset,
+            max_cluster: clusters + 1,
+            fs_info,
+            next_free: 2,
+            buf,
+            cached: None,
+        })
+    }
+
+    /// Release the underlying device
+    pub fn release(self) -> D {
+        self.device
+    }
+
+    pub fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+
+    /// Open a file of the root directory for appending, and create it if it
+    /// does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// - `name`: The 8.3 short name of the file, e.g. `LOG0001.CSV`. It is
+    ///   case insensitive.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidName` if the name is not a valid short name,
+    /// `Error::IsDirectory` if it names a directory, and
+    /// `Error::DirectoryFull` if the file cannot be created in the FAT16 root
+    /// directory.
+    pub async fn open_append(&mut self, name: &str) -> Result<File<'_, D>, Error<D::Error>> {
+        let name = short_name(name).ok_or(Error::InvalidName)?;
+
+        let (location, start, size) = match self.lookup(&name).await? {
+            Lookup::Found(location, start, size) => (location, start, size),
+            Lookup::Free(location) => {
+                self.create_entry(location, &name).await?;
+                (location, 0, 0)
+            }
+            Lookup::Full(None) => return Err(Error::DirectoryFull),
+            Lookup::Full(Some(last_cluster)) => {
+                // The FAT32 root directory grows by a cluster
+                let cluster = self.allocate_cluster(last_cluster).await?;
+                self.buf.fill(0);
+                for sector in 0..self.sectors_per_cluster {
+                    self.write(self.cluster_lba(cluster) + sector).await?;
+                }
+                let location = Location {
+                    lba: self.cluster_lba(cluster),
+                    offset: 0,
+                };
+                self.create_entry(location, &name).await?;
+                (location, 0, 0)
+            }
+        };
+
+        // Find the cluster holding the last byte of the file
+        let mut cluster = 0;
+        if size > 0 {
+            cluster = start;
+            for _ in 0..(size - 1) / self.cluster_size() {
+                cluster = self.next_cluster(cluster).await?.ok_or(Error::Corrupted)?;
+            }
+        }
+
+        // Load the partial last block, to append to it
+        let mut block = [0u8; BLOCK_SIZE];
+        if !(size as usize).is_multiple_of(BLOCK_SIZE) {
+            let lba =
+                self.cluster_lba(cluster) + (size - 1) % self.cluster_size() / BLOCK_SIZE as u32;
+            self.device
+                .read_block(lba, &mut block)
+                .await
+                .map_err(Error::Device)?;
+        }
+
+        Ok(File {
+            volume: self,
+            location,
+            start,
+            cluster,
+            size,
+            block,
+            dirty: false,
+        })
+    }
+
+    /// Search the root directory for a name.
+    async fn lookup(&mut self, name: &[u8; 11]) -> Result<Lookup, Error<D::Error>> {
+        let mut free = None;
+        let mut cluster = self.root_cluster;
+        let mut sector = 0;
+        loop {
+            let lba = match self.fat_type {
+                FatType::Fat16 => {
+                    if sector == self.root_sectors {
+                        return Ok(free.map_or(Lookup::Full(None), Lookup::Free));
+                    }
+                    self.root_start + sector
+                }
+                FatType::Fat32 => {
+                    if sector == self.sectors_per_cluster {
+                        match self.next_cluster(cluster).await? {
+                            Some(next) => cluster = next,
+                            None => {
+                                return Ok(free.map_or(Lookup::Full(Some(cluster)), Lookup::Free))
+                            }
+                        }
+                        sector = 0;
+                    }
+                    self.cluster_lba(cluster) + sector
+                }
+            };
+            self.read(lba).await?;
+
+            for offset in (0..BLOCK_SIZE).step_by(DIR_ENTRY_SIZE) {
+                let entry = &self.buf[offset..offset + DIR_ENTRY_SIZE];
+                let location = Location { lba, offset };
+                let attributes = entry[11];
+                match entry[0] {
+                    ENTRY_END => return Ok(Lookup::Free(free.unwrap_or(location))),
+                    ENTRY_DELETED => {
+                        free.get_or_insert(location);
+                    }
+                    _ if attributes & ATTR_LONG_NAME == ATTR_LONG_NAME => {}
+                    _ if attributes & ATTR_VOLUME_ID != 0 => {}
+                    _ if entry[..11] == name[..] => {
+                        if attributes & ATTR_DIRECTORY != 0 {
+                            return Err(Error::IsDirectory);
+                        }
+                        let start = (le16(entry, 20) << 16) | le16(entry, 26);
+                        return Ok(Lookup::Found(location, start, le32(entry, 28)));
+                    }
+                    _ => {}
+                }
+            }
+            sector += 1;
+        }
+    }
+
+    /// Write an empty file entry.
+    async fn create_entry(
+        &mut self,
+        location: Location,
+        name: &[u8; 11],
+    ) -> Result<(), Error<D::Error>> {
+        self.read(location.lba).await?;
+        let entry = &mut self.buf[location.offset..location.offset + DIR_ENTRY_SIZE];
+        entry.fill(0);
+        entry[..11].copy_from_slice(name);
+        entry[11] = ATTR_ARCHIVE;
+        let date = FAT_EPOCH_DATE.to_le_bytes();
+        entry[16..18].copy_from_slice(&date);
+        entry[18..20].copy_from_slice(&date);
+        entry[24..26].copy_from_slice(&date);
+        self.write(location.lba).await
+    }
+
+    /// Update the first cluster and size of a file entry.
+    async fn update_entry(
+        &mut self,
+        location: Location,
+        start: u32,
+        size: u32,
+    ) -> Result<(), Error<D::Error>> {
+        self.read(location.lba).await?;
+        let entry = &mut self.buf[location.offset..location.offset + DIR_ENTRY_SIZE];
+        entry[20..22].copy_from_slice(&((start >> 16) as u16).to_le_bytes());
+        entry[26..28].copy_from_slice(&(start as u16).to_le_bytes());
+        entry[28..32].copy_from_slice(&size.to_le_bytes());
+        self.write(location.lba).await
+    }
+
+    /// Find a free cluster, mark it as the end of a chain, and link it after
+    /// another cluster, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::VolumeFull` if there is no free cluster.
+    async fn allocate_cluster(&mut self, previous: u32) -> Result<u32, Error<D::Error>> {
+        let mut cluster = self.next_free;
+        for _ in 2..=self.max_cluster {
+            if cluster > self.max_cluster {
+                cluster = 2;
+            }
+            if self.fat_entry(cluster).await? == 0 {
+                self.set_fat_entry(cluster, self.fat_type.end_of_chain())
+                    .await?;
+                if previous != 0 {
+                    self.set_fat_entry(previous, cluster).await?;
+                }
+                self.next_free = cluster + 1;
+                self.invalidate_fs_info().await?;
+                return Ok(cluster);
+            }
+            cluster += 1;
+        }
+        Err(Error::VolumeFull)
+    }
+
+    /// Mark the free cluster count of the FSInfo as unknown, rather than
+    /// keeping it up to date.
+    async fn invalidate_fs_info(&mut self) -> Result<(), Error<D::Error>> {
+        let Some(lba) = self.fs_info.take() else {
+            return Ok(());
+        };
+        self.read(lba).await?;
+        if le32(&self.buf, 0) != FS_INFO_LEAD_SIGNATURE
+            || le32(&self.buf, 484) != FS_INFO_STRUCT_SIGNATURE
+        {
+            return Ok(());
+        }
+        self.buf[488..492].copy_from_slice(&FS_INFO_UNKNOWN.to_le_bytes());
+        self.buf[492..496].copy_from_slice(&FS_INFO_UNKNOWN.to_le_bytes());
+        self.write(lba).await
+    }
+
+    /// Get the cluster following another in its chain, if any.
+    async fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, Error<D::Error>> {
+        let next = self.fat_entry(cluster).await?;
+        if next >= self.fat_type.min_end_of_chain() {
+            Ok(None)
+        } else if next < 2 || next > self.max_cluster {
+            Err(Error::Corrupted)
+        } else {
+            Ok(Some(next))
+        }
+    }
+
+    async fn fat_entry(&mut self, cluster: u32) -> Result<u32, Error<D::Error>> {
+        let offset = cluster * self.fat_type.entry_size();
+        self.read(self.fat_start + offset / BLOCK_SIZE as u32)
+            .await?;
+        let i = offset as usize % BLOCK_SIZE;
+        Ok(match self.fat_type {
+            FatType::Fat16 => le16(&self.buf, i),
+            FatType::Fat32 => le32(&self.buf, i) & 0x0FFF_FFFF,
+        })
+    }
+
+    /// Set an entry in every copy of the table.
+    async fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), Error<D::Error>> {
+        let offset = cluster * self.fat_type.entry_size();
+        let i = offset as usize % BLOCK_SIZE;
+        for fat in 0..self.fat_count {
+            let lba = self.fat_start + fat * self.fat_size + offset / BLOCK_SIZE as u32;
+            self.read(lba).await?;
+            match self.fat_type {
+                FatType::Fat16 => {
+                    self.buf[i..i + 2].copy_from_slice(&(value as u16).to_le_bytes());
+                }
+                FatType::Fat32 => {
+                    // The upper 4 bits are reserved and kept
+                    let value = (le32(&self.buf, i) & 0xF000_0000) | value;
+                    self.buf[i..i + 4].copy_from_slice(&value.to_le_bytes());
+                }
+            }
+            self.write(lba).await?;
+        }
+        Ok(())
+    }
+
+    fn cluster_size(&self) -> u32 {
+        self.sectors_per_cluster * BLOCK_SIZE as u32
+    }
+
+    fn cluster_lba(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - 2) * self.sectors_per_cluster
+    }
+
+    /// Read a sector into the cache, unless it is already there.
+    async fn read(&mut self, lba: u32) -> Result<(), Error<D::Error>> {
+        if self.cached == Some(lba) {
+            return Ok(());
+        }
+        self.cached = None;
+        self.device
+            .read_block(lba, &mut self.buf)
+            .await
+            .map_err(Error::Device)?;
+        self.cached = Some(lba);
+        Ok(())
+    }
+
+    /// Write the cache to a sector.
+    async fn write(&mut self, lba: u32) -> Result<(), Error<D::Error>> {
+        self.cached = None;
+        self.device
+            .write_block(lba, &self.buf)
+            .await
+            .map_err(Error::Device)?;
+        self.cached = Some(lba);
+        Ok(())
+    }
+}
+
+/// A file of the root directory opened for appending
+///
+/// The data and size of the file only reach the device once flushed.
+pub struct File<'a, D> {
+    volume: &'a mut Volume<D>,
+    location: Location,
+    /// First cluster, 0 while the file is empty
+    start: u32,
+    /// Cluster holding the last byte, 0 while the file is empty
+    cluster: u32,
+    size: u32,
+    /// Last block of the file, partially filled
+    block: [u8; BLOCK_SIZE],
+    dirty: bool,
+}
+
+impl<D: BlockDevice> File<'_, D> {
+    /// Get the size of the file in bytes, including the data not yet flushed
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Append data to the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::VolumeFull` if there is no free cluster left, and
+    /// `Error::FileTooLarge` if the file would exceed 4 GiB.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), Error<D::Error>> {
+        if self.size.checked_add(data.len() as u32).is_none() || data.len() > u32::MAX as usize {
+            return Err(Error::FileTooLarge);
+        }
+
+        let cluster_size = self.volume.cluster_size();
+        let mut data = data;
+        while !data.is_empty() {
+            let offset = self.size as usize % BLOCK_SIZE;
+            if offset == 0 {
+                if self.size.is_multiple_of(cluster_size) {
+                    // Files created elsewhere may have a cluster while empty
+                    if self.size == 0 && self.start != 0 {
+                        self.cluster = self.start;
+                    } else {
+                        self.cluster = self.volume.allocate_cluster(self.cluster).await?;
+                        if self.start == 0 {
+                            self.start = self.cluster;
+                        }
+                    }
+                }
+                self.block.fill(0);
+            }
+
+            let len = data.len().min(BLOCK_SIZE - offset);
+            self.block[offset..offset + len].copy_from_slice(&data[..len]);
+            self.size += len as u32;
+            self.dirty = true;
+            data = &data[len..];
+
+            if (self.size as usize).is_multiple_of(BLOCK_SIZE) {
+                self.write_block().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the buffered data and the size of the file to the device.
+    pub async fn flush(&mut self) -> Result<(), Error<D::Error>> {
+        if self.dirty {
+            self.write_block().await?;
+        }
+        self.volume
+            .update_entry(self.location, self.start, self.size)
+            .await
+    }
+
+    /// Flush and close the file.
+    pub async fn close(mut self) -> Result<(), Error<D::Error>> {
+        self.flush().await
+    }
+
+    /// Write the last block of the file.
+    async fn write_block(&mut self) -> Result<(), Error<D::Error>> {
+        let within = (self.size - 1) % self.volume.cluster_size();
+        let lba = self.volume.cluster_lba(self.cluster) + within / BLOCK_SIZE as u32;
+        self.volume
+            .device
+            .write_block(lba, &self.block)
+            .await
+            .map_err(Error::Device)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Convert a name to its padded 8.3 form, e.g. `log1.csv` to
+/// `LOG1    CSV`.
+fn short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, extension) = name.split_once('.').unwrap_or((name, ""));
+    if base.is_empty() || base.len() > 8 || extension.len() > 3 {
+        return None;
+    }
+
+    let mut short = [b' '; 11];
+    let (base_slots, extension_slots) = short.split_at_mut(8);
+    let characters = base.bytes().zip(base_slots);
+    let extension = extension.bytes().zip(extension_slots);
+    for (c, slot) in characters.chain(extension) {
+        if !c.is_ascii_alphanumeric() && !b"!#$%&'()-@^_`{}~".contains(&c) {
+            return None;
+        }
+        *slot = c.to_ascii_uppercase();
+    }
+    Some(short)
+}
+
+/// Whether a sector is a FAT boot sector with 512-byte sectors
+fn is_boot_sector(buf: &[u8; BLOCK_SIZE]) -> bool {
+    buf[510..512] == [0x55, 0xAA] && matches!(buf[0], 0xEB | 0xE9) && le16(buf, 11) == 512
+}
+
+fn le16(buf: &[u8], i: usize) -> u32 {
+    u16::from_le_bytes([buf[i], buf[i + 1]]) as u32
+}
+
+fn le32(buf: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]])
+}
+
+/// All possible errors in this module
+///
+/// `E` is the error type of the underlying block device.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Block device error
+    Device(E),
+    /// The device holds no FAT volume.
+    NoFilesystem,
+    /// The volume is FAT12.
+    Unsupported,
+    /// The table or a directory is inconsistent.
+    Corrupted,
+    /// The name is not a valid 8.3 short name.
+    InvalidName,
+    /// The name is that of a directory.
+    IsDirectory,
+    /// The FAT16 root directory has no free entry.
+    DirectoryFull,
+    /// The volume has no free cluster.
+    VolumeFull,
+    /// The file would exceed 4 GiB.
+    FileTooLarge,
+}
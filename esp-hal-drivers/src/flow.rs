@@ -0,0 +1,133 @@
+//! # flow
+//!
+//! ## Overview
+//!
+//! This driver measures liquids with Hall-effect flow meters outputting a
+//! pulse per fraction of a turn of their rotor, such as the YF-S201.
+//!
+//! - The K-factor of the meter converts the pulses to a volume. The YF-S201
+//!   outputs 7.5 Hz per L/min, i.e. 450 pulses per liter.
+//! - The flow is measured by counting pulses over a window, and the volume
+//!   is accumulated since the creation of the meter or the last reset.
+//! - [FlowMeter::wait_for_volume] waits for a volume to flow, e.g. to fill
+//!   a tank or a glass.
+//!
+//! The ESP32-C3 has no pulse counter peripheral, so the driver awaits the
+//! pulses on GPIO interrupts. It only counts while a method is awaited, so it
+//! should run in a dedicated task.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let pin = Input::new(peripherals.GPIO5, Pull::Up);
+//! let mut meter = FlowMeter::new(pin, K_FACTOR_YF_S201);
+//!
+//! println!("{} L/min", meter.flow(Duration::from_secs(1)).await?.0);
+//!
+//! valve.set_high();
+//! meter.wait_for_volume(0.25).await?;
+//! valve.set_low();
+//! println!("{} mL poured", meter.volume_ml());
+//! ```
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::InputPin;
+use embedded_hal_async::digital::Wait;
+
+use crate::units::LitersPerMinute;
+
+/// K-factor of the YF-S201 in pulses per liter
+pub const K_FACTOR_YF_S201: u32 = 450;
+
+/// A pulse-output flow meter
+pub struct FlowMeter<P> {
+    pin: P,
+    /// Pulses per liter
+    k_factor: u32,
+    /// Pulses counted since the creation of the meter or the last reset
+    pulses: u64,
+}
+
+impl<P: InputPin + Wait> FlowMeter<P> {
+    /// Create a new flow meter.
+    ///
+    /// # Arguments
+    ///
+    /// - `pin`: The pin the output of the meter is connected to.
+    /// - `k_factor`: The number of pulses per liter.
+    pub fn new(pin: P, k_factor: u32) -> Self {
+        Self {
+            pin,
+            k_factor: k_factor.max(1),
+            pulses: 0,
+        }
+    }
+
+    /// Release the underlying pin
+    pub fn release(self) -> P {
+        self.pin
+    }
+
+    /// Get the number of pulses counted
+    pub fn pulses(&self) -> u64 {
+        self.pulses
+    }
+
+    /// Get the volume in mL that flowed since the creation of the meter or
+    /// the last reset
+    pub fn volume_ml(&self) -> u64 {
+        self.pulses * 1_000 / self.k_factor as u64
+    }
+
+    /// Reset the accumulated volume to 0.
+    pub fn reset_volume(&mut self) {
+        self.pulses = 0;
+    }
+
+    /// Measure the flow, counting pulses over a window.
+    ///
+    /// Longer windows are more precise at low flows.
+    pub async fn flow(&mut self, window: Duration) -> Result<LitersPerMinute, Error> {
+        let start = Instant::now();
+        let end = start + window;
+        let mut pulses = 0u32;
+
+        loop {
+            match select(Timer::at(end), self.pin.wait_for_rising_edge()).await {
+                Either::First(()) => break,
+                Either::Second(result) => result.map_err(|_| Error::Pin)?,
+            }
+            pulses += 1;
+            self.pulses += 1;
+        }
+
+        let elapsed_us = (Instant::now() - start).as_micros().max(1) as f32;
+        Ok(LitersPerMinute(
+            pulses as f32 * 60_000_000.0 / (elapsed_us * self.k_factor as f32),
+        ))
+    }
+
+    /// Wait for a volume in liters to flow, counted from now.
+    pub async fn wait_for_volume(&mut self, liters: f32) -> Result<(), Error> {
+        let pulses = libm::ceilf(liters * self.k_factor as f32) as u64;
+
+        for _ in 0..pulses {
+            self.pin
+                .wait_for_rising_edge()
+                .await
+                .map_err(|_| Error::Pin)?;
+            self.pulses += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// All possible errors in this driver
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Error of the pin
+    Pin,
+}
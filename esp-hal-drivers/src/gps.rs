@@ -0,0 +1,643 @@
+//! # gps
+//!
+//! ## Overview
+//!
+//! This module provides an abstraction to interact with GPS receivers that
+//! output NMEA 0183 sentences over UART, such as the u-blox NEO-6M or the
+//! Quectel L76.
+//!
+//! - The [Parser] turns the received bytes into sentences one byte at a
+//!   time, validating their checksum. GGA, RMC and GSV sentences from any
+//!   constellation are supported.
+//! - The [Gps] holds the latest [State] of the receiver: its fix, time,
+//!   date, speed and satellites. A background task runs [Gps::run] to update
+//!   it from the UART, while other tasks read it or wait for a fix.
+//!
+//! Positions are in 10⁻⁷ degrees, so that they keep the precision of the
+//! receiver without floating point.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! static GPS: Gps<CriticalSectionRawMutex> = Gps::new();
+//!
+//! #[embassy_executor::task]
+//! async fn gps_task(uart_rx: UartRx<'static, Async>) {
+//!     GPS.run(uart_rx).await.ok();
+//! }
+//!
+//! spawner.spawn(gps_task(uart_rx)).unwrap();
+//! let fix = GPS.wait_for_fix().await;
+//! println!("{} {}", fix.position.latitude, fix.position.longitude);
+//! ```
+
+use core::cell::Cell;
+
+use embassy_sync::{
+    blocking_mutex::{raw::RawMutex, Mutex},
+    signal::Signal,
+};
+use embedded_io_async::Read;
+
+/// Longest sentence allowed by NMEA 0183, from `$` to the checksum
+const MAX_SENTENCE_LEN: usize = 82;
+
+/// Number of satellites described by a GSV sentence at most
+const GSV_SATELLITES: usize = 4;
+
+/// Conversion factor from knots to millimeters per second, times 1000
+const KNOT_MM_PER_S_MILLI: i64 = 514_444;
+
+/// Quality of a fix, as reported by GGA sentences
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FixQuality {
+    /// No fix
+    #[default]
+    Invalid = 0,
+    /// Standalone GPS fix
+    Gps = 1,
+    /// Differential GPS fix, e.g. with SBAS
+    Dgps = 2,
+    /// PPS fix
+    Pps = 3,
+    /// Real time kinematic fix
+    Rtk = 4,
+    /// Float real time kinematic fix
+    FloatRtk = 5,
+    /// Dead reckoning
+    Estimated = 6,
+    /// Manual input
+    Manual = 7,
+    /// Simulation
+    Simulation = 8,
+}
+
+impl FixQuality {
+    fn from_digit(digit: u8) -> Option<Self> {
+        Some(match digit {
+            0 => FixQuality::Invalid,
+            1 => FixQuality::Gps,
+            2 => FixQuality::Dgps,
+            3 => FixQuality::Pps,
+            4 => FixQuality::Rtk,
+            5 => FixQuality::FloatRtk,
+            6 => FixQuality::Estimated,
+            7 => FixQuality::Manual,
+            8 => FixQuality::Simulation,
+            _ => return None,
+        })
+    }
+}
+
+/// UTC time of day
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Time {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub milliseconds: u16,
+}
+
+/// UTC date
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+/// Position on the WGS 84 ellipsoid
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Position {
+    /// Latitude in 10⁻⁷ degrees, positive to the north
+    pub latitude: i32,
+    /// Longitude in 10⁻⁷ degrees, positive to the east
+    pub longitude: i32,
+}
+
+/// A satellite in view
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Satellite {
+    /// Number of the satellite, whose range depends on its constellation
+    pub prn: u8,
+    /// Elevation in degrees
+    pub elevation: Option<u8>,
+    /// Azimuth in degrees from true north
+    pub azimuth: Option<u16>,
+    /// Signal to noise ratio in dB-Hz, if tracked
+    pub snr: Option<u8>,
+}
+
+/// Fix data of a GGA sentence
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Gga {
+    pub time: Option<Time>,
+    pub position: Option<Position>,
+    pub quality: FixQuality,
+    /// Number of satellites used by the fix
+    pub satellites: u8,
+    /// Horizontal dilution of precision in hundredths
+    pub hdop: Option<u16>,
+    /// Altitude above the mean sea level in millimeters
+    pub altitude: Option<i32>,
+}
+
+/// Recommended minimum data of an RMC sentence
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rmc {
+    pub time: Option<Time>,
+    /// Whether the receiver has a fix
+    pub valid: bool,
+    pub position: Option<Position>,
+    /// Speed over ground in millimeters per second
+    pub speed: Option<u32>,
+    /// Course over ground in hundredths of a degree from true north
+    pub course: Option<u16>,
+    pub date: Option<Date>,
+}
+
+/// Satellites in view of a GSV sentence, one of a group of sentences
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Gsv {
+    /// Number of sentences of the group
+    pub sentences: u8,
+    /// Number of this sentence in the group, from 1
+    pub sentence: u8,
+    /// Number of satellites in view of the constellation
+    pub satellites_in_view: u8,
+    pub satellites: [Option<Satellite>; GSV_SATELLITES],
+}
+
+/// A sentence received from the receiver
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Sentence {
+    Gga(Gga),
+    Rmc(Rmc),
+    Gsv(Gsv),
+}
+
+/// Streaming parser of NMEA 0183 sentences
+#[derive(Debug, Clone)]
+pub struct Parser {
+    buf: [u8; MAX_SENTENCE_LEN],
+    len: usize,
+    /// Whether a `$` started a sentence that has not overflowed
+    in_sentence: bool,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_SENTENCE_LEN],
+            len: 0,
+            in_sentence: false,
+        }
+    }
+
+    /// Feed a byte to the parser, and return the sentence it completes, if
+    /// any.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::Checksum` if the sentence is corrupted,
+    /// `ParseError::Malformed` if its fields are invalid, and
+    /// `ParseError::Unsupported` if it is not a GGA, RMC or GSV sentence.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Sentence, ParseError>> {
+        match byte {
+            b'$' => {
+                self.len = 0;
+                self.in_sentence = true;
+                None
+            }
+            b'\r' | b'\n' if self.in_sentence => {
+                self.in_sentence = false;
+                Some(parse_sentence(&self.buf[..self.len]))
+            }
+            _ if self.in_sentence => {
+                if self.len == MAX_SENTENCE_LEN {
+                    self.in_sentence = false;
+                } else {
+                    self.buf[self.len] = byte;
+                    self.len += 1;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parse a sentence, between its `$` and its end of line.
+fn parse_sentence(sentence: &[u8]) -> Result<Sentence, ParseError> {
+    // The checksum is the XOR of the characters before the `*`
+    let star = sentence
+        .iter()
+        .position(|&c| c == b'*')
+        .ok_or(ParseError::Checksum)?;
+    let (body, checksum) = (&sentence[..star], &sentence[star + 1..]);
+    let expected = body.iter().fold(0, |sum, c| sum ^ c);
+    if checksum.len() != 2 || parse_hex(checksum) != Some(expected) {
+        return Err(ParseError::Checksum);
+    }
+
+    // The address is a talker, e.g. `GP` or `GN`, and a sentence type
+    let mut fields = body.split(is_comma as fn(&u8) -> bool);
+    let address = fields.next().ok_or(ParseError::Malformed)?;
+    if address.len() != 5 {
+        return Err(ParseError::Malformed);
+    }
+    let mut fields = Fields(fields);
+    match &address[2..] {
+        b"GGA" => parse_gga(&mut fields).map(Sentence::Gga),
+        b"RMC" => parse_rmc(&mut fields).map(Sentence::Rmc),
+        b"GSV" => parse_gsv(&mut fields).map(Sentence::Gsv),
+        _ => Err(ParseError::Unsupported),
+    }
+}
+
+fn parse_gga(fields: &mut Fields<'_>) -> Result<Gga, ParseError> {
+    let time = parse_time(fields.next()?)?;
+    let position = parse_position(fields)?;
+    let quality = match fields.next()? {
+        [digit] => FixQuality::from_digit(digit.wrapping_sub(b'0')).ok_or(ParseError::Malformed)?,
+        _ => return Err(ParseError::Malformed),
+    };
+    let satellites = parse_fixed(fields.next()?, 0)?.unwrap_or(0) as u8;
+    let hdop = parse_fixed(fields.next()?, 2)?.map(|hdop| hdop as u16);
+    let altitude = parse_fixed(fields.next()?, 3)?.map(|altitude| altitude as i32);
+    Ok(Gga {
+        time,
+        position,
+        quality,
+        satellites,
+        hdop,
+        altitude,
+    })
+}
+
+fn parse_rmc(fields: &mut Fields<'_>) -> Result<Rmc, ParseError> {
+    let time = parse_time(fields.next()?)?;
+    let valid = fields.next()? == b"A";
+    let position = parse_position(fields)?;
+    let speed = parse_fixed(fields.next()?, 3)?
+        .map(|knots| (knots * KNOT_MM_PER_S_MILLI / 1_000_000) as u32);
+    let course = parse_fixed(fields.next()?, 2)?.map(|course| course as u16);
+    let date = match fields.next()? {
+        [] => None,
+        [d0, d1, m0, m1, y0, y1] => Some(Date {
+            day: digits([*d0, *d1])?,
+            month: digits([*m0, *m1])?,
+            year: 2000 + digits([*y0, *y1])? as u16,
+        }),
+        _ => return Err(ParseError::Malformed),
+    };
+    Ok(Rmc {
+        time,
+        valid,
+        position,
+        speed,
+        course,
+        date,
+    })
+}
+
+fn parse_gsv(fields: &mut Fields<'_>) -> Result<Gsv, ParseError> {
+    let mut gsv = Gsv {
+        sentences: parse_fixed(fields.next()?, 0)?.ok_or(ParseError::Malformed)? as u8,
+        sentence: parse_fixed(fields.next()?, 0)?.ok_or(ParseError::Malformed)? as u8,
+        satellites_in_view: parse_fixed(fields.next()?, 0)?.unwrap_or(0) as u8,
+        satellites: [None; GSV_SATELLITES],
+    };
+
+    // Up to 4 satellites, possibly followed by a signal ID
+    for satellite in gsv.satellites.iter_mut() {
+        let Some(prn) = fields.0.next() else {
+            break;
+        };
+        let Some(prn) = parse_fixed(prn, 0)? else {
+            break;
+        };
+        *satellite = Some(Satellite {
+            prn: prn as u8,
+            elevation: parse_fixed(fields.next()?, 0)?.map(|elevation| elevation as u8),
+            azimuth: parse_fixed(fields.next()?, 0)?.map(|azimuth| azimuth as u16),
+            snr: parse_fixed(fields.next()?, 0)?.map(|snr| snr as u8),
+        });
+    }
+    Ok(gsv)
+}
+
+/// Parse a time as `hhmmss.sss`.
+fn parse_time(field: &[u8]) -> Result<Option<Time>, ParseError> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    let [h0, h1, m0, m1, s0, s1, ..] = *field else {
+        return Err(ParseError::Malformed);
+    };
+    // Whole seconds are parsed again along with their fraction
+    let milliseconds = match &field[6..] {
+        [] => 0,
+        [b'.', ..] => (parse_fixed(&field[4..], 3)?.unwrap_or(0) % 1000) as u16,
+        _ => return Err(ParseError::Malformed),
+    };
+    Ok(Some(Time {
+        hours: digits([h0, h1])?,
+        minutes: digits([m0, m1])?,
+        seconds: digits([s0, s1])?,
+        milliseconds,
+    }))
+}
+
+/// Parse a latitude as `ddmm.mmmm,N` and a longitude as `dddmm.mmmm,E`.
+fn parse_position(fields: &mut Fields<'_>) -> Result<Option<Position>, ParseError> {
+    let latitude = parse_coordinate(fields.next()?, fields.next()?, b'S')?;
+    let longitude = parse_coordinate(fields.next()?, fields.next()?, b'W')?;
+    Ok(match (latitude, longitude) {
+        (Some(latitude), Some(longitude)) => Some(Position {
+            latitude,
+            longitude,
+        }),
+        _ => None,
+    })
+}
+
+/// Parse a coordinate in degrees and minutes into 10⁻⁷ degrees.
+fn parse_coordinate(
+    value: &[u8],
+    hemisphere: &[u8],
+    negative: u8,
+) -> Result<Option<i32>, ParseError> {
+    // Minutes in 10⁻⁵ minutes
+    let Some(value) = parse_fixed(value, 5)? else {
+        return Ok(None);
+    };
+    let degrees = value / 10_000_000;
+    let minutes = value % 10_000_000;
+    let coordinate = degrees * 10_000_000 + minutes * 100 / 60;
+    Ok(Some(match hemisphere {
+        [c] if *c == negative => -coordinate as i32,
+        _ => coordinate as i32,
+    }))
+}
+
+/// Parse a decimal number scaled by `10^decimals`, with its extra decimals
+/// truncated, or `None` if the field is empty.
+fn parse_fixed(field: &[u8], decimals: u32) -> Result<Option<i64>, ParseError> {
+    let (negative, field) = match field {
+        [] => return Ok(None),
+        [b'-', rest @ ..] => (true, rest),
+        _ => (false, field),
+    };
+
+    let mut value: i64 = 0;
+    let mut fraction_digits = None;
+    for &c in field {
+        match c {
+            b'.' if fraction_digits.is_none() => fraction_digits = Some(0),
+            b'0'..=b'9' => {
+                if fraction_digits == Some(decimals) {
+                    continue;
+                }
+                value = value
+                    .checked_mul(10)
+                    .and_then(|value| value.checked_add((c - b'0') as i64))
+                    .ok_or(ParseError::Malformed)?;
+                if let Some(digits) = fraction_digits.as_mut() {
+                    *digits += 1;
+                }
+            }
+            _ => return Err(ParseError::Malformed),
+        }
+    }
+
+    let scale = 10i64.pow(decimals - fraction_digits.unwrap_or(0));
+    let value = value.checked_mul(scale).ok_or(ParseError::Malformed)?;
+    Ok(Some(if negative { -value } else { value }))
+}
+
+fn parse_hex(field: &[u8]) -> Option<u8> {
+    let text = core::str::from_utf8(field).ok()?;
+    u8::from_str_radix(text, 16).ok()
+}
+
+fn digits([tens, units]: [u8; 2]) -> Result<u8, ParseError> {
+    if !tens.is_ascii_digit() || !units.is_ascii_digit() {
+        return Err(ParseError::Malformed);
+    }
+    Ok((tens - b'0') * 10 + units - b'0')
+}
+
+fn is_comma(c: &u8) -> bool {
+    *c == b','
+}
+
+/// Fields of a sentence, missing fields being malformed
+struct Fields<'a>(core::slice::Split<'a, u8, fn(&u8) -> bool>);
+
+impl<'a> Fields<'a> {
+    fn next(&mut self) -> Result<&'a [u8], ParseError> {
+        self.0.next().ok_or(ParseError::Malformed)
+    }
+}
+
+/// A fix of the receiver
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Fix {
+    pub quality: FixQuality,
+    pub position: Position,
+    /// Altitude above the mean sea level in millimeters
+    pub altitude: Option<i32>,
+    /// Number of satellites used by the fix
+    pub satellites: u8,
+    /// Horizontal dilution of precision in hundredths
+    pub hdop: Option<u16>,
+}
+
+/// Latest state of the receiver
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct State {
+    pub fix: Option<Fix>,
+    pub time: Option<Time>,
+    pub date: Option<Date>,
+    /// Speed over ground in millimeters per second
+    pub speed: Option<u32>,
+    /// Course over ground in hundredths of a degree from true north
+    pub course: Option<u16>,
+    /// Number of satellites in view, summed over the constellations
+    pub satellites_in_view: u8,
+    /// Satellites in view counted by the GSV sentences of the current epoch
+    satellites_in_view_partial: Option<u8>,
+}
+
+impl State {
+    const fn new() -> Self {
+        Self {
+            fix: None,
+            time: None,
+            date: None,
+            speed: None,
+            course: None,
+            satellites_in_view: 0,
+            satellites_in_view_partial: None,
+        }
+    }
+
+    /// Update the state with a sentence.
+    pub fn update(&mut self, sentence: &Sentence) {
+        match sentence {
+            Sentence::Gga(gga) => {
+                self.time = gga.time.or(self.time);
+                self.fix = match (gga.quality, gga.position) {
+                    (FixQuality::Invalid, _) | (_, None) => None,
+                    (quality, Some(position)) => Some(Fix {
+                        quality,
+                        position,
+                        altitude: gga.altitude,
+                        satellites: gga.satellites,
+                        hdop: gga.hdop,
+                    }),
+                };
+
+                // The fix data start a new epoch, so the satellites counted
+                // during the last one are complete
+                if let Some(satellites) = self.satellites_in_view_partial.take() {
+                    self.satellites_in_view = satellites;
+                }
+            }
+            Sentence::Rmc(rmc) => {
+                self.time = rmc.time.or(self.time);
+                self.date = rmc.date.or(self.date);
+                if !rmc.valid {
+                    self.fix = None;
+                } else if let (Some(fix), Some(position)) = (self.fix.as_mut(), rmc.position) {
+                    fix.position = position;
+                }
+                self.speed = rmc.speed;
+                self.course = rmc.course;
+            }
+            Sentence::Gsv(gsv) => {
+                // Every constellation sends its own group of sentences, each
+                // with the number of satellites in view of the constellation
+                if gsv.sentence == 1 {
+                    let partial = self.satellites_in_view_partial.get_or_insert(0);
+                    *partial = partial.saturating_add(gsv.satellites_in_view);
+                }
+            }
+        }
+    }
+}
+
+/// A GPS receiver whose state is shared between tasks
+///
+/// `M` is the mutex protecting the state, e.g. `CriticalSectionRawMutex`.
+pub struct Gps<M: RawMutex> {
+    state: Mutex<M, Cell<State>>,
+    fix: Signal<M, Fix>,
+}
+
+impl<M: RawMutex> Default for Gps<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: RawMutex> Gps<M> {
+    /// Create a new receiver, without a fix.
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(Cell::new(State::new())),
+            fix: Signal::new(),
+        }
+    }
+
+    /// Get the latest state of the receiver
+    pub fn state(&self) -> State {
+        self.state.lock(Cell::get)
+    }
+
+    /// Wait for the receiver to have a fix and return it.
+    ///
+    /// Returns immediately if the receiver has a fix. Only one task can wait
+    /// at a time.
+    pub async fn wait_for_fix(&self) -> Fix {
+        if let Some(fix) = self.state().fix {
+            return fix;
+        }
+        self.fix.reset();
+        self.fix.wait().await
+    }
+
+    /// Read the sentences of the receiver from a UART and update the state.
+    ///
+    /// Sentences that are corrupted or not supported are ignored.
+    ///
+    /// Only returns if an error occurs.
+    pub async fn run<R: Read>(&self, mut uart: R) -> Result<(), Error<R::Error>> {
+        let mut parser = Parser::new();
+        let mut buf = [0u8; 64];
+        loop {
+            let len = uart.read(&mut buf).await.map_err(Error::Uart)?;
+            if len == 0 {
+                return Err(Error::Eof);
+            }
+
+            for &byte in &buf[..len] {
+                let Some(Ok(sentence)) = parser.push(byte) else {
+                    continue;
+                };
+                let state = self.state.lock(|state| {
+                    let mut value = state.get();
+                    value.update(&sentence);
+                    state.set(value);
+                    value
+                });
+                if let Some(fix) = state.fix {
+                    self.fix.signal(fix);
+                }
+            }
+        }
+    }
+}
+
+/// Errors of a sentence
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParseError {
+    /// The checksum is missing or wrong.
+    Checksum,
+    /// A field is missing or invalid.
+    Malformed,
+    /// The sentence is not a GGA, RMC or GSV sentence.
+    Unsupported,
+}
+
+/// All possible errors in this module
+///
+/// `E` is the error type of the underlying UART.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// UART error
+    Uart(E),
+    /// The UART has no more data.
+    Eof,
+}
@@ -0,0 +1,176 @@
+//! # hcsr04
+//!
+//! ## Overview
+//!
+//! This driver measures distances with HC-SR04 ultrasonic sensors.
+//!
+//! A pulse on the trigger pin makes the sensor send an ultrasonic burst, then
+//! hold its echo pin high until the echo comes back. The driver timestamps
+//! the edges of the echo pulse on GPIO interrupts rather than busy-waiting,
+//! so other tasks keep running during the up to 25 ms measurement.
+//!
+//! - The speed of sound depends on the temperature of the air, which can be
+//!   given to the driver to compensate for it, e.g. from a [DHT](crate::dht).
+//! - Single measurements are noisy, so [HcSr04::measure_median] returns the
+//!   median of several of them.
+//!
+//! The sensor needs a 5 V supply, and its echo output must be divided down to
+//! 3.3 V before it reaches the ESP32-C3.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let trigger = Output::new(peripherals.GPIO5, Level::Low);
+//! let echo = Input::new(peripherals.GPIO6, Pull::None);
+//! let mut sensor = HcSr04::new(trigger, echo).with_temperature(Celsius(25.0));
+//!
+//! let distance = sensor.measure().await?;
+//! let filtered = sensor.measure_median::<5>().await?;
+//! println!("{} mm / {} mm", distance.0, filtered.0);
+//! ```
+
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::digital::Wait;
+
+use crate::units::{Celsius, Millimeters};
+
+/// Length of the trigger pulse
+const TRIGGER_TIME: Duration = Duration::from_micros(10);
+
+/// Maximum time between the trigger and the start of the echo pulse
+const ECHO_START_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Maximum length of the echo pulse, a round trip of about 4 m which is the
+/// range of the sensor
+const ECHO_TIMEOUT: Duration = Duration::from_millis(25);
+
+/// Minimum time between two measurements, for the echoes of the previous
+/// burst to fade out
+const MIN_INTERVAL: Duration = Duration::from_millis(60);
+
+/// An HC-SR04 sensor on two GPIOs
+pub struct HcSr04<TRIG, ECHO> {
+    trigger: TRIG,
+    echo: ECHO,
+    temperature: Celsius,
+    ready_at: Instant,
+}
+
+impl<TRIG: OutputPin, ECHO: Wait> HcSr04<TRIG, ECHO> {
+    /// Create a new sensor, assuming air at 20 °C.
+    ///
+    /// # Arguments
+    ///
+    /// - `trigger`: The pin the trigger input of the sensor is connected to.
+    /// - `echo`: The pin the echo output of the sensor is connected to.
+    pub fn new(mut trigger: TRIG, echo: ECHO) -> Self {
+        let _ = trigger.set_low();
+        Self {
+            trigger,
+            echo,
+            temperature: Celsius(20.0),
+            ready_at: Instant::now(),
+        }
+    }
+
+    /// Set the temperature of the air used to compute the speed of sound.
+    pub fn with_temperature(mut self, temperature: Celsius) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    /// Set the temperature of the air used to compute the speed of sound.
+    pub fn set_temperature(&mut self, temperature: Celsius) {
+        self.temperature = temperature;
+    }
+
+    /// Release the underlying pins
+    pub fn release(self) -> (TRIG, ECHO) {
+        (self.trigger, self.echo)
+    }
+
+    /// Measure the distance to the nearest obstacle.
+    ///
+    /// Waits for the minimum interval since the last measurement to elapse
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::Timeout] if no echo came back, which happens when the
+    /// obstacle is out of range or absorbs the burst.
+    pub async fn measure(&mut self) -> Result<Millimeters, Error> {
+        Timer::at(self.ready_at).await;
+
+        let result = self.echo_time().await;
+        self.ready_at = Instant::now() + MIN_INTERVAL;
+        let echo_time = result?;
+
+        // The burst travels to the obstacle and back
+        let speed = self.speed_of_sound();
+        let distance = echo_time.as_micros() as f32 * speed / 1000.0 / 2.0;
+        Ok(Millimeters(distance as u32))
+    }
+
+    /// Measure the distance `N` times and return the median, ignoring the
+    /// measurements without an echo.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::Timeout] if none of the measurements got an echo.
+    pub async fn measure_median<const N: usize>(&mut self) -> Result<Millimeters, Error> {
+        let mut distances = [Millimeters(0); N];
+        let mut len = 0;
+        for _ in 0..N {
+            match self.measure().await {
+                Ok(distance) => {
+                    distances[len] = distance;
+                    len += 1;
+                }
+                Err(Error::Timeout) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if len == 0 {
+            return Err(Error::Timeout);
+        }
+        let distances = &mut distances[..len];
+        distances.sort_unstable();
+        Ok(distances[len / 2])
+    }
+
+    /// Speed of sound in the air in mm/µs
+    fn speed_of_sound(&self) -> f32 {
+        (331.3 + 0.606 * self.temperature.0) / 1000.0
+    }
+
+    /// Trigger a burst and return the length of the echo pulse.
+    async fn echo_time(&mut self) -> Result<Duration, Error> {
+        self.trigger.set_high().map_err(|_| Error::Pin)?;
+        Timer::after(TRIGGER_TIME).await;
+        self.trigger.set_low().map_err(|_| Error::Pin)?;
+
+        with_timeout(ECHO_START_TIMEOUT, self.echo.wait_for_rising_edge())
+            .await
+            .map_err(|_| Error::Timeout)?
+            .map_err(|_| Error::Pin)?;
+        let start = Instant::now();
+
+        with_timeout(ECHO_TIMEOUT, self.echo.wait_for_falling_edge())
+            .await
+            .map_err(|_| Error::Timeout)?
+            .map_err(|_| Error::Pin)?;
+        Ok(Instant::now() - start)
+    }
+}
+
+/// All possible errors in this driver
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A pin could not be driven or read.
+    Pin,
+    /// No echo came back in time.
+    Timeout,
+}
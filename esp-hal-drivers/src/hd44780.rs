@@ -0,0 +1,269 @@
+//! # hd44780
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with HD44780 character
+//! LCDs through the common [PCF8574](crate::pcf8574) I2C backpack, which
+//! drives the LCD in 4-bit mode.
+//!
+//! The backpack wires RS to P0, RW to P1, E to P2, the backlight to P3 and
+//! D4-D7 to P4-P7.
+//!
+//! Text can be printed directly with [Hd44780::print], or formatted with the
+//! `write!` macro into a pending buffer which is then sent by
+//! [Hd44780::flush].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let expander = Pcf8574::new(i2c, pcf8574::BASE_ADDRESS + 7);
+//! let mut lcd = Hd44780::new(expander, Lines::Two).await?;
+//!
+//! lcd.print("Hello, world!").await?;
+//!
+//! // Upload a degree sign and use it
+//! lcd.upload_glyph(0, &[0x06, 0x09, 0x09, 0x06, 0x00, 0x00, 0x00, 0x00]).await?;
+//! lcd.set_cursor(0, 1).await?;
+//! write!(lcd, "{:.1}\x00C", temperature)?;
+//! lcd.flush().await?;
+//! ```
+
+use core::fmt;
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use crate::pcf8574::{Error, Pcf8574};
+
+/// Pins of the backpack
+const RS: u8 = 1 << 0;
+const ENABLE: u8 = 1 << 2;
+const BACKLIGHT: u8 = 1 << 3;
+
+/// Size of the display data RAM, which bounds the pending text
+const DDRAM_SIZE: usize = 80;
+
+/// Time for the controller to start up after power-on
+const POWER_ON_TIME: Duration = Duration::from_millis(50);
+
+/// Time taken by the clear and home commands
+const CLEAR_TIME: Duration = Duration::from_millis(2);
+
+/// Address of the first character of each line in the display data RAM
+const LINE_ADDRESSES: [u8; 4] = [0x00, 0x40, 0x14, 0x54];
+
+/// Commands of the controller. See datasheet table 6 for more details.
+struct Command;
+
+impl Command {
+    const CLEAR: u8 = 0x01;
+    const HOME: u8 = 0x02;
+    const ENTRY_MODE: u8 = 0x04;
+    const DISPLAY_CONTROL: u8 = 0x08;
+    const FUNCTION_SET: u8 = 0x20;
+    const SET_CGRAM_ADDRESS: u8 = 0x40;
+    const SET_DDRAM_ADDRESS: u8 = 0x80;
+}
+
+/// Flags of the entry mode command
+const ENTRY_INCREMENT: u8 = 1 << 1;
+
+/// Flags of the display control command
+const DISPLAY_ON: u8 = 1 << 2;
+const CURSOR_ON: u8 = 1 << 1;
+const BLINK_ON: u8 = 1 << 0;
+
+/// Flag of the function set command selecting two display lines
+const FUNCTION_TWO_LINES: u8 = 1 << 3;
+
+/// Number of lines of the display
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Lines {
+    One,
+    /// Also used by 4-line displays, which are two lines split in halves.
+    Two,
+}
+
+/// An HD44780 LCD on a PCF8574 backpack
+pub struct Hd44780<I2C> {
+    expander: Pcf8574<I2C>,
+    backlight: u8,
+    display_control: u8,
+    /// Formatted text waiting to be sent
+    pending: [u8; DDRAM_SIZE],
+    pending_len: usize,
+}
+
+impl<I2C: I2c> Hd44780<I2C> {
+    /// Create a new LCD, initialize it in 4-bit mode and clear it.
+    ///
+    /// # Arguments
+    ///
+    /// - `expander`: The backpack the LCD is on.
+    /// - `lines`: The number of lines of the display.
+    pub async fn new(expander: Pcf8574<I2C>, lines: Lines) -> Result<Self, Error<I2C::Error>> {
+        let mut lcd = Self {
+            expander,
+            backlight: BACKLIGHT,
+            display_control: DISPLAY_ON,
+            pending: [0; DDRAM_SIZE],
+            pending_len: 0,
+        };
+
+        // Switch to 4-bit mode from any state. See datasheet figure 24 for
+        // more details.
+        Timer::after(POWER_ON_TIME).await;
+        for delay in [4_100, 100, 100] {
+            lcd.write_nibble(0x03, 0).await?;
+            Timer::after(Duration::from_micros(delay)).await;
+        }
+        lcd.write_nibble(0x02, 0).await?;
+
+        let lines = match lines {
+            Lines::One => 0,
+            Lines::Two => FUNCTION_TWO_LINES,
+        };
+        lcd.command(Command::FUNCTION_SET | lines).await?;
+        lcd.command(Command::DISPLAY_CONTROL | lcd.display_control)
+            .await?;
+        lcd.command(Command::ENTRY_MODE | ENTRY_INCREMENT).await?;
+        lcd.clear().await?;
+        Ok(lcd)
+    }
+
+    /// Release the underlying backpack
+    pub fn release(self) -> Pcf8574<I2C> {
+        self.expander
+    }
+
+    /// Clear the display and move the cursor home.
+    pub async fn clear(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.command(Command::CLEAR).await?;
+        Timer::after(CLEAR_TIME).await;
+        Ok(())
+    }
+
+    /// Move the cursor to the first character of the display.
+    pub async fn home(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.command(Command::HOME).await?;
+        Timer::after(CLEAR_TIME).await;
+        Ok(())
+    }
+
+    /// Move the cursor to a character.
+    ///
+    /// # Arguments
+    ///
+    /// - `column`: The column of the character, from 0.
+    /// - `line`: The line of the character, from 0 to 3.
+    pub async fn set_cursor(&mut self, column: u8, line: u8) -> Result<(), Error<I2C::Error>> {
+        let address = LINE_ADDRESSES[(line & 0x03) as usize] + column;
+        self.command(Command::SET_DDRAM_ADDRESS | address).await
+    }
+
+    /// Show or hide the cursor, optionally blinking.
+    pub async fn set_cursor_visible(
+        &mut self,
+        visible: bool,
+        blink: bool,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.display_control = DISPLAY_ON;
+        if visible {
+            self.display_control |= CURSOR_ON;
+        }
+        if blink {
+            self.display_control |= BLINK_ON;
+        }
+        self.command(Command::DISPLAY_CONTROL | self.display_control)
+            .await
+    }
+
+    pub async fn set_backlight(&mut self, on: bool) -> Result<(), Error<I2C::Error>> {
+        self.backlight = if on { BACKLIGHT } else { 0 };
+        self.expander.write(self.backlight).await
+    }
+
+    /// Upload a custom glyph, printed with the characters `'\x00'` to
+    /// `'\x07'`.
+    ///
+    /// # Arguments
+    ///
+    /// - `slot`: The slot of the glyph, from 0 to 7.
+    /// - `rows`: The 8 rows of the glyph, top first, where the 5 low bits are
+    ///   the pixels.
+    pub async fn upload_glyph(
+        &mut self,
+        slot: u8,
+        rows: &[u8; 8],
+    ) -> Result<(), Error<I2C::Error>> {
+        self.command(Command::SET_CGRAM_ADDRESS | ((slot & 0x07) << 3))
+            .await?;
+        for row in rows {
+            self.write_byte(row & 0x1F, RS).await?;
+        }
+        // Go back to the display data RAM, at the first character
+        self.command(Command::SET_DDRAM_ADDRESS).await
+    }
+
+    /// Print text at the cursor. Characters outside of ASCII are printed as
+    /// `?`.
+    pub async fn print(&mut self, text: &str) -> Result<(), Error<I2C::Error>> {
+        for c in text.chars() {
+            self.write_byte(char_code(c), RS).await?;
+        }
+        Ok(())
+    }
+
+    /// Print the text formatted with `write!` since the last flush at the
+    /// cursor.
+    pub async fn flush(&mut self) -> Result<(), Error<I2C::Error>> {
+        let (pending, len) = (self.pending, self.pending_len);
+        self.pending_len = 0;
+        for byte in &pending[..len] {
+            self.write_byte(*byte, RS).await?;
+        }
+        Ok(())
+    }
+
+    async fn command(&mut self, command: u8) -> Result<(), Error<I2C::Error>> {
+        self.write_byte(command, 0).await
+    }
+
+    /// Write a byte as two nibbles, high first.
+    async fn write_byte(&mut self, byte: u8, mode: u8) -> Result<(), Error<I2C::Error>> {
+        self.write_nibble(byte >> 4, mode).await?;
+        self.write_nibble(byte & 0x0F, mode).await
+    }
+
+    /// Write a nibble on D4-D7 and latch it with a pulse of E. An I2C write
+    /// outlasts both the pulse and the execution time of most commands.
+    async fn write_nibble(&mut self, nibble: u8, mode: u8) -> Result<(), Error<I2C::Error>> {
+        let value = (nibble << 4) | self.backlight | mode;
+        self.expander.write(value | ENABLE).await?;
+        self.expander.write(value).await
+    }
+}
+
+impl<I2C> fmt::Write for Hd44780<I2C> {
+    /// Append text to the pending text, sent by [Hd44780::flush].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.pending_len == DDRAM_SIZE {
+                return Err(fmt::Error);
+            }
+            self.pending[self.pending_len] = char_code(c);
+            self.pending_len += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Character code of a character in the ROM of the controller
+fn char_code(c: char) -> u8 {
+    if c.is_ascii() {
+        c as u8
+    } else {
+        b'?'
+    }
+}
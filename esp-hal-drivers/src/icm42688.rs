@@ -0,0 +1,655 @@
+//! # icm42688
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the TDK InvenSense
+//! ICM-42688-P 6-axis accelerometer and gyroscope over I2C or SPI.
+//!
+//! The sensor samples at up to 32 kHz with far less noise than the
+//! [MPU6050](crate::mpu6050), which suits balancing robots and flight
+//! controllers. At such rates, samples are best read in batches from its
+//! 2 kB FIFO, over SPI at up to 24 MHz.
+//!
+//! - The output data rate and the bandwidth of the low-pass filters are
+//!   configured together for the accelerometer and the gyroscope, so that
+//!   their samples stay paired in the FIFO.
+//! - The FIFO can assert the INT1 pin once it holds a number of samples, so
+//!   that [Icm42688::wait_for_fifo] wakes up once per batch.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let spi = SpiDevice::new(spi_bus, cs);
+//! let config = Config::new()
+//!     .with_odr(Odr::Hz2k)
+//!     .with_gyro_range(GyroRange::Dps1000);
+//! let mut imu = Icm42688::new(SpiInterface::new(spi), config)
+//!     .await?
+//!     .with_interrupt_pin(Input::new(peripherals.GPIO5, Pull::Down));
+//!
+//! // Wake up every 32 samples
+//! imu.enable_fifo(true).await?;
+//! imu.set_fifo_watermark(Some(32)).await?;
+//!
+//! let mut samples = [Sample::default(); 32];
+//! loop {
+//!     let len = imu.wait_for_fifo(&mut samples).await?;
+//!     controller.update(&samples[..len]);
+//! }
+//! ```
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::{
+    digital::Wait,
+    i2c::I2c,
+    spi::{Operation, SpiDevice},
+};
+
+use crate::{
+    imu::{Sample, Vector3},
+    units::Celsius,
+};
+
+/// I2C address of the sensor when AP_AD0 is tied to ground
+pub const ADDRESS_AD0_LOW: u8 = 0x68;
+
+/// I2C address of the sensor when AP_AD0 is tied to the supply
+pub const ADDRESS_AD0_HIGH: u8 = 0x69;
+
+/// Value of the WHO_AM_I register
+const DEVICE_ID: u8 = 0x47;
+
+/// Time taken by a soft reset
+const RESET_TIME: Duration = Duration::from_millis(1);
+
+/// Time for the gyroscope to start up once enabled
+const GYRO_STARTUP_TIME: Duration = Duration::from_millis(45);
+
+/// Bytes of a sample in the FIFO: a header, the accelerometer, the
+/// gyroscope, the temperature and a timestamp
+const FIFO_SAMPLE_SIZE: usize = 16;
+
+/// Samples read from the FIFO per transaction
+const FIFO_BATCH: usize = 8;
+
+/// Flag of a FIFO header marking an empty FIFO
+const FIFO_HEADER_EMPTY: u8 = 1 << 7;
+
+/// Registers of the sensor, all in bank 0. See datasheet section 14 for more
+/// details.
+struct Register;
+
+impl Register {
+    const DEVICE_CONFIG: u8 = 0x11;
+    const INT_CONFIG: u8 = 0x14;
+    const FIFO_CONFIG: u8 = 0x16;
+    const TEMP_DATA1: u8 = 0x1D;
+    const ACCEL_DATA_X1: u8 = 0x1F;
+    const INT_STATUS: u8 = 0x2D;
+    const FIFO_COUNTH: u8 = 0x2E;
+    const FIFO_DATA: u8 = 0x30;
+    const SIGNAL_PATH_RESET: u8 = 0x4B;
+    const PWR_MGMT0: u8 = 0x4E;
+    const GYRO_CONFIG0: u8 = 0x4F;
+    const ACCEL_CONFIG0: u8 = 0x50;
+    const GYRO_ACCEL_CONFIG0: u8 = 0x52;
+    const FIFO_CONFIG1: u8 = 0x5F;
+    const FIFO_CONFIG2: u8 = 0x60;
+    const FIFO_CONFIG3: u8 = 0x61;
+    const INT_CONFIG1: u8 = 0x64;
+    const INT_SOURCE0: u8 = 0x65;
+    const WHO_AM_I: u8 = 0x75;
+}
+
+/// Flag of the device configuration register
+const DEVICE_SOFT_RESET: u8 = 1 << 0;
+
+/// Flags of the interrupt configuration register, making INT1 latched,
+/// push-pull and active high
+const INT1_LATCHED_PUSH_PULL_HIGH: u8 = 0x07;
+
+/// Flags of the interrupt status register
+const INT_STATUS_FIFO_FULL: u8 = 1 << 1;
+
+/// Flags of the INT1 sources register
+const INT1_DATA_READY: u8 = 1 << 3;
+const INT1_FIFO_THRESHOLD: u8 = 1 << 2;
+
+/// Modes of the FIFO configuration register
+const FIFO_MODE_BYPASS: u8 = 0b00 << 6;
+const FIFO_MODE_STOP_ON_FULL: u8 = 0b10 << 6;
+
+/// Flags of the FIFO configuration 1 register, storing the gyroscope and the
+/// accelerometer
+const FIFO_GYRO_AND_ACCEL: u8 = 0b11;
+
+/// Flag of the signal path reset register
+const SIGNAL_PATH_FIFO_FLUSH: u8 = 1 << 1;
+
+/// Accelerometer and gyroscope in low noise mode
+const PWR_LOW_NOISE: u8 = 0x0F;
+
+/// Access to the registers of the sensor over a bus
+#[allow(async_fn_in_trait)]
+pub trait Interface {
+    /// Error of the underlying bus
+    type Error;
+
+    /// Read consecutive registers, starting from `register`.
+    async fn read_registers(&mut self, register: u8, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Self::Error>;
+}
+
+/// The sensor on an I2C bus
+pub struct I2cInterface<I2C> {
+    address: u8,
+    i2c: I2C,
+}
+
+impl<I2C: I2c> I2cInterface<I2C> {
+    /// Create a new interface.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the sensor is on.
+    /// - `address`: The I2C address of the sensor, [ADDRESS_AD0_LOW] or
+    ///   [ADDRESS_AD0_HIGH].
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self { address, i2c }
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+impl<I2C: I2c> Interface for I2cInterface<I2C> {
+    type Error = I2C::Error;
+
+    async fn read_registers(&mut self, register: u8, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.i2c.write_read(self.address, &[register], buf).await
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+        self.i2c.write(self.address, &[register, value]).await
+    }
+}
+
+/// The sensor on an SPI bus, in mode 0 or 3
+pub struct SpiInterface<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: SpiDevice> SpiInterface<SPI> {
+    pub fn new(spi: SPI) -> Self {
+        Self { spi }
+    }
+
+    /// Release the underlying SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+}
+
+impl<SPI: SpiDevice> Interface for SpiInterface<SPI> {
+    type Error = SPI::Error;
+
+    async fn read_registers(&mut self, register: u8, buf: &mut [u8]) -> Result<(), Self::Error> {
+        // The most significant bit of the address selects a read
+        self.spi
+            .transaction(&mut [Operation::Write(&[register | 0x80]), Operation::Read(buf)])
+            .await
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+        self.spi.write(&[register, value]).await
+    }
+}
+
+/// Configuration of the sensor
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    accel_range: AccelRange,
+    gyro_range: GyroRange,
+    odr: Odr,
+    bandwidth: Bandwidth,
+}
+
+impl Default for Config {
+    /// The widest ranges at 1 kHz, the power-on defaults of the sensor.
+    fn default() -> Self {
+        Self {
+            accel_range: AccelRange::default(),
+            gyro_range: GyroRange::default(),
+            odr: Odr::default(),
+            bandwidth: Bandwidth::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_accel_range(mut self, range: AccelRange) -> Self {
+        self.accel_range = range;
+        self
+    }
+
+    pub fn with_gyro_range(mut self, range: GyroRange) -> Self {
+        self.gyro_range = range;
+        self
+    }
+
+    /// Set the output data rate of both the accelerometer and the
+    /// gyroscope.
+    pub fn with_odr(mut self, odr: Odr) -> Self {
+        self.odr = odr;
+        self
+    }
+
+    /// Set the bandwidth of the low-pass filters of both the accelerometer
+    /// and the gyroscope.
+    pub fn with_bandwidth(mut self, bandwidth: Bandwidth) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+}
+
+/// Full-scale range of the accelerometer
+///
+/// Defaults to `G16`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AccelRange {
+    #[default]
+    G16 = 0b000,
+    G8 = 0b001,
+    G4 = 0b010,
+    G2 = 0b011,
+}
+
+impl AccelRange {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    fn lsb_per_g(&self) -> f32 {
+        2_048.0 * (1 << self.bits()) as f32
+    }
+}
+
+/// Full-scale range of the gyroscope
+///
+/// Defaults to `Dps2000`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GyroRange {
+    #[default]
+    Dps2000 = 0b000,
+    Dps1000 = 0b001,
+    Dps500 = 0b010,
+    Dps250 = 0b011,
+    Dps125 = 0b100,
+}
+
+impl GyroRange {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    fn lsb_per_dps(&self) -> f32 {
+        16.4 * (1 << self.bits()) as f32
+    }
+}
+
+/// Output data rate
+///
+/// Defaults to `Hz1k`. Rates above 1 kHz are only reachable in low noise
+/// mode, which the driver uses.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Odr {
+    Hz32k = 0x01,
+    Hz16k = 0x02,
+    Hz8k = 0x03,
+    Hz4k = 0x04,
+    Hz2k = 0x05,
+    #[default]
+    Hz1k = 0x06,
+    Hz500 = 0x0F,
+    Hz200 = 0x07,
+    Hz100 = 0x08,
+    Hz50 = 0x09,
+    Hz25 = 0x0A,
+    Hz12_5 = 0x0B,
+}
+
+impl Odr {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Bandwidth of the low-pass filters, relative to the output data rate
+///
+/// Defaults to `OdrDiv4`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Bandwidth {
+    OdrDiv2 = 0,
+    /// A quarter of the output data rate, at least 100 Hz.
+    #[default]
+    OdrDiv4 = 1,
+    OdrDiv5 = 2,
+    OdrDiv8 = 3,
+    OdrDiv10 = 4,
+    OdrDiv16 = 5,
+    OdrDiv20 = 6,
+    OdrDiv40 = 7,
+    /// A filter of lower latency, with a bandwidth of about ODR/4.
+    LowLatency = 14,
+}
+
+impl Bandwidth {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// An ICM-42688-P on an I2C or SPI [Interface]
+///
+/// `INT` is the pin the INT1 output of the sensor is connected to, if any.
+pub struct Icm42688<IF, INT = ()> {
+    interface: IF,
+    interrupt: INT,
+    config: Config,
+}
+
+impl<IF: Interface> Icm42688<IF> {
+    /// Create a new sensor, reset it and start sampling.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidDeviceId` if the device does not identify as an
+    /// ICM-42688-P.
+    pub async fn new(interface: IF, config: Config) -> Result<Self, Error<IF::Error>> {
+        let mut icm42688 = Self {
+            interface,
+            interrupt: (),
+            config,
+        };
+
+        icm42688
+            .write_register(Register::DEVICE_CONFIG, DEVICE_SOFT_RESET)
+            .await?;
+        Timer::after(RESET_TIME).await;
+
+        let id = icm42688.read_register(Register::WHO_AM_I).await?;
+        if id != DEVICE_ID {
+            return Err(Error::InvalidDeviceId(id));
+        }
+
+        // Interrupts are cleared by reading the interrupt status. The
+        // asynchronous reset must be disabled for the interrupts to work.
+        icm42688
+            .write_register(Register::INT_CONFIG, INT1_LATCHED_PUSH_PULL_HIGH)
+            .await?;
+        icm42688.write_register(Register::INT_CONFIG1, 0x00).await?;
+        icm42688
+            .write_register(Register::INT_SOURCE0, INT1_DATA_READY)
+            .await?;
+
+        icm42688.set_config(config).await?;
+        icm42688
+            .write_register(Register::PWR_MGMT0, PWR_LOW_NOISE)
+            .await?;
+        Timer::after(GYRO_STARTUP_TIME).await;
+        Ok(icm42688)
+    }
+
+    /// Attach the pin the INT1 output of the sensor is connected to.
+    pub fn with_interrupt_pin<P: Wait>(self, interrupt: P) -> Icm42688<IF, P> {
+        Icm42688 {
+            interface: self.interface,
+            interrupt,
+            config: self.config,
+        }
+    }
+}
+
+impl<IF: Interface, P: Wait> Icm42688<IF, P> {
+    /// Wait for a new sample and return it.
+    ///
+    /// Only works while the FIFO watermark is disabled.
+    pub async fn wait_for_sample(&mut self) -> Result<Sample, Error<IF::Error>> {
+        self.wait_for_interrupt().await?;
+        self.read().await
+    }
+
+    /// Wait for the FIFO to reach its watermark, then read samples from it.
+    ///
+    /// See [Icm42688::read_fifo] for more details.
+    pub async fn wait_for_fifo(
+        &mut self,
+        samples: &mut [Sample],
+    ) -> Result<usize, Error<IF::Error>> {
+        self.wait_for_interrupt().await?;
+        self.read_fifo(samples).await
+    }
+
+    /// Release the underlying interface and interrupt pin
+    pub fn release_with_interrupt_pin(self) -> (IF, P) {
+        (self.interface, self.interrupt)
+    }
+
+    /// Wait for INT1 to be asserted, then clear the interrupt.
+    async fn wait_for_interrupt(&mut self) -> Result<(), Error<IF::Error>> {
+        self.interrupt
+            .wait_for_high()
+            .await
+            .map_err(|_| Error::Pin)?;
+        self.read_register(Register::INT_STATUS).await?;
+        Ok(())
+    }
+}
+
+impl<IF: Interface, INT> Icm42688<IF, INT> {
+    /// Get the current configuration of the sensor
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<IF::Error>> {
+        self.config = config;
+        self.write_register(
+            Register::GYRO_CONFIG0,
+            (config.gyro_range.bits() << 5) | config.odr.bits(),
+        )
+        .await?;
+        self.write_register(
+            Register::ACCEL_CONFIG0,
+            (config.accel_range.bits() << 5) | config.odr.bits(),
+        )
+        .await?;
+        self.write_register(
+            Register::GYRO_ACCEL_CONFIG0,
+            (config.bandwidth.bits() << 4) | config.bandwidth.bits(),
+        )
+        .await
+    }
+
+    /// Release the underlying interface
+    pub fn release(self) -> IF {
+        self.interface
+    }
+
+    /// Read the latest sample.
+    pub async fn read(&mut self) -> Result<Sample, Error<IF::Error>> {
+        let mut buf = [0u8; 12];
+        self.interface
+            .read_registers(Register::ACCEL_DATA_X1, &mut buf)
+            .await
+            .map_err(Error::Bus)?;
+        Ok(self.decode(&buf))
+    }
+
+    /// Read the temperature of the sensor.
+    pub async fn temperature(&mut self) -> Result<Celsius, Error<IF::Error>> {
+        let mut buf = [0u8; 2];
+        self.interface
+            .read_registers(Register::TEMP_DATA1, &mut buf)
+            .await
+            .map_err(Error::Bus)?;
+        Ok(Celsius(i16::from_be_bytes(buf) as f32 / 132.48 + 25.0))
+    }
+
+    /// Enable or disable the FIFO. Enabling it clears it.
+    ///
+    /// The FIFO stops storing samples once full, until it is read.
+    pub async fn enable_fifo(&mut self, enabled: bool) -> Result<(), Error<IF::Error>> {
+        if enabled {
+            self.write_register(Register::FIFO_CONFIG1, FIFO_GYRO_AND_ACCEL)
+                .await?;
+            self.write_register(Register::FIFO_CONFIG, FIFO_MODE_STOP_ON_FULL)
+                .await?;
+            self.flush_fifo().await
+        } else {
+            self.write_register(Register::FIFO_CONFIG, FIFO_MODE_BYPASS)
+                .await?;
+            self.write_register(Register::FIFO_CONFIG1, 0).await
+        }
+    }
+
+    /// Set the number of samples in the FIFO asserting INT1, or `None` to
+    /// assert it on every new sample instead.
+    pub async fn set_fifo_watermark(
+        &mut self,
+        samples: Option<u16>,
+    ) -> Result<(), Error<IF::Error>> {
+        match samples {
+            Some(samples) => {
+                // The watermark is in bytes, over 12 bits
+                let bytes = (samples.max(1) as usize * FIFO_SAMPLE_SIZE).min(0x0FFF) as u16;
+                let [msb, lsb] = bytes.to_be_bytes();
+                self.write_register(Register::FIFO_CONFIG2, lsb).await?;
+                self.write_register(Register::FIFO_CONFIG3, msb).await?;
+                self.write_register(Register::INT_SOURCE0, INT1_FIFO_THRESHOLD)
+                    .await
+            }
+            None => {
+                self.write_register(Register::INT_SOURCE0, INT1_DATA_READY)
+                    .await
+            }
+        }
+    }
+
+    /// Get the number of samples waiting in the FIFO.
+    pub async fn fifo_len(&mut self) -> Result<usize, Error<IF::Error>> {
+        Ok(self.fifo_count().await? / FIFO_SAMPLE_SIZE)
+    }
+
+    /// Read samples from the FIFO, oldest first, and return the number of
+    /// samples read.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FifoOverflow` if the FIFO filled up, in which case the
+    /// newest samples were lost and the FIFO is cleared.
+    pub async fn read_fifo(&mut self, samples: &mut [Sample]) -> Result<usize, Error<IF::Error>> {
+        if self.read_register(Register::INT_STATUS).await? & INT_STATUS_FIFO_FULL != 0 {
+            self.flush_fifo().await?;
+            return Err(Error::FifoOverflow);
+        }
+
+        let len = samples.len().min(self.fifo_len().await?);
+        let mut buf = [0u8; FIFO_BATCH * FIFO_SAMPLE_SIZE];
+        let mut read = 0;
+        for batch in samples[..len].chunks_mut(FIFO_BATCH) {
+            let bytes = &mut buf[..batch.len() * FIFO_SAMPLE_SIZE];
+            self.interface
+                .read_registers(Register::FIFO_DATA, bytes)
+                .await
+                .map_err(Error::Bus)?;
+
+            for (sample, packet) in batch
+                .iter_mut()
+                .zip(bytes.as_chunks::<FIFO_SAMPLE_SIZE>().0)
+            {
+                if packet[0] & FIFO_HEADER_EMPTY != 0 {
+                    return Ok(read);
+                }
+                *sample = self.decode(&packet[1..13]);
+                read += 1;
+            }
+        }
+        Ok(read)
+    }
+
+    async fn flush_fifo(&mut self) -> Result<(), Error<IF::Error>> {
+        self.write_register(Register::SIGNAL_PATH_RESET, SIGNAL_PATH_FIFO_FLUSH)
+            .await
+    }
+
+    /// Number of bytes waiting in the FIFO
+    async fn fifo_count(&mut self) -> Result<usize, Error<IF::Error>> {
+        let mut buf = [0u8; 2];
+        self.interface
+            .read_registers(Register::FIFO_COUNTH, &mut buf)
+            .await
+            .map_err(Error::Bus)?;
+        Ok(u16::from_be_bytes(buf) as usize)
+    }
+
+    /// Scale the raw big-endian axes of the accelerometer then the
+    /// gyroscope.
+    fn decode(&self, bytes: &[u8]) -> Sample {
+        let axis = |i: usize, lsb: f32| i16::from_be_bytes([bytes[i], bytes[i + 1]]) as f32 / lsb;
+        let accel_lsb = self.config.accel_range.lsb_per_g();
+        let gyro_lsb = self.config.gyro_range.lsb_per_dps();
+        Sample {
+            acceleration: Vector3::new(axis(0, accel_lsb), axis(2, accel_lsb), axis(4, accel_lsb)),
+            angular_rate: Vector3::new(axis(6, gyro_lsb), axis(8, gyro_lsb), axis(10, gyro_lsb)),
+        }
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<IF::Error>> {
+        let mut buf = [0u8; 1];
+        self.interface
+            .read_registers(register, &mut buf)
+            .await
+            .map_err(Error::Bus)?;
+        Ok(buf[0])
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<IF::Error>> {
+        self.interface
+            .write_register(register, value)
+            .await
+            .map_err(Error::Bus)
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C or SPI bus error
+    Bus(E),
+    /// The interrupt pin could not be read.
+    Pin,
+    /// The device does not identify as an ICM-42688-P.
+    InvalidDeviceId(u8),
+    /// The FIFO overflowed and was cleared.
+    FifoOverflow,
+}
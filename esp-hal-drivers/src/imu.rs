@@ -0,0 +1,141 @@
+//! # imu
+//!
+//! ## Overview
+//!
+//! Samples and helpers shared by the inertial measurement unit drivers of
+//! this crate, the [MPU6050](crate::mpu6050) and the
+//! [ICM-42688](crate::icm42688).
+//!
+//! A [ComplementaryFilter] estimates the roll and pitch of the sensor by
+//! combining its gyroscope, which is precise over short times but drifts,
+//! with the direction of gravity seen by its accelerometer, which is noisy but
+//! does not drift.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut filter = ComplementaryFilter::new(0.98);
+//! let mut ticker = Ticker::every(Duration::from_millis(10));
+//!
+//! loop {
+//!     let sample = imu.read().await?;
+//!     let orientation = filter.update(&sample, Duration::from_millis(10));
+//!     println!("Roll {}°, pitch {}°", orientation.roll, orientation.pitch);
+//!     ticker.next().await;
+//! }
+//! ```
+
+use embassy_time::Duration;
+
+/// A vector along the axes of the sensor
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Vector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vector3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Return the length of the vector.
+    pub fn norm(&self) -> f32 {
+        libm::sqrtf(self.x * self.x + self.y * self.y + self.z * self.z)
+    }
+}
+
+/// A sample of an accelerometer and a gyroscope
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sample {
+    /// Acceleration in g, including gravity
+    pub acceleration: Vector3,
+    /// Angular rate in °/s
+    pub angular_rate: Vector3,
+}
+
+/// Orientation of the sensor in degrees, relative to the horizontal
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Orientation {
+    /// Rotation around the X axis
+    pub roll: f32,
+    /// Rotation around the Y axis
+    pub pitch: f32,
+}
+
+impl Orientation {
+    /// Orientation given by the direction of gravity in an acceleration
+    fn from_gravity(acceleration: &Vector3) -> Self {
+        let Vector3 { x, y, z } = *acceleration;
+        Self {
+            roll: libm::atan2f(y, z).to_degrees(),
+            pitch: libm::atan2f(-x, libm::sqrtf(y * y + z * z)).to_degrees(),
+        }
+    }
+}
+
+/// Estimates the orientation of an IMU from its samples
+///
+/// The yaw cannot be estimated from gravity, so only the roll and the pitch
+/// are tracked.
+#[derive(Debug, Copy, Clone)]
+pub struct ComplementaryFilter {
+    alpha: f32,
+    orientation: Option<Orientation>,
+}
+
+impl ComplementaryFilter {
+    /// Create a new filter.
+    ///
+    /// # Arguments
+    ///
+    /// - `alpha`: The weight of the gyroscope, from 0 to 1. Typical values
+    ///   are 0.95 to 0.98; higher values reject more vibrations but correct
+    ///   the drift more slowly.
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            orientation: None,
+        }
+    }
+
+    /// Get the last estimated orientation.
+    pub fn orientation(&self) -> Orientation {
+        self.orientation.unwrap_or_default()
+    }
+
+    /// Forget the estimated orientation, so that the next sample sets it
+    /// from gravity alone.
+    pub fn reset(&mut self) {
+        self.orientation = None;
+    }
+
+    /// Update the orientation with a new sample.
+    ///
+    /// # Arguments
+    ///
+    /// - `sample`: The new sample of the IMU.
+    /// - `dt`: The time elapsed since the previous sample.
+    pub fn update(&mut self, sample: &Sample, dt: Duration) -> Orientation {
+        let measured = Orientation::from_gravity(&sample.acceleration);
+        let orientation = match self.orientation {
+            Some(previous) => {
+                let dt = dt.as_micros() as f32 / 1_000_000.0;
+                let roll = previous.roll + sample.angular_rate.x * dt;
+                let pitch = previous.pitch + sample.angular_rate.y * dt;
+                Orientation {
+                    roll: self.alpha * roll + (1.0 - self.alpha) * measured.roll,
+                    pitch: self.alpha * pitch + (1.0 - self.alpha) * measured.pitch,
+                }
+            }
+            None => measured,
+        };
+
+        self.orientation = Some(orientation);
+        orientation
+    }
+}
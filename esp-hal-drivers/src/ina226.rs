@@ -0,0 +1,445 @@
+//! # ina226
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Texas Instruments
+//! INA226 current and power monitor over I2C.
+//!
+//! The sensor measures the voltage across a shunt resistor and the voltage of
+//! the bus, from which it computes the current and the power given the
+//! calibration derived from the shunt resistance.
+//!
+//! The alert pin of the sensor can be asserted when a measurement crosses a
+//! limit, e.g. to cut a motor off on over-current. Once an alert pin is
+//! attached with [Ina226::with_alert_pin], [Ina226::wait_for_alert] awaits
+//! the alert on a GPIO interrupt.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let config = Config::new()
+//!     .with_shunt(0.01, 5.0)
+//!     .with_averaging(Averaging::X16);
+//! let mut ina226 = Ina226::new(i2c, ADDRESS_DEFAULT, config)
+//!     .await?
+//!     .with_alert_pin(Input::new(peripherals.GPIO5, Pull::Up));
+//!
+//! println!(
+//!     "{} mV, {} mA, {} mW",
+//!     ina226.bus_voltage().await?.0,
+//!     ina226.current().await?.0,
+//!     ina226.power().await?.0
+//! );
+//!
+//! // Stop the motor when it draws more than 3 A
+//! ina226.set_alert(Alert::OverCurrent(Milliamps(3000.0)), true).await?;
+//! ina226.wait_for_alert().await?;
+//! motor.stop();
+//! ```
+
+use embedded_hal_async::{digital::Wait, i2c::I2c};
+
+use crate::units::{Microvolts, Milliamps, Millivolts, Milliwatts};
+
+/// I2C address of the sensor when A0 and A1 are tied to ground
+pub const ADDRESS_DEFAULT: u8 = 0x40;
+
+/// Value of the manufacturer ID register of Texas Instruments
+const MANUFACTURER_ID: u16 = 0x5449;
+
+/// Resolution of the shunt voltage in nV per LSB
+const SHUNT_NV_PER_LSB: i32 = 2_500;
+
+/// Resolution of the bus voltage in µV per LSB
+const BUS_UV_PER_LSB: i32 = 1_250;
+
+/// Scaling constant of the calibration register. See datasheet section 7.5
+/// for more details.
+const CALIBRATION_SCALE: f32 = 0.005_12;
+
+/// Registers of the sensor. See datasheet section 7.6 for more details.
+struct Register;
+
+impl Register {
+    const CONFIGURATION: u8 = 0x00;
+    const SHUNT_VOLTAGE: u8 = 0x01;
+    const BUS_VOLTAGE: u8 = 0x02;
+    const POWER: u8 = 0x03;
+    const CURRENT: u8 = 0x04;
+    const CALIBRATION: u8 = 0x05;
+    const MASK_ENABLE: u8 = 0x06;
+    const ALERT_LIMIT: u8 = 0x07;
+    const MANUFACTURER_ID: u8 = 0xFE;
+}
+
+/// Flags of the mask/enable register
+const MASK_ALERT_FUNCTION: u16 = 1 << 4;
+const MASK_CONVERSION_READY: u16 = 1 << 3;
+const MASK_OVERFLOW: u16 = 1 << 2;
+const MASK_LATCH: u16 = 1 << 0;
+
+/// Configuration of the sensor
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    shunt_ohms: f32,
+    max_current_a: f32,
+    averaging: Averaging,
+    bus_conversion: ConversionTime,
+    shunt_conversion: ConversionTime,
+}
+
+impl Default for Config {
+    /// The power-on defaults of the sensor, with the 0.1 Ω shunt of the
+    /// common breakout boards.
+    fn default() -> Self {
+        Self {
+            shunt_ohms: 0.1,
+            max_current_a: 0.8,
+            averaging: Averaging::default(),
+            bus_conversion: ConversionTime::default(),
+            shunt_conversion: ConversionTime::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the shunt resistor and the maximum current expected through it,
+    /// from which the resolution of the current is derived.
+    ///
+    /// # Arguments
+    ///
+    /// - `shunt_ohms`: The resistance of the shunt in Ω.
+    /// - `max_current_a`: The maximum expected current in A. The shunt
+    ///   voltage saturates at 81.92 mV, so this should not exceed
+    ///   `0.08192 / shunt_ohms`.
+    pub fn with_shunt(mut self, shunt_ohms: f32, max_current_a: f32) -> Self {
+        self.shunt_ohms = shunt_ohms;
+        self.max_current_a = max_current_a;
+        self
+    }
+
+    pub fn with_averaging(mut self, averaging: Averaging) -> Self {
+        self.averaging = averaging;
+        self
+    }
+
+    pub fn with_bus_conversion(mut self, conversion: ConversionTime) -> Self {
+        self.bus_conversion = conversion;
+        self
+    }
+
+    pub fn with_shunt_conversion(mut self, conversion: ConversionTime) -> Self {
+        self.shunt_conversion = conversion;
+        self
+    }
+
+    /// Value of the configuration register, measuring the shunt and bus
+    /// voltages continuously
+    fn bits(&self) -> u16 {
+        const CONTINUOUS_SHUNT_AND_BUS: u16 = 0b111;
+        // Bit 14 is reserved and reads as 1
+        (1 << 14)
+            | ((self.averaging.bits() as u16) << 9)
+            | ((self.bus_conversion.bits() as u16) << 6)
+            | ((self.shunt_conversion.bits() as u16) << 3)
+            | CONTINUOUS_SHUNT_AND_BUS
+    }
+
+    /// Current represented by an LSB of the current register in mA
+    fn current_lsb_ma(&self) -> f32 {
+        self.max_current_a * 1000.0 / 32_768.0
+    }
+
+    /// Value of the calibration register
+    fn calibration(&self) -> u16 {
+        let current_lsb_a = self.current_lsb_ma() / 1000.0;
+        (CALIBRATION_SCALE / (current_lsb_a * self.shunt_ohms)) as u16
+    }
+}
+
+/// Number of conversions averaged into each measurement
+///
+/// Defaults to `X1`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Averaging {
+    #[default]
+    X1 = 0b000,
+    X4 = 0b001,
+    X16 = 0b010,
+    X64 = 0b011,
+    X128 = 0b100,
+    X256 = 0b101,
+    X512 = 0b110,
+    X1024 = 0b111,
+}
+
+impl Averaging {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Time taken by a conversion of the shunt or bus voltage
+///
+/// Defaults to `Us1100`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConversionTime {
+    Us140 = 0b000,
+    Us204 = 0b001,
+    Us332 = 0b010,
+    Us588 = 0b011,
+    #[default]
+    Us1100 = 0b100,
+    Us2116 = 0b101,
+    Us4156 = 0b110,
+    Us8244 = 0b111,
+}
+
+impl ConversionTime {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Condition asserting the alert pin
+///
+/// Only one condition can be monitored at a time.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Alert {
+    /// The current exceeds the limit.
+    OverCurrent(Milliamps),
+    /// The current falls below the limit.
+    UnderCurrent(Milliamps),
+    /// The bus voltage exceeds the limit.
+    BusOverVoltage(Millivolts),
+    /// The bus voltage falls below the limit.
+    BusUnderVoltage(Millivolts),
+    /// The power exceeds the limit.
+    PowerOverLimit(Milliwatts),
+    /// A conversion completed.
+    ConversionReady,
+}
+
+/// Flags of the sensor, read when an alert is cleared
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AlertFlags {
+    /// The monitored limit was crossed.
+    pub limit: bool,
+    /// A conversion completed.
+    pub conversion_ready: bool,
+    /// The computed current or power overflowed.
+    pub overflow: bool,
+}
+
+/// An INA226 sensor on an I2C bus
+///
+/// `ALERT` is the pin the alert output of the sensor is connected to, if any.
+pub struct Ina226<I2C, ALERT = ()> {
+    address: u8,
+    i2c: I2C,
+    alert: ALERT,
+    config: Config,
+}
+
+impl<I2C: I2c> Ina226<I2C> {
+    /// Create a new sensor and configure it to measure continuously.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the sensor is on.
+    /// - `address`: The I2C address of the sensor, e.g. [ADDRESS_DEFAULT].
+    /// - `config`: The configuration of the sensor.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidManufacturerId` if the device is not made by
+    /// Texas Instruments.
+    pub async fn new(i2c: I2C, address: u8, config: Config) -> Result<Self, Error<I2C::Error>> {
+        let mut ina226 = Self {
+            address,
+            i2c,
+            alert: (),
+            config,
+        };
+
+        let id = ina226.read_register(Register::MANUFACTURER_ID).await?;
+        if id != MANUFACTURER_ID {
+            return Err(Error::InvalidManufacturerId(id));
+        }
+
+        ina226.set_config(config).await?;
+        Ok(ina226)
+    }
+
+    /// Attach the pin the alert output of the sensor is connected to.
+    ///
+    /// The alert output is open-drain and active low, so the pin needs a
+    /// pull-up.
+    pub fn with_alert_pin<A: Wait>(self, alert: A) -> Ina226<I2C, A> {
+        Ina226 {
+            address: self.address,
+            i2c: self.i2c,
+            alert,
+            config: self.config,
+        }
+    }
+}
+
+impl<I2C: I2c, A: Wait> Ina226<I2C, A> {
+    /// Wait for the alert pin to be asserted, then clear the alert.
+    ///
+    /// Returns immediately if the alert is already asserted.
+    pub async fn wait_for_alert(&mut self) -> Result<AlertFlags, Error<I2C::Error>> {
+        self.alert.wait_for_low().await.map_err(|_| Error::Pin)?;
+        self.clear_alert().await
+    }
+
+    /// Release the underlying I2C bus and alert pin
+    pub fn release_with_alert_pin(self) -> (I2C, A) {
+        (self.i2c, self.alert)
+    }
+}
+
+impl<I2C: I2c, ALERT> Ina226<I2C, ALERT> {
+    /// Get the current configuration of the sensor
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Set the configuration and calibration of the sensor.
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
+        self.config = config;
+        self.write_register(Register::CONFIGURATION, config.bits())
+            .await?;
+        self.write_register(Register::CALIBRATION, config.calibration())
+            .await
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Read the voltage across the shunt.
+    pub async fn shunt_voltage(&mut self) -> Result<Microvolts, Error<I2C::Error>> {
+        let raw = self.read_register(Register::SHUNT_VOLTAGE).await? as i16;
+        Ok(Microvolts(raw as i32 * SHUNT_NV_PER_LSB / 1000))
+    }
+
+    /// Read the voltage of the bus.
+    pub async fn bus_voltage(&mut self) -> Result<Millivolts, Error<I2C::Error>> {
+        let raw = self.read_register(Register::BUS_VOLTAGE).await?;
+        Ok(Millivolts(raw as i32 * BUS_UV_PER_LSB / 1000))
+    }
+
+    /// Read the current through the shunt.
+    pub async fn current(&mut self) -> Result<Milliamps, Error<I2C::Error>> {
+        let raw = self.read_register(Register::CURRENT).await? as i16;
+        Ok(Milliamps(raw as f32 * self.config.current_lsb_ma()))
+    }
+
+    /// Read the power drawn from the bus.
+    pub async fn power(&mut self) -> Result<Milliwatts, Error<I2C::Error>> {
+        let raw = self.read_register(Register::POWER).await?;
+        // See datasheet section 7.5 for more details
+        Ok(Milliwatts(raw as f32 * 25.0 * self.config.current_lsb_ma()))
+    }
+
+    /// Set the condition asserting the alert pin.
+    ///
+    /// # Arguments
+    ///
+    /// - `alert`: The condition to monitor.
+    /// - `latch`: Whether the alert stays asserted until it is cleared, even
+    ///   if the condition stops holding.
+    pub async fn set_alert(&mut self, alert: Alert, latch: bool) -> Result<(), Error<I2C::Error>> {
+        let (function, limit) = match alert {
+            Alert::OverCurrent(current) => (15, self.current_limit(current)),
+            Alert::UnderCurrent(current) => (14, self.current_limit(current)),
+            Alert::BusOverVoltage(voltage) => (13, bus_limit(voltage)),
+            Alert::BusUnderVoltage(voltage) => (12, bus_limit(voltage)),
+            Alert::PowerOverLimit(power) => (11, self.power_limit(power)),
+            Alert::ConversionReady => (10, 0),
+        };
+
+        self.write_register(Register::ALERT_LIMIT, limit).await?;
+        let latch = if latch { MASK_LATCH } else { 0 };
+        self.write_register(Register::MASK_ENABLE, (1 << function) | latch)
+            .await
+    }
+
+    /// Stop asserting the alert pin on any condition.
+    pub async fn disable_alert(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write_register(Register::MASK_ENABLE, 0).await
+    }
+
+    /// Read the flags of the sensor, which clears a latched alert.
+    pub async fn clear_alert(&mut self) -> Result<AlertFlags, Error<I2C::Error>> {
+        let mask = self.read_register(Register::MASK_ENABLE).await?;
+        Ok(AlertFlags {
+            limit: mask & MASK_ALERT_FUNCTION != 0,
+            conversion_ready: mask & MASK_CONVERSION_READY != 0,
+            overflow: mask & MASK_OVERFLOW != 0,
+        })
+    }
+
+    /// Value of the alert limit register for a current limit. The current is
+    /// compared through the shunt voltage.
+    fn current_limit(&self, current: Milliamps) -> u16 {
+        let shunt_nv = current.0 * self.config.shunt_ohms * 1_000_000.0;
+        (shunt_nv / SHUNT_NV_PER_LSB as f32) as i16 as u16
+    }
+
+    /// Value of the alert limit register for a power limit
+    fn power_limit(&self, power: Milliwatts) -> u16 {
+        (power.0 / (25.0 * self.config.current_lsb_ma())) as u16
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u16, Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.i2c
+            .write_read(self.address, &[register], &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    async fn write_register(&mut self, register: u8, value: u16) -> Result<(), Error<I2C::Error>> {
+        let [msb, lsb] = value.to_be_bytes();
+        self.i2c
+            .write(self.address, &[register, msb, lsb])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+/// Value of the alert limit register for a bus voltage limit
+fn bus_limit(voltage: Millivolts) -> u16 {
+    (voltage.0.max(0) * 1000 / BUS_UV_PER_LSB) as u16
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The alert pin could not be read.
+    Pin,
+    /// The device answered with an unexpected manufacturer ID, so it is not
+    /// an INA226.
+    InvalidManufacturerId(u16),
+}
@@ -0,0 +1,575 @@
+//! # ir
+//!
+//! ## Overview
+//!
+//! This driver receives and sends the commands of infrared remote controls
+//! with the RMT peripheral of the ESP32-C3. Commands are received with an IR
+//! receiver module such as the Vishay TSOP38238 demodulating the 38 kHz
+//! carrier, and sent with an IR LED driven by a transistor.
+//!
+//! - The RMT records the pulses of a frame in hardware, and the driver awaits
+//!   the end of the frame instead of timing the pulses itself.
+//! - NEC and RC5 frames are decoded into [Event]s, with the repeat codes sent
+//!   while a button is held marked as repeats.
+//! - [IrReceiver::run] sends the events to a channel, so that a background
+//!   task can receive them for the rest of the application.
+//! - The [IrTransmitter] sends the same [Event]s, or raw timings captured
+//!   from remotes using other protocols, such as those of air conditioners.
+//!   The RMT modulates the 38 kHz carrier in hardware.
+//!
+//! The driver expects the RMT to be clocked at 80 MHz, as pulses are measured
+//! in µs. The polarity of the receiver does not matter.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! static EVENTS: Channel<CriticalSectionRawMutex, Event, 8> = Channel::new();
+//!
+//! #[embassy_executor::task]
+//! async fn ir_task(mut receiver: IrReceiver<rmt::Channel<Async, 2>>) {
+//!     receiver.run(EVENTS.sender()).await.ok();
+//! }
+//!
+//! let rmt = Rmt::new(peripherals.RMT, 80.MHz()).unwrap().into_async();
+//! let channel = rmt
+//!     .channel2
+//!     .configure(peripherals.GPIO4, ir::rx_channel_config(1))
+//!     .unwrap();
+//! spawner.spawn(ir_task(IrReceiver::new(channel, 1))).unwrap();
+//!
+//! let channel = rmt
+//!     .channel0
+//!     .configure(peripherals.GPIO5, ir::tx_channel_config(1))
+//!     .unwrap();
+//! let mut transmitter = IrTransmitter::new(channel, 1);
+//!
+//! // Send back every command received
+//! loop {
+//!     let event = EVENTS.receive().await;
+//!     println!("{:?} {:#x} {:#x}", event.protocol, event.address, event.command);
+//!     transmitter.send(&event).await?;
+//! }
+//! ```
+
+use embassy_sync::{blocking_mutex::raw::RawMutex, channel::Sender};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::rmt::{Error, RxChannelAsync, RxChannelConfig, TxChannelAsync, TxChannelConfig};
+
+/// Words of RMT memory in a block of a channel
+const BLOCK_WORDS: usize = 48;
+
+/// Maximum number of memory blocks of a channel
+const MAX_BLOCKS: usize = 4;
+
+/// Divider of the 80 MHz clock of the RMT giving ticks of 1 µs
+const CLK_DIVIDER: u8 = 80;
+
+/// Silence ending a frame in µs, longer than any pulse of a frame
+const IDLE_THRESHOLD_US: u16 = 12_000;
+
+/// Glitches filtered out, in cycles of the 80 MHz clock
+const FILTER_THRESHOLD: u8 = 250;
+
+/// Period of the 38 kHz carrier in cycles of the 80 MHz clock, high for a
+/// third of it
+const CARRIER_PERIOD: u16 = 2_105;
+const CARRIER_HIGH: u16 = CARRIER_PERIOD / 3;
+
+/// Longest pulse of a RMT word in µs
+const MAX_PULSE_US: u16 = 0x7FFF;
+
+/// Tolerance on the length of the pulses, in percent
+const TOLERANCE: u32 = 25;
+
+/// Pulse lengths of the NEC protocol in µs
+const NEC_LEADER_MARK: u16 = 9_000;
+const NEC_LEADER_SPACE: u16 = 4_500;
+const NEC_REPEAT_SPACE: u16 = 2_250;
+const NEC_BIT_MARK: u16 = 560;
+const NEC_ZERO_SPACE: u16 = 560;
+const NEC_ONE_SPACE: u16 = 1_690;
+
+/// Longest time between two NEC frames for a repeat code to repeat the
+/// first, repeat codes being sent every 108 ms
+const NEC_REPEAT_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// Periods of the frames of the protocols, from start to start
+const NEC_FRAME_PERIOD: Duration = Duration::from_millis(108);
+const RC5_FRAME_PERIOD: Duration = Duration::from_micros(113_778);
+
+/// Half of a bit of the RC5 protocol in µs
+const RC5_HALF_BIT: u16 = 889;
+
+/// Bits of a RC5 frame: two start bits, the toggle bit, 5 bits of address
+/// and 6 bits of command
+const RC5_BITS: usize = 14;
+
+/// Configuration of the receive channel expected by the driver
+///
+/// # Arguments
+///
+/// - `memsize`: The number of memory blocks of the channel, from 1 to 4.
+///   Blocks are taken from the following channels.
+pub fn rx_channel_config(memsize: u8) -> RxChannelConfig {
+    RxChannelConfig {
+        clk_divider: CLK_DIVIDER,
+        idle_threshold: IDLE_THRESHOLD_US,
+        filter_threshold: FILTER_THRESHOLD,
+        memsize,
+        ..RxChannelConfig::default()
+    }
+}
+
+/// Configuration of the transmit channel expected by the driver
+///
+/// # Arguments
+///
+/// - `memsize`: The number of memory blocks of the channel, from 1 to 4.
+///   Blocks are taken from the following channels.
+pub fn tx_channel_config(memsize: u8) -> TxChannelConfig {
+    TxChannelConfig {
+        clk_divider: CLK_DIVIDER,
+        idle_output_level: false,
+        idle_output: true,
+        carrier_modulation: true,
+        carrier_high: CARRIER_HIGH,
+        carrier_low: CARRIER_PERIOD - CARRIER_HIGH,
+        carrier_level: true,
+        memsize,
+    }
+}
+
+/// Protocols of the remote controls
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    /// NEC protocol, with 8-bit or extended 16-bit addresses
+    Nec,
+    /// Philips RC5 protocol, with extended 7-bit commands
+    Rc5,
+}
+
+/// A command received from a remote control
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Event {
+    pub protocol: Protocol,
+    pub address: u16,
+    pub command: u8,
+    /// Whether the command repeats the previous one, because its button is
+    /// held
+    pub repeat: bool,
+}
+
+/// An IR receiver on a RMT channel
+pub struct IrReceiver<RX> {
+    channel: RX,
+    /// Number of words received per frame
+    words: usize,
+    /// Last event received, and when
+    last: Option<(Event, Instant)>,
+    /// Toggle bit of the last RC5 frame
+    rc5_toggle: Option<bool>,
+}
+
+impl<RX: RxChannelAsync> IrReceiver<RX> {
+    /// Create a new receiver.
+    ///
+    /// # Arguments
+    ///
+    /// - `channel`: The receive channel, configured with
+    ///   [rx_channel_config].
+    /// - `memsize`: The number of memory blocks the channel was configured
+    ///   with.
+    pub fn new(channel: RX, memsize: u8) -> Self {
+        Self {
+            channel,
+            words: BLOCK_WORDS * (memsize as usize).clamp(1, MAX_BLOCKS),
+            last: None,
+            rc5_toggle: None,
+        }
+    }
+
+    /// Release the underlying channel
+    pub fn release(self) -> RX {
+        self.channel
+    }
+
+    /// Wait for a command from a remote control. Frames that cannot be
+    /// decoded are ignored.
+    pub async fn receive(&mut self) -> Result<Event, Error> {
+        let mut words = [0u32; BLOCK_WORDS * MAX_BLOCKS];
+        loop {
+            let words = &mut words[..self.words];
+            words.fill(0);
+            self.channel.receive(words).await?;
+            if let Some(event) = self.decode(words) {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Receive commands and send them to a channel.
+    ///
+    /// Only returns if an error occurs.
+    pub async fn run<M: RawMutex, const N: usize>(
+        &mut self,
+        sender: Sender<'_, M, Event, N>,
+    ) -> Result<(), Error> {
+        loop {
+            let event = self.receive().await?;
+            sender.send(event).await;
+        }
+    }
+
+    /// Decode the words of a frame.
+    fn decode(&mut self, words: &[u32]) -> Option<Event> {
+        let now = Instant::now();
+        let event = match decode_nec(words) {
+            Some(Nec::Frame { address, command }) => Event {
+                protocol: Protocol::Nec,
+                address,
+                command,
+                repeat: false,
+            },
+            // A repeat code repeats the last NEC frame, if recent
+            Some(Nec::Repeat) => match self.last {
+                Some((last, at))
+                    if last.protocol == Protocol::Nec && now - at < NEC_REPEAT_TIMEOUT =>
+                {
+                    Event {
+                        repeat: true,
+                        ..last
+                    }
+                }
+                _ => return None,
+            },
+            None => {
+                let (toggle, address, command) = decode_rc5(words)?;
+                // The toggle bit flips on every press of a button
+                let repeat = self.rc5_toggle == Some(toggle)
+                    && self.last.is_some_and(|(last, _)| {
+                        last.protocol == Protocol::Rc5
+                            && last.address == address
+                            && last.command == command
+                    });
+                self.rc5_toggle = Some(toggle);
+                Event {
+                    protocol: Protocol::Rc5,
+                    address,
+                    command,
+                    repeat,
+                }
+            }
+        };
+        self.last = Some((event, now));
+        Some(event)
+    }
+}
+
+/// An IR transmitter on a RMT channel
+pub struct IrTransmitter<TX> {
+    channel: TX,
+    /// Number of words sent per frame
+    words: usize,
+    /// Toggle bit of the last RC5 frame
+    rc5_toggle: bool,
+    /// Earliest start of the next frame
+    next_frame_at: Instant,
+}
+
+impl<TX: TxChannelAsync> IrTransmitter<TX> {
+    /// Create a new transmitter.
+    ///
+    /// # Arguments
+    ///
+    /// - `channel`: The transmit channel, configured with
+    ///   [tx_channel_config].
+    /// - `memsize`: The number of memory blocks the channel was configured
+    ///   with.
+    pub fn new(channel: TX, memsize: u8) -> Self {
+        Self {
+            channel,
+            words: BLOCK_WORDS * (memsize as usize).clamp(1, MAX_BLOCKS),
+            rc5_toggle: false,
+            next_frame_at: Instant::now(),
+        }
+    }
+
+    /// Release the underlying channel
+    pub fn release(self) -> TX {
+        self.channel
+    }
+
+    /// Send a command.
+    ///
+    /// A command repeating the previous one, while its button is held, is
+    /// sent as a NEC repeat code or as a RC5 frame with the same toggle bit.
+    /// Frames are spaced by the period of their protocol.
+    pub async fn send(&mut self, event: &Event) -> Result<(), Error> {
+        let mut pulses = [0u16; 2 * (2 + 32 + 1)];
+        let (len, period) = match event.protocol {
+            Protocol::Nec => (encode_nec(event, &mut pulses), NEC_FRAME_PERIOD),
+            Protocol::Rc5 => {
+                if !event.repeat {
+                    self.rc5_toggle = !self.rc5_toggle;
+                }
+                (
+                    encode_rc5(event, self.rc5_toggle, &mut pulses),
+                    RC5_FRAME_PERIOD,
+                )
+            }
+        };
+
+        Timer::at(self.next_frame_at).await;
+        self.next_frame_at = Instant::now() + period;
+        self.send_raw(&pulses[..len]).await
+    }
+
+    /// Send raw timings in µs, alternating marks and spaces and starting with
+    /// a mark.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if the timings do not fit in the
+    /// memory of the channel.
+    pub async fn send_raw(&mut self, timings: &[u16]) -> Result<(), Error> {
+        // Pulses longer than a RMT word are split
+        let pulses = timings.iter().enumerate().flat_map(|(i, &length)| {
+            let mark = i % 2 == 0;
+            let count = length.div_ceil(MAX_PULSE_US);
+            (0..count).map(move |j| {
+                let rest = length - j * MAX_PULSE_US;
+                (mark, rest.min(MAX_PULSE_US))
+            })
+        });
+
+        let mut words = [0u32; BLOCK_WORDS * MAX_BLOCKS];
+        let mut len = 0;
+        let mut half = None;
+        for (level, length) in pulses {
+            match half.take() {
+                None => half = Some((level, length)),
+                Some((level0, length0)) => {
+                    // Keep a word for the end marker
+                    if len + 1 >= self.words {
+                        return Err(Error::InvalidArgument);
+                    }
+                    words[len] = pulse(level0, length0, level, length);
+                    len += 1;
+                }
+            }
+        }
+        if let Some((level0, length0)) = half {
+            if len + 1 >= self.words {
+                return Err(Error::InvalidArgument);
+            }
+            // A zero length ends the transmission
+            words[len] = pulse(level0, length0, false, 0);
+            len += 1;
+        }
+        // End marker
+        words[len] = 0;
+        self.channel.transmit(&words[..=len]).await
+    }
+}
+
+/// Encode a NEC frame as timings, or a repeat code if the event repeats the
+/// previous one, returning the number of timings written.
+fn encode_nec(event: &Event, timings: &mut [u16]) -> usize {
+    if event.repeat {
+        timings[..3].copy_from_slice(&[NEC_LEADER_MARK, NEC_REPEAT_SPACE, NEC_BIT_MARK]);
+        return 3;
+    }
+
+    // 8-bit addresses are followed by their inverse
+    let [address_low, address_high] = match event.address.to_le_bytes() {
+        [low, 0] => [low, !low],
+        bytes => bytes,
+    };
+    let bits = u32::from_le_bytes([address_low, address_high, event.command, !event.command]);
+
+    timings[0] = NEC_LEADER_MARK;
+    timings[1] = NEC_LEADER_SPACE;
+    for i in 0..32 {
+        timings[2 + 2 * i] = NEC_BIT_MARK;
+        timings[3 + 2 * i] = if bits & (1 << i) != 0 {
+            NEC_ONE_SPACE
+        } else {
+            NEC_ZERO_SPACE
+        };
+    }
+    // Final mark ending the last space
+    timings[66] = NEC_BIT_MARK;
+    67
+}
+
+/// Encode a RC5 frame as timings, returning the number of timings written.
+fn encode_rc5(event: &Event, toggle: bool, timings: &mut [u16]) -> usize {
+    // The second start bit is the inverse of the seventh bit of the command
+    let field = event.command & 0x40 == 0;
+    let bits = 1 << 13
+        | (field as u16) << 12
+        | (toggle as u16) << 11
+        | (event.address & 0x1F) << 6
+        | (event.command & 0x3F) as u16;
+
+    // Half bits as marks, merged into pulses, the space of the first start
+    // bit being silent
+    let mut len = 0;
+    let mut level = true;
+    for i in (0..RC5_BITS).rev() {
+        let one = bits & (1 << i) != 0;
+        for mark in [!one, one] {
+            if len == 0 && !mark {
+                continue;
+            }
+            if len > 0 && mark == level {
+                timings[len - 1] += RC5_HALF_BIT;
+            } else {
+                timings[len] = RC5_HALF_BIT;
+                len += 1;
+                level = mark;
+            }
+        }
+    }
+    // A final space is silent
+    if !level {
+        len -= 1;
+    }
+    len
+}
+
+/// A RMT word, made of two pulses. See the technical reference manual
+/// section 35.3.1 for more details.
+const fn pulse(level0: bool, length0: u16, level1: bool, length1: u16) -> u32 {
+    (length0 as u32 & 0x7FFF)
+        | ((level0 as u32) << 15)
+        | ((length1 as u32 & 0x7FFF) << 16)
+        | ((level1 as u32) << 31)
+}
+
+/// A decoded NEC frame
+enum Nec {
+    Frame { address: u16, command: u8 },
+    Repeat,
+}
+
+/// Decode a NEC frame, sent least significant bit first as the address, its
+/// inverse, the command and its inverse.
+fn decode_nec(words: &[u32]) -> Option<Nec> {
+    let mut pulses = pulses(words);
+    let (mark, leader_mark) = pulses.next()?;
+    let (_, leader_space) = pulses.next()?;
+    if !matches(leader_mark, NEC_LEADER_MARK) {
+        return None;
+    }
+    if matches(leader_space, NEC_REPEAT_SPACE) {
+        return Some(Nec::Repeat);
+    }
+    if !matches(leader_space, NEC_LEADER_SPACE) {
+        return None;
+    }
+
+    let mut bits = 0u32;
+    for i in 0..32 {
+        let (level, length) = pulses.next()?;
+        if level != mark || !matches(length, NEC_BIT_MARK) {
+            return None;
+        }
+        let (_, length) = pulses.next()?;
+        if matches(length, NEC_ONE_SPACE) {
+            bits |= 1 << i;
+        } else if !matches(length, NEC_ZERO_SPACE) {
+            return None;
+        }
+    }
+
+    let [address, address_inverse, command, command_inverse] = bits.to_le_bytes();
+    if command != !command_inverse {
+        return None;
+    }
+    // Extended frames use the inverse of the address as its high byte
+    let address = if address == !address_inverse {
+        address as u16
+    } else {
+        u16::from_le_bytes([address, address_inverse])
+    };
+    Some(Nec::Frame { address, command })
+}
+
+/// Decode a RC5 frame, returning its toggle bit, address and command.
+///
+/// Bits are Manchester encoded, most significant bit first, a one being a
+/// space followed by a mark.
+fn decode_rc5(words: &[u32]) -> Option<(bool, u16, u8)> {
+    let mut pulses = pulses(words).peekable();
+    let mark = pulses.peek()?.0;
+
+    // Half bits as marks, starting with the space of the first start bit
+    let mut halves = [false; 2 * RC5_BITS];
+    let mut len = 1;
+    for (level, length) in pulses {
+        // The silence after the frame may be recorded
+        if length > 3 * RC5_HALF_BIT {
+            break;
+        }
+        let count = if matches(length, RC5_HALF_BIT) {
+            1
+        } else if matches(length, 2 * RC5_HALF_BIT) {
+            2
+        } else {
+            return None;
+        };
+        for _ in 0..count {
+            *halves.get_mut(len)? = level == mark;
+            len += 1;
+        }
+    }
+    // The space of a final zero merges with the silence after the frame
+    if len == 2 * RC5_BITS - 1 {
+        len += 1;
+    }
+    if len != 2 * RC5_BITS {
+        return None;
+    }
+
+    let mut bits = 0u16;
+    for half in halves.chunks(2) {
+        bits = match half {
+            [false, true] => bits << 1 | 1,
+            [true, false] => bits << 1,
+            _ => return None,
+        };
+    }
+
+    // The second start bit is the inverse of the seventh bit of the command
+    let start = bits >> 12;
+    if start & 0b10 == 0 {
+        return None;
+    }
+    let command = (bits & 0x3F) as u8 | (((start & 1) ^ 1) as u8) << 6;
+    let address = (bits >> 6) & 0x1F;
+    let toggle = bits & (1 << 11) != 0;
+    Some((toggle, address, command))
+}
+
+/// Iterate over the pulses of RMT words, as levels and lengths, until the
+/// end marker.
+fn pulses(words: &[u32]) -> impl Iterator<Item = (bool, u16)> + '_ {
+    words
+        .iter()
+        .flat_map(|word| {
+            [
+                (word & (1 << 15) != 0, (word & 0x7FFF) as u16),
+                (word & (1 << 31) != 0, ((word >> 16) & 0x7FFF) as u16),
+            ]
+        })
+        .take_while(|(_, length)| *length != 0)
+}
+
+/// Whether the length of a pulse matches an expected length.
+fn matches(length: u16, expected: u16) -> bool {
+    let (length, expected) = (length as u32, expected as u32);
+    length * 100 >= expected * (100 - TOLERANCE) && length * 100 <= expected * (100 + TOLERANCE)
+}
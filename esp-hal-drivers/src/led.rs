@@ -0,0 +1,132 @@
+//! # led
+//!
+//! ## Overview
+//!
+//! Colors and pixel buffers shared by the addressable LED drivers of this
+//! crate, the [WS2812](crate::ws2812) and the [APA102](crate::apa102).
+//!
+//! - Colors can be gamma-corrected so that perceived brightness is linear.
+//! - A global brightness scales all colors before they are sent.
+//! - A [PixelBuffer] holds the colors of a strip, and shows them on any
+//!   [LedStrip].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut pixels = PixelBuffer::<8>::new();
+//! pixels.fill(Rgb8::new(0, 0, 32));
+//! pixels.set(0, Rgb8::new(255, 0, 0));
+//! pixels.show(&mut strip).await?;
+//! ```
+
+/// An 8-bit RGB color
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rgb8 {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb8 {
+    pub const BLACK: Self = Self::new(0, 0, 0);
+    pub const WHITE: Self = Self::new(255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Return the color scaled by a brightness, where 255 is full
+    /// brightness.
+    pub fn scaled(&self, brightness: u8) -> Self {
+        let scale = |c: u8| ((c as u16 * (brightness as u16 + 1)) >> 8) as u8;
+        Self::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+
+    /// Return the color with a gamma of 2.8 applied to each component.
+    pub fn gamma_corrected(&self) -> Self {
+        Self::new(
+            GAMMA8[self.r as usize],
+            GAMMA8[self.g as usize],
+            GAMMA8[self.b as usize],
+        )
+    }
+}
+
+/// Gamma-correction table for a gamma of 2.8
+const GAMMA8: [u8; 256] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 5, 5, 5,
+    5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 14,
+    14, 15, 15, 16, 16, 17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22, 22, 23, 24, 24, 25, 25, 26, 27,
+    27, 28, 29, 29, 30, 31, 32, 32, 33, 34, 35, 35, 36, 37, 38, 39, 39, 40, 41, 42, 43, 44, 45, 46,
+    47, 48, 49, 50, 50, 51, 52, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 66, 67, 68, 69, 70, 72,
+    73, 74, 75, 77, 78, 79, 81, 82, 83, 85, 86, 87, 89, 90, 92, 93, 95, 96, 98, 99, 101, 102, 104,
+    105, 107, 109, 110, 112, 114, 115, 117, 119, 120, 122, 124, 126, 127, 129, 131, 133, 135, 137,
+    138, 140, 142, 144, 146, 148, 150, 152, 154, 156, 158, 160, 162, 164, 167, 169, 171, 173, 175,
+    177, 180, 182, 184, 186, 189, 191, 193, 196, 198, 200, 203, 205, 208, 210, 213, 215, 218, 220,
+    223, 225, 228, 231, 233, 236, 239, 241, 244, 247, 249, 252, 255,
+];
+
+/// A strip of addressable LEDs
+#[allow(async_fn_in_trait)]
+pub trait LedStrip {
+    /// Error returned by a failed write
+    type Error;
+
+    /// Send colors to the strip, starting from its first LED.
+    async fn write(&mut self, pixels: &[Rgb8]) -> Result<(), Self::Error>;
+}
+
+/// The colors of a strip of `N` LEDs
+#[derive(Debug, Copy, Clone)]
+pub struct PixelBuffer<const N: usize> {
+    pixels: [Rgb8; N],
+}
+
+impl<const N: usize> Default for PixelBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> PixelBuffer<N> {
+    /// Create a new buffer with all LEDs off.
+    pub fn new() -> Self {
+        Self {
+            pixels: [Rgb8::BLACK; N],
+        }
+    }
+
+    /// Set the color of a LED. LEDs beyond the strip are ignored.
+    pub fn set(&mut self, index: usize, color: Rgb8) {
+        if let Some(pixel) = self.pixels.get_mut(index) {
+            *pixel = color;
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<Rgb8> {
+        self.pixels.get(index).copied()
+    }
+
+    pub fn fill(&mut self, color: Rgb8) {
+        self.pixels.fill(color);
+    }
+
+    pub fn clear(&mut self) {
+        self.fill(Rgb8::BLACK);
+    }
+
+    pub fn pixels(&self) -> &[Rgb8; N] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Rgb8; N] {
+        &mut self.pixels
+    }
+
+    /// Send the colors to a strip.
+    pub async fn show<S: LedStrip>(&self, strip: &mut S) -> Result<(), S::Error> {
+        strip.write(&self.pixels).await
+    }
+}
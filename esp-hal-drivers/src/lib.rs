@@ -1,2 +1,86 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+pub mod ads1115;
+pub mod apa102;
+pub mod apds9960;
+pub mod at;
+pub mod at24cxx;
+pub mod battery;
+pub mod bme280;
+#[cfg(feature = "esp32c3")]
+pub mod button;
+pub mod ccs811;
+pub mod current;
+pub mod dht;
+pub mod ds18b20;
+pub mod encoder;
+pub mod fat;
+pub mod flow;
+pub mod gps;
+pub mod hcsr04;
+pub mod hd44780;
+pub mod icm42688;
+pub mod imu;
+pub mod ina226;
+#[cfg(feature = "esp32c3")]
+pub mod ir;
+pub mod led;
+pub mod lora;
+pub mod max31855;
+pub mod max31865;
+pub mod mcp23017;
 pub mod mcp3428;
+pub mod mcp3x08;
+pub mod mcp4725;
+pub mod mfrc522;
+pub mod mpr121;
+pub mod mpu6050;
+pub mod onewire;
+pub mod pcf8574;
+pub mod pn532;
+pub mod qmc5883l;
+pub mod relay;
+#[cfg(feature = "esp32c3")]
+pub mod sar_adc;
+pub mod sdcard;
+pub mod sgp40;
+pub mod sht4x;
+pub mod sim7000;
+pub mod soil;
+#[cfg(all(feature = "esp32c3", feature = "motor"))]
+pub mod speed;
+pub mod ssd1306;
+pub mod stepper;
+pub mod sx1262;
+pub mod sx1276;
+#[cfg(feature = "esp32c3")]
+pub mod temperature;
+pub mod thermistor;
+pub mod tmp117;
+pub mod units;
+pub mod vl53l0x;
+pub mod w25q;
+#[cfg(feature = "esp32c3")]
+pub mod ws2812;
+
+use units::Millivolts;
+
+/// An ADC channel that can be read asynchronously
+///
+/// Implemented by the ADC drivers of this crate so that higher-level helpers,
+/// such as [battery::BatteryMonitor], can be generic over the ADC source.
+#[allow(async_fn_in_trait)]
+pub trait AsyncAdcChannel {
+    /// Error returned by a failed read
+    type Error;
+
+    /// Read the voltage at the input of the channel.
+    async fn read_millivolts(&mut self) -> Result<Millivolts, Self::Error>;
+}
+
+impl<T: AsyncAdcChannel + ?Sized> AsyncAdcChannel for &mut T {
+    type Error = T::Error;
+
+    async fn read_millivolts(&mut self) -> Result<Millivolts, Self::Error> {
+        T::read_millivolts(self).await
+    }
+}
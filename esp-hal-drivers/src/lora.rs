@@ -0,0 +1,254 @@
+//! # lora
+//!
+//! ## Overview
+//!
+//! Modulation settings and packets shared by the LoRa radio drivers of this
+//! crate, the [SX1276](crate::sx1276) and the [SX1262](crate::sx1262).
+//!
+//! - A [Config] holds the frequency, spreading factor, bandwidth and coding
+//!   rate of a link, which must match on both ends.
+//! - Both radios implement the [Radio] trait, so links can be written once
+//!   for either of them.
+//! - [Config::time_on_air] gives the time taken to send a packet, to respect
+//!   the duty cycle limits of the band.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let config = Config::new(868_100_000)
+//!     .with_spreading_factor(SpreadingFactor::Sf9)
+//!     .with_tx_power(14);
+//!
+//! async fn ping<R: Radio>(radio: &mut R) -> Result<(), R::Error> {
+//!     radio.transmit(b"ping").await?;
+//!     let mut buf = [0u8; 255];
+//!     let (len, status) = radio.receive(&mut buf).await?;
+//!     println!("{:?} at {} dBm", &buf[..len], status.rssi);
+//!     Ok(())
+//! }
+//! ```
+
+use embassy_time::Duration;
+
+/// Sync word of private networks
+pub const SYNC_WORD_PRIVATE: u8 = 0x12;
+
+/// Sync word of public networks, such as LoRaWAN
+pub const SYNC_WORD_PUBLIC: u8 = 0x34;
+
+/// Shortest symbol for which the low data rate optimization is required, in
+/// µs
+const LOW_DATA_RATE_SYMBOL_US: u64 = 16_000;
+
+/// Spreading factor, the number of bits carried by a symbol
+///
+/// Higher spreading factors reach further but take longer to send.
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpreadingFactor {
+    /// Only supported by the SX1262
+    Sf5 = 5,
+    Sf6 = 6,
+    Sf7 = 7,
+    Sf8 = 8,
+    Sf9 = 9,
+    Sf10 = 10,
+    Sf11 = 11,
+    Sf12 = 12,
+}
+
+impl SpreadingFactor {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Bandwidth of the signal
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Bandwidth {
+    Khz7_8,
+    Khz10_4,
+    Khz15_6,
+    Khz20_8,
+    Khz31_25,
+    Khz41_7,
+    Khz62_5,
+    Khz125,
+    Khz250,
+    Khz500,
+}
+
+impl Bandwidth {
+    /// Get the bandwidth in Hz
+    pub fn hz(&self) -> u32 {
+        match self {
+            Bandwidth::Khz7_8 => 7_800,
+            Bandwidth::Khz10_4 => 10_400,
+            Bandwidth::Khz15_6 => 15_600,
+            Bandwidth::Khz20_8 => 20_800,
+            Bandwidth::Khz31_25 => 31_250,
+            Bandwidth::Khz41_7 => 41_700,
+            Bandwidth::Khz62_5 => 62_500,
+            Bandwidth::Khz125 => 125_000,
+            Bandwidth::Khz250 => 250_000,
+            Bandwidth::Khz500 => 500_000,
+        }
+    }
+}
+
+/// Coding rate of the forward error correction
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CodingRate {
+    Cr4_5 = 1,
+    Cr4_6 = 2,
+    Cr4_7 = 3,
+    Cr4_8 = 4,
+}
+
+impl CodingRate {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Settings of a LoRa link
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    pub(crate) frequency: u32,
+    pub(crate) spreading_factor: SpreadingFactor,
+    pub(crate) bandwidth: Bandwidth,
+    pub(crate) coding_rate: CodingRate,
+    pub(crate) preamble_len: u16,
+    pub(crate) tx_power: i8,
+    pub(crate) sync_word: u8,
+    pub(crate) crc: bool,
+}
+
+impl Config {
+    /// Create a new configuration on a frequency in Hz, with SF7, a
+    /// bandwidth of 125 kHz, a coding rate of 4/5, a preamble of 8 symbols,
+    /// a power of 14 dBm, the private sync word and CRCs.
+    pub fn new(frequency: u32) -> Self {
+        Self {
+            frequency,
+            spreading_factor: SpreadingFactor::Sf7,
+            bandwidth: Bandwidth::Khz125,
+            coding_rate: CodingRate::Cr4_5,
+            preamble_len: 8,
+            tx_power: 14,
+            sync_word: SYNC_WORD_PRIVATE,
+            crc: true,
+        }
+    }
+
+    pub fn with_frequency(mut self, frequency: u32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn with_spreading_factor(mut self, spreading_factor: SpreadingFactor) -> Self {
+        self.spreading_factor = spreading_factor;
+        self
+    }
+
+    pub fn with_bandwidth(mut self, bandwidth: Bandwidth) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+
+    pub fn with_coding_rate(mut self, coding_rate: CodingRate) -> Self {
+        self.coding_rate = coding_rate;
+        self
+    }
+
+    /// Set the length of the preamble in symbols.
+    pub fn with_preamble_len(mut self, preamble_len: u16) -> Self {
+        self.preamble_len = preamble_len;
+        self
+    }
+
+    /// Set the output power in dBm, clamped to the range of the radio.
+    pub fn with_tx_power(mut self, tx_power: i8) -> Self {
+        self.tx_power = tx_power;
+        self
+    }
+
+    /// Set the sync word, e.g. [SYNC_WORD_PRIVATE], separating networks on
+    /// the same frequency.
+    pub fn with_sync_word(mut self, sync_word: u8) -> Self {
+        self.sync_word = sync_word;
+        self
+    }
+
+    /// Set whether packets carry a CRC, checked on reception.
+    pub fn with_crc(mut self, crc: bool) -> Self {
+        self.crc = crc;
+        self
+    }
+
+    /// Get the frequency in Hz
+    pub fn frequency(&self) -> u32 {
+        self.frequency
+    }
+
+    /// Get the time taken to send a packet of a given length. See the
+    /// SX1276 datasheet section 4.1.1.7 for more details.
+    pub fn time_on_air(&self, len: usize) -> Duration {
+        let sf = self.spreading_factor.bits() as i64;
+        let symbol_us = self.symbol_us() as i64;
+        let low_data_rate = self.low_data_rate_optimize() as i64;
+        let crc = self.crc as i64;
+
+        // Symbols of the payload, with an explicit header
+        let bits = 8 * len as i64 - 4 * sf + 28 + 16 * crc;
+        let bits_per_block = 4 * (sf - 2 * low_data_rate);
+        let blocks = (bits.max(0) + bits_per_block - 1) / bits_per_block;
+        let payload_symbols = 8 + blocks * (self.coding_rate.bits() as i64 + 4);
+
+        // The preamble ends with 4.25 symbols
+        let preamble_us = (4 * self.preamble_len as i64 + 17) * symbol_us / 4;
+        Duration::from_micros((preamble_us + payload_symbols * symbol_us) as u64)
+    }
+
+    /// Get the duration of a symbol in µs
+    fn symbol_us(&self) -> u64 {
+        (1_000_000u64 << self.spreading_factor.bits()) / self.bandwidth.hz() as u64
+    }
+
+    /// Whether symbols are long enough to require the low data rate
+    /// optimization
+    pub(crate) fn low_data_rate_optimize(&self) -> bool {
+        self.symbol_us() >= LOW_DATA_RATE_SYMBOL_US
+    }
+}
+
+/// Signal quality of a received packet
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PacketStatus {
+    /// Received signal strength in dBm
+    pub rssi: i16,
+    /// Signal to noise ratio in dB, negative below the noise floor
+    pub snr: f32,
+}
+
+/// A LoRa radio
+#[allow(async_fn_in_trait)]
+pub trait Radio {
+    /// Error returned by a failed operation
+    type Error;
+
+    /// Send a packet and wait for it to be sent.
+    async fn transmit(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Wait for a packet, read it into a buffer, and return its length and
+    /// signal quality. The radio listens until a packet is received, so the
+    /// future can be wrapped in a timeout.
+    async fn receive(&mut self, buf: &mut [u8]) -> Result<(usize, PacketStatus), Self::Error>;
+}
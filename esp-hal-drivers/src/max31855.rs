@@ -0,0 +1,138 @@
+//! # max31855
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Maxim MAX31855
+//! thermocouple-to-digital converter over SPI.
+//!
+//! - The thermocouple temperature is compensated for the cold junction, with
+//!   a resolution of 0.25 °C.
+//! - The cold junction temperature, i.e. the temperature of the converter
+//!   itself, is measured with a resolution of 0.0625 °C.
+//! - An open thermocouple, or one shorted to GND or VCC, is reported as a
+//!   [Fault] instead of a temperature, so that e.g. a heater is never driven
+//!   from a bogus reading.
+//!
+//! The converter is read-only and converts continuously, a new temperature
+//! being ready every 100 ms.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let spi = SpiDevice::new(spi_bus, cs);
+//! let mut max31855 = Max31855::new(spi);
+//!
+//! match max31855.read().await {
+//!     Ok(measurement) => println!("{} °C", measurement.thermocouple.0),
+//!     Err(Error::Fault(fault)) if fault.open => println!("No thermocouple"),
+//!     Err(error) => println!("{:?}", error),
+//! }
+//! ```
+
+use embassy_time::Duration;
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::units::Celsius;
+
+/// Time taken by a conversion
+pub const CONVERSION_TIME: Duration = Duration::from_millis(100);
+
+/// Resolution of the thermocouple temperature in °C per LSB
+const THERMOCOUPLE_CELSIUS_PER_LSB: f32 = 0.25;
+
+/// Resolution of the cold junction temperature in °C per LSB
+const COLD_JUNCTION_CELSIUS_PER_LSB: f32 = 0.0625;
+
+/// Flags of the conversion. See datasheet table 2 for more details.
+const FLAG_FAULT: u32 = 1 << 16;
+const FLAG_SHORT_TO_VCC: u32 = 1 << 2;
+const FLAG_SHORT_TO_GND: u32 = 1 << 1;
+const FLAG_OPEN: u32 = 1 << 0;
+
+/// A measurement of the converter
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    /// Temperature at the tip of the thermocouple
+    pub thermocouple: Celsius,
+    /// Temperature of the converter, where the thermocouple is connected
+    pub cold_junction: Celsius,
+}
+
+/// Faults of the thermocouple
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Fault {
+    /// The thermocouple is open, or not connected.
+    pub open: bool,
+    /// The thermocouple is shorted to GND.
+    pub short_to_gnd: bool,
+    /// The thermocouple is shorted to VCC.
+    pub short_to_vcc: bool,
+    /// The temperature of the converter, still valid during a fault
+    pub cold_junction: Celsius,
+}
+
+/// A MAX31855 on an SPI bus
+pub struct Max31855<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: SpiDevice> Max31855<SPI> {
+    /// Create a new converter.
+    ///
+    /// # Arguments
+    ///
+    /// - `spi`: The SPI device of the converter, in mode 0 and up to 5 MHz.
+    pub fn new(spi: SPI) -> Self {
+        Self { spi }
+    }
+
+    /// Release the underlying SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Read the latest conversion.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Fault` if the thermocouple is faulty.
+    pub async fn read(&mut self) -> Result<Measurement, Error<SPI::Error>> {
+        let mut buf = [0u8; 4];
+        self.spi.read(&mut buf).await.map_err(Error::Spi)?;
+        let raw = u32::from_be_bytes(buf);
+
+        // Both temperatures are left-aligned two's complement, so shifting the
+        // signed word sign-extends them
+        let thermocouple = (raw as i32 >> 18) as f32 * THERMOCOUPLE_CELSIUS_PER_LSB;
+        let cold_junction = ((raw << 16) as i32 >> 20) as f32 * COLD_JUNCTION_CELSIUS_PER_LSB;
+        let cold_junction = Celsius(cold_junction);
+
+        if raw & FLAG_FAULT != 0 {
+            return Err(Error::Fault(Fault {
+                open: raw & FLAG_OPEN != 0,
+                short_to_gnd: raw & FLAG_SHORT_TO_GND != 0,
+                short_to_vcc: raw & FLAG_SHORT_TO_VCC != 0,
+                cold_junction,
+            }));
+        }
+
+        Ok(Measurement {
+            thermocouple: Celsius(thermocouple),
+            cold_junction,
+        })
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying SPI device.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI bus error
+    Spi(E),
+    /// The thermocouple is faulty.
+    Fault(Fault),
+}
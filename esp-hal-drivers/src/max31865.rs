@@ -0,0 +1,505 @@
+//! # max31865
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Maxim MAX31865
+//! RTD-to-digital converter over SPI, for PT100 and PT1000 sensors.
+//!
+//! - 2-wire, 3-wire and 4-wire RTDs are supported. 3-wire RTDs need the
+//!   matching jumpers of the board, and 2-wire RTDs include the resistance of
+//!   their leads in the measurement.
+//! - The resistance of the RTD is measured relative to a reference resistor,
+//!   430 Ω on most PT100 boards and 4.3 kΩ on most PT1000 boards.
+//! - The temperature is computed from the resistance with the
+//!   Callendar–Van Dusen equation of IEC 60751, in fixed point math.
+//! - The fault detection cycle checks the RTD and its wiring for opens and
+//!   shorts.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let spi = SpiDevice::new(spi_bus, cs);
+//! let config = Config::new().with_wires(Wires::Three).with_filter(Filter::Hz50);
+//! let mut max31865 = Max31865::new(spi, config).await?;
+//!
+//! max31865.detect_faults().await?;
+//! let temperature = max31865.one_shot_measurement().await?;
+//! println!("{} °C", temperature.0);
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use crate::units::Celsius;
+
+/// Full scale of the RTD register, the reference resistance
+const FULL_SCALE: u64 = 1 << 15;
+
+/// Time taken by the bias voltage to settle before a conversion
+const BIAS_TIME: Duration = Duration::from_millis(10);
+
+/// Time between two polls of the fault detection cycle
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Maximum time taken by the fault detection cycle
+const FAULT_DETECTION_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Registers of the converter. See datasheet table 1 for more details.
+struct Register;
+
+impl Register {
+    const CONFIG: u8 = 0x00;
+    const RTD_MSB: u8 = 0x01;
+    const HIGH_FAULT_THRESHOLD_MSB: u8 = 0x03;
+    const FAULT_STATUS: u8 = 0x07;
+}
+
+/// Flag of the address selecting a write
+const WRITE: u8 = 0x80;
+
+/// Flags of the configuration register
+const CONFIG_BIAS: u8 = 1 << 7;
+const CONFIG_AUTO: u8 = 1 << 6;
+const CONFIG_ONE_SHOT: u8 = 1 << 5;
+const CONFIG_THREE_WIRE: u8 = 1 << 4;
+const CONFIG_FAULT_DETECTION_AUTO: u8 = 0b01 << 2;
+const CONFIG_FAULT_DETECTION_MASK: u8 = 0b11 << 2;
+const CONFIG_FAULT_CLEAR: u8 = 1 << 1;
+const CONFIG_FILTER_50HZ: u8 = 1 << 0;
+
+/// Callendar–Van Dusen coefficients of IEC 60751, scaled to integers: `A` in
+/// 1e-7/°C, `B` in 1e-10/°C² and `C` in 1e-15/°C⁴
+const CVD_A: i128 = 39_083;
+const CVD_B: i128 = -5_775;
+const CVD_C: i128 = -4_183;
+
+/// Scale of the resistance ratios, in nano
+const RATIO_ONE: i128 = 1_000_000_000;
+
+/// Maximum number of Newton iterations solving the Callendar–Van Dusen
+/// equation
+const CVD_ITERATIONS: usize = 10;
+
+/// Configuration of the converter
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    rtd: Rtd,
+    wires: Wires,
+    filter: Filter,
+    reference_milliohms: u32,
+}
+
+impl Default for Config {
+    /// Default configuration of the converter, for a 4-wire PT100 with a
+    /// 430 Ω reference resistor
+    fn default() -> Self {
+        Self {
+            rtd: Rtd::default(),
+            wires: Wires::default(),
+            filter: Filter::default(),
+            reference_milliohms: 430_000,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rtd(mut self, rtd: Rtd) -> Self {
+        self.rtd = rtd;
+        self
+    }
+
+    pub fn with_wires(mut self, wires: Wires) -> Self {
+        self.wires = wires;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Set the resistance of the reference resistor, in mΩ.
+    pub fn with_reference_resistor(mut self, milliohms: u32) -> Self {
+        self.reference_milliohms = milliohms;
+        self
+    }
+
+    /// Value of the configuration register, without the bias nor a
+    /// conversion
+    fn bits(&self) -> u8 {
+        let mut bits = 0;
+        if let Wires::Three = self.wires {
+            bits |= CONFIG_THREE_WIRE;
+        }
+        if let Filter::Hz50 = self.filter {
+            bits |= CONFIG_FILTER_50HZ;
+        }
+        bits
+    }
+}
+
+/// Type of RTD
+///
+/// Defaults to `Pt100`.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Rtd {
+    #[default]
+    Pt100,
+    Pt1000,
+}
+
+impl Rtd {
+    /// Resistance of the RTD at 0 °C, in mΩ
+    pub fn nominal_milliohms(&self) -> u32 {
+        match self {
+            Rtd::Pt100 => 100_000,
+            Rtd::Pt1000 => 1_000_000,
+        }
+    }
+}
+
+/// Number of wires of the RTD
+///
+/// Defaults to `Four`.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Wires {
+    Two,
+    Three,
+    #[default]
+    Four,
+}
+
+/// Frequency of the mains rejected by the input filter
+///
+/// Defaults to `Hz60`.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Filter {
+    Hz50,
+    #[default]
+    Hz60,
+}
+
+impl Filter {
+    /// Maximum time taken by a conversion
+    fn conversion_time(&self) -> Duration {
+        match self {
+            Filter::Hz50 => Duration::from_micros(62_500),
+            Filter::Hz60 => Duration::from_micros(52_000),
+        }
+    }
+}
+
+/// Faults reported by the converter
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Fault {
+    /// The resistance is above the high fault threshold, e.g. an open RTD.
+    pub rtd_high: bool,
+    /// The resistance is below the low fault threshold, e.g. a shorted RTD.
+    pub rtd_low: bool,
+    /// REFIN- is above 0.85 × VBIAS.
+    pub refin_high: bool,
+    /// REFIN- is below 0.85 × VBIAS, with FORCE- open.
+    pub refin_low: bool,
+    /// RTDIN- is below 0.85 × VBIAS, with FORCE- open.
+    pub rtdin_low: bool,
+    /// An input is over or under voltage.
+    pub voltage: bool,
+}
+
+impl Fault {
+    fn from_bits(bits: u8) -> Self {
+        Self {
+            rtd_high: bits & (1 << 7) != 0,
+            rtd_low: bits & (1 << 6) != 0,
+            refin_high: bits & (1 << 5) != 0,
+            refin_low: bits & (1 << 4) != 0,
+            rtdin_low: bits & (1 << 3) != 0,
+            voltage: bits & (1 << 2) != 0,
+        }
+    }
+}
+
+/// A MAX31865 on an SPI bus
+pub struct Max31865<SPI> {
+    spi: SPI,
+    config: Config,
+    continuous: bool,
+}
+
+impl<SPI: SpiDevice> Max31865<SPI> {
+    /// Create a new converter and clear its faults. The bias is off until a
+    /// conversion is requested.
+    ///
+    /// # Arguments
+    ///
+    /// - `spi`: The SPI device of the converter, in mode 1 or 3 and up to
+    ///   5 MHz.
+    /// - `config`: The configuration of the converter.
+    pub async fn new(spi: SPI, config: Config) -> Result<Self, Error<SPI::Error>> {
+        let mut max31865 = Self {
+            spi,
+            config,
+            continuous: false,
+        };
+        max31865.set_config(config).await?;
+        Ok(max31865)
+    }
+
+    /// Get the current configuration of the converter
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Set the configuration of the converter, stopping continuous
+    /// conversions.
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<SPI::Error>> {
+        self.config = config;
+        self.continuous = false;
+        self.write_config(CONFIG_FAULT_CLEAR).await
+    }
+
+    /// Release the underlying SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Set the thresholds of the RTD faults, as raw 15-bit codes relative to
+    /// the reference resistance. They default to the whole range.
+    pub async fn set_fault_thresholds(
+        &mut self,
+        low: u16,
+        high: u16,
+    ) -> Result<(), Error<SPI::Error>> {
+        let [high_msb, high_lsb] = (high << 1).to_be_bytes();
+        let [low_msb, low_lsb] = (low << 1).to_be_bytes();
+        self.spi
+            .write(&[
+                Register::HIGH_FAULT_THRESHOLD_MSB | WRITE,
+                high_msb,
+                high_lsb,
+                low_msb,
+                low_lsb,
+            ])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Run the automatic fault detection cycle, checking the RTD and its
+    /// wiring.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Fault` with the detected faults, which are then
+    /// cleared, and `Error::Timeout` if the cycle does not complete.
+    pub async fn detect_faults(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.write_config(CONFIG_BIAS | CONFIG_FAULT_DETECTION_AUTO)
+            .await?;
+
+        let poll = async {
+            loop {
+                let config = self.read_register(Register::CONFIG).await?;
+                if config & CONFIG_FAULT_DETECTION_MASK == 0 {
+                    return Ok(());
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        };
+        with_timeout(FAULT_DETECTION_TIMEOUT, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))?;
+
+        let fault = self.read_fault().await?;
+        self.write_config(self.mode_flags() | CONFIG_FAULT_CLEAR)
+            .await?;
+        match fault {
+            Some(fault) => Err(Error::Fault(fault)),
+            None => Ok(()),
+        }
+    }
+
+    /// Bias the RTD, start a single conversion and wait for its result. The
+    /// bias is turned off once the conversion is complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Fault` if the conversion raised a fault, which is then
+    /// cleared.
+    pub async fn one_shot_measurement(&mut self) -> Result<Celsius, Error<SPI::Error>> {
+        self.write_config(CONFIG_BIAS).await?;
+        Timer::after(BIAS_TIME).await;
+        self.write_config(CONFIG_BIAS | CONFIG_ONE_SHOT).await?;
+        Timer::after(self.config.filter.conversion_time()).await;
+
+        let temperature = self.get_measurement().await;
+        self.write_config(self.mode_flags()).await?;
+        temperature
+    }
+
+    /// Bias the RTD and let the converter convert continuously, every 20 ms
+    /// with the 50 Hz filter or 16.7 ms with the 60 Hz filter.
+    pub async fn start_continuous(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.write_config(CONFIG_BIAS | CONFIG_AUTO).await?;
+        self.continuous = true;
+        Ok(())
+    }
+
+    /// Stop the continuous conversions and turn the bias off.
+    pub async fn shutdown(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.write_config(0).await?;
+        self.continuous = false;
+        Ok(())
+    }
+
+    /// Read the latest conversion as a temperature.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Fault` if the conversion raised a fault, which is then
+    /// cleared.
+    pub async fn get_measurement(&mut self) -> Result<Celsius, Error<SPI::Error>> {
+        let resistance = self.read_resistance().await?;
+        let temperature = temperature_millicelsius(resistance, self.config.rtd.nominal_milliohms());
+        Ok(Celsius(temperature as f32 / 1000.0))
+    }
+
+    /// Read the latest conversion as the resistance of the RTD, in mΩ.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Fault` if the conversion raised a fault, which is then
+    /// cleared.
+    pub async fn read_resistance(&mut self) -> Result<u32, Error<SPI::Error>> {
+        let mut buf = [0u8; 2];
+        self.read_registers(Register::RTD_MSB, &mut buf).await?;
+        let raw = u16::from_be_bytes(buf);
+
+        // The least significant bit flags a fault
+        if raw & 1 != 0 {
+            let fault = self.read_fault().await?.unwrap_or_default();
+            self.write_config(self.mode_flags() | CONFIG_FAULT_CLEAR)
+                .await?;
+            return Err(Error::Fault(fault));
+        }
+
+        let code = (raw >> 1) as u64;
+        Ok((code * self.config.reference_milliohms as u64 / FULL_SCALE) as u32)
+    }
+
+    /// Flags of the configuration register keeping the current mode
+    fn mode_flags(&self) -> u8 {
+        if self.continuous {
+            CONFIG_BIAS | CONFIG_AUTO
+        } else {
+            0
+        }
+    }
+
+    /// Read the fault status register, if any fault is flagged.
+    async fn read_fault(&mut self) -> Result<Option<Fault>, Error<SPI::Error>> {
+        let bits = self.read_register(Register::FAULT_STATUS).await?;
+        if bits == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Fault::from_bits(bits)))
+    }
+
+    /// Write the configuration register, with some flags on top of the
+    /// configuration.
+    async fn write_config(&mut self, flags: u8) -> Result<(), Error<SPI::Error>> {
+        let config = self.config.bits() | flags;
+        self.spi
+            .write(&[Register::CONFIG | WRITE, config])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<SPI::Error>> {
+        let mut buf = [0u8; 1];
+        self.read_registers(register, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn read_registers(
+        &mut self,
+        register: u8,
+        buf: &mut [u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.spi
+            .transaction(&mut [Operation::Write(&[register]), Operation::Read(buf)])
+            .await
+            .map_err(Error::Spi)
+    }
+}
+
+/// Compute the temperature of a platinum RTD from its resistance, in m°C,
+/// with the Callendar–Van Dusen equation of IEC 60751.
+///
+/// Only integer math is used. The equation is solved with Newton's method,
+/// from -200 °C to 850 °C.
+///
+/// # Arguments
+///
+/// - `resistance`: The resistance of the RTD, in mΩ.
+/// - `nominal`: The resistance of the RTD at 0 °C, in mΩ, e.g. from
+///   [Rtd::nominal_milliohms].
+pub fn temperature_millicelsius(resistance: u32, nominal: u32) -> i32 {
+    if nominal == 0 {
+        return 0;
+    }
+
+    // Resistance ratio R / R0, in nano
+    let ratio = resistance as i128 * RATIO_ONE / nominal as i128;
+
+    // Start from the linear approximation, then refine
+    let mut t = (ratio - RATIO_ONE) * 10 / CVD_A;
+    for _ in 0..CVD_ITERATIONS {
+        let step = (cvd_ratio(t) - ratio) * 10 / cvd_slope(t);
+        t -= step;
+        if step == 0 {
+            break;
+        }
+    }
+    t.clamp(i32::MIN as i128, i32::MAX as i128) as i32
+}
+
+/// Resistance ratio R / R0 of a platinum RTD at `t` m°C, in nano
+fn cvd_ratio(t: i128) -> i128 {
+    let mut ratio = RATIO_ONE + CVD_A * t / 10 + CVD_B * t * t / 10_000_000;
+    if t < 0 {
+        ratio += CVD_C * (t - 100_000) * t * t * t / 1_000_000_000_000_000_000;
+    }
+    ratio
+}
+
+/// Derivative of the resistance ratio of a platinum RTD at `t` m°C, in
+/// 1e-10 per m°C
+fn cvd_slope(t: i128) -> i128 {
+    let mut slope = CVD_A + 2 * CVD_B * t / 1_000_000;
+    if t < 0 {
+        slope += CVD_C * (4 * t * t * t - 300_000 * t * t) / 100_000_000_000_000_000;
+    }
+    slope
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying SPI device.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI bus error
+    Spi(E),
+    /// The converter reported faults.
+    Fault(Fault),
+    /// The fault detection cycle did not complete in time.
+    Timeout,
+}
@@ -0,0 +1,376 @@
+//! # mcp23017
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Microchip
+//! MCP23017 16-bit GPIO expander over I2C.
+//!
+//! - Each pin can be an input, with an optional pull-up, or an output.
+//! - The pins can be read and written one at a time, by port of 8 pins, or
+//!   all 16 at once.
+//! - Inputs can interrupt on change. The INTA and INTB outputs of the
+//!   expander are mirrored, so a single GPIO attached with
+//!   [Mcp23017::with_interrupt_pin] is enough for
+//!   [Mcp23017::wait_for_change] to await the changes of any pin.
+//!
+//! The driver keeps a copy of the configuration registers so that changing a
+//! single pin only takes a write.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut expander = Mcp23017::new(i2c, BASE_ADDRESS)
+//!     .await?
+//!     .with_interrupt_pin(Input::new(peripherals.GPIO7, Pull::Up));
+//!
+//! // Drive a LED
+//! expander.set_direction(Pin::A0, Direction::Output).await?;
+//! expander.set_output(Pin::A0, true).await?;
+//!
+//! // Wait for a button to be pressed
+//! expander.set_pull_up(Pin::B3, true).await?;
+//! expander.enable_interrupt(Pin::B3).await?;
+//! loop {
+//!     let change = expander.wait_for_change().await?;
+//!     if change.is_triggered(Pin::B3) && !change.level(Pin::B3) {
+//!         println!("Button pressed");
+//!     }
+//! }
+//! ```
+
+use embedded_hal_async::{digital::Wait, i2c::I2c};
+
+/// I2C address of the expander when A0, A1 and A2 are tied to ground
+pub const BASE_ADDRESS: u8 = 0x20;
+
+/// Registers of port A when IOCON.BANK is cleared. The registers of port B
+/// directly follow, so both ports are accessed with a single 16-bit transfer.
+/// See datasheet table 3-5 for more details.
+struct Register;
+
+impl Register {
+    const IODIR: u8 = 0x00;
+    const GPINTEN: u8 = 0x04;
+    const IOCON: u8 = 0x0A;
+    const GPPU: u8 = 0x0C;
+    const INTF: u8 = 0x0E;
+    const INTCAP: u8 = 0x10;
+    const GPIO: u8 = 0x12;
+    const OLAT: u8 = 0x14;
+}
+
+/// Flag of the IOCON register connecting INTA and INTB together
+const IOCON_MIRROR: u8 = 1 << 6;
+
+/// Flag of the IOCON register making INTA and INTB open-drain
+const IOCON_ODR: u8 = 1 << 2;
+
+/// A pin of the expander
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pin {
+    A0,
+    A1,
+    A2,
+    A3,
+    A4,
+    A5,
+    A6,
+    A7,
+    B0,
+    B1,
+    B2,
+    B3,
+    B4,
+    B5,
+    B6,
+    B7,
+}
+
+impl Pin {
+    /// Return the mask of the pin in 16-bit values, where port A is the low
+    /// byte.
+    pub fn mask(&self) -> u16 {
+        1 << (*self as u8)
+    }
+}
+
+/// A port of 8 pins of the expander
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Port {
+    A,
+    B,
+}
+
+impl Port {
+    /// Offset of the registers of the port from those of port A
+    fn offset(&self) -> u8 {
+        match self {
+            Port::A => 0,
+            Port::B => 1,
+        }
+    }
+}
+
+/// Direction of a pin
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+/// Pins that changed, as returned by [Mcp23017::wait_for_change]
+///
+/// Bits of 16-bit values are the pins, where port A is the low byte.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Change {
+    /// Pins that triggered the interrupt
+    pub triggered: u16,
+    /// Levels of the pins when the interrupt was triggered
+    pub captured: u16,
+}
+
+impl Change {
+    /// Return whether a pin triggered the interrupt.
+    pub fn is_triggered(&self, pin: Pin) -> bool {
+        self.triggered & pin.mask() != 0
+    }
+
+    /// Return the level of a pin when the interrupt was triggered.
+    pub fn level(&self, pin: Pin) -> bool {
+        self.captured & pin.mask() != 0
+    }
+}
+
+/// An MCP23017 on an I2C bus
+///
+/// `INT` is the pin the interrupt outputs of the expander are connected to, if
+/// any.
+pub struct Mcp23017<I2C, INT = ()> {
+    address: u8,
+    i2c: I2C,
+    interrupt: INT,
+    /// Copy of the IODIR registers, where a set bit is an input
+    directions: u16,
+    /// Copy of the GPPU registers
+    pull_ups: u16,
+    /// Copy of the OLAT registers
+    outputs: u16,
+    /// Copy of the GPINTEN registers
+    interrupts: u16,
+}
+
+impl<I2C: I2c> Mcp23017<I2C> {
+    /// Create a new expander and reset its pins to inputs without pull-ups
+    /// nor interrupts.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the expander is on.
+    /// - `address`: The I2C address of the expander, [BASE_ADDRESS] plus the
+    ///   value of its address pins.
+    pub async fn new(i2c: I2C, address: u8) -> Result<Self, Error<I2C::Error>> {
+        let mut expander = Self {
+            address,
+            i2c,
+            interrupt: (),
+            directions: 0xFFFF,
+            pull_ups: 0,
+            outputs: 0,
+            interrupts: 0,
+        };
+
+        // Mirror the open-drain, active-low interrupt outputs so that either
+        // can be wired to a single GPIO
+        expander
+            .i2c
+            .write(address, &[Register::IOCON, IOCON_MIRROR | IOCON_ODR])
+            .await
+            .map_err(Error::I2c)?;
+
+        expander.write_pair(Register::IODIR, 0xFFFF).await?;
+        expander.write_pair(Register::GPPU, 0).await?;
+        expander.write_pair(Register::OLAT, 0).await?;
+        expander.write_pair(Register::GPINTEN, 0).await?;
+        Ok(expander)
+    }
+
+    /// Attach the pin the interrupt outputs of the expander are connected
+    /// to.
+    ///
+    /// The outputs are open-drain and active low, so the pin needs a pull-up.
+    pub fn with_interrupt_pin<P: Wait>(self, interrupt: P) -> Mcp23017<I2C, P> {
+        Mcp23017 {
+            address: self.address,
+            i2c: self.i2c,
+            interrupt,
+            directions: self.directions,
+            pull_ups: self.pull_ups,
+            outputs: self.outputs,
+            interrupts: self.interrupts,
+        }
+    }
+}
+
+impl<I2C: I2c, P: Wait> Mcp23017<I2C, P> {
+    /// Wait for an input with interrupts enabled to change, then clear the
+    /// interrupt.
+    ///
+    /// Returns immediately if an interrupt is already pending.
+    pub async fn wait_for_change(&mut self) -> Result<Change, Error<I2C::Error>> {
+        self.interrupt
+            .wait_for_low()
+            .await
+            .map_err(|_| Error::Pin)?;
+
+        // Reading the captured levels clears the interrupt
+        let triggered = self.read_pair(Register::INTF).await?;
+        let captured = self.read_pair(Register::INTCAP).await?;
+        Ok(Change {
+            triggered,
+            captured,
+        })
+    }
+
+    /// Release the underlying I2C bus and interrupt pin
+    pub fn release_with_interrupt_pin(self) -> (I2C, P) {
+        (self.i2c, self.interrupt)
+    }
+}
+
+impl<I2C: I2c, INT> Mcp23017<I2C, INT> {
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    pub async fn set_direction(
+        &mut self,
+        pin: Pin,
+        direction: Direction,
+    ) -> Result<(), Error<I2C::Error>> {
+        let directions = match direction {
+            Direction::Input => self.directions | pin.mask(),
+            Direction::Output => self.directions & !pin.mask(),
+        };
+        self.set_directions(directions).await
+    }
+
+    /// Set the direction of all pins at once, where a set bit is an input.
+    pub async fn set_directions(&mut self, inputs: u16) -> Result<(), Error<I2C::Error>> {
+        self.write_pair(Register::IODIR, inputs).await?;
+        self.directions = inputs;
+        Ok(())
+    }
+
+    /// Enable or disable the 100 kΩ pull-up of an input.
+    pub async fn set_pull_up(&mut self, pin: Pin, enabled: bool) -> Result<(), Error<I2C::Error>> {
+        let pull_ups = if enabled {
+            self.pull_ups | pin.mask()
+        } else {
+            self.pull_ups & !pin.mask()
+        };
+        self.write_pair(Register::GPPU, pull_ups).await?;
+        self.pull_ups = pull_ups;
+        Ok(())
+    }
+
+    /// Set the level of an output.
+    pub async fn set_output(&mut self, pin: Pin, high: bool) -> Result<(), Error<I2C::Error>> {
+        let outputs = if high {
+            self.outputs | pin.mask()
+        } else {
+            self.outputs & !pin.mask()
+        };
+        self.write_all(outputs).await
+    }
+
+    /// Read the level of a pin.
+    pub async fn read_pin(&mut self, pin: Pin) -> Result<bool, Error<I2C::Error>> {
+        Ok(self.read_all().await? & pin.mask() != 0)
+    }
+
+    /// Read the levels of the pins of a port.
+    pub async fn read_port(&mut self, port: Port) -> Result<u8, Error<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.i2c
+            .write_read(self.address, &[Register::GPIO + port.offset()], &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+        Ok(buf[0])
+    }
+
+    /// Set the levels of the outputs of a port.
+    pub async fn write_port(&mut self, port: Port, value: u8) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(self.address, &[Register::OLAT + port.offset(), value])
+            .await
+            .map_err(Error::I2c)?;
+
+        let shift = 8 * port.offset();
+        self.outputs = (self.outputs & !(0xFF << shift)) | ((value as u16) << shift);
+        Ok(())
+    }
+
+    /// Read the levels of all pins, where port A is the low byte.
+    pub async fn read_all(&mut self) -> Result<u16, Error<I2C::Error>> {
+        self.read_pair(Register::GPIO).await
+    }
+
+    /// Set the levels of all outputs, where port A is the low byte.
+    pub async fn write_all(&mut self, value: u16) -> Result<(), Error<I2C::Error>> {
+        self.write_pair(Register::OLAT, value).await?;
+        self.outputs = value;
+        Ok(())
+    }
+
+    /// Interrupt when an input changes from its previous level.
+    pub async fn enable_interrupt(&mut self, pin: Pin) -> Result<(), Error<I2C::Error>> {
+        let interrupts = self.interrupts | pin.mask();
+        self.write_pair(Register::GPINTEN, interrupts).await?;
+        self.interrupts = interrupts;
+        Ok(())
+    }
+
+    pub async fn disable_interrupt(&mut self, pin: Pin) -> Result<(), Error<I2C::Error>> {
+        let interrupts = self.interrupts & !pin.mask();
+        self.write_pair(Register::GPINTEN, interrupts).await?;
+        self.interrupts = interrupts;
+        Ok(())
+    }
+
+    /// Read a register of both ports, where port A is the low byte.
+    async fn read_pair(&mut self, register: u8) -> Result<u16, Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.i2c
+            .write_read(self.address, &[register], &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// Write a register of both ports, where port A is the low byte.
+    async fn write_pair(&mut self, register: u8, value: u16) -> Result<(), Error<I2C::Error>> {
+        let [a, b] = value.to_le_bytes();
+        self.i2c
+            .write(self.address, &[register, a, b])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The interrupt pin could not be read.
+    Pin,
+}
@@ -6,38 +6,46 @@
 //!
 //! This driver provides an abstraction to interact with the MCP3428 ADC.
 //!
+//! The driver is generic over [embedded_hal_async::i2c::I2c], so it can be
+//! used with the esp-hal I2C driver, bus adapters or mocks.
+//!
+//! The same register interface is shared by the whole MCP342x family. The
+//! number of available channels is selected at compile time with the
+//! `single_channel` (MCP3425), `dual_channel` (MCP3426/7) or `quad_channel`
+//! (MCP3428) cargo features.
+//!
 //! ## Example
 //!
 //! ```rust,ignore
 //! use embassy_time::{Duration, Timer};
-//! use esp_hal::i2c::master::{Config, I2c};
+//! use esp_hal::i2c::master::{Config as I2cConfig, I2c};
 //!
 //! // Prepare the I2C peripheral
 //! let peripherals = esp_hal::init(esp_hal::Config::default());
-//! let i2c = I2c::new(peripherals.I2C0, Config::default())
+//! let i2c = I2c::new(peripherals.I2C0, I2cConfig::default())
 //!     .unwrap()
 //!     .with_sda(peripherals.GPIO9)
 //!     .with_scl(peripherals.GPIO8)
 //!     .into_async();
 //!
-//! // Generate the configuration
-//! let address = 0x68;
-//! let mut config = ThermostatConfig::new(address, i2c, Mode::OneShot)
+//! // Generate the configuration, with both address pins tied to ground
+//! let address = Address::new(AddressPin::Low, AddressPin::Low);
+//! let config = Config::new()
 //!     .with_gain(Gain::Gain1)
 //!     .with_resolution(Resolution::Bits12Sps240);
+//! let mut adc = Mcp3428::new(i2c, address, config);
 //!
 //! // Read channel 1 and channel 2 in one-shot mode
-//! config.set_channel(Channel::Channel1);
-//! let voltage_1 = config.one_shot_measurement().await.ok();
-//! config.set_channel(Channel::Channel2);
-//! let voltage_2 = config.one_shot_measurement().await.ok();
+//! adc.set_channel(Channel::Channel1);
+//! let voltage_1 = adc.one_shot_measurement().await.ok();
+//! adc.set_channel(Channel::Channel2);
+//! let voltage_2 = adc.one_shot_measurement().await.ok();
 //! println!("Voltage 1: {}", voltage_1);
 //! println!("Voltage 2: {}", voltage_2);
 //!
-//! // Prepare the configuration for continuous reading of channel 1
-//! config.set_channel(Channel::Channel1);
-//! config.set_mode(Mode::Continuous);
-//! config.write_config().await.ok();
+//! // Switch to continuous reading of channel 1
+//! adc.set_channel(Channel::Channel1);
+//! let mut adc = adc.into_continuous().await.unwrap();
 //!
 //! // Read the measurement in a loop
 //! loop {
@@ -45,34 +53,78 @@
 //!     Timer::after(Duration::from_millis(1_000)).await;
 //!
 //!     // Read the measurement
-//!     let voltage = config.get_measurement().await.ok();
+//!     let voltage = adc.get_measurement().await.ok();
 //!     println!("Voltage: {}", voltage);
 //! }
 //! ```
+//!
+//! ## Shared bus
+//!
+//! The driver only needs an [embedded_hal_async::i2c::I2c] implementation, so
+//! the MCP3428 can share its bus with other devices through
+//! `embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice`:
+//!
+//! ```rust,ignore
+//! use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
+//! use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+//!
+//! let i2c_bus = Mutex::<NoopRawMutex, _>::new(i2c);
+//!
+//! let mut adc = Mcp3428::new(I2cDevice::new(&i2c_bus), 0x68, Config::new());
+//! let mut rtc = Rtc::new(I2cDevice::new(&i2c_bus));
+//!
+//! let voltage = adc.one_shot_measurement().await;
+//! ```
 
-use embassy_time::{Duration, Timer};
-use esp_hal::{i2c::master::I2c, Async};
+use core::marker::PhantomData;
 
-pub struct ThermostatConfig {
-    address: u8,
-    mode: Mode,
-    i2c: I2c<'static, Async>,
-    resolution: Resolution,
-    gain: Gain,
-    channel: Channel,
-}
+use embassy_sync::{blocking_mutex::raw::RawMutex, signal::Signal};
+use embassy_time::{with_timeout, Duration, Instant, Ticker, Timer};
+use embedded_hal_async::i2c::I2c;
 
-#[allow(unused, dead_code)]
-impl ThermostatConfig {
-    pub fn new(address: u8, i2c: I2c<'static, Async>, mode: Mode) -> Self {
-        Self {
-            address,
-            mode,
-            i2c,
-            resolution: Resolution::default(),
-            gain: Gain::default(),
-            channel: Channel::default(),
+use crate::{
+    units::{Microvolts, Millivolts},
+    AsyncAdcChannel,
+};
+
+/// Log a transaction at trace level when the `defmt` feature is enabled
+macro_rules! trace {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        #[cfg(feature = "defmt")]
+        defmt::trace!($fmt $(, $arg)*);
+        #[cfg(not(feature = "defmt"))]
+        {
+            $(let _ = &$arg;)*
         }
+    };
+}
+
+#[cfg(not(any(
+    feature = "single_channel",
+    feature = "dual_channel",
+    feature = "quad_channel"
+)))]
+compile_error!(
+    "One of the `single_channel`, `dual_channel` or `quad_channel` features must be enabled"
+);
+
+/// Configuration of the MCP3428
+///
+/// The conversion mode is selected by the type of the [Mcp3428] device.
+///
+/// Defaults to the power-on configuration of the device.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    pub resolution: Resolution,
+    pub gain: Gain,
+    pub channel: Channel,
+    pub filter: Filter,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
     }
 
     pub fn with_resolution(mut self, resolution: Resolution) -> Self {
@@ -85,20 +137,36 @@ impl ThermostatConfig {
         self
     }
 
-    pub fn with_channel(mut self, channel: Channel) -> Self {
-        self.channel = channel;
+    /// Select the fastest resolution whose voltage step at the input pins is
+    /// at most `step_uv` µV.
+    ///
+    /// The step accounts for the gain, so the gain should be configured
+    /// first. If no resolution is fine enough, the finest one is selected.
+    pub fn with_min_resolution_uv(mut self, step_uv: u32) -> Self {
+        let step_nv = step_uv as i64 * 1000;
+        self.resolution = [
+            Resolution::Bits12Sps240,
+            Resolution::Bits14Sps60,
+            Resolution::Bits16Sps15,
+        ]
+        .into_iter()
+        .find(|resolution| resolution.step_nv() / self.gain.factor() as i64 <= step_nv)
+        .unwrap_or(Resolution::Bits16Sps15);
         self
     }
 
-    pub fn set_channel(&mut self, channel: Channel) {
+    pub fn with_channel(mut self, channel: Channel) -> Self {
         self.channel = channel;
+        self
     }
 
-    pub fn set_mode(&mut self, mode: Mode) {
-        self.mode = mode;
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
     }
 
-    fn get_sleep_ms(&self) -> u64 {
+    /// Time in ms taken by a conversion at the configured resolution
+    fn conversion_time_ms(&self) -> u64 {
         match self.resolution {
             Resolution::Bits12Sps240 => 4,
             Resolution::Bits14Sps60 => 15,
@@ -110,127 +178,821 @@ impl ThermostatConfig {
         self.channel.bits() | self.resolution.bits() | self.gain.bits()
     }
 
-    fn command(&self) -> u8 {
-        match self.mode {
-            Mode::OneShot => ConfigRegister::NOT_READY | self.mode.bits() | self.config_flag(),
-            Mode::Continuous => self.mode.bits() | self.config_flag(),
+    /// Configuration byte written to the device in the given mode
+    fn command(&self, mode: Mode) -> u8 {
+        match mode {
+            Mode::OneShot => ConfigRegister::NOT_READY | mode.bits() | self.config_flag(),
+            Mode::Continuous => mode.bits() | self.config_flag(),
         }
     }
+}
 
-    pub async fn one_shot_measurement(&mut self) -> Result<i32, Error> {
-        if self
-            .i2c
-            .write(self.address, &[self.command()])
-            .await
-            .is_err()
-        {
-            return Err(Error::I2c);
+/// Conversion mode of an [Mcp3428] device
+pub trait DeviceMode {
+    /// Mode written to the configuration register
+    const MODE: Mode;
+}
+
+/// One-shot conversion mode
+///
+/// Each measurement triggers a single conversion, after which the device goes
+/// back to standby.
+pub struct OneShot;
+
+impl DeviceMode for OneShot {
+    const MODE: Mode = Mode::OneShot;
+}
+
+/// Continuous conversion mode
+///
+/// The device converts continuously and measurements return the latest
+/// conversion result.
+pub struct Continuous;
+
+impl DeviceMode for Continuous {
+    const MODE: Mode = Mode::Continuous;
+}
+
+/// An MCP3428 ADC on an I2C bus
+///
+/// The conversion mode is tracked in the type of the device so that
+/// continuous-mode measurements can only be read once the configuration has
+/// been written to the device.
+pub struct Mcp3428<I2C, MODE = OneShot> {
+    address: u8,
+    i2c: I2C,
+    config: Config,
+    timeout: Duration,
+    max_retries: u32,
+    calibrations: [Calibration; CHANNEL_COUNT],
+    filters: [FilterState; CHANNEL_COUNT],
+    ready_at: Option<Instant>,
+    last_sample_at: Option<Instant>,
+    _mode: PhantomData<MODE>,
+}
+
+impl<I2C: I2c> Mcp3428<I2C, OneShot> {
+    /// Create a new device.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the device is on.
+    /// - `address`: The 7-bit I2C address of the device, either as a raw value
+    ///   or as an [Address] computed from the state of the address pins.
+    /// - `config`: The configuration used for the conversions.
+    pub fn new(i2c: I2C, address: impl Into<u8>, config: Config) -> Self {
+        Self {
+            address: address.into(),
+            i2c,
+            config,
+            timeout: DEFAULT_TIMEOUT,
+            max_retries: DEFAULT_MAX_RETRIES,
+            calibrations: [Calibration::default(); CHANNEL_COUNT],
+            filters: [FilterState::default(); CHANNEL_COUNT],
+            ready_at: None,
+            last_sample_at: None,
+            _mode: PhantomData,
         }
-        Timer::after(Duration::from_millis(self.get_sleep_ms() + 2)).await;
+    }
 
-        let voltage = self.get_measurement().await?;
-        Ok(voltage)
+    /// Set the configuration used for the next conversions.
+    pub fn set_config(&mut self, config: Config) {
+        self.update_config(config);
     }
 
-    pub async fn write_config(&mut self) -> Result<(), Error> {
-        // Prepare to read channel 1
-        if self
-            .i2c
-            .write(self.address, &[self.command()])
-            .await
-            .is_err()
-        {
-            return Err(Error::I2c);
-        };
-        Timer::after(Duration::from_millis(self.get_sleep_ms())).await;
+    pub fn set_channel(&mut self, channel: Channel) {
+        self.config.channel = channel;
+    }
+
+    pub async fn one_shot_measurement(&mut self) -> Result<Millivolts, Error<I2C::Error>> {
+        self.sample().await
+    }
+
+    /// Trigger a one-shot conversion on each channel in turn and return the
+    /// results in channel order.
+    ///
+    /// The configured channel is restored afterwards.
+    pub async fn read_all_channels(
+        &mut self,
+    ) -> [Result<Millivolts, Error<I2C::Error>>; CHANNEL_COUNT] {
+        let channel = self.config.channel;
+
+        let mut results = [const { Err(Error::NotReady) }; CHANNEL_COUNT];
+        for (result, channel) in results.iter_mut().zip(Channel::ALL) {
+            self.config.channel = channel;
+            *result = self.one_shot_measurement().await;
+        }
+
+        self.config.channel = channel;
+        results
+    }
+
+    /// Return a stream of one-shot measurements, taken every `interval`.
+    ///
+    /// Between samples, the device stays in standby and no I2C traffic
+    /// happens, so the executor can keep the chip in light sleep. This is the
+    /// preferred way to sample slowly on battery power.
+    pub fn sample_every(&mut self, interval: Duration) -> Measurements<'_, I2C, OneShot> {
+        Measurements {
+            adc: self,
+            ticker: Ticker::every(interval),
+        }
+    }
+
+    /// Borrow a single channel of the device, e.g. to pass it to helpers
+    /// generic over [AsyncAdcChannel].
+    pub fn channel(&mut self, channel: Channel) -> Mcp3428Channel<'_, I2C> {
+        Mcp3428Channel { adc: self, channel }
+    }
+
+    /// Switch the device to continuous mode by writing the configuration to
+    /// it.
+    pub async fn into_continuous(self) -> Result<Mcp3428<I2C, Continuous>, Error<I2C::Error>> {
+        let mut adc = self.into_mode();
+        adc.write_config().await?;
+        Ok(adc)
+    }
+}
+
+impl<I2C: I2c> Mcp3428<I2C, Continuous> {
+    /// Write the configuration to the device and wait for the first conversion
+    /// result.
+    ///
+    /// The setters of the configuration already write it, so this is only
+    /// needed to restore it, e.g. after [Mcp3428::general_call_reset].
+    pub async fn write_config(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.send_command().await?;
 
         // Poll until ready
-        let mut buf = [0u8; 3];
+        self.poll_ready().await?;
+        Ok(())
+    }
+
+    /// Set the configuration, write it to the device and wait for the first
+    /// conversion result with it.
+    ///
+    /// If an error is returned, the device may still have the previous
+    /// configuration, so it should be written again with
+    /// [Mcp3428::write_config] before reading measurements.
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
+        self.update_config(config);
+        self.write_config().await
+    }
+
+    /// Select the converted channel, write the configuration to the device
+    /// and wait for the first conversion result of the channel.
+    pub async fn set_channel(&mut self, channel: Channel) -> Result<(), Error<I2C::Error>> {
+        self.config.channel = channel;
+        self.write_config().await
+    }
+
+    pub async fn get_measurement(&mut self) -> Result<Millivolts, Error<I2C::Error>> {
+        self.measure().await
+    }
+
+    /// Read the latest conversion result without waiting for a new one.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::NotReady] if no new conversion has completed since the
+    /// last read, e.g. when polling faster than the sample rate. Use
+    /// [Mcp3428::sample_age] to know how old the last result is.
+    pub async fn try_get_measurement(&mut self) -> Result<Millivolts, Error<I2C::Error>> {
+        let (measurement, config_reg) = self.read_raw().await?;
+        trace!(
+            "mcp3428@{=u8:#x}: poll, code {=i16}, config {=u8:#010b}",
+            self.address,
+            measurement,
+            config_reg.value
+        );
+
+        // Check "Not Ready" flag. See datasheet section 5.1.1 for more details.
+        if !config_reg.is_ready() {
+            return Err(Error::NotReady);
+        }
+
+        self.mark_ready(Duration::from_millis(self.config.conversion_time_ms()));
+        self.decode(measurement)
+    }
+
+    /// Return a stream of the measurements, sampled every `interval`.
+    ///
+    /// Only fresh conversion results are yielded: if the device has not
+    /// completed a new conversion since the last sample, the stream waits
+    /// for it.
+    pub fn measurements(&mut self, interval: Duration) -> Measurements<'_, I2C> {
+        Measurements {
+            adc: self,
+            ticker: Ticker::every(interval),
+        }
+    }
+
+    /// Watch a channel for threshold crossings, like a software comparator.
+    ///
+    /// The channel is converted continuously and sampled every
+    /// `config.interval`. Each time the reading enters or leaves the high or
+    /// low zone, the corresponding [ThresholdEvent] is signaled. Leaving a zone
+    /// requires the reading to move back past the threshold by
+    /// `config.hysteresis`, so noise around a threshold does not flood the
+    /// signal.
+    ///
+    /// Only returns if an error occurs.
+    pub async fn watch<M: RawMutex>(
+        &mut self,
+        channel: Channel,
+        config: ThresholdConfig,
+        signal: &Signal<M, ThresholdEvent>,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.set_channel(channel).await?;
+
+        let mut zone = ThresholdEvent::Normal;
+        let mut measurements = self.measurements(config.interval);
         loop {
-            if self.i2c.read(self.address, &mut buf).await.is_err() {
-                return Err(Error::I2c);
+            let voltage = measurements.next_measurement().await?;
+            let next = config.zone(zone, voltage);
+            if next != zone {
+                zone = next;
+                signal.signal(zone);
             }
-            let config_reg = ConfigRegister::new(ConfigRegister::ALL & buf[2]);
+        }
+    }
 
-            if config_reg.is_ready() {
-                return Ok(());
-            } else {
-                // Not yet ready, wait some more time
-                Timer::after(Duration::from_millis(1)).await;
-            }
+    /// Switch the device to one-shot mode.
+    ///
+    /// The device stops converting continuously on the next one-shot
+    /// measurement.
+    pub fn into_one_shot(self) -> Mcp3428<I2C, OneShot> {
+        self.into_mode()
+    }
+}
+
+#[allow(unused, dead_code)]
+impl<I2C: I2c, MODE: DeviceMode> Mcp3428<I2C, MODE> {
+    /// Set the maximum time to wait for a conversion result to be ready.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the maximum number of times the device is polled for a conversion
+    /// result after the expected conversion time has elapsed.
+    ///
+    /// The delay between polls starts at 1 ms and doubles up to 16 ms.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Get the configuration used for the next conversions
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Replace the configuration, clearing the filters if their kind changed.
+    fn update_config(&mut self, config: Config) {
+        if config.filter != self.config.filter {
+            self.reset_filters();
         }
+        self.config = config;
     }
 
-    pub async fn get_measurement(&mut self) -> Result<i32, Error> {
-        loop {
-            // Read measurement and config register
-            let (measurement, config_reg) = self.read_i2c().await?;
-
-            // Check "Not Ready" flag. See datasheet section 5.1.1 for more details.
-            if config_reg.is_ready() {
-                // Calculate voltage from raw value
-                let voltage = self.calculate_voltage(measurement)?;
-                return Ok(voltage);
-            } else {
-                // Not yet ready, wait some more time
-                Timer::after(Duration::from_millis(1)).await;
+    /// Clear the history of the measurement filters of all channels.
+    pub fn reset_filters(&mut self) {
+        self.filters = [FilterState::default(); CHANNEL_COUNT];
+    }
+
+    /// Set the calibration applied to every measurement of a channel.
+    pub fn with_calibration(mut self, channel: Channel, calibration: Calibration) -> Self {
+        self.set_calibration(channel, calibration);
+        self
+    }
+
+    /// Set the calibration applied to every measurement of a channel.
+    pub fn set_calibration(&mut self, channel: Channel, calibration: Calibration) {
+        self.calibrations[channel.index()] = calibration;
+    }
+
+    /// Get the calibration applied to the measurements of a channel.
+    pub fn calibration(&self, channel: Channel) -> &Calibration {
+        &self.calibrations[channel.index()]
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Reset all MCP342x devices on the bus with an I2C general call.
+    ///
+    /// The devices latch their address pins and return to their power-on
+    /// configuration. Write the configuration again afterwards to restore the
+    /// configuration of this device.
+    ///
+    /// Note: Every device on the bus answering general calls receives the
+    /// command.
+    pub async fn general_call_reset(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.general_call(GENERAL_CALL_RESET).await
+    }
+
+    /// Make all MCP342x devices on the bus latch their address pins again
+    /// with an I2C general call, without resetting them.
+    ///
+    /// Note: Every device on the bus answering general calls receives the
+    /// command.
+    pub async fn general_call_latch(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.general_call(GENERAL_CALL_LATCH).await
+    }
+
+    async fn general_call(&mut self, command: u8) -> Result<(), Error<I2C::Error>> {
+        trace!("mcp3428: general call {=u8:#04x}", command);
+        self.i2c
+            .write(GENERAL_CALL_ADDRESS, &[command])
+            .await
+            .map_err(Error::I2c)
+    }
+
+    /// Take `samples` measurements and return their mean, minimum and maximum
+    /// in mV.
+    ///
+    /// In one-shot mode, a conversion is triggered for each sample. In
+    /// continuous mode, each sample waits for a fresh conversion result. At
+    /// least one sample is always taken.
+    pub async fn read_averaged(&mut self, samples: u8) -> Result<Average, Error<I2C::Error>> {
+        let samples = samples.max(1);
+
+        let mut sum = 0i32;
+        let mut min = Millivolts(i32::MAX);
+        let mut max = Millivolts(i32::MIN);
+        for _ in 0..samples {
+            let voltage = self.sample().await?;
+            sum += voltage.0;
+            min = min.min(voltage);
+            max = max.max(voltage);
+        }
+
+        Ok(Average {
+            mean: Millivolts(sum / samples as i32),
+            min,
+            max,
+        })
+    }
+
+    /// Take a single measurement in the current mode
+    async fn sample(&mut self) -> Result<Millivolts, Error<I2C::Error>> {
+        if let Mode::OneShot = MODE::MODE {
+            self.send_command().await?;
+        }
+        self.measure().await
+    }
+
+    /// Write the configuration byte for the current mode to the device, which
+    /// starts a new conversion.
+    async fn send_command(&mut self) -> Result<(), Error<I2C::Error>> {
+        let command = self.config.command(MODE::MODE);
+        trace!(
+            "mcp3428@{=u8:#x}: write config {=u8:#010b}",
+            self.address,
+            command
+        );
+        self.i2c
+            .write(self.address, &[command])
+            .await
+            .map_err(Error::I2c)?;
+
+        let conversion_time = Duration::from_millis(self.config.conversion_time_ms());
+        self.ready_at = Some(Instant::now() + conversion_time);
+        Ok(())
+    }
+
+    /// Wait for a conversion result and return the calibrated voltage in mV
+    async fn measure(&mut self) -> Result<Millivolts, Error<I2C::Error>> {
+        let measurement = self.poll_ready().await?;
+        self.decode(measurement)
+    }
+
+    /// Convert a fresh conversion result to the calibrated and filtered
+    /// voltage in mV
+    fn decode(&mut self, measurement: i16) -> Result<Millivolts, Error<I2C::Error>> {
+        // Calculate calibrated input voltage from raw value
+        let voltage = self.calibrated_millivolts(measurement)?;
+
+        // Filter the voltage with the history of the channel
+        let filter = self.config.filter;
+        let voltage = self.filters[self.config.channel.index()].apply(filter, voltage.0);
+        trace!(
+            "mcp3428@{=u8:#x}: code {=i16} -> {=i32} mV",
+            self.address,
+            measurement,
+            voltage
+        );
+        Ok(Millivolts(voltage))
+    }
+
+    /// Poll the device until a conversion result is ready and return it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::Timeout] if no result is ready within the configured
+    /// timeout or number of retries.
+    async fn poll_ready(&mut self) -> Result<i16, Error<I2C::Error>> {
+        let timeout = self.timeout;
+        let max_retries = self.max_retries;
+        let conversion_time = Duration::from_millis(self.config.conversion_time_ms());
+        let poll = async {
+            // Sleep through the expected remaining conversion time first
+            if let Some(ready_at) = self.ready_at {
+                Timer::at(ready_at).await;
+            }
+
+            let mut backoff = MIN_POLL_INTERVAL;
+            for attempt in 0..=max_retries {
+                // Read measurement and config register
+                let (measurement, config_reg) = self.read_raw().await?;
+                trace!(
+                    "mcp3428@{=u8:#x}: poll {=u32}, code {=i16}, config {=u8:#010b}",
+                    self.address,
+                    attempt,
+                    measurement,
+                    config_reg.value
+                );
+
+                // Check "Not Ready" flag. See datasheet section 5.1.1 for more details.
+                if config_reg.is_ready() {
+                    self.mark_ready(conversion_time);
+                    return Ok(measurement);
+                }
+
+                // Not yet ready, back off exponentially
+                Timer::after(backoff).await;
+                backoff = (backoff * 2).min(MAX_POLL_INTERVAL);
             }
+            Err(Error::Timeout)
+        };
+
+        let result = with_timeout(timeout, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout));
+        if let Err(Error::Timeout) = result {
+            trace!(
+                "mcp3428@{=u8:#x}: timed out waiting for a result",
+                self.address
+            );
         }
+        result
     }
 
-    async fn read_i2c(&mut self) -> Result<(i16, ConfigRegister), Error> {
+    /// Record that a fresh conversion result has been read
+    fn mark_ready(&mut self, conversion_time: Duration) {
+        let now = Instant::now();
+        self.last_sample_at = Some(now);
+
+        // In continuous mode, the next result is a conversion away
+        self.ready_at = match MODE::MODE {
+            Mode::OneShot => None,
+            Mode::Continuous => Some(now + conversion_time),
+        };
+    }
+
+    /// Get the time elapsed since the last fresh conversion result was read,
+    /// or `None` if no result has been read yet.
+    pub fn sample_age(&self) -> Option<Duration> {
+        self.last_sample_at.map(|at| at.elapsed())
+    }
+
+    /// Read the untouched output code and the configuration register.
+    ///
+    /// The output code is not checked for readiness nor converted, which is
+    /// useful for custom calibration or ratiometric measurements. Use
+    /// [ConfigRegister::is_ready] to check whether the code is fresh and
+    /// [Mcp3428::input_millivolts] to convert it.
+    pub async fn read_raw(&mut self) -> Result<(i16, ConfigRegister), Error<I2C::Error>> {
         let mut buf = [0u8; 3];
-        if self.i2c.read(self.address, &mut buf).await.is_err() {
-            return Err(Error::I2c);
-        }
+        self.i2c
+            .read(self.address, &mut buf)
+            .await
+            .map_err(Error::I2c)?;
         let measurement = i16::from_be_bytes([buf[0], buf[1]]);
         let config_reg = ConfigRegister::new(buf[2] & ConfigRegister::ALL);
         Ok((measurement, config_reg))
     }
 
-    /// Calculate the voltage in mV for the measurement result at the specified sample rate.
+    /// Calculate the voltage in mV seen by the ADC for the measurement result
+    /// at the configured resolution, i.e. after amplification by the PGA.
     ///
     /// If the value is a saturation value, an error is returned.
-    fn calculate_voltage(&self, measurement: i16) -> Result<i32, Error> {
+    pub fn code_millivolts(&self, measurement: i16) -> Result<Millivolts, Error<I2C::Error>> {
+        let resolution = self.config.resolution;
+
         // Handle saturation / out of range values
-        if measurement == self.resolution.max() {
+        if measurement == resolution.max() {
             return Err(Error::VoltageTooHigh);
-        } else if measurement == self.resolution.min() {
+        } else if measurement == resolution.min() {
             return Err(Error::VoltageTooLow);
         }
 
-        Ok(measurement as i32 * (REF_MILLIVOLTS * 2) as i32 / (1 << self.resolution.res_bits()))
+        Ok(Millivolts(
+            measurement as i32 * (REF_MILLIVOLTS * 2) as i32 / (1 << resolution.res_bits()),
+        ))
+    }
+
+    /// Calculate the voltage in mV at the input pins for the measurement result
+    /// at the configured resolution, i.e. corrected for the gain of the PGA.
+    ///
+    /// If the value is a saturation value, an error is returned.
+    pub fn input_millivolts(&self, measurement: i16) -> Result<Millivolts, Error<I2C::Error>> {
+        Ok(Millivolts(
+            self.code_millivolts(measurement)?.0 / self.config.gain.factor(),
+        ))
+    }
+
+    /// Calculate the voltage in µV at the input pins for the measurement result
+    /// at the configured resolution, i.e. corrected for the gain of the PGA.
+    ///
+    /// If the value is a saturation value, an error is returned.
+    pub fn input_microvolts(&self, measurement: i16) -> Result<Microvolts, Error<I2C::Error>> {
+        // Check for saturation
+        self.code_millivolts(measurement)?;
+
+        let microvolts = measurement as i64 * self.config.resolution.step_nv()
+            / 1000
+            / self.config.gain.factor() as i64;
+        Ok(Microvolts(microvolts as i32))
+    }
+
+    /// Calculate the calibrated voltage in mV at the input pins for the
+    /// measurement result, using the calibration of the configured channel.
+    ///
+    /// If the value is a saturation value, an error is returned.
+    fn calibrated_millivolts(&self, measurement: i16) -> Result<Millivolts, Error<I2C::Error>> {
+        // Work in µV to keep the precision of the offset
+        let microvolts = self.input_microvolts(measurement)?;
+        let calibration = &self.calibrations[self.config.channel.index()];
+
+        Ok(calibration.apply(microvolts).millivolts())
+    }
+
+    /// Change the mode tracked in the type of the device
+    fn into_mode<NEW: DeviceMode>(self) -> Mcp3428<I2C, NEW> {
+        Mcp3428 {
+            address: self.address,
+            i2c: self.i2c,
+            config: self.config,
+            timeout: self.timeout,
+            max_retries: self.max_retries,
+            calibrations: self.calibrations,
+            filters: self.filters,
+            ready_at: self.ready_at,
+            last_sample_at: self.last_sample_at,
+            _mode: PhantomData,
+        }
+    }
+}
+
+/// Calibration of a channel
+///
+/// Measurements are corrected as `measured * scale + offset`, which allows
+/// calibrating out sensor and voltage divider tolerances.
+///
+/// Defaults to no correction.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Calibration {
+    /// Offset added to the scaled measurement, in µV
+    pub offset_uv: i32,
+    /// Factor by which the measurement is multiplied
+    pub scale: f32,
+}
+
+impl Calibration {
+    pub fn new(offset_uv: i32, scale: f32) -> Self {
+        Self { offset_uv, scale }
+    }
+
+    /// Apply the calibration to a voltage.
+    fn apply(&self, voltage: Microvolts) -> Microvolts {
+        Microvolts((voltage.0 as f32 * self.scale) as i32 + self.offset_uv)
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            offset_uv: 0,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Filter applied to the measurements of each channel
+///
+/// Filters keep a separate history for each channel. Defaults to no
+/// filtering.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Filter {
+    /// Measurements are returned as is.
+    #[default]
+    None,
+    /// Median of the last 3 measurements, rejecting single-sample spikes.
+    Median3,
+    /// Median of the last 5 measurements, rejecting two-sample spikes.
+    Median5,
+    /// Exponential moving average, where each new measurement is weighted by
+    /// `alpha_pct` percent (1 to 100).
+    Ema { alpha_pct: u8 },
+}
+
+/// History of the measurements of a channel used by its [Filter]
+#[derive(Debug, Default, Copy, Clone)]
+struct FilterState {
+    window: [i32; 5],
+    len: usize,
+    next: usize,
+    ema: Option<i32>,
+}
+
+impl FilterState {
+    /// Add a measurement to the history and return the filtered value.
+    fn apply(&mut self, filter: Filter, value: i32) -> i32 {
+        match filter {
+            Filter::None => value,
+            Filter::Median3 => self.median::<3>(value),
+            Filter::Median5 => self.median::<5>(value),
+            Filter::Ema { alpha_pct } => {
+                let alpha = alpha_pct.clamp(1, 100) as i32;
+                let ema = match self.ema {
+                    Some(ema) => ema + (value - ema) * alpha / 100,
+                    None => value,
+                };
+                self.ema = Some(ema);
+                ema
+            }
+        }
+    }
+
+    /// Median of the last `N` measurements, or of all of them until `N`
+    /// measurements have been taken.
+    fn median<const N: usize>(&mut self, value: i32) -> i32 {
+        self.window[self.next] = value;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+
+        let mut sorted = [0; N];
+        sorted[..self.len].copy_from_slice(&self.window[..self.len]);
+        sorted[..self.len].sort_unstable();
+        sorted[self.len / 2]
+    }
+}
+
+/// Result of an averaged measurement
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Average {
+    pub mean: Millivolts,
+    pub min: Millivolts,
+    pub max: Millivolts,
+}
+
+/// Reads the configured channel, triggering a conversion in one-shot mode.
+impl<I2C: I2c, MODE: DeviceMode> AsyncAdcChannel for Mcp3428<I2C, MODE> {
+    type Error = Error<I2C::Error>;
+
+    async fn read_millivolts(&mut self) -> Result<Millivolts, Self::Error> {
+        self.sample().await
+    }
+}
+
+/// A single channel of an [Mcp3428] in one-shot mode
+///
+/// Created by [Mcp3428::channel].
+pub struct Mcp3428Channel<'a, I2C> {
+    adc: &'a mut Mcp3428<I2C, OneShot>,
+    channel: Channel,
+}
+
+impl<I2C: I2c> AsyncAdcChannel for Mcp3428Channel<'_, I2C> {
+    type Error = Error<I2C::Error>;
+
+    /// Trigger a one-shot conversion on the channel. The channel previously
+    /// selected on the device is restored afterwards.
+    async fn read_millivolts(&mut self) -> Result<Millivolts, Self::Error> {
+        let channel = self.adc.config.channel;
+        self.adc.config.channel = self.channel;
+        let voltage = self.adc.one_shot_measurement().await;
+        self.adc.config.channel = channel;
+        voltage
+    }
+}
+
+/// Thresholds of [Mcp3428::watch]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ThresholdConfig {
+    /// Readings above this voltage are in the high zone
+    pub high: Millivolts,
+    /// Readings below this voltage are in the low zone
+    pub low: Millivolts,
+    /// Distance the reading must move back past a threshold to leave its zone
+    pub hysteresis: Millivolts,
+    /// Time between samples
+    pub interval: Duration,
+}
+
+impl ThresholdConfig {
+    /// Return the zone of a reading, given the current zone.
+    fn zone(&self, current: ThresholdEvent, voltage: Millivolts) -> ThresholdEvent {
+        match current {
+            ThresholdEvent::High if voltage > self.high - self.hysteresis => ThresholdEvent::High,
+            ThresholdEvent::Low if voltage < self.low + self.hysteresis => ThresholdEvent::Low,
+            _ if voltage > self.high => ThresholdEvent::High,
+            _ if voltage < self.low => ThresholdEvent::Low,
+            _ => ThresholdEvent::Normal,
+        }
+    }
+}
+
+/// Zone entered by a watched reading
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ThresholdEvent {
+    /// The reading rose above the high threshold.
+    High,
+    /// The reading is back between the thresholds.
+    Normal,
+    /// The reading fell below the low threshold.
+    Low,
+}
+
+/// Stream of periodic measurements
+///
+/// Created by [Mcp3428::measurements] in continuous mode, or by
+/// [Mcp3428::sample_every] in one-shot mode.
+pub struct Measurements<'a, I2C, MODE = Continuous> {
+    adc: &'a mut Mcp3428<I2C, MODE>,
+    ticker: Ticker,
+}
+
+impl<I2C: I2c, MODE: DeviceMode> Measurements<'_, I2C, MODE> {
+    /// Wait for the next sample and return the measured voltage in mV.
+    pub async fn next_measurement(&mut self) -> Result<Millivolts, Error<I2C::Error>> {
+        self.ticker.next().await;
+        self.adc.sample().await
     }
 }
 
+/// I2C address of the device with all address bits cleared
+const BASE_ADDRESS: u8 = 0b110_1000;
+
 /// ADC reference voltage: +-2048mV
 const REF_MILLIVOLTS: i16 = 2048;
 
-/// All possible errors in this crate
+/// Default maximum time to wait for a conversion result
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Default maximum number of polls for a conversion result
+const DEFAULT_MAX_RETRIES: u32 = 100;
+
+/// Initial delay between polls of a conversion result that is late
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Maximum delay between polls of a conversion result that is late
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+/// I2C general call address. See datasheet section 5.4 for more details.
+const GENERAL_CALL_ADDRESS: u8 = 0x00;
+
+/// General call command resetting the device and latching its address pins
+const GENERAL_CALL_RESET: u8 = 0x06;
+
+/// General call command latching the address pins of the device
+const GENERAL_CALL_LATCH: u8 = 0x04;
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
 #[allow(unused, dead_code)]
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub enum Error {
+pub enum Error<E> {
     /// I2C bus error
-    I2c,
+    I2c(E),
     /// Voltage is too high to be measured.
     VoltageTooHigh,
     /// Voltage is too low to be measured.
     VoltageTooLow,
-    /// A measurement in continuous mode has been triggered without previously
-    /// writing the configuration to the device.
-    NotInitialized,
     /// A measurement returned a stale result.
     ///
-    /// In continuous mode, this can happen if you poll faster than the sample
+    /// In continuous mode, this is returned by
+    /// [Mcp3428::try_get_measurement] if you poll faster than the sample
     /// rate. See datasheet section 5.1.1 for more details.
-    ///
-    /// In one-shot mode, this is probably a timing bug that should be reported to
-    /// <https://github.com/dbrgn/mcp3425-rs/issues/>!
-    ///
     NotReady,
+    /// No conversion result was ready within the configured timeout or
+    /// number of retries.
+    Timeout,
 }
 
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ConfigRegister {
     pub value: u8,
 }
@@ -273,6 +1035,13 @@ impl Mode {
     }
 }
 
+impl Default for Mode {
+    /// Default implementation matching the power-on defaults of the device.
+    fn default() -> Self {
+        Mode::Continuous
+    }
+}
+
 /// Conversion bit resolution and sample rate
 ///
 /// * 15 SPS -> 16 bits
@@ -308,6 +1077,11 @@ impl Resolution {
         }
     }
 
+    /// Return the voltage step of one output code at unity gain in nV.
+    pub fn step_nv(&self) -> i64 {
+        (REF_MILLIVOLTS as i64 * 2 * 1_000_000) >> self.res_bits()
+    }
+
     /// Return the maximum output code.
     pub fn max(&self) -> i16 {
         match *self {
@@ -357,6 +1131,11 @@ impl Gain {
     pub fn bits(&self) -> u8 {
         *self as u8
     }
+
+    /// Return the amplification factor of this gain configuration.
+    pub fn factor(&self) -> i32 {
+        1 << (*self as u8)
+    }
 }
 
 impl Default for Gain {
@@ -366,41 +1145,250 @@ impl Default for Gain {
     }
 }
 
+/// Number of channels of the selected device variant
+#[cfg(feature = "quad_channel")]
+pub const CHANNEL_COUNT: usize = 4;
+/// Number of channels of the selected device variant
+#[cfg(all(feature = "dual_channel", not(feature = "quad_channel")))]
+pub const CHANNEL_COUNT: usize = 2;
+/// Number of channels of the selected device variant
+#[cfg(not(any(feature = "dual_channel", feature = "quad_channel")))]
+pub const CHANNEL_COUNT: usize = 1;
+
 /// Selected ADC channel
 ///
 /// Defaults to channel 1.
 #[allow(unused, dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Default)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Channel {
     /// First channel (Default)
+    #[default]
     Channel1 = 0b0000_0000,
     /// Second channel
     ///
     /// Note: Only supported by MCP3426/7/8, and if the `dual_channel` or
     /// `quad_channel` cargo feature is enabled.
+    #[cfg(any(feature = "dual_channel", feature = "quad_channel"))]
     Channel2 = 0b0010_0000,
     /// Third channel
     ///
     /// Note: Only supported by MCP3428, and if the `quad_channel` cargo
     /// feature is enabled.
+    #[cfg(feature = "quad_channel")]
     Channel3 = 0b0100_0000,
     /// Fourth channel
     ///
     /// Note: Only supported by MCP3428, and if the `quad_channel` cargo
     /// feature is enabled.
+    #[cfg(feature = "quad_channel")]
     Channel4 = 0b0110_0000,
 }
 
-impl Default for Channel {
-    fn default() -> Self {
-        Self::Channel1
-    }
-}
-
 impl Channel {
+    /// All channels of the selected device variant, in order.
+    pub const ALL: [Channel; CHANNEL_COUNT] = [
+        Channel::Channel1,
+        #[cfg(any(feature = "dual_channel", feature = "quad_channel"))]
+        Channel::Channel2,
+        #[cfg(feature = "quad_channel")]
+        Channel::Channel3,
+        #[cfg(feature = "quad_channel")]
+        Channel::Channel4,
+    ];
+
     /// Return the bitmask for this channel configuration.
     pub fn bits(&self) -> u8 {
         *self as u8
     }
+
+    /// Return the zero-based index of this channel.
+    pub fn index(&self) -> usize {
+        (self.bits() >> 5) as usize
+    }
+}
+
+/// State of an address selection pin
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AddressPin {
+    /// Tied to ground
+    Low,
+    /// Tied to the supply
+    High,
+    /// Left floating
+    Float,
+}
+
+/// I2C address of the device, selected by the state of its Adr0 and Adr1 pins
+///
+/// Defaults to both pins floating, which is also the address of devices
+/// without address pins (MCP3425/6).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Address {
+    pub adr0: AddressPin,
+    pub adr1: AddressPin,
+}
+
+impl Address {
+    pub fn new(adr0: AddressPin, adr1: AddressPin) -> Self {
+        Self { adr0, adr1 }
+    }
+
+    /// Return the 7-bit I2C address. See datasheet table 5-3 for more details.
+    pub fn bits(&self) -> u8 {
+        use AddressPin::{Float, High, Low};
+
+        let address_bits = match (self.adr0, self.adr1) {
+            (Low, Low) | (Float, Float) => 0b000,
+            (Low, Float) => 0b001,
+            (Low, High) => 0b010,
+            (Float, Low) => 0b011,
+            (High, Low) => 0b100,
+            (High, Float) => 0b101,
+            (High, High) => 0b110,
+            (Float, High) => 0b111,
+        };
+        BASE_ADDRESS | address_bits
+    }
+}
+
+impl Default for Address {
+    fn default() -> Self {
+        Self::new(AddressPin::Float, AddressPin::Float)
+    }
+}
+
+impl From<Address> for u8 {
+    fn from(address: Address) -> Self {
+        address.bits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    use super::*;
+
+    /// Address of the device with both address pins tied to ground
+    const ADDRESS: u8 = 0x68;
+
+    fn adc(transactions: &[Transaction]) -> Mcp3428<Mock> {
+        Mcp3428::new(Mock::new(transactions), ADDRESS, Config::new())
+    }
+
+    #[test]
+    fn command_default_config() {
+        let config = Config::new();
+        assert_eq!(config.command(Mode::OneShot), 0b1000_0000);
+        assert_eq!(config.command(Mode::Continuous), 0b0001_0000);
+    }
+
+    #[test]
+    fn command_combines_fields() {
+        let config = Config::new()
+            .with_channel(Channel::Channel2)
+            .with_resolution(Resolution::Bits16Sps15)
+            .with_gain(Gain::Gain8);
+        assert_eq!(config.command(Mode::OneShot), 0b1010_1011);
+        assert_eq!(config.command(Mode::Continuous), 0b0011_1011);
+    }
+
+    #[test]
+    fn config_register_ready_flag() {
+        assert!(ConfigRegister::new(0b0001_0000).is_ready());
+        assert!(!ConfigRegister::new(0b1001_0000).is_ready());
+    }
+
+    #[test]
+    fn one_shot_retries_until_ready() {
+        let mut adc = adc(&[
+            Transaction::write(ADDRESS, vec![0b1000_0000]),
+            Transaction::read(ADDRESS, vec![0x03, 0xE8, 0b1000_0000]),
+            Transaction::read(ADDRESS, vec![0x03, 0xE8, 0b0000_0000]),
+        ]);
+
+        let voltage = block_on(adc.one_shot_measurement()).unwrap();
+        assert_eq!(voltage, Millivolts(1_000));
+        adc.release().done();
+    }
+
+    #[test]
+    fn one_shot_times_out_when_never_ready() {
+        let mut adc = adc(&[
+            Transaction::write(ADDRESS, vec![0b1000_0000]),
+            Transaction::read(ADDRESS, vec![0x03, 0xE8, 0b1000_0000]),
+            Transaction::read(ADDRESS, vec![0x03, 0xE8, 0b1000_0000]),
+        ])
+        .with_max_retries(1);
+
+        let result = block_on(adc.one_shot_measurement());
+        assert!(matches!(result, Err(Error::Timeout)));
+        adc.release().done();
+    }
+
+    #[test]
+    fn continuous_set_channel_writes_config() {
+        let adc = adc(&[
+            Transaction::write(ADDRESS, vec![0b0001_0000]),
+            Transaction::read(ADDRESS, vec![0x00, 0x00, 0b0001_0000]),
+            Transaction::write(ADDRESS, vec![0b0011_0000]),
+            Transaction::read(ADDRESS, vec![0x00, 0x00, 0b0011_0000]),
+        ]);
+
+        let mut adc = block_on(adc.into_continuous()).unwrap();
+        block_on(adc.set_channel(Channel::Channel2)).unwrap();
+        adc.release().done();
+    }
+
+    #[test]
+    fn code_millivolts_saturates() {
+        let mut adc = adc(&[]);
+        assert!(matches!(
+            adc.code_millivolts(2047),
+            Err(Error::VoltageTooHigh)
+        ));
+        assert!(matches!(
+            adc.code_millivolts(-2048),
+            Err(Error::VoltageTooLow)
+        ));
+        assert_eq!(adc.code_millivolts(2046).unwrap(), Millivolts(2_046));
+
+        adc.set_config(Config::new().with_resolution(Resolution::Bits16Sps15));
+        assert!(matches!(
+            adc.code_millivolts(32767),
+            Err(Error::VoltageTooHigh)
+        ));
+        assert!(matches!(
+            adc.code_millivolts(-32768),
+            Err(Error::VoltageTooLow)
+        ));
+        assert_eq!(adc.code_millivolts(16_000).unwrap(), Millivolts(1_000));
+        adc.release().done();
+    }
+
+    #[test]
+    fn gain_factor() {
+        assert_eq!(Gain::Gain1.factor(), 1);
+        assert_eq!(Gain::Gain2.factor(), 2);
+        assert_eq!(Gain::Gain4.factor(), 4);
+        assert_eq!(Gain::Gain8.factor(), 8);
+    }
+
+    #[test]
+    fn input_millivolts_corrects_gain() {
+        let mut adc = adc(&[]);
+        adc.set_config(Config::new().with_gain(Gain::Gain4));
+        assert_eq!(adc.code_millivolts(1_000).unwrap(), Millivolts(1_000));
+        assert_eq!(adc.input_millivolts(1_000).unwrap(), Millivolts(250));
+        assert!(matches!(
+            adc.input_millivolts(2047),
+            Err(Error::VoltageTooHigh)
+        ));
+        adc.release().done();
+    }
 }
@@ -0,0 +1,201 @@
+//! # mcp3x08
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Microchip
+//! MCP3008 and MCP3208 8-channel ADCs over SPI.
+//!
+//! - The MCP3008 converts on 10 bits and the MCP3208 on 12 bits, and both
+//!   share the same commands.
+//! - Each channel is read single-ended, or differentially against the other
+//!   channel of its pair.
+//! - [Mcp3x08::channel] returns a channel implementing [AsyncAdcChannel], so
+//!   it can be used by the helpers of this crate like the channels of the
+//!   MCP3428.
+//!
+//! The voltages are computed from the reference voltage on VREF, usually
+//! the supply voltage.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let spi = SpiDevice::new(spi_bus, cs);
+//! let mut adc = Mcp3x08::new(spi, Model::Mcp3008, Millivolts(3_300));
+//!
+//! let raw = adc.read_raw(Channel::Channel0).await?;
+//! let voltage = adc.read_millivolts(Channel::Channel1).await?;
+//! println!("{} / {} mV", raw, voltage.0);
+//!
+//! let mut battery = BatteryMonitor::new(adc.channel(Channel::Channel7), 2.0, Chemistry::LiIon);
+//! ```
+
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::{units::Millivolts, AsyncAdcChannel};
+
+/// Model of the ADC
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Model {
+    /// 10 bits
+    Mcp3008,
+    /// 12 bits
+    Mcp3208,
+}
+
+impl Model {
+    /// Resolution of the conversions in bits
+    pub fn resolution(&self) -> u8 {
+        match self {
+            Model::Mcp3008 => 10,
+            Model::Mcp3208 => 12,
+        }
+    }
+}
+
+/// Input channel of the ADC
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Channel {
+    Channel0 = 0,
+    Channel1 = 1,
+    Channel2 = 2,
+    Channel3 = 3,
+    Channel4 = 4,
+    Channel5 = 5,
+    Channel6 = 6,
+    Channel7 = 7,
+}
+
+impl Channel {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// An MCP3008 or MCP3208 on an SPI bus
+pub struct Mcp3x08<SPI> {
+    spi: SPI,
+    model: Model,
+    vref: Millivolts,
+}
+
+impl<SPI: SpiDevice> Mcp3x08<SPI> {
+    /// Create a new ADC.
+    ///
+    /// # Arguments
+    ///
+    /// - `spi`: The SPI device of the ADC, in mode 0 and up to 1 MHz.
+    /// - `model`: The model of the ADC.
+    /// - `vref`: The reference voltage on VREF.
+    pub fn new(spi: SPI, model: Model, vref: Millivolts) -> Self {
+        Self { spi, model, vref }
+    }
+
+    /// Get the model of the ADC
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Release the underlying SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Read the raw conversion of a channel against ground.
+    pub async fn read_raw(&mut self, channel: Channel) -> Result<u16, Error<SPI::Error>> {
+        self.convert(true, channel).await
+    }
+
+    /// Read the raw conversion of a channel against the other channel of its
+    /// pair, e.g. CH0 against CH1 or CH1 against CH0.
+    ///
+    /// Conversions are 0 when the channel is below the other one.
+    pub async fn read_raw_differential(
+        &mut self,
+        channel: Channel,
+    ) -> Result<u16, Error<SPI::Error>> {
+        self.convert(false, channel).await
+    }
+
+    /// Read the voltage of a channel against ground.
+    pub async fn read_millivolts(
+        &mut self,
+        channel: Channel,
+    ) -> Result<Millivolts, Error<SPI::Error>> {
+        let raw = self.read_raw(channel).await?;
+        Ok(self.millivolts(raw))
+    }
+
+    /// Read the voltage of a channel against the other channel of its pair.
+    /// See [Mcp3x08::read_raw_differential].
+    pub async fn read_millivolts_differential(
+        &mut self,
+        channel: Channel,
+    ) -> Result<Millivolts, Error<SPI::Error>> {
+        let raw = self.read_raw_differential(channel).await?;
+        Ok(self.millivolts(raw))
+    }
+
+    /// Borrow a channel of the ADC, read single-ended, as an
+    /// [AsyncAdcChannel].
+    pub fn channel(&mut self, channel: Channel) -> Mcp3x08Channel<'_, SPI> {
+        Mcp3x08Channel { adc: self, channel }
+    }
+
+    /// Convert a raw conversion to a voltage.
+    fn millivolts(&self, raw: u16) -> Millivolts {
+        Millivolts((raw as i32 * self.vref.0) >> self.model.resolution())
+    }
+
+    /// Run a conversion. See datasheet section 6.1 for more details.
+    async fn convert(
+        &mut self,
+        single_ended: bool,
+        channel: Channel,
+    ) -> Result<u16, Error<SPI::Error>> {
+        // The start bit, the mode and the channel are aligned so that the
+        // conversion ends with the last byte
+        let mode = single_ended as u8;
+        let channel = channel.bits();
+        let mut buf = match self.model {
+            Model::Mcp3008 => [0x01, (mode << 7) | (channel << 4), 0],
+            Model::Mcp3208 => [0x04 | (mode << 1) | (channel >> 2), channel << 6, 0],
+        };
+        self.spi
+            .transfer_in_place(&mut buf)
+            .await
+            .map_err(Error::Spi)?;
+
+        let raw = u16::from_be_bytes([buf[1], buf[2]]);
+        Ok(raw & ((1 << self.model.resolution()) - 1))
+    }
+}
+
+/// A single channel of an [Mcp3x08], read single-ended
+///
+/// Created by [Mcp3x08::channel].
+pub struct Mcp3x08Channel<'a, SPI> {
+    adc: &'a mut Mcp3x08<SPI>,
+    channel: Channel,
+}
+
+impl<SPI: SpiDevice> AsyncAdcChannel for Mcp3x08Channel<'_, SPI> {
+    type Error = Error<SPI::Error>;
+
+    async fn read_millivolts(&mut self) -> Result<Millivolts, Self::Error> {
+        self.adc.read_millivolts(self.channel).await
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying SPI device.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI bus error
+    Spi(E),
+}
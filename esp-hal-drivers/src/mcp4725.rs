@@ -0,0 +1,288 @@
+//! # mcp4725
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Microchip MCP4725
+//! 12-bit DAC over I2C.
+//!
+//! - Outputs are updated with the fast write command, which only takes a
+//!   two-byte transfer.
+//! - The output and power-down mode loaded on power-up can be stored in the
+//!   EEPROM of the DAC.
+//! - The output can be powered down with one of three pull-down resistors.
+//!
+//! The output spans from ground to the supply of the DAC, which is therefore
+//! its reference.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut dac = Mcp4725::new(i2c, ADDRESS_A0_LOW, Millivolts(3300));
+//!
+//! // Output 1.2 V
+//! dac.set_voltage(Millivolts(1200)).await?;
+//!
+//! // Start at 0 V on the next power-up
+//! dac.store(0, PowerDown::Normal).await?;
+//!
+//! // Power the output down through 100 kΩ
+//! dac.set_power_down(PowerDown::Pulldown100k).await?;
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use crate::units::Millivolts;
+
+/// I2C address of the DAC when A0 is tied to ground, for the default A0
+/// address option
+pub const ADDRESS_A0_LOW: u8 = 0x60;
+
+/// I2C address of the DAC when A0 is tied to the supply, for the default A0
+/// address option
+pub const ADDRESS_A0_HIGH: u8 = 0x61;
+
+/// Maximum code of the DAC
+pub const MAX_CODE: u16 = 0x0FFF;
+
+/// Command writing the DAC register. See datasheet section 6.1 for more
+/// details.
+const WRITE_DAC: u8 = 0x40;
+
+/// Command writing the DAC register and the EEPROM
+const WRITE_DAC_EEPROM: u8 = 0x60;
+
+/// Flag of the status byte cleared while the EEPROM is being written
+const STATUS_READY: u8 = 1 << 7;
+
+/// Typical time taken by an EEPROM write
+const EEPROM_WRITE_TIME: Duration = Duration::from_millis(25);
+
+/// Maximum time taken by an EEPROM write before giving up
+const EEPROM_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Time between two polls of an EEPROM write
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Power-down mode of the output
+///
+/// Defaults to `Normal`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerDown {
+    /// The output is driven.
+    #[default]
+    Normal = 0b00,
+    /// The output is pulled down through 1 kΩ.
+    Pulldown1k = 0b01,
+    /// The output is pulled down through 100 kΩ.
+    Pulldown100k = 0b10,
+    /// The output is pulled down through 500 kΩ.
+    Pulldown500k = 0b11,
+}
+
+impl PowerDown {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => PowerDown::Normal,
+            0b01 => PowerDown::Pulldown1k,
+            0b10 => PowerDown::Pulldown100k,
+            _ => PowerDown::Pulldown500k,
+        }
+    }
+}
+
+/// State of the DAC, as read back from it
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct State {
+    /// Code of the output
+    pub code: u16,
+    /// Power-down mode of the output
+    pub power_down: PowerDown,
+    /// Code loaded from the EEPROM on power-up
+    pub eeprom_code: u16,
+    /// Power-down mode loaded from the EEPROM on power-up
+    pub eeprom_power_down: PowerDown,
+}
+
+/// An MCP4725 on an I2C bus
+pub struct Mcp4725<I2C> {
+    address: u8,
+    i2c: I2C,
+    vdd: Millivolts,
+    code: u16,
+}
+
+impl<I2C: I2c> Mcp4725<I2C> {
+    /// Create a new DAC.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the DAC is on.
+    /// - `address`: The I2C address of the DAC, e.g. [ADDRESS_A0_LOW].
+    /// - `vdd`: The supply of the DAC, which is the full scale of its output.
+    pub fn new(i2c: I2C, address: u8, vdd: Millivolts) -> Self {
+        Self {
+            address,
+            i2c,
+            vdd,
+            code: 0,
+        }
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Set the code of the output with a fast write. Codes above [MAX_CODE]
+    /// are saturated.
+    pub async fn set_code(&mut self, code: u16) -> Result<(), Error<I2C::Error>> {
+        self.fast_write(code.min(MAX_CODE), PowerDown::Normal).await
+    }
+
+    /// Set the voltage of the output, rounded to the nearest code.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::VoltageTooHigh` if the voltage is above the supply, and
+    /// `Error::VoltageTooLow` if it is negative.
+    pub async fn set_voltage(&mut self, voltage: Millivolts) -> Result<(), Error<I2C::Error>> {
+        let code = self.voltage_code(voltage)?;
+        self.set_code(code).await
+    }
+
+    /// Power the output down, or power it back up with `PowerDown::Normal`.
+    /// The code of the output is kept.
+    pub async fn set_power_down(&mut self, power_down: PowerDown) -> Result<(), Error<I2C::Error>> {
+        self.fast_write(self.code, power_down).await
+    }
+
+    /// Set the code and power-down mode of the output, and store them in the
+    /// EEPROM so that they are loaded on every power-up.
+    ///
+    /// The EEPROM endures a limited number of writes, so only store the
+    /// power-up state when it changes.
+    pub async fn store(
+        &mut self,
+        code: u16,
+        power_down: PowerDown,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write_dac(WRITE_DAC_EEPROM, code, power_down).await?;
+        Timer::after(EEPROM_WRITE_TIME).await;
+
+        // Poll until the EEPROM write is complete
+        let poll = async {
+            loop {
+                let mut status = [0u8; 1];
+                self.i2c
+                    .read(self.address, &mut status)
+                    .await
+                    .map_err(Error::I2c)?;
+                if status[0] & STATUS_READY != 0 {
+                    return Ok(());
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        };
+        with_timeout(EEPROM_TIMEOUT, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    /// Read the state of the output and the power-up state stored in the
+    /// EEPROM.
+    pub async fn read(&mut self) -> Result<State, Error<I2C::Error>> {
+        let mut buf = [0u8; 5];
+        self.i2c
+            .read(self.address, &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+
+        // See datasheet figure 6-3 for more details
+        Ok(State {
+            code: ((buf[1] as u16) << 4) | (buf[2] >> 4) as u16,
+            power_down: PowerDown::from_bits(buf[0] >> 1),
+            eeprom_code: (((buf[3] & 0x0F) as u16) << 8) | buf[4] as u16,
+            eeprom_power_down: PowerDown::from_bits(buf[3] >> 5),
+        })
+    }
+
+    /// Set the code and power-down mode of the output with a regular write,
+    /// leaving the EEPROM untouched. Codes above [MAX_CODE] are saturated.
+    pub async fn write(
+        &mut self,
+        code: u16,
+        power_down: PowerDown,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write_dac(WRITE_DAC, code, power_down).await
+    }
+
+    async fn write_dac(
+        &mut self,
+        command: u8,
+        code: u16,
+        power_down: PowerDown,
+    ) -> Result<(), Error<I2C::Error>> {
+        let code = code.min(MAX_CODE);
+        let command = command | (power_down.bits() << 1);
+        self.i2c
+            .write(
+                self.address,
+                &[command, (code >> 4) as u8, (code << 4) as u8],
+            )
+            .await
+            .map_err(Error::I2c)?;
+        self.code = code;
+        Ok(())
+    }
+
+    async fn fast_write(
+        &mut self,
+        code: u16,
+        power_down: PowerDown,
+    ) -> Result<(), Error<I2C::Error>> {
+        let [msb, lsb] = code.to_be_bytes();
+        self.i2c
+            .write(self.address, &[(power_down.bits() << 4) | msb, lsb])
+            .await
+            .map_err(Error::I2c)?;
+        self.code = code;
+        Ok(())
+    }
+
+    fn voltage_code(&self, voltage: Millivolts) -> Result<u16, Error<I2C::Error>> {
+        if voltage.0 < 0 {
+            return Err(Error::VoltageTooLow);
+        }
+        if voltage.0 > self.vdd.0 {
+            return Err(Error::VoltageTooHigh);
+        }
+
+        let full_scale = MAX_CODE as i32 + 1;
+        let code = (voltage.0 * full_scale + self.vdd.0 / 2) / self.vdd.0;
+        Ok((code as u16).min(MAX_CODE))
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The voltage is above the supply of the DAC.
+    VoltageTooHigh,
+    /// The voltage is negative.
+    VoltageTooLow,
+    /// The DAC did not complete an EEPROM write in time.
+    Timeout,
+}
@@ -0,0 +1,703 @@
+//! # mfrc522
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the NXP MFRC522
+//! 13.56 MHz RFID reader over SPI, for ISO/IEC 14443 A cards and tags such as
+//! the MIFARE Classic.
+//!
+//! - Cards are detected with REQA, then selected through the anticollision
+//!   loop of every cascade level, so that 4, 7 and 10 bytes UIDs are read
+//!   even with several cards in the field.
+//! - The blocks of a MIFARE Classic card are read and written once their
+//!   sector is authenticated with its key A or B, the reader then encrypting
+//!   the exchanges itself.
+//! - A card is halted once done with, so that it is not detected again until
+//!   it leaves the field and comes back.
+//!
+//! The reader cannot detect cards without transmitting, so
+//! [Mfrc522::wait_for_card] polls the field instead of waiting for the IRQ
+//! pin.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let spi = SpiDevice::new(spi_bus, cs);
+//! let mut reader = Mfrc522::new(spi).await?;
+//!
+//! loop {
+//!     let uid = reader.wait_for_card().await?;
+//!     println!("Card {:02X?}", uid.bytes());
+//!
+//!     reader.authenticate(KeyType::A, 4, &DEFAULT_KEY, &uid).await?;
+//!     let block = reader.read_block(4).await?;
+//!     reader.stop_crypto().await?;
+//!     reader.halt().await?;
+//! }
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::spi::SpiDevice;
+
+/// Versions reported by the MFRC522 and its common clones
+const VERSIONS: [u8; 4] = [0x12, 0x88, 0x91, 0x92];
+
+/// Default key of the sectors of a MIFARE Classic card
+pub const DEFAULT_KEY: [u8; 6] = [0xFF; 6];
+
+/// Time between two polls of the field for a card
+const CARD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Maximum time taken by the oscillator to start after a soft reset
+const RESET_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Maximum time taken by a command, longer than the 25 ms the timer of the
+/// reader waits for a card to answer
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Size of the FIFO of the reader
+const FIFO_SIZE: usize = 64;
+
+/// Registers of the reader. See datasheet section 9.2 for more details.
+struct Register;
+
+impl Register {
+    const COMMAND: u8 = 0x01;
+    const COM_IRQ: u8 = 0x04;
+    const DIV_IRQ: u8 = 0x05;
+    const ERROR: u8 = 0x06;
+    const STATUS_2: u8 = 0x08;
+    const FIFO_DATA: u8 = 0x09;
+    const FIFO_LEVEL: u8 = 0x0A;
+    const CONTROL: u8 = 0x0C;
+    const BIT_FRAMING: u8 = 0x0D;
+    const COLL: u8 = 0x0E;
+    const MODE: u8 = 0x11;
+    const TX_CONTROL: u8 = 0x14;
+    const TX_ASK: u8 = 0x15;
+    const CRC_RESULT_MSB: u8 = 0x21;
+    const CRC_RESULT_LSB: u8 = 0x22;
+    const T_MODE: u8 = 0x2A;
+    const T_PRESCALER: u8 = 0x2B;
+    const T_RELOAD_MSB: u8 = 0x2C;
+    const T_RELOAD_LSB: u8 = 0x2D;
+    const VERSION: u8 = 0x37;
+}
+
+/// Commands of the reader. See datasheet section 10.3 for more details.
+struct Command;
+
+impl Command {
+    const IDLE: u8 = 0x00;
+    const CALC_CRC: u8 = 0x03;
+    const TRANSCEIVE: u8 = 0x0C;
+    const MF_AUTHENT: u8 = 0x0E;
+    const SOFT_RESET: u8 = 0x0F;
+}
+
+/// Commands of the cards. See ISO/IEC 14443-3 and the MIFARE Classic
+/// datasheet for more details.
+struct PiccCommand;
+
+impl PiccCommand {
+    const REQA: u8 = 0x26;
+    const HLTA: u8 = 0x50;
+    const SELECT: [u8; 3] = [0x93, 0x95, 0x97];
+    const READ: u8 = 0x30;
+    const WRITE: u8 = 0xA0;
+}
+
+/// Flag of the command register set while the reader is powered down
+const COMMAND_POWER_DOWN: u8 = 1 << 4;
+
+/// Flags of the interrupt request registers
+const IRQ_SET_ALL: u8 = 0x7F;
+const IRQ_RX: u8 = 1 << 5;
+const IRQ_IDLE: u8 = 1 << 4;
+const IRQ_TIMER: u8 = 1 << 0;
+const IRQ_CRC: u8 = 1 << 2;
+
+/// Flags of the error register
+const ERROR_PROTOCOL: u8 = 1 << 0;
+const ERROR_PARITY: u8 = 1 << 1;
+const ERROR_COLLISION: u8 = 1 << 3;
+const ERROR_BUFFER_OVERFLOW: u8 = 1 << 4;
+
+/// Flag of the status 2 register set while MIFARE Crypto1 is on
+const STATUS_2_CRYPTO_1_ON: u8 = 1 << 3;
+
+/// Flag of the FIFO level register flushing the FIFO
+const FIFO_FLUSH: u8 = 1 << 7;
+
+/// Flag of the bit framing register starting a transceive
+const BIT_FRAMING_START_SEND: u8 = 1 << 7;
+
+/// Flags of the collision register
+const COLL_VALUES_AFTER_COLL: u8 = 1 << 7;
+const COLL_POS_NOT_VALID: u8 = 1 << 5;
+
+/// Flags of the TX control register driving the antenna
+const TX_CONTROL_ANTENNA_ON: u8 = 0x03;
+
+/// Configuration of the reader after a reset, as done by the application note
+/// of the MFRC522. The timer stops a transceive 25 ms after the end of the
+/// transmission, the modulation is 100% ASK and the CRC preset is 0x6363.
+const T_MODE_AUTO: u8 = 0x80;
+const T_PRESCALER_40KHZ: u8 = 0xA9;
+const T_RELOAD_25MS: u16 = 1000;
+const TX_ASK_FORCE_100: u8 = 0x40;
+const MODE_CRC_6363: u8 = 0x3D;
+
+/// Acknowledge of a MIFARE command, in 4 bits
+const MIFARE_ACK: u8 = 0x0A;
+
+/// Cascade tag announcing that the UID continues at the next cascade level
+const CASCADE_TAG: u8 = 0x88;
+
+/// Size of a block of a MIFARE Classic card
+pub const BLOCK_SIZE: usize = 16;
+
+/// Key to authenticate a sector of a MIFARE Classic card with
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyType {
+    A = 0x60,
+    B = 0x61,
+}
+
+impl KeyType {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// UID of a selected card
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Uid {
+    bytes: [u8; 10],
+    len: usize,
+    sak: u8,
+}
+
+impl Uid {
+    /// Get the 4, 7 or 10 bytes of the UID
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Get the SAK of the card, whose bits tell its type, e.g. `0x08` for a
+    /// MIFARE Classic 1K
+    pub fn sak(&self) -> u8 {
+        self.sak
+    }
+}
+
+/// An MFRC522 on an SPI bus
+pub struct Mfrc522<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: SpiDevice> Mfrc522<SPI> {
+    /// Create a new reader, reset it and turn its antenna on.
+    ///
+    /// # Arguments
+    ///
+    /// - `spi`: The SPI device of the reader, in mode 0 and up to 10 MHz.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidVersion` if the device does not identify as an
+    /// MFRC522.
+    pub async fn new(spi: SPI) -> Result<Self, Error<SPI::Error>> {
+        let mut mfrc522 = Self { spi };
+
+        let version = mfrc522.read_register(Register::VERSION).await?;
+        if !VERSIONS.contains(&version) {
+            return Err(Error::InvalidVersion(version));
+        }
+
+        mfrc522.reset().await?;
+        Ok(mfrc522)
+    }
+
+    /// Release the underlying SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Reset the reader, configure it and turn its antenna on.
+    pub async fn reset(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.write_register(Register::COMMAND, Command::SOFT_RESET)
+            .await?;
+        let poll = async {
+            loop {
+                Timer::after_millis(5).await;
+                let command = self.read_register(Register::COMMAND).await?;
+                if command & COMMAND_POWER_DOWN == 0 {
+                    return Ok(());
+                }
+            }
+        };
+        with_timeout(RESET_TIMEOUT, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))?;
+
+        let [reload_msb, reload_lsb] = T_RELOAD_25MS.to_be_bytes();
+        self.write_register(Register::T_MODE, T_MODE_AUTO).await?;
+        self.write_register(Register::T_PRESCALER, T_PRESCALER_40KHZ)
+            .await?;
+        self.write_register(Register::T_RELOAD_MSB, reload_msb)
+            .await?;
+        self.write_register(Register::T_RELOAD_LSB, reload_lsb)
+            .await?;
+        self.write_register(Register::TX_ASK, TX_ASK_FORCE_100)
+            .await?;
+        self.write_register(Register::MODE, MODE_CRC_6363).await?;
+        self.set_antenna(true).await
+    }
+
+    /// Turn the antenna on or off. Cards are only powered and detected while
+    /// it is on.
+    pub async fn set_antenna(&mut self, on: bool) -> Result<(), Error<SPI::Error>> {
+        let tx_control = self.read_register(Register::TX_CONTROL).await?;
+        let tx_control = if on {
+            tx_control | TX_CONTROL_ANTENNA_ON
+        } else {
+            tx_control & !TX_CONTROL_ANTENNA_ON
+        };
+        self.write_register(Register::TX_CONTROL, tx_control).await
+    }
+
+    /// Wait for a card to enter the field, select it and return its UID.
+    ///
+    /// Halted cards are ignored until they leave the field.
+    pub async fn wait_for_card(&mut self) -> Result<Uid, Error<SPI::Error>> {
+        loop {
+            // Several cards answering at once collide, but are still there
+            match self.request().await {
+                Ok(_) | Err(Error::Collision) => match self.select().await {
+                    Ok(uid) => return Ok(uid),
+                    Err(Error::Spi(error)) => return Err(Error::Spi(error)),
+                    Err(_) => {}
+                },
+                Err(Error::Spi(error)) => return Err(Error::Spi(error)),
+                Err(_) => {}
+            }
+            Timer::after(CARD_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Send REQA and return the ATQA of the cards in the field.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoAnswer` if there is no card in the field.
+    pub async fn request(&mut self) -> Result<[u8; 2], Error<SPI::Error>> {
+        let mut atqa = [0u8; 2];
+        // REQA is a short frame of 7 bits
+        let (len, bits) = self
+            .transceive(&[PiccCommand::REQA], 7, &mut atqa, 0)
+            .await?;
+        if len != 2 || bits != 0 {
+            return Err(Error::Communication(0));
+        }
+        Ok(atqa)
+    }
+
+    /// Select a card that answered REQA, resolving the collisions between
+    /// cards at every cascade level, and return its UID.
+    pub async fn select(&mut self) -> Result<Uid, Error<SPI::Error>> {
+        let mut uid = Uid {
+            bytes: [0; 10],
+            len: 0,
+            sak: 0,
+        };
+
+        // Keep the bits received after a collision at 0
+        let coll = self.read_register(Register::COLL).await?;
+        self.write_register(Register::COLL, coll & !COLL_VALUES_AFTER_COLL)
+            .await?;
+
+        for select in PiccCommand::SELECT {
+            // UID CLn and BCC, whose bits are known up to a collision
+            let mut known = [0u8; 5];
+            let mut known_bits = 0;
+
+            loop {
+                let mut frame = [0u8; 7];
+                let byte = known_bits / 8;
+                let bits = known_bits % 8;
+                frame[0] = select;
+                frame[1] = (((2 + byte) << 4) | bits) as u8;
+                frame[2..7].copy_from_slice(&known);
+                let frame_len = 2 + byte + usize::from(bits != 0);
+
+                // The answer continues the last byte sent, if partial
+                let mut response = [0u8; 5];
+                response[0] = known[byte.min(4)];
+                match self
+                    .transceive(
+                        &frame[..frame_len],
+                        bits as u8,
+                        &mut response[..5 - byte],
+                        bits as u8,
+                    )
+                    .await
+                {
+                    Ok(_) => {
+                        known[byte..].copy_from_slice(&response[..5 - byte]);
+                        break;
+                    }
+                    Err(Error::Collision) => {
+                        let coll = self.read_register(Register::COLL).await?;
+                        if coll & COLL_POS_NOT_VALID != 0 {
+                            return Err(Error::Collision);
+                        }
+                        let position = match (coll & 0x1F) as usize {
+                            0 => 32,
+                            position => position,
+                        };
+                        if position <= known_bits {
+                            return Err(Error::Collision);
+                        }
+
+                        // Follow the cards with a 1 at the collision
+                        known[byte..].copy_from_slice(&response[..5 - byte]);
+                        known_bits = position;
+                        known[(position - 1) / 8] |= 1 << ((position - 1) % 8);
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+
+            if known[..4].iter().fold(0, |bcc, b| bcc ^ b) != known[4] {
+                return Err(Error::Crc);
+            }
+
+            // Select the card with its complete UID CLn
+            let mut frame = [0u8; 9];
+            frame[0] = select;
+            frame[1] = 0x70;
+            frame[2..7].copy_from_slice(&known);
+            let crc = self.calculate_crc(&frame[..7]).await?;
+            frame[7..9].copy_from_slice(&crc);
+            let mut sak = [0u8; 3];
+            let (len, _) = self.transceive(&frame, 0, &mut sak, 0).await?;
+            if len != 3 {
+                return Err(Error::Communication(0));
+            }
+            self.check_crc(&sak).await?;
+
+            if known[0] == CASCADE_TAG {
+                uid.bytes[uid.len..uid.len + 3].copy_from_slice(&known[1..4]);
+                uid.len += 3;
+            } else {
+                uid.bytes[uid.len..uid.len + 4].copy_from_slice(&known[..4]);
+                uid.len += 4;
+                uid.sak = sak[0];
+                return Ok(uid);
+            }
+        }
+
+        Err(Error::Communication(0))
+    }
+
+    /// Halt the selected card, so that it ignores REQA until it leaves the
+    /// field.
+    pub async fn halt(&mut self) -> Result<(), Error<SPI::Error>> {
+        let mut frame = [PiccCommand::HLTA, 0, 0, 0];
+        let crc = self.calculate_crc(&frame[..2]).await?;
+        frame[2..].copy_from_slice(&crc);
+
+        // A halted card does not answer
+        match self.transceive(&frame, 0, &mut [], 0).await {
+            Err(Error::NoAnswer) => Ok(()),
+            Err(error) => Err(error),
+            Ok(_) => Err(Error::Communication(0)),
+        }
+    }
+
+    /// Authenticate the sector of a block of the selected MIFARE Classic
+    /// card, to read and write the blocks of the sector.
+    ///
+    /// # Arguments
+    ///
+    /// - `key_type`: Whether the key is the key A or B of the sector.
+    /// - `block`: Any block of the sector.
+    /// - `key`: The key, [DEFAULT_KEY] on new cards.
+    /// - `uid`: The UID of the card.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::AuthenticationFailed` if the key is wrong.
+    pub async fn authenticate(
+        &mut self,
+        key_type: KeyType,
+        block: u8,
+        key: &[u8; 6],
+        uid: &Uid,
+    ) -> Result<(), Error<SPI::Error>> {
+        // The last 4 bytes of the UID take part in the authentication
+        let mut data = [0u8; 12];
+        data[0] = key_type.bits();
+        data[1] = block;
+        data[2..8].copy_from_slice(key);
+        data[8..12].copy_from_slice(&uid.bytes()[uid.len - 4..]);
+
+        self.start_command(Command::MF_AUTHENT, &data).await?;
+        let irq = self.wait_for_irq(IRQ_IDLE).await?;
+        if irq & IRQ_TIMER != 0 {
+            return Err(Error::NoAnswer);
+        }
+
+        let status = self.read_register(Register::STATUS_2).await?;
+        if status & STATUS_2_CRYPTO_1_ON == 0 {
+            return Err(Error::AuthenticationFailed);
+        }
+        Ok(())
+    }
+
+    /// Stop encrypting the exchanges, once done with an authenticated card.
+    pub async fn stop_crypto(&mut self) -> Result<(), Error<SPI::Error>> {
+        let status = self.read_register(Register::STATUS_2).await?;
+        self.write_register(Register::STATUS_2, status & !STATUS_2_CRYPTO_1_ON)
+            .await
+    }
+
+    /// Read a block of an authenticated sector.
+    pub async fn read_block(&mut self, block: u8) -> Result<[u8; BLOCK_SIZE], Error<SPI::Error>> {
+        let mut frame = [PiccCommand::READ, block, 0, 0];
+        let crc = self.calculate_crc(&frame[..2]).await?;
+        frame[2..].copy_from_slice(&crc);
+
+        let mut response = [0u8; BLOCK_SIZE + 2];
+        let (len, _) = self.transceive(&frame, 0, &mut response, 0).await?;
+        if len == 1 {
+            return Err(Error::Nak(response[0]));
+        }
+        if len != response.len() {
+            return Err(Error::Communication(0));
+        }
+        self.check_crc(&response).await?;
+
+        let mut data = [0u8; BLOCK_SIZE];
+        data.copy_from_slice(&response[..BLOCK_SIZE]);
+        Ok(data)
+    }
+
+    /// Write a block of an authenticated sector.
+    ///
+    /// Writing the last block of a sector, its trailer, changes its keys and
+    /// access bits, and a wrong trailer locks the sector for good.
+    pub async fn write_block(
+        &mut self,
+        block: u8,
+        data: &[u8; BLOCK_SIZE],
+    ) -> Result<(), Error<SPI::Error>> {
+        let mut frame = [PiccCommand::WRITE, block, 0, 0];
+        let crc = self.calculate_crc(&frame[..2]).await?;
+        frame[2..].copy_from_slice(&crc);
+        self.mifare_transceive(&frame).await?;
+
+        let mut frame = [0u8; BLOCK_SIZE + 2];
+        frame[..BLOCK_SIZE].copy_from_slice(data);
+        let crc = self.calculate_crc(data).await?;
+        frame[BLOCK_SIZE..].copy_from_slice(&crc);
+        self.mifare_transceive(&frame).await
+    }
+
+    /// Send a MIFARE frame and check that the card acknowledges it.
+    async fn mifare_transceive(&mut self, frame: &[u8]) -> Result<(), Error<SPI::Error>> {
+        let mut ack = [0u8];
+        let (len, bits) = self.transceive(frame, 0, &mut ack, 0).await?;
+        if len != 1 || bits != 4 {
+            return Err(Error::Communication(0));
+        }
+        if ack[0] & 0x0F != MIFARE_ACK {
+            return Err(Error::Nak(ack[0] & 0x0F));
+        }
+        Ok(())
+    }
+
+    /// Send a frame to the cards and receive their answer.
+    ///
+    /// # Arguments
+    ///
+    /// - `frame`: The frame to send.
+    /// - `tx_last_bits`: The number of bits of the last byte to send, or 0
+    ///   for all of them.
+    /// - `response`: The buffer receiving the answer. With `rx_align`, its
+    ///   first byte keeps its bits below the alignment.
+    /// - `rx_align`: The position in the first byte of the first bit
+    ///   received.
+    ///
+    /// Returns the number of bytes received and the number of valid bits of
+    /// the last one, or 0 if all are valid.
+    async fn transceive(
+        &mut self,
+        frame: &[u8],
+        tx_last_bits: u8,
+        response: &mut [u8],
+        rx_align: u8,
+    ) -> Result<(usize, u8), Error<SPI::Error>> {
+        self.write_register(Register::BIT_FRAMING, (rx_align << 4) | tx_last_bits)
+            .await?;
+        self.start_command(Command::TRANSCEIVE, frame).await?;
+        self.write_register(
+            Register::BIT_FRAMING,
+            BIT_FRAMING_START_SEND | (rx_align << 4) | tx_last_bits,
+        )
+        .await?;
+
+        let irq = self.wait_for_irq(IRQ_RX | IRQ_IDLE).await?;
+        if irq & IRQ_TIMER != 0 && irq & (IRQ_RX | IRQ_IDLE) == 0 {
+            return Err(Error::NoAnswer);
+        }
+
+        let error = self.read_register(Register::ERROR).await?;
+        if error & (ERROR_BUFFER_OVERFLOW | ERROR_PARITY | ERROR_PROTOCOL) != 0 {
+            return Err(Error::Communication(error));
+        }
+
+        let len = self.read_register(Register::FIFO_LEVEL).await? as usize;
+        if len > response.len() {
+            return Err(Error::Communication(error));
+        }
+        if len > 0 {
+            let first = response[0];
+            self.read_fifo(&mut response[..len]).await?;
+            let mask = 0xFF << rx_align;
+            response[0] = (first & !mask) | (response[0] & mask);
+        }
+        let bits = self.read_register(Register::CONTROL).await? & 0x07;
+
+        if error & ERROR_COLLISION != 0 {
+            return Err(Error::Collision);
+        }
+        Ok((len, bits))
+    }
+
+    /// Compute the CRC_A of data with the coprocessor of the reader.
+    async fn calculate_crc(&mut self, data: &[u8]) -> Result<[u8; 2], Error<SPI::Error>> {
+        self.write_register(Register::DIV_IRQ, IRQ_CRC).await?;
+        self.start_command(Command::CALC_CRC, data).await?;
+
+        let poll = async {
+            loop {
+                let irq = self.read_register(Register::DIV_IRQ).await?;
+                if irq & IRQ_CRC != 0 {
+                    return Ok(());
+                }
+            }
+        };
+        with_timeout(COMMAND_TIMEOUT, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))?;
+        self.write_register(Register::COMMAND, Command::IDLE)
+            .await?;
+
+        let lsb = self.read_register(Register::CRC_RESULT_LSB).await?;
+        let msb = self.read_register(Register::CRC_RESULT_MSB).await?;
+        Ok([lsb, msb])
+    }
+
+    /// Check the CRC_A at the end of a received frame.
+    async fn check_crc(&mut self, frame: &[u8]) -> Result<(), Error<SPI::Error>> {
+        let (data, crc) = frame.split_at(frame.len() - 2);
+        if self.calculate_crc(data).await? != crc {
+            return Err(Error::Crc);
+        }
+        Ok(())
+    }
+
+    /// Load the FIFO with data and start a command.
+    async fn start_command(&mut self, command: u8, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.write_register(Register::COMMAND, Command::IDLE)
+            .await?;
+        self.write_register(Register::COM_IRQ, IRQ_SET_ALL).await?;
+        self.write_register(Register::FIFO_LEVEL, FIFO_FLUSH)
+            .await?;
+        self.write_fifo(data).await?;
+        self.write_register(Register::COMMAND, command).await
+    }
+
+    /// Wait for any of the interrupt requests, or the timer, and return them.
+    async fn wait_for_irq(&mut self, irq: u8) -> Result<u8, Error<SPI::Error>> {
+        let poll = async {
+            loop {
+                let status = self.read_register(Register::COM_IRQ).await?;
+                if status & (irq | IRQ_TIMER) != 0 {
+                    return Ok(status);
+                }
+            }
+        };
+        with_timeout(COMMAND_TIMEOUT, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<SPI::Error>> {
+        let mut buf = [0x80 | (register << 1), 0];
+        self.spi
+            .transfer_in_place(&mut buf)
+            .await
+            .map_err(Error::Spi)?;
+        Ok(buf[1])
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<SPI::Error>> {
+        self.spi
+            .write(&[register << 1, value])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Read bytes from the FIFO, whose address is sent again for every byte.
+    async fn read_fifo(&mut self, data: &mut [u8]) -> Result<(), Error<SPI::Error>> {
+        let mut buf = [0x80 | (Register::FIFO_DATA << 1); FIFO_SIZE + 1];
+        let buf = &mut buf[..data.len() + 1];
+        buf[data.len()] = 0;
+        self.spi.transfer_in_place(buf).await.map_err(Error::Spi)?;
+        data.copy_from_slice(&buf[1..]);
+        Ok(())
+    }
+
+    async fn write_fifo(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        let mut buf = [0u8; FIFO_SIZE + 1];
+        buf[0] = Register::FIFO_DATA << 1;
+        buf[1..data.len() + 1].copy_from_slice(data);
+        self.spi
+            .write(&buf[..data.len() + 1])
+            .await
+            .map_err(Error::Spi)
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying SPI device.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI bus error
+    Spi(E),
+    /// The device does not identify as an MFRC522.
+    InvalidVersion(u8),
+    /// The reader did not complete a command in time.
+    Timeout,
+    /// No card answered.
+    NoAnswer,
+    /// Several cards answered at once.
+    Collision,
+    /// The answer of the card is malformed, with the error register of the
+    /// reader.
+    Communication(u8),
+    /// The CRC of the answer of the card is wrong.
+    Crc,
+    /// The key does not authenticate the sector.
+    AuthenticationFailed,
+    /// The card refused a MIFARE command, with its 4-bit answer.
+    Nak(u8),
+}
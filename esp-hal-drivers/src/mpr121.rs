@@ -0,0 +1,376 @@
+//! # mpr121
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the NXP MPR121
+//! 12-electrode capacitive touch controller over I2C.
+//!
+//! - The charge current and time of the electrodes are set by the
+//!   auto-configuration of the controller, from the supply voltage.
+//! - Each electrode has its own touch and release thresholds, relative to a
+//!   baseline tracking slow changes of the environment.
+//! - With the IRQ line attached with [Mpr121::with_interrupt_pin],
+//!   [Mpr121::wait_for_event] awaits the touches and releases of the
+//!   electrodes one at a time, without polling.
+//!
+//! The registers setting the electrodes can only be written while they are
+//! stopped, so the driver stops and restarts them around each change.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut touch = Mpr121::new(i2c, BASE_ADDRESS)
+//!     .await?
+//!     .with_interrupt_pin(Input::new(peripherals.GPIO7, Pull::Up));
+//!
+//! // A less sensitive electrode behind a thicker overlay
+//! touch.set_thresholds(11, 24, 12).await?;
+//!
+//! loop {
+//!     match touch.wait_for_event().await? {
+//!         Event::Touched(electrode) => println!("Touched {}", electrode),
+//!         Event::Released(electrode) => println!("Released {}", electrode),
+//!     }
+//! }
+//! ```
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::{digital::Wait, i2c::I2c};
+
+use crate::units::Millivolts;
+
+/// I2C address of the controller when ADDR is tied to ground
+pub const BASE_ADDRESS: u8 = 0x5A;
+
+/// Number of electrodes of the controller
+pub const ELECTRODES: u8 = 12;
+
+/// Default touch threshold, in counts below the baseline
+pub const DEFAULT_TOUCH_THRESHOLD: u8 = 12;
+
+/// Default release threshold, in counts below the baseline
+pub const DEFAULT_RELEASE_THRESHOLD: u8 = 6;
+
+/// Supply voltage assumed by the auto-configuration run by [Mpr121::new]
+const DEFAULT_SUPPLY: Millivolts = Millivolts(3_300);
+
+/// Value of the CONFIG2 register after a reset
+const CONFIG2_RESET: u8 = 0x24;
+
+/// Value written to SOFT_RESET to reset the controller
+const SOFT_RESET: u8 = 0x63;
+
+/// Time for the controller to reset
+const RESET_TIME: Duration = Duration::from_millis(1);
+
+/// ECR value running the 12 electrodes, with the baseline initialized from
+/// the 5 high bits of the first measurement
+const ECR_RUN: u8 = 0x80 | ELECTRODES;
+
+/// Flag of the touch status set when the REXT pin is over-current
+const STATUS_OVER_CURRENT: u16 = 1 << 15;
+
+/// Mask of the electrodes in the touch status
+const STATUS_ELECTRODES: u16 = (1 << ELECTRODES) - 1;
+
+/// See datasheet section 5 for more details.
+struct Register;
+
+impl Register {
+    const TOUCH_STATUS: u8 = 0x00;
+    const FILTERED_DATA: u8 = 0x04;
+    const BASELINE: u8 = 0x1E;
+    const MHDR: u8 = 0x2B;
+    const TOUCH_THRESHOLD: u8 = 0x41;
+    const DEBOUNCE: u8 = 0x5B;
+    const CONFIG1: u8 = 0x5C;
+    const CONFIG2: u8 = 0x5D;
+    const ECR: u8 = 0x5E;
+    const AUTOCONFIG0: u8 = 0x7B;
+    const SOFT_RESET: u8 = 0x80;
+}
+
+/// Baseline filter settings from MHDR to FDLF, as recommended by AN3944
+const BASELINE_FILTER: [u8; 8] = [0x01, 0x01, 0x0E, 0x00, 0x01, 0x05, 0x01, 0x00];
+
+/// A change of an electrode, as returned by [Mpr121::wait_for_event]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The electrode (0-11) was touched.
+    Touched(u8),
+    /// The electrode (0-11) was released.
+    Released(u8),
+}
+
+/// An MPR121 on an I2C bus
+///
+/// `INT` is the pin the IRQ output of the controller is connected to, if any.
+pub struct Mpr121<I2C, INT = ()> {
+    address: u8,
+    i2c: I2C,
+    interrupt: INT,
+    /// Electrodes touched at the last read of the touch status
+    touched: u16,
+    /// Electrodes touched as reported by the events
+    reported: u16,
+}
+
+impl<I2C: I2c> Mpr121<I2C> {
+    /// Create a new controller, reset it, and run its 12 electrodes with
+    /// the default thresholds and an auto-configuration for 3.3 V.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the controller is on.
+    /// - `address`: The I2C address of the controller, from [BASE_ADDRESS]
+    ///   to `BASE_ADDRESS + 3` depending on what ADDR is tied to.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidResetValue` if the controller does not reset
+    /// like a MPR121.
+    pub async fn new(i2c: I2C, address: u8) -> Result<Self, Error<I2C::Error>> {
+        let mut touch = Self {
+            address,
+            i2c,
+            interrupt: (),
+            touched: 0,
+            reported: 0,
+        };
+
+        touch
+            .write_register(Register::SOFT_RESET, SOFT_RESET)
+            .await?;
+        Timer::after(RESET_TIME).await;
+
+        let config2 = touch.read_register(Register::CONFIG2).await?;
+        if config2 != CONFIG2_RESET {
+            return Err(Error::InvalidResetValue(config2));
+        }
+
+        touch
+            .write_registers(Register::MHDR, &BASELINE_FILTER)
+            .await?;
+        let mut thresholds = [0u8; 2 * ELECTRODES as usize];
+        for pair in thresholds.as_chunks_mut::<2>().0 {
+            pair.copy_from_slice(&[DEFAULT_TOUCH_THRESHOLD, DEFAULT_RELEASE_THRESHOLD]);
+        }
+        touch
+            .write_registers(Register::TOUCH_THRESHOLD, &thresholds)
+            .await?;
+        touch.write_register(Register::DEBOUNCE, 0).await?;
+        // 16 µA, 6 samples for the first filter, then 0.5 µs, 4 samples for
+        // the second filter and a period of 1 ms
+        touch.write_register(Register::CONFIG1, 0x10).await?;
+        touch.write_register(Register::CONFIG2, 0x20).await?;
+
+        touch.auto_config(DEFAULT_SUPPLY).await?;
+        Ok(touch)
+    }
+
+    /// Attach the pin the IRQ output of the controller is connected to.
+    ///
+    /// The output is open-drain and active low, so the pin needs a pull-up.
+    pub fn with_interrupt_pin<P: Wait>(self, interrupt: P) -> Mpr121<I2C, P> {
+        Mpr121 {
+            address: self.address,
+            i2c: self.i2c,
+            interrupt,
+            touched: self.touched,
+            reported: self.reported,
+        }
+    }
+}
+
+impl<I2C: I2c, P: Wait> Mpr121<I2C, P> {
+    /// Wait for an electrode to be touched or released.
+    ///
+    /// Electrodes changing at the same time are reported by successive
+    /// calls, by increasing electrode.
+    pub async fn wait_for_event(&mut self) -> Result<Event, Error<I2C::Error>> {
+        loop {
+            let changed = self.touched ^ self.reported;
+            if changed != 0 {
+                let electrode = changed.trailing_zeros() as u8;
+                self.reported ^= 1 << electrode;
+                return Ok(if self.touched & (1 << electrode) != 0 {
+                    Event::Touched(electrode)
+                } else {
+                    Event::Released(electrode)
+                });
+            }
+
+            self.interrupt
+                .wait_for_low()
+                .await
+                .map_err(|_| Error::Pin)?;
+
+            // Reading the touch status releases the IRQ line
+            self.touched().await?;
+        }
+    }
+
+    /// Release the underlying I2C bus and interrupt pin
+    pub fn release_with_interrupt_pin(self) -> (I2C, P) {
+        (self.i2c, self.interrupt)
+    }
+}
+
+impl<I2C: I2c, INT> Mpr121<I2C, INT> {
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Read the electrodes touched, where bit `n` is electrode `n`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::OverCurrent` if the REXT pin is shorted, which stops
+    /// the electrodes.
+    pub async fn touched(&mut self) -> Result<u16, Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.read_registers(Register::TOUCH_STATUS, &mut buf)
+            .await?;
+        let status = u16::from_le_bytes(buf);
+        if status & STATUS_OVER_CURRENT != 0 {
+            return Err(Error::OverCurrent);
+        }
+
+        self.touched = status & STATUS_ELECTRODES;
+        Ok(self.touched)
+    }
+
+    /// Return whether an electrode is touched.
+    pub async fn is_touched(&mut self, electrode: u8) -> Result<bool, Error<I2C::Error>> {
+        let electrode = check_electrode(electrode)?;
+        Ok(self.touched().await? & (1 << electrode) != 0)
+    }
+
+    /// Read the filtered measurement of an electrode, on 10 bits.
+    ///
+    /// Touching an electrode lowers its measurement.
+    pub async fn filtered_data(&mut self, electrode: u8) -> Result<u16, Error<I2C::Error>> {
+        let electrode = check_electrode(electrode)?;
+        let mut buf = [0u8; 2];
+        self.read_registers(Register::FILTERED_DATA + 2 * electrode, &mut buf)
+            .await?;
+        Ok(u16::from_le_bytes(buf) & 0x3FF)
+    }
+
+    /// Read the baseline of an electrode, on 10 bits.
+    pub async fn baseline(&mut self, electrode: u8) -> Result<u16, Error<I2C::Error>> {
+        let electrode = check_electrode(electrode)?;
+        // Only the 8 high bits are stored
+        Ok((self.read_register(Register::BASELINE + electrode).await? as u16) << 2)
+    }
+
+    /// Set the thresholds of an electrode, in counts below its baseline.
+    ///
+    /// # Arguments
+    ///
+    /// - `electrode`: The electrode (0-11).
+    /// - `touch`: The threshold under which the electrode is touched.
+    /// - `release`: The threshold over which the electrode is released,
+    ///   lower than `touch` to add hysteresis.
+    pub async fn set_thresholds(
+        &mut self,
+        electrode: u8,
+        touch: u8,
+        release: u8,
+    ) -> Result<(), Error<I2C::Error>> {
+        let electrode = check_electrode(electrode)?;
+
+        self.write_register(Register::ECR, 0).await?;
+        self.write_registers(Register::TOUCH_THRESHOLD + 2 * electrode, &[touch, release])
+            .await?;
+        self.write_register(Register::ECR, ECR_RUN).await
+    }
+
+    /// Run the auto-configuration of the charge current and time of the
+    /// electrodes for a supply voltage. See AN3889 for more details.
+    ///
+    /// It is run by [Mpr121::new] for 3.3 V.
+    pub async fn auto_config(&mut self, supply: Millivolts) -> Result<(), Error<I2C::Error>> {
+        // Target charge levels, from the highest level charging the
+        // electrodes without saturating them
+        let upper = ((supply.0 - 700).max(0) * 256 / supply.0.max(1)).min(255);
+        let target = upper * 90 / 100;
+        let lower = upper * 65 / 100;
+
+        self.write_register(Register::ECR, 0).await?;
+        // Same first filter and baseline initialization as the electrodes,
+        // with auto-reconfiguration and auto-configuration enabled
+        self.write_registers(
+            Register::AUTOCONFIG0,
+            &[0x0B, 0x00, upper as u8, lower as u8, target as u8],
+        )
+        .await?;
+        self.write_register(Register::ECR, ECR_RUN).await
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.read_registers(register, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn read_registers(
+        &mut self,
+        register: u8,
+        buf: &mut [u8],
+    ) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write_read(self.address, &[register], buf)
+            .await
+            .map_err(Error::I2c)
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<I2C::Error>> {
+        self.write_registers(register, &[value]).await
+    }
+
+    /// Write consecutive registers, at most 24.
+    async fn write_registers(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<(), Error<I2C::Error>> {
+        let mut buf = [0u8; 1 + 2 * ELECTRODES as usize];
+        buf[0] = register;
+        buf[1..=values.len()].copy_from_slice(values);
+        self.i2c
+            .write(self.address, &buf[..=values.len()])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+/// Check that an electrode exists.
+fn check_electrode<E>(electrode: u8) -> Result<u8, Error<E>> {
+    if electrode < ELECTRODES {
+        Ok(electrode)
+    } else {
+        Err(Error::InvalidElectrode)
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The interrupt pin could not be read.
+    Pin,
+    /// The controller did not reset like a MPR121, with the value read from
+    /// CONFIG2.
+    InvalidResetValue(u8),
+    /// The REXT pin is over-current, which stops the electrodes.
+    OverCurrent,
+    /// The electrode is not between 0 and 11.
+    InvalidElectrode,
+}
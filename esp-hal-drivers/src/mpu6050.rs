@@ -0,0 +1,475 @@
+//! # mpu6050
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the InvenSense
+//! MPU6050 6-axis accelerometer and gyroscope over I2C.
+//!
+//! - The ranges of the accelerometer and the gyroscope trade resolution for
+//!   the largest measurable values.
+//! - The digital low-pass filter and the sample rate divider set how often
+//!   the sensor samples, and how much it smooths the samples.
+//! - The FIFO of the sensor buffers up to 85 samples, so they can be read in
+//!   batches without missing any.
+//!
+//! The sensor asserts its INT pin when a new sample is ready. Once a GPIO is
+//! attached with [Mpu6050::with_interrupt_pin], [Mpu6050::wait_for_sample]
+//! awaits the sample on a GPIO interrupt instead of polling the bus.
+//!
+//! The [ComplementaryFilter](crate::imu::ComplementaryFilter) turns the
+//! samples into an orientation.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let config = Config::new()
+//!     .with_accel_range(AccelRange::G4)
+//!     .with_gyro_range(GyroRange::Dps500);
+//! let mut mpu6050 = Mpu6050::new(i2c, ADDRESS_AD0_LOW, config)
+//!     .await?
+//!     .with_interrupt_pin(Input::new(peripherals.GPIO5, Pull::Down));
+//!
+//! let sample = mpu6050.wait_for_sample().await?;
+//! println!("{} g", sample.acceleration.norm());
+//!
+//! // Or read the samples in batches from the FIFO
+//! mpu6050.enable_fifo(true).await?;
+//! Timer::after(Duration::from_millis(100)).await;
+//! let mut samples = [Sample::default(); 16];
+//! let len = mpu6050.read_fifo(&mut samples).await?;
+//! ```
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::{digital::Wait, i2c::I2c};
+
+use crate::{
+    imu::{Sample, Vector3},
+    units::Celsius,
+};
+
+/// I2C address of the sensor when AD0 is tied to ground
+pub const ADDRESS_AD0_LOW: u8 = 0x68;
+
+/// I2C address of the sensor when AD0 is tied to the supply
+pub const ADDRESS_AD0_HIGH: u8 = 0x69;
+
+/// Value of the WHO_AM_I register
+const DEVICE_ID: u8 = 0x68;
+
+/// Time taken by a reset
+const RESET_TIME: Duration = Duration::from_millis(100);
+
+/// Size of the FIFO in bytes
+const FIFO_SIZE: usize = 1024;
+
+/// Bytes of a sample in the FIFO, the accelerometer then the gyroscope
+const FIFO_SAMPLE_SIZE: usize = 12;
+
+/// Samples read from the FIFO per I2C transaction
+const FIFO_BATCH: usize = 8;
+
+/// Registers of the sensor. See the register map for more details.
+struct Register;
+
+impl Register {
+    const SMPLRT_DIV: u8 = 0x19;
+    const CONFIG: u8 = 0x1A;
+    const GYRO_CONFIG: u8 = 0x1B;
+    const ACCEL_CONFIG: u8 = 0x1C;
+    const FIFO_EN: u8 = 0x23;
+    const INT_PIN_CFG: u8 = 0x37;
+    const INT_ENABLE: u8 = 0x38;
+    const ACCEL_XOUT_H: u8 = 0x3B;
+    const TEMP_OUT_H: u8 = 0x41;
+    const USER_CTRL: u8 = 0x6A;
+    const PWR_MGMT_1: u8 = 0x6B;
+    const FIFO_COUNT_H: u8 = 0x72;
+    const FIFO_R_W: u8 = 0x74;
+    const WHO_AM_I: u8 = 0x75;
+}
+
+/// Flags of the power management register
+const PWR_RESET: u8 = 1 << 7;
+const PWR_CLOCK_PLL_GYRO_X: u8 = 0x01;
+
+/// Flags of the interrupt pin configuration, keeping the pin asserted until
+/// any register is read
+const INT_LATCH: u8 = 1 << 5;
+const INT_READ_CLEAR: u8 = 1 << 4;
+
+/// Flag of the interrupt enable register
+const INT_DATA_READY: u8 = 1 << 0;
+
+/// Flags of the user control register
+const USER_FIFO_ENABLE: u8 = 1 << 6;
+const USER_FIFO_RESET: u8 = 1 << 2;
+
+/// Flags of the FIFO enable register, storing the gyroscope and the
+/// accelerometer
+const FIFO_GYRO_AND_ACCEL: u8 = 0x78;
+
+/// Configuration of the sensor
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    accel_range: AccelRange,
+    gyro_range: GyroRange,
+    bandwidth: Bandwidth,
+    sample_rate_divider: u8,
+}
+
+impl Default for Config {
+    /// The most sensitive ranges, sampled at 100 Hz with a 44 Hz bandwidth.
+    fn default() -> Self {
+        Self {
+            accel_range: AccelRange::default(),
+            gyro_range: GyroRange::default(),
+            bandwidth: Bandwidth::default(),
+            sample_rate_divider: 9,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_accel_range(mut self, range: AccelRange) -> Self {
+        self.accel_range = range;
+        self
+    }
+
+    pub fn with_gyro_range(mut self, range: GyroRange) -> Self {
+        self.gyro_range = range;
+        self
+    }
+
+    pub fn with_bandwidth(mut self, bandwidth: Bandwidth) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+
+    /// Set the sample rate to the internal rate divided by `1 + divider`.
+    /// The internal rate is 1 kHz, or 8 kHz with [Bandwidth::Hz260].
+    pub fn with_sample_rate_divider(mut self, divider: u8) -> Self {
+        self.sample_rate_divider = divider;
+        self
+    }
+}
+
+/// Full-scale range of the accelerometer
+///
+/// Defaults to `G2`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AccelRange {
+    #[default]
+    G2 = 0b00,
+    G4 = 0b01,
+    G8 = 0b10,
+    G16 = 0b11,
+}
+
+impl AccelRange {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    fn lsb_per_g(&self) -> f32 {
+        16_384.0 / (1 << self.bits()) as f32
+    }
+}
+
+/// Full-scale range of the gyroscope
+///
+/// Defaults to `Dps250`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GyroRange {
+    #[default]
+    Dps250 = 0b00,
+    Dps500 = 0b01,
+    Dps1000 = 0b10,
+    Dps2000 = 0b11,
+}
+
+impl GyroRange {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    fn lsb_per_dps(&self) -> f32 {
+        131.0 / (1 << self.bits()) as f32
+    }
+}
+
+/// Bandwidth of the digital low-pass filter of the accelerometer. The
+/// gyroscope bandwidth is about the same.
+///
+/// Defaults to `Hz44`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Bandwidth {
+    /// No filtering
+    Hz260 = 0,
+    Hz184 = 1,
+    Hz94 = 2,
+    #[default]
+    Hz44 = 3,
+    Hz21 = 4,
+    Hz10 = 5,
+    Hz5 = 6,
+}
+
+impl Bandwidth {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// An MPU6050 on an I2C bus
+///
+/// `INT` is the pin the interrupt output of the sensor is connected to, if
+/// any.
+pub struct Mpu6050<I2C, INT = ()> {
+    address: u8,
+    i2c: I2C,
+    interrupt: INT,
+    config: Config,
+}
+
+impl<I2C: I2c> Mpu6050<I2C> {
+    /// Create a new sensor, reset it and wake it up.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the sensor is on.
+    /// - `address`: The I2C address of the sensor, [ADDRESS_AD0_LOW] or
+    ///   [ADDRESS_AD0_HIGH].
+    /// - `config`: The configuration of the sensor.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidDeviceId` if the device does not identify as an
+    /// MPU6050.
+    pub async fn new(i2c: I2C, address: u8, config: Config) -> Result<Self, Error<I2C::Error>> {
+        let mut mpu6050 = Self {
+            address,
+            i2c,
+            interrupt: (),
+            config,
+        };
+
+        let id = mpu6050.read_register(Register::WHO_AM_I).await?;
+        if id != DEVICE_ID {
+            return Err(Error::InvalidDeviceId(id));
+        }
+
+        mpu6050
+            .write_register(Register::PWR_MGMT_1, PWR_RESET)
+            .await?;
+        Timer::after(RESET_TIME).await;
+        // The gyroscope clock is more stable than the internal oscillator
+        mpu6050
+            .write_register(Register::PWR_MGMT_1, PWR_CLOCK_PLL_GYRO_X)
+            .await?;
+
+        mpu6050
+            .write_register(Register::INT_PIN_CFG, INT_LATCH | INT_READ_CLEAR)
+            .await?;
+        mpu6050
+            .write_register(Register::INT_ENABLE, INT_DATA_READY)
+            .await?;
+        mpu6050.set_config(config).await?;
+        Ok(mpu6050)
+    }
+
+    /// Attach the pin the interrupt output of the sensor is connected to.
+    ///
+    /// The output is push-pull and active high.
+    pub fn with_interrupt_pin<P: Wait>(self, interrupt: P) -> Mpu6050<I2C, P> {
+        Mpu6050 {
+            address: self.address,
+            i2c: self.i2c,
+            interrupt,
+            config: self.config,
+        }
+    }
+}
+
+impl<I2C: I2c, P: Wait> Mpu6050<I2C, P> {
+    /// Wait for a new sample and return it.
+    ///
+    /// Returns immediately if a sample is ready since the last read of a
+    /// register.
+    pub async fn wait_for_sample(&mut self) -> Result<Sample, Error<I2C::Error>> {
+        self.interrupt
+            .wait_for_high()
+            .await
+            .map_err(|_| Error::Pin)?;
+
+        // Reading the sample clears the interrupt
+        self.read().await
+    }
+
+    /// Release the underlying I2C bus and interrupt pin
+    pub fn release_with_interrupt_pin(self) -> (I2C, P) {
+        (self.i2c, self.interrupt)
+    }
+}
+
+impl<I2C: I2c, INT> Mpu6050<I2C, INT> {
+    /// Get the current configuration of the sensor
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
+        self.config = config;
+        self.write_register(Register::SMPLRT_DIV, config.sample_rate_divider)
+            .await?;
+        self.write_register(Register::CONFIG, config.bandwidth.bits())
+            .await?;
+        self.write_register(Register::GYRO_CONFIG, config.gyro_range.bits() << 3)
+            .await?;
+        self.write_register(Register::ACCEL_CONFIG, config.accel_range.bits() << 3)
+            .await
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Read the latest sample.
+    pub async fn read(&mut self) -> Result<Sample, Error<I2C::Error>> {
+        // The temperature sits between the accelerometer and the gyroscope
+        let mut buf = [0u8; 14];
+        self.read_registers(Register::ACCEL_XOUT_H, &mut buf)
+            .await?;
+        Ok(self.decode(&buf[..6], &buf[8..]))
+    }
+
+    /// Read the temperature of the sensor.
+    pub async fn temperature(&mut self) -> Result<Celsius, Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.read_registers(Register::TEMP_OUT_H, &mut buf).await?;
+        Ok(Celsius(i16::from_be_bytes(buf) as f32 / 340.0 + 36.53))
+    }
+
+    /// Enable or disable the FIFO. Enabling it clears it.
+    pub async fn enable_fifo(&mut self, enabled: bool) -> Result<(), Error<I2C::Error>> {
+        if enabled {
+            self.write_register(Register::FIFO_EN, FIFO_GYRO_AND_ACCEL)
+                .await?;
+            self.write_register(Register::USER_CTRL, USER_FIFO_RESET)
+                .await?;
+            self.write_register(Register::USER_CTRL, USER_FIFO_ENABLE)
+                .await
+        } else {
+            self.write_register(Register::USER_CTRL, 0).await?;
+            self.write_register(Register::FIFO_EN, 0).await
+        }
+    }
+
+    /// Get the number of samples waiting in the FIFO.
+    pub async fn fifo_len(&mut self) -> Result<usize, Error<I2C::Error>> {
+        Ok(self.fifo_count().await? / FIFO_SAMPLE_SIZE)
+    }
+
+    /// Read samples from the FIFO, oldest first, and return the number of
+    /// samples read.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FifoOverflow` if the FIFO filled up, in which case the
+    /// oldest samples were lost and the FIFO is cleared.
+    pub async fn read_fifo(&mut self, samples: &mut [Sample]) -> Result<usize, Error<I2C::Error>> {
+        let count = self.fifo_count().await?;
+        if count >= FIFO_SIZE {
+            // The FIFO is not a whole number of samples, so it lost its
+            // alignment
+            self.write_register(Register::USER_CTRL, USER_FIFO_ENABLE | USER_FIFO_RESET)
+                .await?;
+            return Err(Error::FifoOverflow);
+        }
+
+        let len = samples.len().min(count / FIFO_SAMPLE_SIZE);
+        let mut buf = [0u8; FIFO_BATCH * FIFO_SAMPLE_SIZE];
+        for batch in samples[..len].chunks_mut(FIFO_BATCH) {
+            let bytes = &mut buf[..batch.len() * FIFO_SAMPLE_SIZE];
+            self.read_registers(Register::FIFO_R_W, bytes).await?;
+            for (sample, bytes) in batch
+                .iter_mut()
+                .zip(bytes.as_chunks::<FIFO_SAMPLE_SIZE>().0)
+            {
+                *sample = self.decode(&bytes[..6], &bytes[6..]);
+            }
+        }
+        Ok(len)
+    }
+
+    /// Number of bytes waiting in the FIFO
+    async fn fifo_count(&mut self) -> Result<usize, Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.read_registers(Register::FIFO_COUNT_H, &mut buf)
+            .await?;
+        Ok(u16::from_be_bytes(buf) as usize)
+    }
+
+    /// Scale the raw big-endian axes of the accelerometer and the gyroscope.
+    fn decode(&self, accel: &[u8], gyro: &[u8]) -> Sample {
+        let axes = |bytes: &[u8], lsb: f32| {
+            let axis = |i: usize| i16::from_be_bytes([bytes[i], bytes[i + 1]]) as f32 / lsb;
+            Vector3::new(axis(0), axis(2), axis(4))
+        };
+        Sample {
+            acceleration: axes(accel, self.config.accel_range.lsb_per_g()),
+            angular_rate: axes(gyro, self.config.gyro_range.lsb_per_dps()),
+        }
+    }
+
+    async fn read_registers(
+        &mut self,
+        register: u8,
+        buf: &mut [u8],
+    ) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write_read(self.address, &[register], buf)
+            .await
+            .map_err(Error::I2c)
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.read_registers(register, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The interrupt pin could not be read.
+    Pin,
+    /// The device does not identify as an MPU6050.
+    InvalidDeviceId(u8),
+    /// The FIFO overflowed and was cleared.
+    FifoOverflow,
+}
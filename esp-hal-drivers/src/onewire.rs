@@ -0,0 +1,324 @@
+//! # onewire
+//!
+//! ## Overview
+//!
+//! This module provides a bit-banged implementation of the Maxim 1-Wire bus
+//! on a single GPIO, with ROM search to enumerate the devices on the bus.
+//!
+//! The pin must be configured as an open-drain output with its input enabled,
+//! and the bus needs a pull-up resistor, typically 4.7 kΩ, to the supply.
+//!
+//! The time slots of the bus only last a few microseconds, so they are timed
+//! by busy-waiting. Longer waits, such as the reset pulse, yield to the
+//! executor. Interrupts firing in the middle of a slot may corrupt it, which
+//! the CRC of the transferred data catches.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let pin = Flex::new(peripherals.GPIO3);
+//! pin.set_as_open_drain(Pull::None);
+//! let mut bus = OneWire::new(pin);
+//!
+//! let mut search = RomSearch::new();
+//! while let Some(rom) = search.next(&mut bus).await? {
+//!     println!("Found device {:?} of family {:#x}", rom, rom.family());
+//! }
+//! ```
+
+use embassy_time::{block_for, Duration, Timer};
+use embedded_hal::digital::{InputPin, OutputPin};
+
+/// ROM command reading the ROM of the only device on the bus
+pub const READ_ROM: u8 = 0x33;
+
+/// ROM command addressing a single device by its ROM
+pub const MATCH_ROM: u8 = 0x55;
+
+/// ROM command addressing all devices on the bus at once
+pub const SKIP_ROM: u8 = 0xCC;
+
+/// ROM command starting a search of the devices on the bus
+pub const SEARCH_ROM: u8 = 0xF0;
+
+/// Timings of the standard speed, in µs. See Maxim application note 126 for
+/// more details.
+struct Timing;
+
+impl Timing {
+    const RESET_LOW: u64 = 480;
+    const PRESENCE_SAMPLE: u64 = 70;
+    const PRESENCE_RECOVERY: u64 = 410;
+    const WRITE_ONE_LOW: u64 = 6;
+    const WRITE_ONE_RECOVERY: u64 = 64;
+    const WRITE_ZERO_LOW: u64 = 60;
+    const WRITE_ZERO_RECOVERY: u64 = 10;
+    const READ_LOW: u64 = 6;
+    const READ_SAMPLE: u64 = 9;
+    const READ_RECOVERY: u64 = 55;
+}
+
+/// The 64-bit ROM identifying a device on the bus
+///
+/// The first byte is the family code of the device and the last byte is the
+/// CRC of the first seven.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rom(pub [u8; 8]);
+
+impl Rom {
+    /// Return the family code of the device.
+    pub fn family(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// Return whether the CRC of the ROM is valid.
+    pub fn is_valid(&self) -> bool {
+        crc8(&self.0[..7]) == self.0[7]
+    }
+}
+
+/// A 1-Wire bus on a GPIO
+pub struct OneWire<P> {
+    pin: P,
+}
+
+impl<P: InputPin + OutputPin> OneWire<P> {
+    /// Create a new bus on an open-drain pin.
+    pub fn new(mut pin: P) -> Self {
+        // Let the bus idle high
+        let _ = pin.set_high();
+        Self { pin }
+    }
+
+    /// Release the underlying pin
+    pub fn release(self) -> P {
+        self.pin
+    }
+
+    /// Reset the bus.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoPresence` if no device answered the reset.
+    pub async fn reset(&mut self) -> Result<(), Error<P::Error>> {
+        self.pin.set_low().map_err(Error::Pin)?;
+        Timer::after(Duration::from_micros(Timing::RESET_LOW)).await;
+        self.pin.set_high().map_err(Error::Pin)?;
+
+        block_for(Duration::from_micros(Timing::PRESENCE_SAMPLE));
+        let present = self.pin.is_low().map_err(Error::Pin)?;
+        Timer::after(Duration::from_micros(Timing::PRESENCE_RECOVERY)).await;
+
+        if present {
+            Ok(())
+        } else {
+            Err(Error::NoPresence)
+        }
+    }
+
+    /// Reset the bus and address a single device, or all of them if `rom` is
+    /// `None`.
+    pub async fn select(&mut self, rom: Option<&Rom>) -> Result<(), Error<P::Error>> {
+        self.reset().await?;
+        match rom {
+            Some(rom) => {
+                self.write_byte(MATCH_ROM)?;
+                self.write_bytes(&rom.0)
+            }
+            None => self.write_byte(SKIP_ROM),
+        }
+    }
+
+    /// Read the ROM of the only device on the bus.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Crc` if the ROM is corrupted, e.g. because several
+    /// devices answered.
+    pub async fn read_rom(&mut self) -> Result<Rom, Error<P::Error>> {
+        self.reset().await?;
+        self.write_byte(READ_ROM)?;
+        let mut rom = Rom([0; 8]);
+        self.read_bytes(&mut rom.0)?;
+        if rom.is_valid() {
+            Ok(rom)
+        } else {
+            Err(Error::Crc)
+        }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error<P::Error>> {
+        bytes.iter().try_for_each(|byte| self.write_byte(*byte))
+    }
+
+    /// Write a byte, least significant bit first.
+    pub fn write_byte(&mut self, byte: u8) -> Result<(), Error<P::Error>> {
+        (0..8).try_for_each(|i| self.write_bit(byte & (1 << i) != 0))
+    }
+
+    pub fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<(), Error<P::Error>> {
+        bytes
+            .iter_mut()
+            .try_for_each(|byte| self.read_byte().map(|value| *byte = value))
+    }
+
+    /// Read a byte, least significant bit first.
+    pub fn read_byte(&mut self) -> Result<u8, Error<P::Error>> {
+        let mut byte = 0;
+        for i in 0..8 {
+            if self.read_bit()? {
+                byte |= 1 << i;
+            }
+        }
+        Ok(byte)
+    }
+
+    pub fn write_bit(&mut self, bit: bool) -> Result<(), Error<P::Error>> {
+        let (low, recovery) = if bit {
+            (Timing::WRITE_ONE_LOW, Timing::WRITE_ONE_RECOVERY)
+        } else {
+            (Timing::WRITE_ZERO_LOW, Timing::WRITE_ZERO_RECOVERY)
+        };
+
+        self.pin.set_low().map_err(Error::Pin)?;
+        block_for(Duration::from_micros(low));
+        self.pin.set_high().map_err(Error::Pin)?;
+        block_for(Duration::from_micros(recovery));
+        Ok(())
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool, Error<P::Error>> {
+        self.pin.set_low().map_err(Error::Pin)?;
+        block_for(Duration::from_micros(Timing::READ_LOW));
+        self.pin.set_high().map_err(Error::Pin)?;
+
+        block_for(Duration::from_micros(Timing::READ_SAMPLE));
+        let bit = self.pin.is_high().map_err(Error::Pin)?;
+        block_for(Duration::from_micros(Timing::READ_RECOVERY));
+        Ok(bit)
+    }
+}
+
+/// The state of a search of the devices on a bus
+///
+/// Each call to [RomSearch::next] finds one more device, so that the devices
+/// can be enumerated without storing all their ROMs. See Maxim application
+/// note 187 for more details.
+#[derive(Debug, Default)]
+pub struct RomSearch {
+    rom: [u8; 8],
+    /// Bit position of the last branch where the 0 path was taken, or 0 if
+    /// there is none
+    last_discrepancy: u8,
+    done: bool,
+}
+
+impl RomSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Find the next device on the bus, or `None` if all devices were found.
+    pub async fn next<P: InputPin + OutputPin>(
+        &mut self,
+        bus: &mut OneWire<P>,
+    ) -> Result<Option<Rom>, Error<P::Error>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        match bus.reset().await {
+            Ok(()) => {}
+            // An empty bus has no devices to find
+            Err(Error::NoPresence) => {
+                self.done = true;
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        }
+        bus.write_byte(SEARCH_ROM)?;
+
+        let mut last_zero = 0;
+        for position in 1..=64u8 {
+            let byte = ((position - 1) / 8) as usize;
+            let mask = 1 << ((position - 1) % 8);
+
+            // Every device sends its bit, then the complement of its bit
+            let bit = bus.read_bit()?;
+            let complement = bus.read_bit()?;
+
+            let direction = match (bit, complement) {
+                // The devices left all disconnected
+                (true, true) => {
+                    self.done = true;
+                    return Ok(None);
+                }
+                // All remaining devices agree on this bit
+                (bit, complement) if bit != complement => bit,
+                // The devices disagree, so pick a branch
+                _ => {
+                    let direction = if position < self.last_discrepancy {
+                        self.rom[byte] & mask != 0
+                    } else {
+                        position == self.last_discrepancy
+                    };
+                    if !direction {
+                        last_zero = position;
+                    }
+                    direction
+                }
+            };
+
+            if direction {
+                self.rom[byte] |= mask;
+            } else {
+                self.rom[byte] &= !mask;
+            }
+            bus.write_bit(direction)?;
+        }
+
+        self.last_discrepancy = last_zero;
+        if last_zero == 0 {
+            self.done = true;
+        }
+
+        let rom = Rom(self.rom);
+        if rom.is_valid() {
+            Ok(Some(rom))
+        } else {
+            Err(Error::Crc)
+        }
+    }
+}
+
+/// Dallas/Maxim CRC-8 of some data, with polynomial 0x31 in its reflected
+/// form.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x01 != 0 {
+                (crc >> 1) ^ 0x8C
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// All possible errors on the bus
+///
+/// `E` is the error type of the underlying pin.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Pin error
+    Pin(E),
+    /// No device answered the reset pulse.
+    NoPresence,
+    /// The data read from the bus failed its CRC check.
+    Crc,
+}
@@ -0,0 +1,231 @@
+//! # pcf8574
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the NXP/TI PCF8574
+//! and PCF8574A 8-bit GPIO expanders over I2C, found on most cheap I2C
+//! backpacks and button boards.
+//!
+//! The pins of the expander are quasi-bidirectional: a pin written low is
+//! driven low, while a pin written high is weakly pulled up and can be read
+//! as an input.
+//!
+//! The expander asserts its INT output when an input changes. Once a GPIO is
+//! attached with [Pcf8574::with_interrupt_pin], [Pcf8574::wait_for_change]
+//! awaits the change on a GPIO interrupt instead of polling the bus.
+//!
+//! A [Keypad] scans a 4x4 matrix keypad wired to the expander, with the rows
+//! on P0-P3 and the columns on P4-P7.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut expander = Pcf8574::new(i2c, BASE_ADDRESS)
+//!     .with_interrupt_pin(Input::new(peripherals.GPIO7, Pull::Up));
+//!
+//! // Drive P0 low, keep the other pins as inputs
+//! expander.write(0xFE).await?;
+//!
+//! // Wait for an input to change
+//! let levels = expander.wait_for_change().await?;
+//!
+//! // Or read a matrix keypad
+//! let mut keypad = Keypad::new(expander).await?;
+//! let key = keypad.wait_for_key().await?;
+//! println!("Row {}, column {}", key.row, key.column);
+//! ```
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::{digital::Wait, i2c::I2c};
+
+/// I2C address of the PCF8574 when A0, A1 and A2 are tied to ground
+pub const BASE_ADDRESS: u8 = 0x20;
+
+/// I2C address of the PCF8574A when A0, A1 and A2 are tied to ground
+pub const BASE_ADDRESS_A: u8 = 0x38;
+
+/// Time for the contacts of a key to settle
+const DEBOUNCE_TIME: Duration = Duration::from_millis(20);
+
+/// A PCF8574 or PCF8574A on an I2C bus
+///
+/// `INT` is the pin the interrupt output of the expander is connected to, if
+/// any.
+pub struct Pcf8574<I2C, INT = ()> {
+    address: u8,
+    i2c: I2C,
+    interrupt: INT,
+    /// Last value written to the pins
+    outputs: u8,
+}
+
+impl<I2C: I2c> Pcf8574<I2C> {
+    /// Create a new expander. All pins are inputs on power-up.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the expander is on.
+    /// - `address`: The I2C address of the expander, [BASE_ADDRESS] or
+    ///   [BASE_ADDRESS_A] plus the value of its address pins.
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self {
+            address,
+            i2c,
+            interrupt: (),
+            outputs: 0xFF,
+        }
+    }
+
+    /// Attach the pin the interrupt output of the expander is connected to.
+    ///
+    /// The output is open-drain and active low, so the pin needs a pull-up.
+    pub fn with_interrupt_pin<P: Wait>(self, interrupt: P) -> Pcf8574<I2C, P> {
+        Pcf8574 {
+            address: self.address,
+            i2c: self.i2c,
+            interrupt,
+            outputs: self.outputs,
+        }
+    }
+}
+
+impl<I2C: I2c, P: Wait> Pcf8574<I2C, P> {
+    /// Wait for an input to change, then return the levels of the pins.
+    ///
+    /// Returns immediately if an input changed since the last read.
+    pub async fn wait_for_change(&mut self) -> Result<u8, Error<I2C::Error>> {
+        self.interrupt
+            .wait_for_low()
+            .await
+            .map_err(|_| Error::Pin)?;
+
+        // Reading the pins clears the interrupt
+        self.read().await
+    }
+
+    /// Release the underlying I2C bus and interrupt pin
+    pub fn release_with_interrupt_pin(self) -> (I2C, P) {
+        (self.i2c, self.interrupt)
+    }
+}
+
+impl<I2C: I2c, INT> Pcf8574<I2C, INT> {
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Read the levels of the pins.
+    pub async fn read(&mut self) -> Result<u8, Error<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.i2c
+            .read(self.address, &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+        Ok(buf[0])
+    }
+
+    /// Write the pins. Pins written high can be read as inputs.
+    pub async fn write(&mut self, value: u8) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(self.address, &[value])
+            .await
+            .map_err(Error::I2c)?;
+        self.outputs = value;
+        Ok(())
+    }
+
+    /// Read the level of a single pin.
+    pub async fn read_pin(&mut self, pin: u8) -> Result<bool, Error<I2C::Error>> {
+        Ok(self.read().await? & (1 << (pin & 0x07)) != 0)
+    }
+
+    /// Drive a single pin low, or release it high, keeping the other pins.
+    pub async fn set_pin(&mut self, pin: u8, high: bool) -> Result<(), Error<I2C::Error>> {
+        let mask = 1 << (pin & 0x07);
+        let outputs = if high {
+            self.outputs | mask
+        } else {
+            self.outputs & !mask
+        };
+        self.write(outputs).await
+    }
+}
+
+/// A key of a [Keypad]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Key {
+    pub row: u8,
+    pub column: u8,
+}
+
+/// A 4x4 matrix keypad on a [Pcf8574]
+///
+/// The rows are wired to P0-P3 and the columns to P4-P7. Between scans, all
+/// rows are driven low so that pressing any key pulls its column low and
+/// asserts the interrupt output.
+pub struct Keypad<I2C, INT = ()> {
+    expander: Pcf8574<I2C, INT>,
+}
+
+impl<I2C: I2c, INT> Keypad<I2C, INT> {
+    /// Create a new keypad on an expander.
+    pub async fn new(mut expander: Pcf8574<I2C, INT>) -> Result<Self, Error<I2C::Error>> {
+        expander.write(0xF0).await?;
+        Ok(Self { expander })
+    }
+
+    /// Release the underlying expander
+    pub fn release(self) -> Pcf8574<I2C, INT> {
+        self.expander
+    }
+
+    /// Return the first key found pressed, if any.
+    pub async fn scan(&mut self) -> Result<Option<Key>, Error<I2C::Error>> {
+        let mut pressed = None;
+        for row in 0..4 {
+            // Drive a single row low; the columns of its pressed keys follow
+            self.expander.write(!(1 << row)).await?;
+            let columns = !self.expander.read().await? >> 4;
+            if columns != 0 {
+                pressed = Some(Key {
+                    row,
+                    column: columns.trailing_zeros() as u8,
+                });
+                break;
+            }
+        }
+
+        self.expander.write(0xF0).await?;
+        Ok(pressed)
+    }
+}
+
+impl<I2C: I2c, P: Wait> Keypad<I2C, P> {
+    /// Wait for a key to be pressed and return it.
+    pub async fn wait_for_key(&mut self) -> Result<Key, Error<I2C::Error>> {
+        loop {
+            self.expander.wait_for_change().await?;
+            Timer::after(DEBOUNCE_TIME).await;
+            if let Some(key) = self.scan().await? {
+                // Scanning changes the inputs, so clear the interrupt it
+                // caused
+                self.expander.read().await?;
+                return Ok(key);
+            }
+        }
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The interrupt pin could not be read.
+    Pin,
+}
@@ -0,0 +1,582 @@
+//! # pn532
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the NXP PN532 NFC
+//! controller over I2C, to detect ISO/IEC 14443 A tags and read the NDEF
+//! message of NFC Forum Type 2 tags, such as the NTAG21x and the MIFARE
+//! Ultralight.
+//!
+//! - The controller pulls its IRQ pin low when an answer is ready, so the
+//!   driver waits for the pin instead of polling the bus.
+//! - While waiting for a tag, the controller keeps looking for one by itself,
+//!   so [Pn532::wait_for_tag] costs nothing until a tag enters the field.
+//! - The NDEF message is found among the TLVs of the tag, and its records can
+//!   be walked with [records].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut pn532 = Pn532::new(i2c, irq).await?;
+//!
+//! loop {
+//!     let tag = pn532.wait_for_tag().await?;
+//!     println!("Tag {:02X?}", tag.uid());
+//!
+//!     let mut message = [0u8; 256];
+//!     let len = pn532.read_ndef(&mut message).await?;
+//!     for record in records(&message[..len]) {
+//!         println!("{:?}", record.payload);
+//!     }
+//! }
+//! ```
+
+use embassy_time::{with_timeout, Duration};
+use embedded_hal_async::{digital::Wait, i2c::I2c};
+
+/// I2C address of the controller
+pub const ADDRESS: u8 = 0x24;
+
+/// IC of the firmware version of the PN532
+const IC_PN532: u8 = 0x32;
+
+/// Maximum time taken by the controller to answer a command
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Largest data of a frame handled by the driver
+const MAX_DATA_LEN: usize = 64;
+
+/// Commands of the controller. See user manual section 7 for more details.
+struct Command;
+
+impl Command {
+    const GET_FIRMWARE_VERSION: u8 = 0x02;
+    const SAM_CONFIGURATION: u8 = 0x14;
+    const RF_CONFIGURATION: u8 = 0x32;
+    const IN_DATA_EXCHANGE: u8 = 0x40;
+    const IN_LIST_PASSIVE_TARGET: u8 = 0x4A;
+    const IN_RELEASE: u8 = 0x52;
+}
+
+/// Frame identifiers of the data sent to and received from the controller
+const TFI_HOST_TO_PN532: u8 = 0xD4;
+const TFI_PN532_TO_HOST: u8 = 0xD5;
+
+/// Start of every frame, after its preamble
+const START_CODE: [u8; 3] = [0x00, 0x00, 0xFF];
+
+/// Acknowledge frame, also aborting the current command when sent
+const ACK_FRAME: [u8; 6] = [0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00];
+
+/// Flag of the status byte preceding every read when the answer is ready
+const STATUS_READY: u8 = 0x01;
+
+/// SAM configuration of the normal mode, with the IRQ pin in use
+const SAM_NORMAL_MODE: u8 = 0x01;
+const SAM_TIMEOUT: u8 = 0x14;
+const SAM_USE_IRQ: u8 = 0x01;
+
+/// RF configuration item of the retries, retrying the activation of a passive
+/// target forever
+const RF_MAX_RETRIES: u8 = 0x05;
+const RF_RETRIES: [u8; 3] = [0xFF, 0x01, 0xFF];
+
+/// Baud rate and modulation of ISO/IEC 14443 A targets
+const BRTY_106_KBPS_TYPE_A: u8 = 0x00;
+
+/// Commands of Type 2 tags
+const TAG_READ: u8 = 0x30;
+
+/// Bytes read by a Type 2 tag read, 4 pages of 4 bytes
+const TAG_READ_SIZE: usize = 16;
+
+/// Page of the capability container of Type 2 tags, followed by the data area
+const CC_PAGE: u8 = 3;
+
+/// Magic number of the capability container of an NDEF tag
+const CC_NDEF_MAGIC: u8 = 0xE1;
+
+/// TLV types of the data area of a tag
+const TLV_NULL: u8 = 0x00;
+const TLV_NDEF_MESSAGE: u8 = 0x03;
+const TLV_TERMINATOR: u8 = 0xFE;
+
+/// Type name formats of NDEF records
+pub const TNF_EMPTY: u8 = 0x00;
+pub const TNF_WELL_KNOWN: u8 = 0x01;
+pub const TNF_MEDIA: u8 = 0x02;
+pub const TNF_URI: u8 = 0x03;
+pub const TNF_EXTERNAL: u8 = 0x04;
+
+/// Flags of the header of an NDEF record
+const RECORD_MESSAGE_END: u8 = 1 << 6;
+const RECORD_SHORT: u8 = 1 << 4;
+const RECORD_ID_LENGTH: u8 = 1 << 3;
+const RECORD_TNF_MASK: u8 = 0x07;
+
+/// Firmware version of the controller
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FirmwareVersion {
+    pub ic: u8,
+    pub version: u8,
+    pub revision: u8,
+    /// Supported protocols, as flags
+    pub support: u8,
+}
+
+/// An ISO/IEC 14443 A tag in the field
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Tag {
+    /// Number given to the tag by the controller
+    number: u8,
+    /// ATQA of the tag
+    pub sens_res: u16,
+    /// SAK of the tag
+    pub sel_res: u8,
+    uid: [u8; 10],
+    uid_len: usize,
+}
+
+impl Tag {
+    /// Get the 4, 7 or 10 bytes of the UID
+    pub fn uid(&self) -> &[u8] {
+        &self.uid[..self.uid_len]
+    }
+}
+
+/// A record of an NDEF message
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Record<'a> {
+    /// Type name format, e.g. [TNF_WELL_KNOWN]
+    pub tnf: u8,
+    /// Type, e.g. `b"T"` for text or `b"U"` for a URI
+    pub record_type: &'a [u8],
+    pub id: &'a [u8],
+    pub payload: &'a [u8],
+}
+
+/// Iterator over the records of an NDEF message
+pub struct Records<'a> {
+    message: &'a [u8],
+    done: bool,
+}
+
+/// Walk the records of an NDEF message. The walk stops at the last record,
+/// or at the first malformed one.
+pub fn records(message: &[u8]) -> Records<'_> {
+    Records {
+        message,
+        done: false,
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Record<'a>> {
+        if self.done {
+            return None;
+        }
+        self.done = true;
+
+        let message = self.message;
+        let header = *message.first()?;
+        let type_len = *message.get(1)? as usize;
+        let (payload_len, mut i) = if header & RECORD_SHORT != 0 {
+            (*message.get(2)? as usize, 3)
+        } else {
+            let len = message.get(2..6)?;
+            (
+                u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize,
+                6,
+            )
+        };
+        let id_len = if header & RECORD_ID_LENGTH != 0 {
+            i += 1;
+            *message.get(i - 1)? as usize
+        } else {
+            0
+        };
+
+        let record_type = message.get(i..i + type_len)?;
+        i += type_len;
+        let id = message.get(i..i + id_len)?;
+        i += id_len;
+        let payload = message.get(i..i.checked_add(payload_len)?)?;
+        i += payload_len;
+
+        self.message = &message[i..];
+        self.done = header & RECORD_MESSAGE_END != 0;
+        Some(Record {
+            tnf: header & RECORD_TNF_MASK,
+            record_type,
+            id,
+            payload,
+        })
+    }
+}
+
+/// A PN532 on an I2C bus
+///
+/// `IRQ` is the pin the IRQ output of the controller is connected to.
+pub struct Pn532<I2C, IRQ> {
+    i2c: I2C,
+    irq: IRQ,
+    /// Number of the last tag found, if any
+    tag: Option<u8>,
+}
+
+impl<I2C: I2c, IRQ: Wait> Pn532<I2C, IRQ> {
+    /// Create a new controller and configure it to look for tags.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the controller is on.
+    /// - `irq`: The pin the IRQ output of the controller is connected to.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidFirmware` if the device does not identify as a
+    /// PN532.
+    pub async fn new(i2c: I2C, irq: IRQ) -> Result<Self, Error<I2C::Error>> {
+        let mut pn532 = Self {
+            i2c,
+            irq,
+            tag: None,
+        };
+
+        pn532
+            .command(
+                Command::SAM_CONFIGURATION,
+                &[SAM_NORMAL_MODE, SAM_TIMEOUT, SAM_USE_IRQ],
+                &mut [],
+            )
+            .await?;
+
+        let firmware = pn532.firmware_version().await?;
+        if firmware.ic != IC_PN532 {
+            return Err(Error::InvalidFirmware(firmware.ic));
+        }
+
+        let [atr, psl, activation] = RF_RETRIES;
+        pn532
+            .command(
+                Command::RF_CONFIGURATION,
+                &[RF_MAX_RETRIES, atr, psl, activation],
+                &mut [],
+            )
+            .await?;
+        Ok(pn532)
+    }
+
+    /// Release the underlying I2C bus and IRQ pin
+    pub fn release(self) -> (I2C, IRQ) {
+        (self.i2c, self.irq)
+    }
+
+    pub async fn firmware_version(&mut self) -> Result<FirmwareVersion, Error<I2C::Error>> {
+        let mut buf = [0u8; 4];
+        self.command(Command::GET_FIRMWARE_VERSION, &[], &mut buf)
+            .await?;
+        Ok(FirmwareVersion {
+            ic: buf[0],
+            version: buf[1],
+            revision: buf[2],
+            support: buf[3],
+        })
+    }
+
+    /// Wait for a tag to enter the field and return it.
+    ///
+    /// The controller keeps looking for a tag until one is found, so if this
+    /// future is dropped before, [Pn532::abort] must be called before the
+    /// next command.
+    pub async fn wait_for_tag(&mut self) -> Result<Tag, Error<I2C::Error>> {
+        self.tag = None;
+        self.send_command(Command::IN_LIST_PASSIVE_TARGET, &[1, BRTY_106_KBPS_TYPE_A])
+            .await?;
+
+        self.irq.wait_for_low().await.map_err(|_| Error::Pin)?;
+        let mut buf = [0u8; MAX_DATA_LEN];
+        let len = self
+            .read_response(Command::IN_LIST_PASSIVE_TARGET, &mut buf)
+            .await?;
+
+        // Number of targets, then the target found
+        let data = &buf[..len];
+        if data.len() < 6 || data[0] == 0 {
+            return Err(Error::Frame);
+        }
+        let uid_len = data[5] as usize;
+        if uid_len > 10 || data.len() < 6 + uid_len {
+            return Err(Error::Frame);
+        }
+
+        let mut tag = Tag {
+            number: data[1],
+            sens_res: u16::from_be_bytes([data[2], data[3]]),
+            sel_res: data[4],
+            uid: [0; 10],
+            uid_len,
+        };
+        tag.uid[..uid_len].copy_from_slice(&data[6..6 + uid_len]);
+        self.tag = Some(tag.number);
+        Ok(tag)
+    }
+
+    /// Abort the current command, e.g. after dropping
+    /// [Pn532::wait_for_tag].
+    pub async fn abort(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(ADDRESS, &ACK_FRAME)
+            .await
+            .map_err(Error::I2c)
+    }
+
+    /// Release the tags found, so that they are found again.
+    pub async fn release_tags(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.tag = None;
+        self.command(Command::IN_RELEASE, &[0], &mut [0u8])
+            .await
+            .map(|_| ())
+    }
+
+    /// Read the NDEF message of the last tag found, a Type 2 tag, and return
+    /// its length.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NoTag` if no tag was found, `Error::NoNdef` if the tag
+    /// holds no NDEF message, and `Error::BufferTooSmall` if the message does
+    /// not fit in the buffer.
+    pub async fn read_ndef(&mut self, buf: &mut [u8]) -> Result<usize, Error<I2C::Error>> {
+        let tag = self.tag.ok_or(Error::NoTag)?;
+
+        // The capability container gives the size of the data area
+        let mut pages = [0u8; TAG_READ_SIZE];
+        self.read_pages(tag, CC_PAGE, &mut pages).await?;
+        if pages[0] != CC_NDEF_MAGIC {
+            return Err(Error::NoNdef);
+        }
+        let size = pages[2] as usize * 8;
+
+        // The data area starts after the capability container, with the
+        // pages just read cached
+        let mut reader = DataReader {
+            tag,
+            cache: [0u8; TAG_READ_SIZE],
+            cached: None,
+        };
+        reader.cache[..TAG_READ_SIZE - 4].copy_from_slice(&pages[4..]);
+
+        let mut offset = 0;
+        while offset < size {
+            let tlv = reader.byte(self, offset).await?;
+            match tlv {
+                TLV_NULL => {
+                    offset += 1;
+                    continue;
+                }
+                TLV_TERMINATOR => break,
+                _ => {}
+            }
+
+            let (len, header) = match reader.byte(self, offset + 1).await? {
+                0xFF => {
+                    let msb = reader.byte(self, offset + 2).await?;
+                    let lsb = reader.byte(self, offset + 3).await?;
+                    (u16::from_be_bytes([msb, lsb]) as usize, 4)
+                }
+                len => (len as usize, 2),
+            };
+
+            if tlv == TLV_NDEF_MESSAGE {
+                let message = buf.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+                for (i, byte) in message.iter_mut().enumerate() {
+                    *byte = reader.byte(self, offset + header + i).await?;
+                }
+                return Ok(len);
+            }
+            offset += header + len;
+        }
+        Err(Error::NoNdef)
+    }
+
+    /// Read 4 pages of a Type 2 tag.
+    async fn read_pages(
+        &mut self,
+        tag: u8,
+        page: u8,
+        buf: &mut [u8; TAG_READ_SIZE],
+    ) -> Result<(), Error<I2C::Error>> {
+        let mut response = [0u8; TAG_READ_SIZE + 1];
+        let len = self
+            .command(
+                Command::IN_DATA_EXCHANGE,
+                &[tag, TAG_READ, page],
+                &mut response,
+            )
+            .await?;
+        let status = response[0] & 0x3F;
+        if status != 0 {
+            return Err(Error::Tag(status));
+        }
+        if len != response.len() {
+            return Err(Error::Frame);
+        }
+        buf.copy_from_slice(&response[1..]);
+        Ok(())
+    }
+
+    /// Send a command and read its answer into a buffer, returning its
+    /// length.
+    async fn command(
+        &mut self,
+        command: u8,
+        params: &[u8],
+        buf: &mut [u8],
+    ) -> Result<usize, Error<I2C::Error>> {
+        self.send_command(command, params).await?;
+
+        let answer = async {
+            self.irq.wait_for_low().await.map_err(|_| Error::Pin)?;
+            self.read_response(command, buf).await
+        };
+        with_timeout(COMMAND_TIMEOUT, answer)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    /// Send a command and wait for the controller to acknowledge it.
+    async fn send_command(&mut self, command: u8, params: &[u8]) -> Result<(), Error<I2C::Error>> {
+        let len = params.len() + 2;
+        let mut frame = [0u8; MAX_DATA_LEN + 8];
+        frame[1..4].copy_from_slice(&START_CODE);
+        frame[4] = len as u8;
+        frame[5] = (len as u8).wrapping_neg();
+        frame[6] = TFI_HOST_TO_PN532;
+        frame[7] = command;
+        frame[8..8 + params.len()].copy_from_slice(params);
+        let sum = frame[6..6 + len]
+            .iter()
+            .fold(0u8, |sum, b| sum.wrapping_add(*b));
+        frame[6 + len] = sum.wrapping_neg();
+        self.i2c
+            .write(ADDRESS, &frame[..len + 8])
+            .await
+            .map_err(Error::I2c)?;
+
+        let ack = async {
+            self.irq.wait_for_low().await.map_err(|_| Error::Pin)?;
+            let mut buf = [0u8; 7];
+            self.i2c.read(ADDRESS, &mut buf).await.map_err(Error::I2c)?;
+            if buf[0] & STATUS_READY == 0 || buf[1..] != ACK_FRAME {
+                return Err(Error::Frame);
+            }
+            Ok(())
+        };
+        with_timeout(COMMAND_TIMEOUT, ack)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    /// Read the answer to a command into a buffer, and return its length.
+    async fn read_response(
+        &mut self,
+        command: u8,
+        buf: &mut [u8],
+    ) -> Result<usize, Error<I2C::Error>> {
+        // Status, start code, length, TFI and command, data, checksum
+        let mut frame = [0u8; MAX_DATA_LEN + 10];
+        self.i2c
+            .read(ADDRESS, &mut frame)
+            .await
+            .map_err(Error::I2c)?;
+        if frame[0] & STATUS_READY == 0 || frame[1..4] != START_CODE {
+            return Err(Error::Frame);
+        }
+
+        let len = frame[4] as usize;
+        if frame[4].wrapping_add(frame[5]) != 0 || !(2..=MAX_DATA_LEN + 2).contains(&len) {
+            return Err(Error::Frame);
+        }
+        let body = &frame[6..6 + len];
+        let sum = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        if sum.wrapping_add(frame[6 + len]) != 0 {
+            return Err(Error::Checksum);
+        }
+        if body[0] != TFI_PN532_TO_HOST || body[1] != command + 1 {
+            return Err(Error::Frame);
+        }
+
+        let data = &body[2..];
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+}
+
+/// Reader of the data area of a Type 2 tag, caching the last pages read
+struct DataReader {
+    tag: u8,
+    cache: [u8; TAG_READ_SIZE],
+    /// Offset of the cache in the data area, the first 12 bytes being cached
+    /// from the read of the capability container
+    cached: Option<usize>,
+}
+
+impl DataReader {
+    /// Read a byte of the data area.
+    async fn byte<I2C: I2c, IRQ: Wait>(
+        &mut self,
+        pn532: &mut Pn532<I2C, IRQ>,
+        offset: usize,
+    ) -> Result<u8, Error<I2C::Error>> {
+        if self.cached.is_none() && offset < TAG_READ_SIZE - 4 {
+            return Ok(self.cache[offset]);
+        }
+
+        let start = offset - offset % TAG_READ_SIZE;
+        if self.cached != Some(start) {
+            // Pages are 4 bytes, and the data area starts after the
+            // capability container
+            let page = CC_PAGE as usize + 1 + start / 4;
+            let page = u8::try_from(page).map_err(|_| Error::NoNdef)?;
+            pn532.read_pages(self.tag, page, &mut self.cache).await?;
+            self.cached = Some(start);
+        }
+        Ok(self.cache[offset - start])
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// Error of the IRQ pin
+    Pin,
+    /// The device does not identify as a PN532.
+    InvalidFirmware(u8),
+    /// The controller did not answer in time.
+    Timeout,
+    /// The frame of the controller is malformed or unexpected.
+    Frame,
+    /// The checksum of the frame of the controller is wrong.
+    Checksum,
+    /// The exchange with the tag failed, with the error code of the
+    /// controller.
+    Tag(u8),
+    /// No tag was found.
+    NoTag,
+    /// The tag holds no NDEF message.
+    NoNdef,
+    /// The NDEF message does not fit in the buffer.
+    BufferTooSmall,
+}
@@ -0,0 +1,481 @@
+//! # qmc5883l
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the QST QMC5883L
+//! 3-axis magnetometer over I2C, found on most cheap "HMC5883L" compass
+//! modules.
+//!
+//! - The sensor measures continuously, and the driver waits for each new
+//!   measurement.
+//! - Nearby magnets and iron distort the field. A [Calibrator] collects
+//!   measurements while the sensor is rotated in every direction, and
+//!   derives the hard iron offsets and soft iron scales that undo the
+//!   distortion.
+//! - [azimuth] computes a heading with integer math only, to a tenth of a
+//!   degree.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut compass = Qmc5883l::new(i2c, Config::new()).await?;
+//!
+//! // Rotate the sensor in every direction for a few seconds
+//! let mut calibrator = Calibrator::new();
+//! for _ in 0..500 {
+//!     calibrator.add(compass.read_raw().await?);
+//! }
+//! compass.set_calibration(calibrator.calibration());
+//!
+//! // Heading in tenths of a degree, with a declination of -14.2°
+//! compass.set_declination(-142);
+//! println!("{}°", compass.heading().await? as f32 / 10.0);
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+/// I2C address of the sensor
+pub const ADDRESS: u8 = 0x0D;
+
+/// Value of the chip ID register
+const CHIP_ID: u8 = 0xFF;
+
+/// Recommended value of the set/reset period register
+const SET_RESET_PERIOD: u8 = 0x01;
+
+/// Scale of the soft iron factors, which are fixed point with 10 fractional
+/// bits
+const SCALE_ONE: i32 = 1 << 10;
+
+/// Time between two polls of the status flags
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Maximum time to wait for a measurement, the period of the slowest data
+/// rate with some margin
+const MEASUREMENT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Registers of the sensor. See datasheet section 9.2 for more details.
+struct Register;
+
+impl Register {
+    const DATA_X_LSB: u8 = 0x00;
+    const STATUS: u8 = 0x06;
+    const CONTROL_1: u8 = 0x09;
+    const CONTROL_2: u8 = 0x0A;
+    const SET_RESET_PERIOD: u8 = 0x0B;
+    const CHIP_ID: u8 = 0x0D;
+}
+
+/// Flags of the status register
+const STATUS_DATA_READY: u8 = 1 << 0;
+const STATUS_OVERFLOW: u8 = 1 << 1;
+
+/// Flag of the control 2 register
+const CONTROL_SOFT_RESET: u8 = 1 << 7;
+
+/// Continuous measurement mode of the control 1 register
+const MODE_CONTINUOUS: u8 = 0b01;
+
+/// Configuration of the sensor
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    range: Range,
+    data_rate: DataRate,
+    oversampling: Oversampling,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_range(mut self, range: Range) -> Self {
+        self.range = range;
+        self
+    }
+
+    pub fn with_data_rate(mut self, data_rate: DataRate) -> Self {
+        self.data_rate = data_rate;
+        self
+    }
+
+    pub fn with_oversampling(mut self, oversampling: Oversampling) -> Self {
+        self.oversampling = oversampling;
+        self
+    }
+
+    /// Value of the control 1 register, measuring continuously
+    fn bits(&self) -> u8 {
+        (self.oversampling.bits() << 6)
+            | (self.range.bits() << 4)
+            | (self.data_rate.bits() << 2)
+            | MODE_CONTINUOUS
+    }
+}
+
+/// Full-scale range of the sensor
+///
+/// Defaults to `Gauss2`, enough for the field of the Earth.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Range {
+    #[default]
+    Gauss2 = 0b00,
+    Gauss8 = 0b01,
+}
+
+impl Range {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    fn lsb_per_gauss(&self) -> i32 {
+        match self {
+            Range::Gauss2 => 12_000,
+            Range::Gauss8 => 3_000,
+        }
+    }
+}
+
+/// Rate of the continuous measurements
+///
+/// Defaults to `Hz50`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DataRate {
+    Hz10 = 0b00,
+    #[default]
+    Hz50 = 0b01,
+    Hz100 = 0b10,
+    Hz200 = 0b11,
+}
+
+impl DataRate {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Over sampling ratio of the internal filter, higher ratios reducing noise
+/// at the cost of power
+///
+/// Defaults to `X512`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Oversampling {
+    #[default]
+    X512 = 0b00,
+    X256 = 0b01,
+    X128 = 0b10,
+    X64 = 0b11,
+}
+
+impl Oversampling {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// A magnetic field in mG
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MagneticField {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// Correction of the hard and soft iron distortions
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Calibration {
+    /// Hard iron offset of each axis, in raw counts
+    pub offset: [i16; 3],
+    /// Soft iron scale of each axis, in 1/1024
+    pub scale: [u16; 3],
+}
+
+impl Default for Calibration {
+    /// No correction
+    fn default() -> Self {
+        Self {
+            offset: [0; 3],
+            scale: [SCALE_ONE as u16; 3],
+        }
+    }
+}
+
+impl Calibration {
+    /// Correct a raw measurement.
+    pub fn apply(&self, raw: [i16; 3]) -> [i32; 3] {
+        let mut corrected = [0; 3];
+        for (axis, value) in corrected.iter_mut().enumerate() {
+            let centered = raw[axis] as i32 - self.offset[axis] as i32;
+            *value = centered * self.scale[axis] as i32 / SCALE_ONE;
+        }
+        corrected
+    }
+}
+
+/// Derives a [Calibration] from measurements taken in every direction
+///
+/// Without distortions, the measurements lie on a sphere centered on zero.
+/// The hard iron offsets move its center, and the soft iron distortions
+/// stretch it into an ellipsoid, corrected here along the axes only.
+#[derive(Debug, Copy, Clone)]
+pub struct Calibrator {
+    min: [i16; 3],
+    max: [i16; 3],
+}
+
+impl Default for Calibrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calibrator {
+    pub fn new() -> Self {
+        Self {
+            min: [i16::MAX; 3],
+            max: [i16::MIN; 3],
+        }
+    }
+
+    /// Add a raw measurement.
+    pub fn add(&mut self, raw: [i16; 3]) {
+        for (axis, value) in raw.iter().enumerate() {
+            self.min[axis] = self.min[axis].min(*value);
+            self.max[axis] = self.max[axis].max(*value);
+        }
+    }
+
+    /// Return the calibration fitting the measurements added so far.
+    pub fn calibration(&self) -> Calibration {
+        let mut calibration = Calibration::default();
+        if self.min[0] > self.max[0] {
+            // No measurement
+            return calibration;
+        }
+
+        let mut spans = [0i32; 3];
+        for (axis, span) in spans.iter_mut().enumerate() {
+            let (min, max) = (self.min[axis] as i32, self.max[axis] as i32);
+            calibration.offset[axis] = ((min + max) / 2) as i16;
+            *span = (max - min).max(1);
+        }
+
+        // Scale every axis to the average span
+        let average = spans.iter().sum::<i32>() / 3;
+        for (scale, span) in calibration.scale.iter_mut().zip(spans) {
+            *scale = (average * SCALE_ONE / span).min(u16::MAX as i32) as u16;
+        }
+        calibration
+    }
+}
+
+/// A QMC5883L on an I2C bus
+pub struct Qmc5883l<I2C> {
+    i2c: I2C,
+    config: Config,
+    calibration: Calibration,
+    /// Declination in tenths of a degree
+    declination: i16,
+}
+
+impl<I2C: I2c> Qmc5883l<I2C> {
+    /// Create a new sensor, reset it and start measuring continuously.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidChipId` if the device does not identify as a
+    /// QMC5883L.
+    pub async fn new(i2c: I2C, config: Config) -> Result<Self, Error<I2C::Error>> {
+        let mut qmc5883l = Self {
+            i2c,
+            config,
+            calibration: Calibration::default(),
+            declination: 0,
+        };
+
+        let id = qmc5883l.read_register(Register::CHIP_ID).await?;
+        if id != CHIP_ID {
+            return Err(Error::InvalidChipId(id));
+        }
+
+        qmc5883l
+            .write_register(Register::CONTROL_2, CONTROL_SOFT_RESET)
+            .await?;
+        qmc5883l
+            .write_register(Register::SET_RESET_PERIOD, SET_RESET_PERIOD)
+            .await?;
+        qmc5883l.set_config(config).await?;
+        Ok(qmc5883l)
+    }
+
+    /// Get the current configuration of the sensor
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
+        self.config = config;
+        self.write_register(Register::CONTROL_1, config.bits())
+            .await
+    }
+
+    pub fn calibration(&self) -> &Calibration {
+        &self.calibration
+    }
+
+    /// Set the correction applied to the measurements, e.g. from a
+    /// [Calibrator] or stored from a previous calibration.
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    /// Set the magnetic declination at the location of the sensor, in tenths
+    /// of a degree, positive to the east. It turns headings relative to the
+    /// magnetic north into headings relative to the true north.
+    pub fn set_declination(&mut self, declination: i16) {
+        self.declination = declination;
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Wait for a new measurement and return it uncorrected, in counts.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Overflow` if the field exceeds the range, and
+    /// `Error::Timeout` if no measurement is ready in time.
+    pub async fn read_raw(&mut self) -> Result<[i16; 3], Error<I2C::Error>> {
+        let poll = async {
+            loop {
+                let status = self.read_register(Register::STATUS).await?;
+                if status & STATUS_OVERFLOW != 0 {
+                    return Err(Error::Overflow);
+                }
+                if status & STATUS_DATA_READY != 0 {
+                    return Ok(());
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        };
+        with_timeout(MEASUREMENT_TIMEOUT, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))?;
+
+        let mut buf = [0u8; 6];
+        self.i2c
+            .write_read(ADDRESS, &[Register::DATA_X_LSB], &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+        Ok([
+            i16::from_le_bytes([buf[0], buf[1]]),
+            i16::from_le_bytes([buf[2], buf[3]]),
+            i16::from_le_bytes([buf[4], buf[5]]),
+        ])
+    }
+
+    /// Wait for a new measurement and return it corrected.
+    pub async fn read(&mut self) -> Result<MagneticField, Error<I2C::Error>> {
+        let raw = self.read_raw().await?;
+        let [x, y, z] = self.calibration.apply(raw);
+        let lsb_per_mg = self.config.range.lsb_per_gauss() / 1000;
+        Ok(MagneticField {
+            x: x / lsb_per_mg,
+            y: y / lsb_per_mg,
+            z: z / lsb_per_mg,
+        })
+    }
+
+    /// Wait for a new measurement and return the heading of the X axis of
+    /// the sensor, in tenths of a degree clockwise from the north.
+    ///
+    /// The sensor must be level, with its Z axis pointing up.
+    pub async fn heading(&mut self) -> Result<u16, Error<I2C::Error>> {
+        let raw = self.read_raw().await?;
+        let [x, y, _] = self.calibration.apply(raw);
+        let heading = azimuth(x, y) as i32 + self.declination as i32;
+        Ok(heading.rem_euclid(3600) as u16)
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.i2c
+            .write_read(ADDRESS, &[register], &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+        Ok(buf[0])
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(ADDRESS, &[register, value])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+/// Compute the azimuth of a horizontal field, in tenths of a degree
+/// clockwise from the X axis towards the Y axis, from 0 to 3599.
+///
+/// The sensor axes are left-handed when seen from above, so this is the
+/// heading of the X axis of a level sensor relative to the magnetic north.
+/// Only integer math is used, with an error below 0.1°.
+pub fn azimuth(x: i32, y: i32) -> u16 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+
+    // Reduce to the first octant, then unfold
+    let (ax, ay) = (x.unsigned_abs() as i64, y.unsigned_abs() as i64);
+    let angle = if ax >= ay {
+        atan_decidegrees(ay, ax)
+    } else {
+        900 - atan_decidegrees(ax, ay)
+    };
+    let angle = match (x >= 0, y >= 0) {
+        (true, true) => angle,
+        (false, true) => 1800 - angle,
+        (false, false) => 1800 + angle,
+        (true, false) => 3600 - angle,
+    };
+    (angle % 3600) as u16
+}
+
+/// Arc tangent of `num / den` in tenths of a degree, for `0 <= num <= den`
+///
+/// Uses `atan(r) ≈ 45r + r(1 - r)(14.02 + 3.80r)` in degrees, with `r` in
+/// fixed point with 15 fractional bits.
+fn atan_decidegrees(num: i64, den: i64) -> i64 {
+    const ONE: i64 = 1 << 15;
+    let r = num * ONE / den;
+    let correction = r * (ONE - r) / ONE * (1402 * ONE + 380 * r) / 10 / ONE;
+    (450 * r + correction + ONE / 2) / ONE
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The device does not identify as a QMC5883L.
+    InvalidChipId(u8),
+    /// The field exceeds the range of the sensor.
+    Overflow,
+    /// No measurement was ready in time.
+    Timeout,
+}
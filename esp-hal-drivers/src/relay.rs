@@ -0,0 +1,275 @@
+//! # relay
+//!
+//! ## Overview
+//!
+//! This driver switches loads through relays and solid-state relays (SSR)
+//! on a GPIO, such as heaters, pumps or compressors.
+//!
+//! - Minimum on and off times protect loads such as compressors from short
+//!   cycling. Switching too early waits until the minimum time has elapsed.
+//! - Relays sharing an [Interlock] are never on at the same time, e.g. the
+//!   heater and the compressor of a thermostat, or the two directions of a
+//!   motor.
+//! - Timed pulses can be scheduled asynchronously, e.g. for a valve or a
+//!   door strike.
+//! - [Relay::cycle] drives a SSR with time-proportional control, turning it
+//!   on for a percentage of a slow period. Zero-crossing SSRs on mains
+//!   switch on whole half-cycles, so periods should last at least a few
+//!   hundred ms.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! static HVAC: Interlock<CriticalSectionRawMutex> = Interlock::new();
+//!
+//! let mut heater = Relay::new(Output::new(peripherals.GPIO4, Level::Low))
+//!     .with_interlock(&HVAC);
+//! let mut compressor = Relay::new(Output::new(peripherals.GPIO5, Level::Low))
+//!     .with_min_on_time(Duration::from_secs(180))
+//!     .with_min_off_time(Duration::from_secs(300))
+//!     .with_interlock(&HVAC);
+//!
+//! compressor.turn_on().await?;
+//! assert_eq!(heater.turn_on().await, Err(Error::Interlocked));
+//! compressor.turn_off().await?;
+//! heater.pulse(Duration::from_secs(10)).await?;
+//! ```
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::{
+    raw::{NoopRawMutex, RawMutex},
+    Mutex,
+};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::{OutputPin, PinState};
+
+/// Group of relays that are never on at the same time
+///
+/// `M` is the mutex protecting the group, e.g. `CriticalSectionRawMutex`
+/// if the relays are used from different executors.
+pub struct Interlock<M: RawMutex> {
+    engaged: Mutex<M, Cell<bool>>,
+}
+
+impl<M: RawMutex> Default for Interlock<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: RawMutex> Interlock<M> {
+    /// Create a new group where all relays are off
+    pub const fn new() -> Self {
+        Self {
+            engaged: Mutex::new(Cell::new(false)),
+        }
+    }
+
+    /// Whether a relay of the group is on
+    pub fn is_engaged(&self) -> bool {
+        self.engaged.lock(Cell::get)
+    }
+
+    /// Engage the group, unless a relay is already on.
+    fn engage(&self) -> bool {
+        self.engaged.lock(|engaged| !engaged.replace(true))
+    }
+
+    fn disengage(&self) {
+        self.engaged.lock(|engaged| engaged.set(false));
+    }
+}
+
+/// A relay on a GPIO
+///
+/// `M` is the mutex of the [Interlock] of the relay, if any.
+pub struct Relay<'a, PIN, M: RawMutex = NoopRawMutex> {
+    pin: PIN,
+    active_low: bool,
+    on: bool,
+    /// Time of the last switch, none until the relay switched once
+    switched_at: Option<Instant>,
+    min_on_time: Duration,
+    min_off_time: Duration,
+    interlock: Option<&'a Interlock<M>>,
+}
+
+impl<PIN: OutputPin> Relay<'_, PIN> {
+    /// Create a new relay and turn it off.
+    ///
+    /// # Arguments
+    ///
+    /// - `pin`: The pin driving the coil of the relay or the input of the
+    ///   SSR, active high.
+    pub fn new(pin: PIN) -> Self {
+        let mut relay = Self {
+            pin,
+            active_low: false,
+            on: false,
+            switched_at: None,
+            min_on_time: Duration::from_ticks(0),
+            min_off_time: Duration::from_ticks(0),
+            interlock: None,
+        };
+        relay.write(false).ok();
+        relay
+    }
+}
+
+impl<'a, PIN: OutputPin, M: RawMutex> Relay<'a, PIN, M> {
+    /// Set whether the relay is on when the pin is low, as on most relay
+    /// modules, and turn it off.
+    pub fn with_active_low(mut self, active_low: bool) -> Self {
+        self.active_low = active_low;
+        self.write(false).ok();
+        self
+    }
+
+    /// Set the minimum time the relay stays on.
+    pub fn with_min_on_time(mut self, min_on_time: Duration) -> Self {
+        self.min_on_time = min_on_time;
+        self
+    }
+
+    /// Set the minimum time the relay stays off.
+    pub fn with_min_off_time(mut self, min_off_time: Duration) -> Self {
+        self.min_off_time = min_off_time;
+        self
+    }
+
+    /// Add the relay to a group of relays never on at the same time.
+    pub fn with_interlock<'b, N: RawMutex>(self, interlock: &'b Interlock<N>) -> Relay<'b, PIN, N> {
+        Relay {
+            pin: self.pin,
+            active_low: self.active_low,
+            on: self.on,
+            switched_at: self.switched_at,
+            min_on_time: self.min_on_time,
+            min_off_time: self.min_off_time,
+            interlock: Some(interlock),
+        }
+    }
+
+    /// Release the underlying pin, leaving the relay in its current state
+    pub fn release(self) -> PIN {
+        self.pin
+    }
+
+    /// Whether the relay is on
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Turn the relay on or off, waiting for the minimum on or off time to
+    /// elapse first.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Interlocked` if another relay of the interlock group
+    /// is on.
+    pub async fn set(&mut self, on: bool) -> Result<(), Error> {
+        if on == self.on {
+            return Ok(());
+        }
+
+        if let Some(switched_at) = self.switched_at {
+            let min_time = if self.on {
+                self.min_on_time
+            } else {
+                self.min_off_time
+            };
+            Timer::at(switched_at + min_time).await;
+        }
+
+        if on && self.interlock.is_some_and(|interlock| !interlock.engage()) {
+            return Err(Error::Interlocked);
+        }
+
+        let result = self.write(on);
+        // Leave the group once off, or if the relay failed to turn on
+        if let (true, Some(interlock)) = (on == result.is_err(), self.interlock) {
+            interlock.disengage();
+        }
+        result?;
+
+        self.on = on;
+        self.switched_at = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Turn the relay on. See [Relay::set].
+    pub async fn turn_on(&mut self) -> Result<(), Error> {
+        self.set(true).await
+    }
+
+    /// Turn the relay off. See [Relay::set].
+    pub async fn turn_off(&mut self) -> Result<(), Error> {
+        self.set(false).await
+    }
+
+    /// Turn the relay on for a duration, then off.
+    ///
+    /// The relay stays on for at least the minimum on time.
+    pub async fn pulse(&mut self, duration: Duration) -> Result<(), Error> {
+        self.turn_on().await?;
+        Timer::after(duration).await;
+        self.turn_off().await
+    }
+
+    /// Turn the relay on for a duration at a given time, then off.
+    pub async fn pulse_at(&mut self, at: Instant, duration: Duration) -> Result<(), Error> {
+        Timer::at(at).await;
+        self.pulse(duration).await
+    }
+
+    /// Run a period of time-proportional control, keeping the relay on for
+    /// a percentage of the period.
+    ///
+    /// The relay is left as is rather than switched for less than the
+    /// minimum on or off time, so short pulses are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// - `duty_pct`: The percentage of the period the relay is on (0-100).
+    /// - `period`: The duration of the period.
+    pub async fn cycle(&mut self, duty_pct: u8, period: Duration) -> Result<(), Error> {
+        if duty_pct > 100 {
+            return Err(Error::DutyOutOfRange);
+        }
+
+        let start = Instant::now();
+        let on_time = period * duty_pct as u32 / 100;
+        let off_time = period - on_time;
+
+        if on_time >= self.min_on_time.max(Duration::from_ticks(1)) {
+            self.turn_on().await?;
+        }
+        if off_time >= self.min_off_time.max(Duration::from_ticks(1)) {
+            Timer::at(start + on_time).await;
+            self.turn_off().await?;
+        }
+
+        Timer::at(start + period).await;
+        Ok(())
+    }
+
+    /// Drive the pin to turn the relay on or off.
+    fn write(&mut self, on: bool) -> Result<(), Error> {
+        self.pin
+            .set_state(PinState::from(on != self.active_low))
+            .map_err(|_| Error::Pin)
+    }
+}
+
+/// All possible errors in this driver
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Error of the pin
+    Pin,
+    /// Another relay of the interlock group is on.
+    Interlocked,
+    /// The duty cycle is not between 0 and 100.
+    DutyOutOfRange,
+}
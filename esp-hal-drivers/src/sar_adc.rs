@@ -0,0 +1,172 @@
+//! # sar_adc
+//!
+//! ## Overview
+//!
+//! This driver reads pins with the internal SAR ADC of the ESP32-C3.
+//!
+//! - Conversions are awaited asynchronously instead of busy-polled.
+//! - The pins are enabled on a [SarAdcConfig] before the ADC is created, each
+//!   with its own attenuation, which selects the input range from about
+//!   750 mV at 0 dB to about 2.5 V at 11 dB.
+//! - The calibration values burnt in the eFuses are applied with the
+//!   curve-fitting scheme, so readings are in mV like the other ADC drivers
+//!   of this crate.
+//! - [SarAdc::channel] returns a channel implementing [AsyncAdcChannel], so
+//!   the pins can be used by the helpers of this crate like the channels of
+//!   the MCP3428.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let peripherals = esp_hal::init(esp_hal::Config::default());
+//!
+//! let mut config = SarAdcConfig::new();
+//! let mut battery = config.enable_pin(peripherals.GPIO2, Attenuation::_11dB);
+//! let mut light = config.enable_pin(peripherals.GPIO3, Attenuation::_6dB);
+//! let mut adc = SarAdc::new(peripherals.ADC1, config);
+//!
+//! let voltage = adc.read_millivolts(&mut battery).await;
+//! let average = adc.read_averaged(&mut light, 16).await;
+//!
+//! let mut monitor = BatteryMonitor::new(adc.channel(&mut battery), 2.0, Chemistry::LiIon);
+//! ```
+
+use core::convert::Infallible;
+
+pub use esp_hal::analog::adc::Attenuation;
+use esp_hal::{
+    analog::adc::{Adc, AdcCalCurve, AdcChannel, AdcConfig, AdcPin},
+    gpio::AnalogPin,
+    peripheral::Peripheral,
+    peripherals::ADC1,
+    Async,
+};
+
+use crate::{units::Millivolts, AsyncAdcChannel};
+
+/// Configuration of the pins read by a [SarAdc]
+pub struct SarAdcConfig {
+    config: AdcConfig<ADC1>,
+}
+
+impl SarAdcConfig {
+    /// Create a new configuration without any pin.
+    pub fn new() -> Self {
+        Self {
+            config: AdcConfig::new(),
+        }
+    }
+
+    /// Enable a pin, which must be connected to ADC1.
+    ///
+    /// # Arguments
+    ///
+    /// - `pin`: The pin to read.
+    /// - `attenuation`: The input attenuation of the pin, selecting its
+    ///   input range.
+    pub fn enable_pin<PIN: AdcChannel + AnalogPin>(
+        &mut self,
+        pin: PIN,
+        attenuation: Attenuation,
+    ) -> SarAdcPin<PIN> {
+        let pin = self
+            .config
+            .enable_pin_with_cal::<_, AdcCalCurve<ADC1>>(pin, attenuation);
+
+        SarAdcPin { pin, attenuation }
+    }
+}
+
+impl Default for SarAdcConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A pin enabled on a [SarAdcConfig]
+pub struct SarAdcPin<PIN> {
+    pin: AdcPin<PIN, ADC1, AdcCalCurve<ADC1>>,
+    attenuation: Attenuation,
+}
+
+impl<PIN> SarAdcPin<PIN> {
+    /// Get the input attenuation.
+    pub fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+
+    /// Get the recommended maximum input voltage at the configured
+    /// attenuation.
+    ///
+    /// Readings above this voltage are not accurate.
+    pub fn full_scale(&self) -> Millivolts {
+        match self.attenuation {
+            Attenuation::_0dB => Millivolts(750),
+            Attenuation::_2p5dB => Millivolts(1_050),
+            Attenuation::_6dB => Millivolts(1_300),
+            Attenuation::_11dB => Millivolts(2_500),
+        }
+    }
+}
+
+/// The internal SAR ADC, shared by the pins enabled on its configuration
+pub struct SarAdc<'d> {
+    adc: Adc<'d, ADC1, Async>,
+}
+
+impl<'d> SarAdc<'d> {
+    /// Create a new ADC reading the pins enabled on `config`.
+    pub fn new(adc: impl Peripheral<P = ADC1> + 'd, config: SarAdcConfig) -> Self {
+        Self {
+            adc: Adc::new(adc, config.config).into_async(),
+        }
+    }
+
+    /// Read the calibrated voltage at a pin.
+    pub async fn read_millivolts<PIN: AdcChannel>(
+        &mut self,
+        pin: &mut SarAdcPin<PIN>,
+    ) -> Millivolts {
+        Millivolts(self.adc.read_oneshot(&mut pin.pin).await as i32)
+    }
+
+    /// Take `samples` readings of a pin and return their mean. At least one
+    /// sample is always taken.
+    pub async fn read_averaged<PIN: AdcChannel>(
+        &mut self,
+        pin: &mut SarAdcPin<PIN>,
+        samples: u8,
+    ) -> Millivolts {
+        let samples = samples.max(1);
+
+        let mut sum = 0;
+        for _ in 0..samples {
+            sum += self.read_millivolts(pin).await.0;
+        }
+        Millivolts(sum / samples as i32)
+    }
+
+    /// Borrow the ADC and one of its pins as an [AsyncAdcChannel].
+    pub fn channel<'a, PIN: AdcChannel>(
+        &'a mut self,
+        pin: &'a mut SarAdcPin<PIN>,
+    ) -> SarAdcChannel<'a, 'd, PIN> {
+        SarAdcChannel { adc: self, pin }
+    }
+}
+
+/// A single pin of a [SarAdc]
+///
+/// Created by [SarAdc::channel].
+pub struct SarAdcChannel<'a, 'd, PIN> {
+    adc: &'a mut SarAdc<'d>,
+    pin: &'a mut SarAdcPin<PIN>,
+}
+
+impl<PIN: AdcChannel> AsyncAdcChannel for SarAdcChannel<'_, '_, PIN> {
+    type Error = Infallible;
+
+    async fn read_millivolts(&mut self) -> Result<Millivolts, Self::Error> {
+        Ok(self.adc.read_millivolts(self.pin).await)
+    }
+}
@@ -0,0 +1,553 @@
+//! # sdcard
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with SD and SDHC cards in
+//! SPI mode, as a [BlockDevice] for the [fat](crate::fat) module.
+//!
+//! - The card is initialized on demand rather than when the driver is
+//!   created, so that it can be inserted and removed at any time.
+//! - Blocks are read and written one at a time, with their 512 bytes.
+//! - The card detect switch of the socket, if connected, is used to wait for
+//!   the card to be inserted or removed.
+//!
+//! The driver owns the SPI bus and drives the chip select pin itself, since
+//! the card needs clock cycles with its chip select high to enter SPI mode,
+//! and the chip select low across the polls of a command. The bus should run
+//! at 400 kHz until the card is initialized, and can then be sped up to
+//! 25 MHz through [SdCard::bus_mut].
+//!
+//! After an error, the card must be initialized again, which lets a card
+//! swapped without a card detect switch be picked up by retrying.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut card = SdCard::new(spi_bus, cs).with_card_detect_pin(cd);
+//!
+//! loop {
+//!     // Initialize the card once inserted
+//!     if card.wait_for_insertion().await.is_err() {
+//!         card.wait_for_removal().await?;
+//!         continue;
+//!     }
+//!
+//!     // Give up on a card that cannot be mounted until it is swapped
+//!     let Ok(mut volume) = Volume::mount(&mut card).await else {
+//!         card.wait_for_removal().await?;
+//!         continue;
+//!     };
+//!     let Ok(mut file) = volume.open_append("LOG0001.CSV").await else {
+//!         card.wait_for_removal().await?;
+//!         continue;
+//!     };
+//!
+//!     // Log to the card until it fails, e.g. once removed
+//!     loop {
+//!         let line = sample_line().await;
+//!         if file.write(line.as_bytes()).await.is_err() || file.flush().await.is_err() {
+//!             break;
+//!         }
+//!     }
+//! }
+//! ```
+
+use embassy_futures::yield_now;
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal_async::{digital::Wait, spi::SpiBus};
+
+use crate::fat::{BlockDevice, BLOCK_SIZE};
+
+/// Time taken by the contacts of the card detect switch to settle, and by
+/// the card to power up once inserted
+const INSERTION_DEBOUNCE_TIME: Duration = Duration::from_millis(250);
+
+/// Time taken by the contacts of the card detect switch to settle once the
+/// card is removed
+const REMOVAL_DEBOUNCE_TIME: Duration = Duration::from_millis(50);
+
+/// Maximum time taken by the card to initialize
+const INIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Maximum time taken by the card to start sending a block
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Maximum time taken by the card to program a block, or to be ready for the
+/// next command
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Number of bytes polled for the response to a command
+const RESPONSE_POLLS: usize = 10;
+
+/// Commands of the card. See the SD simplified physical layer specification
+/// section 7.3.1 for more details.
+struct Command;
+
+impl Command {
+    const GO_IDLE_STATE: u8 = 0;
+    const SEND_IF_COND: u8 = 8;
+    const SEND_CSD: u8 = 9;
+    const SET_BLOCKLEN: u8 = 16;
+    const READ_SINGLE_BLOCK: u8 = 17;
+    const WRITE_BLOCK: u8 = 24;
+    const APP_CMD: u8 = 55;
+    const READ_OCR: u8 = 58;
+    const SD_SEND_OP_COND: u8 = 41;
+}
+
+/// Flags of the R1 response
+const R1_IDLE: u8 = 1 << 0;
+const R1_ILLEGAL_COMMAND: u8 = 1 << 2;
+
+/// Argument of SEND_IF_COND, 2.7-3.6 V and a check pattern echoed back
+const IF_COND_ARGUMENT: u32 = 0x1AA;
+
+/// Flag of the argument of SD_SEND_OP_COND announcing SDHC support
+const HIGH_CAPACITY: u32 = 1 << 30;
+
+/// Flag of the OCR set by SDHC and SDXC cards
+const OCR_CCS: u32 = 1 << 30;
+
+/// Token starting a data block
+const DATA_START_TOKEN: u8 = 0xFE;
+
+/// Mask and value of the data response accepting a written block
+const DATA_RESPONSE_MASK: u8 = 0x1F;
+const DATA_ACCEPTED: u8 = 0x05;
+
+/// Kind of card
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CardType {
+    /// Standard capacity card of version 1, addressed by byte
+    Sd1,
+    /// Standard capacity card of version 2, addressed by byte
+    Sd2,
+    /// High or extended capacity card, addressed by block
+    Sdhc,
+}
+
+/// An SD card on an SPI bus
+///
+/// `CD` is the pin the card detect switch of the socket is connected to, if
+/// any.
+pub struct SdCard<SPI, CS, CD = ()> {
+    spi: SPI,
+    cs: CS,
+    card_detect: CD,
+    card: Option<CardType>,
+}
+
+impl<SPI: SpiBus, CS: OutputPin> SdCard<SPI, CS> {
+    /// Create a new card, not yet initialized.
+    ///
+    /// # Arguments
+    ///
+    /// - `spi`: The SPI bus the card is on, in mode 0 and at 400 kHz.
+    /// - `cs`: The chip select pin of the card.
+    pub fn new(spi: SPI, cs: CS) -> Self {
+        Self {
+            spi,
+            cs,
+            card_detect: (),
+            card: None,
+        }
+    }
+
+    /// Attach the pin the card detect switch of the socket is connected to.
+    ///
+    /// The switch is expected to connect the pin to ground while a card is
+    /// inserted, so the pin needs a pull-up.
+    pub fn with_card_detect_pin<P: InputPin + Wait>(self, card_detect: P) -> SdCard<SPI, CS, P> {
+        SdCard {
+            spi: self.spi,
+            cs: self.cs,
+            card_detect,
+            card: self.card,
+        }
+    }
+}
+
+impl<SPI: SpiBus, CS: OutputPin, P: InputPin + Wait> SdCard<SPI, CS, P> {
+    /// Return whether a card is in the socket.
+    pub fn is_present(&mut self) -> Result<bool, Error<SPI::Error>> {
+        self.card_detect.is_low().map_err(|_| Error::Pin)
+    }
+
+    /// Wait for a card to be inserted, or return immediately if there is one,
+    /// and initialize it.
+    pub async fn wait_for_insertion(&mut self) -> Result<CardType, Error<SPI::Error>> {
+        loop {
+            self.card_detect
+                .wait_for_low()
+                .await
+                .map_err(|_| Error::Pin)?;
+            Timer::after(INSERTION_DEBOUNCE_TIME).await;
+            if self.is_present()? {
+                return self.init().await;
+            }
+        }
+    }
+
+    /// Wait for the card to be removed, or return immediately if there is
+    /// none.
+    pub async fn wait_for_removal(&mut self) -> Result<(), Error<SPI::Error>> {
+        loop {
+            self.card_detect
+                .wait_for_high()
+                .await
+                .map_err(|_| Error::Pin)?;
+            self.card = None;
+            Timer::after(REMOVAL_DEBOUNCE_TIME).await;
+            if !self.is_present()? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Release the underlying SPI bus, chip select pin and card detect pin
+    pub fn release_with_card_detect_pin(self) -> (SPI, CS, P) {
+        (self.spi, self.cs, self.card_detect)
+    }
+}
+
+impl<SPI: SpiBus, CS: OutputPin, CD> SdCard<SPI, CS, CD> {
+    /// Release the underlying SPI bus and chip select pin
+    pub fn release(self) -> (SPI, CS) {
+        (self.spi, self.cs)
+    }
+
+    /// Get the underlying SPI bus, e.g. to raise its frequency once the card
+    /// is initialized.
+    pub fn bus_mut(&mut self) -> &mut SPI {
+        &mut self.spi
+    }
+
+    /// Get the type of the card, if initialized
+    pub fn card_type(&self) -> Option<CardType> {
+        self.card
+    }
+
+    /// Initialize the card in SPI mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Timeout` if no card answers or the card does not
+    /// leave its idle state, and `Error::UnsupportedCard` if the card does
+    /// not support 3.3 V.
+    pub async fn init(&mut self) -> Result<CardType, Error<SPI::Error>> {
+        self.card = None;
+
+        // At least 74 clock cycles with the chip select high
+        self.cs.set_high().map_err(|_| Error::Pin)?;
+        self.spi.write(&[0xFF; 10]).await.map_err(Error::Spi)?;
+
+        self.select()?;
+        let result = with_timeout(INIT_TIMEOUT, self.init_selected())
+            .await
+            .unwrap_or(Err(Error::Timeout));
+        let card = self.deselect().await.and(result)?;
+
+        self.card = Some(card);
+        Ok(card)
+    }
+
+    /// Get the number of blocks of the card.
+    pub async fn num_blocks(&mut self) -> Result<u32, Error<SPI::Error>> {
+        self.check_initialized()?;
+
+        self.select()?;
+        let mut csd = [0u8; 16];
+        let result = self.read_data(Command::SEND_CSD, 0, &mut csd).await;
+        self.deselect().await.and(result)?;
+
+        // See the SD simplified physical layer specification section 5.3
+        if csd[0] >> 6 == 1 {
+            let size = ((csd[7] as u32 & 0x3F) << 16) | ((csd[8] as u32) << 8) | csd[9] as u32;
+            Ok((size + 1) * 1024)
+        } else {
+            let size =
+                ((csd[6] as u32 & 0x03) << 10) | ((csd[7] as u32) << 2) | (csd[8] as u32 >> 6);
+            let multiplier = ((csd[9] & 0x03) << 1) | (csd[10] >> 7);
+            let block_length = csd[5] & 0x0F;
+            Ok((size + 1) << (multiplier + 2 + block_length).saturating_sub(9))
+        }
+    }
+
+    /// Read a block of 512 bytes.
+    pub async fn read_block(
+        &mut self,
+        lba: u32,
+        block: &mut [u8; BLOCK_SIZE],
+    ) -> Result<(), Error<SPI::Error>> {
+        let address = self.address(lba)?;
+
+        self.select()?;
+        let result = self
+            .read_data(Command::READ_SINGLE_BLOCK, address, block)
+            .await;
+        let result = self.deselect().await.and(result);
+        if result.is_err() {
+            self.card = None;
+        }
+        result
+    }
+
+    /// Write a block of 512 bytes and wait for it to be programmed.
+    pub async fn write_block(
+        &mut self,
+        lba: u32,
+        block: &[u8; BLOCK_SIZE],
+    ) -> Result<(), Error<SPI::Error>> {
+        let address = self.address(lba)?;
+
+        self.select()?;
+        let result = self.write_data(address, block).await;
+        let result = self.deselect().await.and(result);
+        if result.is_err() {
+            self.card = None;
+        }
+        result
+    }
+
+    /// Go through the initialization sequence, with the card selected.
+    async fn init_selected(&mut self) -> Result<CardType, Error<SPI::Error>> {
+        // Enter SPI mode
+        while self.command(Command::GO_IDLE_STATE, 0).await? != R1_IDLE {
+            Timer::after_millis(10).await;
+        }
+
+        // Only version 2 cards know SEND_IF_COND
+        let r1 = self
+            .command(Command::SEND_IF_COND, IF_COND_ARGUMENT)
+            .await?;
+        let version_2 = r1 & R1_ILLEGAL_COMMAND == 0;
+        if version_2 {
+            let mut r7 = [0xFF; 4];
+            self.spi
+                .transfer_in_place(&mut r7)
+                .await
+                .map_err(Error::Spi)?;
+            if u32::from_be_bytes(r7) & 0xFFF != IF_COND_ARGUMENT {
+                return Err(Error::UnsupportedCard);
+            }
+        }
+
+        // Start the initialization and wait for it to complete
+        let argument = if version_2 { HIGH_CAPACITY } else { 0 };
+        loop {
+            self.command(Command::APP_CMD, 0).await?;
+            match self.command(Command::SD_SEND_OP_COND, argument).await? {
+                0 => break,
+                R1_IDLE => Timer::after_millis(10).await,
+                r1 => return Err(Error::Card(r1)),
+            }
+        }
+
+        if !version_2 {
+            self.set_block_length().await?;
+            return Ok(CardType::Sd1);
+        }
+
+        self.expect_ready(Command::READ_OCR, 0).await?;
+        let mut ocr = [0xFF; 4];
+        self.spi
+            .transfer_in_place(&mut ocr)
+            .await
+            .map_err(Error::Spi)?;
+        if u32::from_be_bytes(ocr) & OCR_CCS != 0 {
+            Ok(CardType::Sdhc)
+        } else {
+            self.set_block_length().await?;
+            Ok(CardType::Sd2)
+        }
+    }
+
+    /// Set the block length of a standard capacity card to 512 bytes.
+    async fn set_block_length(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.expect_ready(Command::SET_BLOCKLEN, BLOCK_SIZE as u32)
+            .await
+    }
+
+    /// Send a command that reads a data block, with the card selected.
+    async fn read_data(
+        &mut self,
+        command: u8,
+        argument: u32,
+        buf: &mut [u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.expect_ready(command, argument).await?;
+
+        let token = async {
+            loop {
+                let byte = self.read_byte().await?;
+                if byte != 0xFF {
+                    return Ok(byte);
+                }
+                yield_now().await;
+            }
+        };
+        let token = with_timeout(READ_TIMEOUT, token)
+            .await
+            .unwrap_or(Err(Error::Timeout))?;
+        if token != DATA_START_TOKEN {
+            return Err(Error::DataError(token));
+        }
+
+        // The card expects the data line high while sending
+        buf.fill(0xFF);
+        self.spi.transfer_in_place(buf).await.map_err(Error::Spi)?;
+
+        // The CRC is disabled in SPI mode
+        let mut crc = [0xFF; 2];
+        self.spi
+            .transfer_in_place(&mut crc)
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Send a block to write, with the card selected.
+    async fn write_data(
+        &mut self,
+        address: u32,
+        block: &[u8; BLOCK_SIZE],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.expect_ready(Command::WRITE_BLOCK, address).await?;
+
+        self.spi
+            .write(&[0xFF, DATA_START_TOKEN])
+            .await
+            .map_err(Error::Spi)?;
+        self.spi.write(block).await.map_err(Error::Spi)?;
+        self.spi.write(&[0xFF; 2]).await.map_err(Error::Spi)?;
+
+        let response = self.read_byte().await?;
+        if response & DATA_RESPONSE_MASK != DATA_ACCEPTED {
+            return Err(Error::DataError(response));
+        }
+        self.wait_not_busy().await
+    }
+
+    /// Send a command and check that the card accepted it.
+    async fn expect_ready(&mut self, command: u8, argument: u32) -> Result<(), Error<SPI::Error>> {
+        match self.command(command, argument).await? {
+            0 => Ok(()),
+            r1 => Err(Error::Card(r1)),
+        }
+    }
+
+    /// Send a command and return its R1 response, with the card selected.
+    async fn command(&mut self, command: u8, argument: u32) -> Result<u8, Error<SPI::Error>> {
+        self.wait_not_busy().await?;
+
+        // The CRC is only checked for the commands sent before SPI mode
+        let crc = match command {
+            Command::GO_IDLE_STATE => 0x95,
+            Command::SEND_IF_COND => 0x87,
+            _ => 0x01,
+        };
+        let [a3, a2, a1, a0] = argument.to_be_bytes();
+        self.spi
+            .write(&[0x40 | command, a3, a2, a1, a0, crc])
+            .await
+            .map_err(Error::Spi)?;
+
+        for _ in 0..RESPONSE_POLLS {
+            let r1 = self.read_byte().await?;
+            if r1 & 0x80 == 0 {
+                return Ok(r1);
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Wait for the card to release the data line after a write.
+    async fn wait_not_busy(&mut self) -> Result<(), Error<SPI::Error>> {
+        let poll = async {
+            loop {
+                if self.read_byte().await? == 0xFF {
+                    return Ok(());
+                }
+                yield_now().await;
+            }
+        };
+        with_timeout(WRITE_TIMEOUT, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    async fn read_byte(&mut self) -> Result<u8, Error<SPI::Error>> {
+        let mut buf = [0xFF];
+        self.spi
+            .transfer_in_place(&mut buf)
+            .await
+            .map_err(Error::Spi)?;
+        Ok(buf[0])
+    }
+
+    fn select(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.cs.set_low().map_err(|_| Error::Pin)
+    }
+
+    /// Deselect the card, and clock a byte for it to release the data line.
+    async fn deselect(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.cs.set_high().map_err(|_| Error::Pin)?;
+        self.spi.write(&[0xFF]).await.map_err(Error::Spi)
+    }
+
+    fn check_initialized(&self) -> Result<CardType, Error<SPI::Error>> {
+        self.card.ok_or(Error::NotInitialized)
+    }
+
+    /// Compute the address of a block in the commands of the card.
+    fn address(&self, lba: u32) -> Result<u32, Error<SPI::Error>> {
+        match self.check_initialized()? {
+            CardType::Sdhc => Ok(lba),
+            CardType::Sd1 | CardType::Sd2 => {
+                lba.checked_mul(BLOCK_SIZE as u32).ok_or(Error::OutOfBounds)
+            }
+        }
+    }
+}
+
+impl<SPI: SpiBus, CS: OutputPin, CD> BlockDevice for SdCard<SPI, CS, CD> {
+    type Error = Error<SPI::Error>;
+
+    async fn read_block(
+        &mut self,
+        lba: u32,
+        block: &mut [u8; BLOCK_SIZE],
+    ) -> Result<(), Self::Error> {
+        SdCard::read_block(self, lba, block).await
+    }
+
+    async fn write_block(&mut self, lba: u32, block: &[u8; BLOCK_SIZE]) -> Result<(), Self::Error> {
+        SdCard::write_block(self, lba, block).await
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying SPI bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI bus error
+    Spi(E),
+    /// Error of the chip select or card detect pin
+    Pin,
+    /// The card is not initialized, or was removed.
+    NotInitialized,
+    /// The card did not answer in time.
+    Timeout,
+    /// The card does not support the voltage of the bus.
+    UnsupportedCard,
+    /// The card rejected a command, with the error flags of its R1 response.
+    Card(u8),
+    /// The card answered a read with an error token, or rejected a write
+    /// with its data response.
+    DataError(u8),
+    /// The block is beyond the addresses of a standard capacity card.
+    OutOfBounds,
+}
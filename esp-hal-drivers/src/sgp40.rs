@@ -0,0 +1,799 @@
+//! # sgp40
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Sensirion SGP40
+//! VOC sensor over I2C.
+//!
+//! - The raw signal of the sensor falls as the concentration of volatile
+//!   organic compounds rises. It depends on the humidity and the temperature,
+//!   which can be fed in from e.g. an [SHT4x](crate::sht4x) with
+//!   [Sgp40::set_compensation].
+//! - The [VocAlgorithm] turns the raw signal into a VOC index from 1 to 500,
+//!   where 100 is the average of the past 24 hours. It is a port of the fixed
+//!   point version of the Sensirion algorithm, using integer math only.
+//! - The built-in self-test checks the hotplate and the sensing material.
+//!
+//! The VOC algorithm expects one measurement per second, and reports 0
+//! during the first 45 seconds.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut sgp40 = Sgp40::new(i2c);
+//! sgp40.self_test().await?;
+//!
+//! let mut ticker = Ticker::every(Duration::from_secs(1));
+//! loop {
+//!     let measurement = sht4x.measure().await?;
+//!     sgp40.set_compensation(Some(measurement.into()));
+//!     println!("VOC index {}", sgp40.measure_voc_index().await?);
+//!     ticker.next().await;
+//! }
+//! ```
+
+use embassy_time::{Duration, Ticker, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use crate::{
+    sht4x::{self, crc8},
+    units::{Celsius, RelativeHumidity},
+};
+
+/// I2C address of the sensor
+pub const ADDRESS: u8 = 0x59;
+
+/// Command measuring the raw signal, followed by the compensation
+const MEASURE_RAW: u16 = 0x260F;
+
+/// Command running the built-in self-test
+const SELF_TEST: u16 = 0x280E;
+
+/// Command turning the hotplate off
+const HEATER_OFF: u16 = 0x3615;
+
+/// Command reading the serial number of the sensor
+const READ_SERIAL: u16 = 0x3682;
+
+/// Maximum time taken by a measurement
+const MEASURE_RAW_TIME: Duration = Duration::from_millis(30);
+
+/// Maximum time taken by the self-test
+const SELF_TEST_TIME: Duration = Duration::from_millis(320);
+
+/// Maximum time taken by the other commands
+const COMMAND_TIME: Duration = Duration::from_millis(1);
+
+/// Result of a passed self-test
+const SELF_TEST_PASSED: u16 = 0xD400;
+
+/// Compensation ticks used without compensation, 50 %RH and 25 °C
+const DEFAULT_HUMIDITY_TICKS: u16 = 0x8000;
+const DEFAULT_TEMPERATURE_TICKS: u16 = 0x6666;
+
+/// Time between two measurements expected by the VOC algorithm
+pub const SAMPLING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Humidity and temperature compensating the raw signal
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Compensation {
+    pub temperature: Celsius,
+    pub humidity: RelativeHumidity,
+}
+
+impl Compensation {
+    /// Ticks of the humidity and the temperature sent to the sensor. See
+    /// datasheet section 4.2 for more details.
+    fn ticks(&self) -> [u16; 2] {
+        let humidity = self.humidity.0.clamp(0.0, 100.0) * 65_535.0 / 100.0;
+        let temperature = (self.temperature.0.clamp(-45.0, 130.0) + 45.0) * 65_535.0 / 175.0;
+        [humidity as u16, temperature as u16]
+    }
+}
+
+impl From<sht4x::Measurement> for Compensation {
+    fn from(measurement: sht4x::Measurement) -> Self {
+        Self {
+            temperature: measurement.temperature,
+            humidity: measurement.humidity,
+        }
+    }
+}
+
+/// An SGP40 sensor on an I2C bus
+pub struct Sgp40<I2C> {
+    i2c: I2C,
+    compensation: Option<Compensation>,
+    algorithm: VocAlgorithm,
+}
+
+impl<I2C: I2c> Sgp40<I2C> {
+    /// Create a new sensor, without compensation.
+    pub fn new(i2c: I2C) -> Self {
+        Self {
+            i2c,
+            compensation: None,
+            algorithm: VocAlgorithm::new(),
+        }
+    }
+
+    /// Set the humidity and the temperature around the sensor, or assume
+    /// 50 %RH and 25 °C with `None`.
+    pub fn set_compensation(&mut self, compensation: Option<Compensation>) {
+        self.compensation = compensation;
+    }
+
+    /// Get the VOC algorithm of the sensor, e.g. to save its state
+    pub fn algorithm(&self) -> &VocAlgorithm {
+        &self.algorithm
+    }
+
+    /// Get the VOC algorithm of the sensor mutably, e.g. to restore its state
+    /// or tune it
+    pub fn algorithm_mut(&mut self) -> &mut VocAlgorithm {
+        &mut self.algorithm
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Measure the raw signal of the sensor, compensated for the humidity and
+    /// the temperature.
+    ///
+    /// The hotplate stays on after the measurement, ready for the next one.
+    pub async fn measure_raw(&mut self) -> Result<u16, Error<I2C::Error>> {
+        let [humidity, temperature] = match self.compensation {
+            Some(compensation) => compensation.ticks(),
+            None => [DEFAULT_HUMIDITY_TICKS, DEFAULT_TEMPERATURE_TICKS],
+        };
+        let [h_msb, h_lsb] = humidity.to_be_bytes();
+        let [t_msb, t_lsb] = temperature.to_be_bytes();
+        let [c_msb, c_lsb] = MEASURE_RAW.to_be_bytes();
+        let command = [
+            c_msb,
+            c_lsb,
+            h_msb,
+            h_lsb,
+            crc8(&[h_msb, h_lsb]),
+            t_msb,
+            t_lsb,
+            crc8(&[t_msb, t_lsb]),
+        ];
+        self.i2c
+            .write(ADDRESS, &command)
+            .await
+            .map_err(Error::I2c)?;
+        Timer::after(MEASURE_RAW_TIME).await;
+
+        let [raw] = self.read_words().await?;
+        Ok(raw)
+    }
+
+    /// Measure the raw signal and process it with the VOC algorithm, returning
+    /// the VOC index.
+    ///
+    /// Must be called every [SAMPLING_INTERVAL].
+    pub async fn measure_voc_index(&mut self) -> Result<u16, Error<I2C::Error>> {
+        let raw = self.measure_raw().await?;
+        Ok(self.algorithm.process(raw))
+    }
+
+    /// Condition the sensor after power up, by measuring every
+    /// [SAMPLING_INTERVAL] for `duration` while feeding the VOC algorithm.
+    ///
+    /// The SGP40 has no dedicated conditioning command; running the hotplate
+    /// is what stabilizes the sensing material. Conditioning for at least 45
+    /// seconds, the blackout of the VOC algorithm, makes the next VOC index
+    /// meaningful.
+    pub async fn condition(&mut self, duration: Duration) -> Result<(), Error<I2C::Error>> {
+        let mut ticker = Ticker::every(SAMPLING_INTERVAL);
+        for _ in 0..duration.as_secs() {
+            self.measure_voc_index().await?;
+            ticker.next().await;
+        }
+        Ok(())
+    }
+
+    /// Run the built-in self-test of the hotplate and the sensing material.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::SelfTest` with the result of the test if it failed.
+    pub async fn self_test(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.command(SELF_TEST).await?;
+        Timer::after(SELF_TEST_TIME).await;
+
+        let [result] = self.read_words().await?;
+        if result != SELF_TEST_PASSED {
+            return Err(Error::SelfTest(result));
+        }
+        Ok(())
+    }
+
+    /// Turn the hotplate off, until the next measurement.
+    pub async fn heater_off(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.command(HEATER_OFF).await?;
+        Timer::after(COMMAND_TIME).await;
+        Ok(())
+    }
+
+    /// Read the unique 48-bit serial number of the sensor.
+    pub async fn serial_number(&mut self) -> Result<u64, Error<I2C::Error>> {
+        self.command(READ_SERIAL).await?;
+        Timer::after(COMMAND_TIME).await;
+
+        let words: [u16; 3] = self.read_words().await?;
+        Ok(words
+            .iter()
+            .fold(0, |serial, word| (serial << 16) | *word as u64))
+    }
+
+    async fn command(&mut self, command: u16) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(ADDRESS, &command.to_be_bytes())
+            .await
+            .map_err(Error::I2c)
+    }
+
+    /// Read the words of a response, checking their CRC.
+    async fn read_words<const N: usize>(&mut self) -> Result<[u16; N], Error<I2C::Error>> {
+        let mut buf = [0u8; 9];
+        let buf = &mut buf[..N * 3];
+        self.i2c.read(ADDRESS, buf).await.map_err(Error::I2c)?;
+
+        let mut words = [0u16; N];
+        for (word, chunk) in words.iter_mut().zip(buf.as_chunks::<3>().0) {
+            if crc8(&chunk[..2]) != chunk[2] {
+                return Err(Error::Crc);
+            }
+            *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+        }
+        Ok(words)
+    }
+}
+
+/// A number in fixed point with 16 fractional bits
+type Fix16 = i32;
+
+/// Convert a constant to fixed point, rounding to nearest.
+const fn f16(x: f64) -> Fix16 {
+    if x >= 0.0 {
+        (x * 65_536.0 + 0.5) as Fix16
+    } else {
+        (x * 65_536.0 - 0.5) as Fix16
+    }
+}
+
+/// Parameters of the VOC algorithm. See the Sensirion gas index algorithm for
+/// more details.
+const SAMPLING_INTERVAL_S: f64 = 1.0;
+const INITIAL_BLACKOUT: f64 = 45.0;
+const VOC_INDEX_GAIN: f64 = 230.0;
+const SRAW_STD_INITIAL: f64 = 50.0;
+const SRAW_STD_BONUS: f64 = 220.0;
+const TAU_MEAN_VARIANCE_HOURS: f64 = 12.0;
+const TAU_INITIAL_MEAN: f64 = 20.0;
+const INIT_DURATION_MEAN: f64 = 3600.0 * 0.75;
+const INIT_TRANSITION_MEAN: f64 = 0.01;
+const TAU_INITIAL_VARIANCE: f64 = 2500.0;
+const INIT_DURATION_VARIANCE: f64 = 3600.0 * 1.45;
+const INIT_TRANSITION_VARIANCE: f64 = 0.01;
+const GATING_THRESHOLD: f64 = 340.0;
+const GATING_THRESHOLD_INITIAL: f64 = 510.0;
+const GATING_THRESHOLD_TRANSITION: f64 = 0.09;
+const GATING_MAX_DURATION_MINUTES: f64 = 60.0 * 3.0;
+const GATING_MAX_RATIO: f64 = 0.3;
+const SIGMOID_L: f64 = 500.0;
+const SIGMOID_K: f64 = -0.0065;
+const SIGMOID_X0: f64 = 213.0;
+const VOC_INDEX_OFFSET_DEFAULT: f64 = 100.0;
+const LP_TAU_FAST: f64 = 20.0;
+const LP_TAU_SLOW: f64 = 500.0;
+const LP_ALPHA: f64 = -0.2;
+const PERSISTENCE_UPTIME_GAMMA: f64 = 3.0 * 3600.0;
+const GAMMA_SCALING: f64 = 64.0;
+const FIX16_MAX: f64 = 32_767.0;
+
+/// State of the VOC algorithm, to persist it across restarts
+///
+/// A state less than 10 minutes old can be restored, skipping the learning
+/// phase of the algorithm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VocState {
+    pub mean: i32,
+    pub std: i32,
+}
+
+/// A sigmoid `l / (1 + exp(k * (x - x0)))`
+#[derive(Debug, Copy, Clone)]
+struct Sigmoid {
+    l: Fix16,
+    k: Fix16,
+    x0: Fix16,
+}
+
+impl Sigmoid {
+    fn new(l: Fix16, x0: Fix16, k: Fix16) -> Self {
+        Self { l, k, x0 }
+    }
+
+    fn process(&self, sample: Fix16) -> Fix16 {
+        let x = mul(self.k, sample - self.x0);
+        if x < f16(-50.0) {
+            self.l
+        } else if x > f16(50.0) {
+            0
+        } else {
+            div(self.l, f16(1.0).saturating_add(exp(x)))
+        }
+    }
+}
+
+/// Estimates the mean and the standard deviation of the raw signal
+#[derive(Debug, Copy, Clone)]
+struct MeanVarianceEstimator {
+    gating_max_duration_minutes: Fix16,
+    initialized: bool,
+    mean: Fix16,
+    sraw_offset: Fix16,
+    std: Fix16,
+    gamma: Fix16,
+    gamma_initial_mean: Fix16,
+    gamma_initial_variance: Fix16,
+    gamma_mean: Fix16,
+    gamma_variance: Fix16,
+    uptime_gamma: Fix16,
+    uptime_gating: Fix16,
+    gating_duration_minutes: Fix16,
+}
+
+impl MeanVarianceEstimator {
+    fn new(
+        std_initial: Fix16,
+        tau_mean_variance_hours: Fix16,
+        gating_max_duration_minutes: Fix16,
+    ) -> Self {
+        Self {
+            gating_max_duration_minutes,
+            initialized: false,
+            mean: 0,
+            sraw_offset: 0,
+            std: std_initial,
+            gamma: div(
+                f16(GAMMA_SCALING * (SAMPLING_INTERVAL_S / 3600.0)),
+                tau_mean_variance_hours + f16(SAMPLING_INTERVAL_S / 3600.0),
+            ),
+            gamma_initial_mean: f16(
+                (GAMMA_SCALING * SAMPLING_INTERVAL_S) / (TAU_INITIAL_MEAN + SAMPLING_INTERVAL_S)
+            ),
+            gamma_initial_variance: f16((GAMMA_SCALING * SAMPLING_INTERVAL_S)
+                / (TAU_INITIAL_VARIANCE + SAMPLING_INTERVAL_S)),
+            gamma_mean: 0,
+            gamma_variance: 0,
+            uptime_gamma: 0,
+            uptime_gating: 0,
+            gating_duration_minutes: 0,
+        }
+    }
+
+    fn set_states(&mut self, mean: Fix16, std: Fix16, uptime_gamma: Fix16) {
+        self.mean = mean;
+        self.std = std;
+        self.uptime_gamma = uptime_gamma;
+        self.initialized = true;
+    }
+
+    fn std(&self) -> Fix16 {
+        self.std
+    }
+
+    fn mean(&self) -> Fix16 {
+        self.mean + self.sraw_offset
+    }
+
+    fn calculate_gamma(&mut self, voc_index_from_prior: Fix16) {
+        let uptime_limit = f16(FIX16_MAX - SAMPLING_INTERVAL_S);
+        if self.uptime_gamma < uptime_limit {
+            self.uptime_gamma += f16(SAMPLING_INTERVAL_S);
+        }
+        if self.uptime_gating < uptime_limit {
+            self.uptime_gating += f16(SAMPLING_INTERVAL_S);
+        }
+
+        let sigmoid = Sigmoid::new(f16(1.0), f16(INIT_DURATION_MEAN), f16(INIT_TRANSITION_MEAN));
+        let sigmoid_gamma_mean = sigmoid.process(self.uptime_gamma);
+        let gamma_mean = self.gamma + mul(self.gamma_initial_mean - self.gamma, sigmoid_gamma_mean);
+        let gating_threshold_mean = f16(GATING_THRESHOLD)
+            + mul(
+                f16(GATING_THRESHOLD_INITIAL - GATING_THRESHOLD),
+                sigmoid.process(self.uptime_gating),
+            );
+        let sigmoid = Sigmoid::new(
+            f16(1.0),
+            gating_threshold_mean,
+            f16(GATING_THRESHOLD_TRANSITION),
+        );
+        let sigmoid_gating_mean = sigmoid.process(voc_index_from_prior);
+        self.gamma_mean = mul(sigmoid_gating_mean, gamma_mean);
+
+        let sigmoid = Sigmoid::new(
+            f16(1.0),
+            f16(INIT_DURATION_VARIANCE),
+            f16(INIT_TRANSITION_VARIANCE),
+        );
+        let sigmoid_gamma_variance = sigmoid.process(self.uptime_gamma);
+        let gamma_variance = self.gamma
+            + mul(
+                self.gamma_initial_variance - self.gamma,
+                sigmoid_gamma_variance - sigmoid_gamma_mean,
+            );
+        let gating_threshold_variance = f16(GATING_THRESHOLD)
+            + mul(
+                f16(GATING_THRESHOLD_INITIAL - GATING_THRESHOLD),
+                sigmoid.process(self.uptime_gating),
+            );
+        let sigmoid = Sigmoid::new(
+            f16(1.0),
+            gating_threshold_variance,
+            f16(GATING_THRESHOLD_TRANSITION),
+        );
+        let sigmoid_gating_variance = sigmoid.process(voc_index_from_prior);
+        self.gamma_variance = mul(sigmoid_gating_variance, gamma_variance);
+
+        self.gating_duration_minutes += mul(
+            f16(SAMPLING_INTERVAL_S / 60.0),
+            mul(f16(1.0) - sigmoid_gating_mean, f16(1.0 + GATING_MAX_RATIO))
+                - f16(GATING_MAX_RATIO),
+        );
+        if self.gating_duration_minutes < 0 {
+            self.gating_duration_minutes = 0;
+        }
+        if self.gating_duration_minutes > self.gating_max_duration_minutes {
+            self.uptime_gating = 0;
+        }
+    }
+
+    fn process(&mut self, sraw: Fix16, voc_index_from_prior: Fix16) {
+        if !self.initialized {
+            self.initialized = true;
+            self.sraw_offset = sraw;
+            self.mean = 0;
+            return;
+        }
+
+        if self.mean >= f16(100.0) || self.mean <= f16(-100.0) {
+            self.sraw_offset += self.mean;
+            self.mean = 0;
+        }
+        let sraw = sraw - self.sraw_offset;
+        self.calculate_gamma(voc_index_from_prior);
+        let delta_sgp = div(sraw - self.mean, f16(GAMMA_SCALING));
+        let c = self.std + delta_sgp.abs();
+        let additional_scaling = if c > f16(1440.0) { f16(4.0) } else { f16(1.0) };
+        self.std = mul(
+            sqrt(mul(
+                additional_scaling,
+                f16(GAMMA_SCALING) - self.gamma_variance,
+            )),
+            sqrt(
+                mul(
+                    self.std,
+                    div(self.std, mul(f16(GAMMA_SCALING), additional_scaling)),
+                ) + mul(
+                    div(mul(self.gamma_variance, delta_sgp), additional_scaling),
+                    delta_sgp,
+                ),
+            ),
+        );
+        self.mean += mul(self.gamma_mean, delta_sgp);
+    }
+}
+
+/// Low-pass filter whose time constant shortens on fast changes
+#[derive(Debug, Copy, Clone)]
+struct AdaptiveLowpass {
+    a1: Fix16,
+    a2: Fix16,
+    initialized: bool,
+    x1: Fix16,
+    x2: Fix16,
+    x3: Fix16,
+}
+
+impl AdaptiveLowpass {
+    fn new() -> Self {
+        Self {
+            a1: f16(SAMPLING_INTERVAL_S / (LP_TAU_FAST + SAMPLING_INTERVAL_S)),
+            a2: f16(SAMPLING_INTERVAL_S / (LP_TAU_SLOW + SAMPLING_INTERVAL_S)),
+            initialized: false,
+            x1: 0,
+            x2: 0,
+            x3: 0,
+        }
+    }
+
+    fn process(&mut self, sample: Fix16) -> Fix16 {
+        if !self.initialized {
+            self.x1 = sample;
+            self.x2 = sample;
+            self.x3 = sample;
+            self.initialized = true;
+        }
+        self.x1 = mul(f16(1.0) - self.a1, self.x1) + mul(self.a1, sample);
+        self.x2 = mul(f16(1.0) - self.a2, self.x2) + mul(self.a2, sample);
+        let abs_delta = (self.x1 - self.x2).abs();
+        let f1 = exp(mul(f16(LP_ALPHA), abs_delta));
+        let tau_a = mul(f16(LP_TAU_SLOW - LP_TAU_FAST), f1) + f16(LP_TAU_FAST);
+        let a3 = div(f16(SAMPLING_INTERVAL_S), f16(SAMPLING_INTERVAL_S) + tau_a);
+        self.x3 = mul(f16(1.0) - a3, self.x3) + mul(a3, sample);
+        self.x3
+    }
+}
+
+/// Turns the raw signal of an SGP40 into a VOC index
+///
+/// The index compares the current signal to its mean and standard deviation
+/// over the learning time, so the algorithm adapts to the environment of the
+/// sensor over the first hours.
+#[derive(Debug, Copy, Clone)]
+pub struct VocAlgorithm {
+    voc_index_offset: Fix16,
+    tau_mean_variance_hours: Fix16,
+    gating_max_duration_minutes: Fix16,
+    sraw_std_initial: Fix16,
+    uptime: Fix16,
+    sraw: Fix16,
+    voc_index: Fix16,
+    estimator: MeanVarianceEstimator,
+    mox_std: Fix16,
+    mox_mean: Fix16,
+    lowpass: AdaptiveLowpass,
+}
+
+impl Default for VocAlgorithm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VocAlgorithm {
+    /// Create a new algorithm with the default tuning.
+    pub fn new() -> Self {
+        let estimator = MeanVarianceEstimator::new(
+            f16(SRAW_STD_INITIAL),
+            f16(TAU_MEAN_VARIANCE_HOURS),
+            f16(GATING_MAX_DURATION_MINUTES),
+        );
+        Self {
+            voc_index_offset: f16(VOC_INDEX_OFFSET_DEFAULT),
+            tau_mean_variance_hours: f16(TAU_MEAN_VARIANCE_HOURS),
+            gating_max_duration_minutes: f16(GATING_MAX_DURATION_MINUTES),
+            sraw_std_initial: f16(SRAW_STD_INITIAL),
+            uptime: 0,
+            sraw: 0,
+            voc_index: 0,
+            estimator,
+            mox_std: estimator.std(),
+            mox_mean: estimator.mean(),
+            lowpass: AdaptiveLowpass::new(),
+        }
+    }
+
+    /// Restart the algorithm, keeping its tuning.
+    pub fn reset(&mut self) {
+        self.uptime = 0;
+        self.sraw = 0;
+        self.voc_index = 0;
+        self.init_instances();
+    }
+
+    /// Tune the algorithm and restart its learning.
+    ///
+    /// # Arguments
+    ///
+    /// - `voc_index_offset`: The VOC index of the average conditions, 100 by
+    ///   default.
+    /// - `learning_time_hours`: The time over which the average conditions
+    ///   are learned, 12 hours by default.
+    /// - `gating_max_duration_minutes`: The maximum time a high VOC index
+    ///   freezes the learning, 180 minutes by default.
+    /// - `std_initial`: The initial estimate of the standard deviation of the
+    ///   raw signal, 50 by default.
+    pub fn set_tuning_parameters(
+        &mut self,
+        voc_index_offset: u16,
+        learning_time_hours: u16,
+        gating_max_duration_minutes: u16,
+        std_initial: u16,
+    ) {
+        self.voc_index_offset = from_int(voc_index_offset as i32);
+        self.tau_mean_variance_hours = from_int(learning_time_hours as i32);
+        self.gating_max_duration_minutes = from_int(gating_max_duration_minutes as i32);
+        self.sraw_std_initial = from_int(std_initial as i32);
+        self.init_instances();
+    }
+
+    /// Get the learned state of the algorithm.
+    pub fn state(&self) -> VocState {
+        VocState {
+            mean: self.estimator.mean(),
+            std: self.estimator.std(),
+        }
+    }
+
+    /// Restore a learned state, e.g. saved before a restart.
+    pub fn set_state(&mut self, state: VocState) {
+        self.estimator
+            .set_states(state.mean, state.std, f16(PERSISTENCE_UPTIME_GAMMA));
+        self.sraw = state.mean;
+    }
+
+    /// Process a raw signal and return the VOC index, from 1 to 500, or 0
+    /// during the initial blackout.
+    ///
+    /// Must be called every [SAMPLING_INTERVAL].
+    pub fn process(&mut self, sraw: u16) -> u16 {
+        if self.uptime <= f16(INITIAL_BLACKOUT) {
+            self.uptime += f16(SAMPLING_INTERVAL_S);
+        } else {
+            if sraw > 0 && sraw < 65_000 {
+                let sraw = sraw.clamp(20_001, 52_767) as i32;
+                self.sraw = from_int(sraw - 20_000);
+            }
+            self.voc_index = self.mox_model(self.sraw);
+            self.voc_index = self.sigmoid_scaled(self.voc_index);
+            self.voc_index = self.lowpass.process(self.voc_index);
+            if self.voc_index < f16(0.5) {
+                self.voc_index = f16(0.5);
+            }
+            if self.sraw > 0 {
+                self.estimator.process(self.sraw, self.voc_index);
+                self.mox_std = self.estimator.std();
+                self.mox_mean = self.estimator.mean();
+            }
+        }
+        ((self.voc_index + f16(0.5)) >> 16) as u16
+    }
+
+    fn init_instances(&mut self) {
+        self.estimator = MeanVarianceEstimator::new(
+            self.sraw_std_initial,
+            self.tau_mean_variance_hours,
+            self.gating_max_duration_minutes,
+        );
+        self.mox_std = self.estimator.std();
+        self.mox_mean = self.estimator.mean();
+        self.lowpass = AdaptiveLowpass::new();
+    }
+
+    /// Normalize the raw signal by its mean and standard deviation.
+    fn mox_model(&self, sraw: Fix16) -> Fix16 {
+        mul(
+            div(sraw - self.mox_mean, -(self.mox_std + f16(SRAW_STD_BONUS))),
+            f16(VOC_INDEX_GAIN),
+        )
+    }
+
+    /// Map the normalized signal to the VOC index, the offset being the index
+    /// of the mean.
+    fn sigmoid_scaled(&self, sample: Fix16) -> Fix16 {
+        let x = mul(f16(SIGMOID_K), sample - f16(SIGMOID_X0));
+        if x < f16(-50.0) {
+            f16(SIGMOID_L)
+        } else if x > f16(50.0) {
+            0
+        } else if sample >= 0 {
+            let shift = div(
+                f16(SIGMOID_L) - mul(f16(5.0), self.voc_index_offset),
+                f16(4.0),
+            );
+            div(f16(SIGMOID_L) + shift, f16(1.0).saturating_add(exp(x))) - shift
+        } else {
+            mul(
+                div(self.voc_index_offset, f16(VOC_INDEX_OFFSET_DEFAULT)),
+                div(f16(SIGMOID_L), f16(1.0).saturating_add(exp(x))),
+            )
+        }
+    }
+}
+
+/// Convert an integer to fixed point.
+fn from_int(x: i32) -> Fix16 {
+    x.saturating_mul(1 << 16)
+}
+
+/// Multiply two fixed point numbers, rounding to nearest.
+fn mul(a: Fix16, b: Fix16) -> Fix16 {
+    let product = a as i64 * b as i64;
+    let result = (product >> 16) + ((product & 0x8000) >> 15);
+    result.clamp(i32::MIN as i64, i32::MAX as i64) as Fix16
+}
+
+/// Divide two fixed point numbers, rounding to nearest.
+fn div(a: Fix16, b: Fix16) -> Fix16 {
+    if b == 0 {
+        return if a >= 0 { i32::MAX } else { i32::MIN };
+    }
+    let numerator = (a as i64).unsigned_abs() << 16;
+    let denominator = (b as i64).unsigned_abs();
+    let quotient = ((numerator + denominator / 2) / denominator) as i64;
+    let quotient = if (a < 0) != (b < 0) {
+        -quotient
+    } else {
+        quotient
+    };
+    quotient.clamp(i32::MIN as i64, i32::MAX as i64) as Fix16
+}
+
+/// Square root of a fixed point number, rounding to nearest. Negative numbers
+/// return the opposite of the root of their absolute value.
+fn sqrt(x: Fix16) -> Fix16 {
+    let value = (x.unsigned_abs() as u64) << 16;
+    let mut root = value.isqrt();
+    if value - root * root > root {
+        root += 1;
+    }
+    if x < 0 {
+        -(root as Fix16)
+    } else {
+        root as Fix16
+    }
+}
+
+/// Exponential of a fixed point number, by multiplying the exponentials of
+/// ±1, ±1/8, ±1/64 and ±1/512.
+fn exp(x: Fix16) -> Fix16 {
+    const EXP_POSITIVE: [Fix16; 4] = [
+        f16(core::f64::consts::E),
+        f16(1.133_148_5),
+        f16(1.015_747_7),
+        f16(1.001_955_0),
+    ];
+    const EXP_NEGATIVE: [Fix16; 4] = [
+        f16(0.367_879_4),
+        f16(0.882_496_9),
+        f16(0.984_496_4),
+        f16(0.998_048_8),
+    ];
+
+    if x >= f16(10.3972) {
+        return i32::MAX;
+    }
+    if x <= f16(-11.7835) {
+        return 0;
+    }
+
+    let (mut x, values) = if x < 0 {
+        (-x, &EXP_NEGATIVE)
+    } else {
+        (x, &EXP_POSITIVE)
+    };
+    let mut result = f16(1.0);
+    let mut step = f16(1.0);
+    for value in values {
+        while x >= step {
+            result = mul(result, *value);
+            x -= step;
+        }
+        step >>= 3;
+    }
+    result
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// A response failed its CRC check.
+    Crc,
+    /// The self-test failed, with its result.
+    SelfTest(u16),
+}
@@ -0,0 +1,281 @@
+//! # sht4x
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Sensirion
+//! SHT40/41/45 humidity and temperature sensors over I2C.
+//!
+//! - The measurement precision trades repeatability for measurement time.
+//! - Every response is verified with its CRC.
+//! - The integrated heater can be pulsed, e.g. to remove condensation.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut sht4x = Sht4x::new(i2c, ADDRESS_A).with_precision(Precision::High);
+//!
+//! let measurement = sht4x.measure().await?;
+//! println!(
+//!     "{} °C, {} %RH",
+//!     measurement.temperature.0,
+//!     measurement.humidity.0
+//! );
+//!
+//! // Dry the sensor with a short heater pulse
+//! let measurement = sht4x.heat(HeaterPower::Mw200, HeaterDuration::Ms100).await?;
+//! ```
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use crate::units::{Celsius, RelativeHumidity};
+
+/// I2C address of the SHT4x-A variants
+pub const ADDRESS_A: u8 = 0x44;
+
+/// I2C address of the SHT4x-B variants
+pub const ADDRESS_B: u8 = 0x45;
+
+/// I2C address of the SHT4x-C variants
+pub const ADDRESS_C: u8 = 0x46;
+
+/// Command reading the serial number of the sensor
+const READ_SERIAL: u8 = 0x89;
+
+/// Command soft resetting the sensor
+const SOFT_RESET: u8 = 0x94;
+
+/// Time taken by a soft reset
+const SOFT_RESET_TIME: Duration = Duration::from_millis(1);
+
+/// Measurement precision, i.e. repeatability of the measurements
+///
+/// Defaults to `High`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Precision {
+    /// Takes up to 1.7 ms.
+    Low,
+    /// Takes up to 4.5 ms.
+    Medium,
+    /// Takes up to 8.3 ms.
+    #[default]
+    High,
+}
+
+impl Precision {
+    fn command(&self) -> u8 {
+        match self {
+            Precision::Low => 0xE0,
+            Precision::Medium => 0xF6,
+            Precision::High => 0xFD,
+        }
+    }
+
+    /// Maximum time taken by a measurement. See datasheet section 3.2 for more
+    /// details.
+    fn duration(&self) -> Duration {
+        match self {
+            Precision::Low => Duration::from_micros(1_700),
+            Precision::Medium => Duration::from_micros(4_500),
+            Precision::High => Duration::from_micros(8_300),
+        }
+    }
+}
+
+/// Power of the integrated heater
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HeaterPower {
+    Mw20,
+    Mw110,
+    Mw200,
+}
+
+/// Duration of a heater pulse
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HeaterDuration {
+    Ms100,
+    S1,
+}
+
+impl HeaterDuration {
+    /// Maximum time taken by the pulse and the measurement that follows it
+    fn duration(&self) -> Duration {
+        match self {
+            HeaterDuration::Ms100 => Duration::from_millis(110),
+            HeaterDuration::S1 => Duration::from_millis(1_100),
+        }
+    }
+}
+
+/// A measurement of the sensor
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    pub temperature: Celsius,
+    pub humidity: RelativeHumidity,
+}
+
+/// An SHT4x sensor on an I2C bus
+pub struct Sht4x<I2C> {
+    address: u8,
+    i2c: I2C,
+    precision: Precision,
+}
+
+impl<I2C: I2c> Sht4x<I2C> {
+    /// Create a new sensor with the default precision.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the sensor is on.
+    /// - `address`: The I2C address of the sensor, e.g. [ADDRESS_A].
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self {
+            address,
+            i2c,
+            precision: Precision::default(),
+        }
+    }
+
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn set_precision(&mut self, precision: Precision) {
+        self.precision = precision;
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Measure the temperature and the relative humidity.
+    pub async fn measure(&mut self) -> Result<Measurement, Error<I2C::Error>> {
+        let precision = self.precision;
+        self.command(precision.command(), precision.duration())
+            .await
+    }
+
+    /// Pulse the heater, then measure the temperature and the relative
+    /// humidity at high precision just before it turns off.
+    ///
+    /// The heater should not be used more than 10% of the time, so leave the
+    /// sensor to cool down between pulses.
+    pub async fn heat(
+        &mut self,
+        power: HeaterPower,
+        duration: HeaterDuration,
+    ) -> Result<Measurement, Error<I2C::Error>> {
+        let command = match (power, duration) {
+            (HeaterPower::Mw200, HeaterDuration::S1) => 0x39,
+            (HeaterPower::Mw200, HeaterDuration::Ms100) => 0x32,
+            (HeaterPower::Mw110, HeaterDuration::S1) => 0x2F,
+            (HeaterPower::Mw110, HeaterDuration::Ms100) => 0x24,
+            (HeaterPower::Mw20, HeaterDuration::S1) => 0x1E,
+            (HeaterPower::Mw20, HeaterDuration::Ms100) => 0x15,
+        };
+        self.command(command, duration.duration()).await
+    }
+
+    /// Read the unique serial number of the sensor.
+    pub async fn serial_number(&mut self) -> Result<u32, Error<I2C::Error>> {
+        let words = self
+            .read_words(READ_SERIAL, Duration::from_millis(1))
+            .await?;
+        Ok(((words[0] as u32) << 16) | words[1] as u32)
+    }
+
+    /// Soft reset the sensor.
+    pub async fn reset(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(self.address, &[SOFT_RESET])
+            .await
+            .map_err(Error::I2c)?;
+        Timer::after(SOFT_RESET_TIME).await;
+        Ok(())
+    }
+
+    /// Send a measurement command and convert its response.
+    async fn command(
+        &mut self,
+        command: u8,
+        duration: Duration,
+    ) -> Result<Measurement, Error<I2C::Error>> {
+        let [raw_temperature, raw_humidity] = self.read_words(command, duration).await?;
+
+        // See datasheet section 4.6 for more details
+        let temperature = -45.0 + 175.0 * raw_temperature as f32 / 65_535.0;
+        let humidity = -6.0 + 125.0 * raw_humidity as f32 / 65_535.0;
+
+        Ok(Measurement {
+            temperature: Celsius(temperature),
+            humidity: RelativeHumidity(humidity.clamp(0.0, 100.0)),
+        })
+    }
+
+    /// Send a command, wait for it to complete and read the two words of its
+    /// response.
+    async fn read_words(
+        &mut self,
+        command: u8,
+        duration: Duration,
+    ) -> Result<[u16; 2], Error<I2C::Error>> {
+        self.i2c
+            .write(self.address, &[command])
+            .await
+            .map_err(Error::I2c)?;
+        Timer::after(duration).await;
+
+        let mut buf = [0u8; 6];
+        self.i2c
+            .read(self.address, &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+
+        let mut words = [0u16; 2];
+        for (word, chunk) in words.iter_mut().zip(buf.as_chunks::<3>().0) {
+            if crc8(&chunk[..2]) != chunk[2] {
+                return Err(Error::Crc);
+            }
+            *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+        }
+        Ok(words)
+    }
+}
+
+/// Sensirion CRC-8 of a word, with polynomial 0x31 and initial value 0xFF.
+/// See datasheet section 4.4 for more details.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xFFu8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x31
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// A response failed its CRC check.
+    Crc,
+}
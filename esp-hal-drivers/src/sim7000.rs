@@ -0,0 +1,251 @@
+//! # sim7000
+//!
+//! ## Overview
+//!
+//! This driver provides a thin layer over the [AT command engine](crate::at)
+//! to send telemetry over TCP with the SIMCom SIM7000 cellular modem. The
+//! SIM800 series share the same TCP/IP commands and work as well.
+//!
+//! - [Sim7000::attach] waits for the modem to register on the network and
+//!   brings up the data connection of an APN.
+//! - [Sim7000::connect] opens a single TCP connection, to which
+//!   [Sim7000::send] writes data.
+//!
+//! Data received on the connection is not handled by this driver.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut modem = Sim7000::new(At::new(uart)).await?;
+//! modem.attach("hologram", Duration::from_secs(120)).await?;
+//! println!("Signal {:?} dBm", modem.signal_quality().await?);
+//!
+//! modem.connect("telemetry.example.com", 4000).await?;
+//! modem.send(b"temperature=21.5\n").await?;
+//! modem.close().await?;
+//! ```
+
+use embassy_time::{Duration, Instant, Timer};
+use embedded_io_async::{Read, Write};
+
+use crate::at::{self, At};
+
+/// Maximum time taken by the modem to answer a simple command
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Maximum time taken by the modem to bring up the data connection
+const CIICR_TIMEOUT: Duration = Duration::from_secs(85);
+
+/// Maximum time taken by the modem to open a TCP connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(75);
+
+/// Maximum time taken by the modem to send data or close a connection
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum time taken by the modem to shut the data connection down
+const SHUT_TIMEOUT: Duration = Duration::from_secs(65);
+
+/// Interval between two checks of the attachment to the network
+const ATTACH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Largest data sent by a single send command
+const MAX_SEND_LEN: usize = 1460;
+
+/// Attempts to synchronize with the modem after it boots
+const SYNC_ATTEMPTS: u8 = 10;
+
+/// A SIM7000 on a UART
+pub struct Sim7000<UART> {
+    at: At<UART>,
+}
+
+impl<UART: Read + Write> Sim7000<UART> {
+    /// Create a new modem, disabling the echo of commands and checking that
+    /// its SIM card is ready.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::SimNotReady` if the SIM card is missing or locked.
+    pub async fn new(at: At<UART>) -> Result<Self, Error<UART::Error>> {
+        let mut modem = Self { at };
+        modem.at.sync(SYNC_ATTEMPTS).await?;
+        modem.at.command("E0", COMMAND_TIMEOUT).await?;
+        // Numeric error codes
+        modem.at.command("+CMEE=1", COMMAND_TIMEOUT).await?;
+
+        let mut response = [0u8; 32];
+        let len = modem
+            .at
+            .query("+CPIN?", &mut response, COMMAND_TIMEOUT)
+            .await?;
+        if &response[..len] != b"+CPIN: READY" {
+            return Err(Error::SimNotReady);
+        }
+        Ok(modem)
+    }
+
+    /// Release the underlying AT command engine
+    pub fn release(self) -> At<UART> {
+        self.at
+    }
+
+    /// Get the underlying AT command engine, to execute other commands
+    pub fn at(&mut self) -> &mut At<UART> {
+        &mut self.at
+    }
+
+    /// Wait for the modem to attach to the packet network, then bring up the
+    /// data connection of an APN.
+    ///
+    /// # Arguments
+    ///
+    /// - `apn`: The access point name given by the operator.
+    /// - `timeout`: The maximum time to wait for the network.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotAttached` if the modem did not attach in time.
+    pub async fn attach(&mut self, apn: &str, timeout: Duration) -> Result<(), Error<UART::Error>> {
+        self.at.command("+CFUN=1", COMMAND_TIMEOUT).await?;
+
+        let deadline = Instant::now() + timeout;
+        let mut response = [0u8; 16];
+        loop {
+            let len = self
+                .at
+                .query("+CGATT?", &mut response, COMMAND_TIMEOUT)
+                .await?;
+            if &response[..len] == b"+CGATT: 1" {
+                break;
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::NotAttached);
+            }
+            Timer::after(ATTACH_POLL_INTERVAL).await;
+        }
+
+        // Start from a clean state, with a single connection
+        self.shut().await?;
+        self.at.command("+CIPMUX=0", COMMAND_TIMEOUT).await?;
+        self.at
+            .command(format_args!("+CSTT=\"{}\"", apn), COMMAND_TIMEOUT)
+            .await?;
+        self.at.command("+CIICR", CIICR_TIMEOUT).await?;
+
+        // The local IP address is the only answer, without a result code
+        self.at.send("+CIFSR").await?;
+        self.at.read_line(&mut response, COMMAND_TIMEOUT).await?;
+        Ok(())
+    }
+
+    /// Shut the data connection down, closing the TCP connection.
+    pub async fn shut(&mut self) -> Result<(), Error<UART::Error>> {
+        self.at.send("+CIPSHUT").await?;
+        self.at.wait_for(&["SHUT OK"], SHUT_TIMEOUT).await?;
+        Ok(())
+    }
+
+    /// Get the signal strength in dBm, if known
+    pub async fn signal_quality(&mut self) -> Result<Option<i16>, Error<UART::Error>> {
+        let mut response = [0u8; 16];
+        let len = self
+            .at
+            .query("+CSQ", &mut response, COMMAND_TIMEOUT)
+            .await?;
+
+        // `+CSQ: <rssi>,<ber>`, where 99 is unknown
+        let rssi = response[..len]
+            .strip_prefix(b"+CSQ: ")
+            .and_then(|fields| fields.split(|&c| c == b',').next())
+            .and_then(|rssi| core::str::from_utf8(rssi).ok())
+            .and_then(|rssi| rssi.parse::<i16>().ok())
+            .ok_or(Error::Response)?;
+        Ok(match rssi {
+            99 => None,
+            rssi => Some(-113 + 2 * rssi),
+        })
+    }
+
+    /// Open a TCP connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ConnectFailed` if the server could not be reached.
+    pub async fn connect(&mut self, host: &str, port: u16) -> Result<(), Error<UART::Error>> {
+        self.at
+            .command(
+                format_args!("+CIPSTART=\"TCP\",\"{}\",{}", host, port),
+                COMMAND_TIMEOUT,
+            )
+            .await?;
+        match self
+            .at
+            .wait_for(
+                &["CONNECT OK", "ALREADY CONNECT", "CONNECT FAIL"],
+                CONNECT_TIMEOUT,
+            )
+            .await?
+        {
+            2 => Err(Error::ConnectFailed),
+            _ => Ok(()),
+        }
+    }
+
+    /// Send data on the TCP connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::SendFailed` if the modem could not send the data, e.g.
+    /// because the connection is closed.
+    pub async fn send(&mut self, data: &[u8]) -> Result<(), Error<UART::Error>> {
+        for chunk in data.chunks(MAX_SEND_LEN) {
+            self.at
+                .send(format_args!("+CIPSEND={}", chunk.len()))
+                .await?;
+            self.at.wait_for_prompt(COMMAND_TIMEOUT).await?;
+            self.at.write(chunk).await?;
+            if self
+                .at
+                .wait_for(&["SEND OK", "SEND FAIL"], SEND_TIMEOUT)
+                .await?
+                == 1
+            {
+                return Err(Error::SendFailed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Close the TCP connection.
+    pub async fn close(&mut self) -> Result<(), Error<UART::Error>> {
+        self.at.send("+CIPCLOSE").await?;
+        self.at.wait_for(&["CLOSE OK"], SEND_TIMEOUT).await?;
+        Ok(())
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying UART.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// AT command error
+    At(at::Error<E>),
+    /// The SIM card is missing or locked.
+    SimNotReady,
+    /// The modem did not attach to the network in time.
+    NotAttached,
+    /// The answer of the modem is unexpected.
+    Response,
+    /// The TCP connection could not be opened.
+    ConnectFailed,
+    /// The data could not be sent.
+    SendFailed,
+}
+
+impl<E> From<at::Error<E>> for Error<E> {
+    fn from(error: at::Error<E>) -> Self {
+        Error::At(error)
+    }
+}
@@ -0,0 +1,143 @@
+//! # soil
+//!
+//! ## Overview
+//!
+//! This module reads capacitive soil-moisture probes, such as the common
+//! v1.2 and v2.0 boards, through any [AsyncAdcChannel].
+//!
+//! The output voltage of a probe depends on the probe and on the soil, so
+//! each probe is calibrated by measuring it in dry air or soil and in water
+//! or soaked soil. The moisture is interpolated linearly between both
+//! points and averaged over multiple samples to smooth out the noise of the
+//! probe.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let calibration = Calibration::new(Millivolts(2_400), Millivolts(1_100));
+//! let mut probe = SoilMoisture::new(adc.channel(Channel::Channel1), calibration)
+//!     .with_samples(16)
+//!     .with_interval(Duration::from_millis(5));
+//!
+//! let reading = probe.read().await?;
+//! println!("{} mV ({}%)", reading.voltage.0, reading.percent);
+//! ```
+
+use embassy_time::{Duration, Timer};
+
+use crate::{units::Millivolts, AsyncAdcChannel};
+
+/// Default number of samples averaged for each reading
+const DEFAULT_SAMPLES: u8 = 8;
+
+/// Voltages of a probe at both ends of the moisture range
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Calibration {
+    /// Voltage in dry air or dry soil, at 0%
+    pub dry: Millivolts,
+    /// Voltage in water or soaked soil, at 100%
+    pub wet: Millivolts,
+}
+
+impl Calibration {
+    /// Create a new calibration from the voltages measured dry and wet.
+    pub const fn new(dry: Millivolts, wet: Millivolts) -> Self {
+        Self { dry, wet }
+    }
+
+    /// Get the moisture in percent at a voltage, clamped between 0 and 100.
+    pub fn percent(&self, voltage: Millivolts) -> u8 {
+        let range = self.wet.0 - self.dry.0;
+        if range == 0 {
+            return 0;
+        }
+
+        let pct = (voltage.0 - self.dry.0) * 100 / range;
+        pct.clamp(0, 100) as u8
+    }
+}
+
+/// Averaged voltage and moisture of a probe
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MoistureReading {
+    /// Average voltage at the output of the probe
+    pub voltage: Millivolts,
+    /// Moisture in percent
+    pub percent: u8,
+}
+
+/// A capacitive soil-moisture probe on an ADC channel
+pub struct SoilMoisture<A> {
+    adc: A,
+    calibration: Calibration,
+    samples: u8,
+    interval: Duration,
+}
+
+impl<A: AsyncAdcChannel> SoilMoisture<A> {
+    /// Create a new probe averaging 8 samples per reading.
+    ///
+    /// # Arguments
+    ///
+    /// - `adc`: The ADC channel measuring the output of the probe.
+    /// - `calibration`: The voltages of the probe dry and wet.
+    pub fn new(adc: A, calibration: Calibration) -> Self {
+        Self {
+            adc,
+            calibration,
+            samples: DEFAULT_SAMPLES,
+            interval: Duration::from_ticks(0),
+        }
+    }
+
+    /// Set the number of samples averaged for each reading.
+    pub fn with_samples(mut self, samples: u8) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Set the time between two samples of a reading.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the calibration, e.g. after calibrating the probe in place.
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    /// Get the calibration
+    pub fn calibration(&self) -> Calibration {
+        self.calibration
+    }
+
+    /// Measure the average voltage of the probe, e.g. to calibrate it.
+    pub async fn read_millivolts(&mut self) -> Result<Millivolts, A::Error> {
+        let mut sum = 0;
+        for i in 0..self.samples {
+            if i > 0 && self.interval.as_ticks() > 0 {
+                Timer::after(self.interval).await;
+            }
+            sum += self.adc.read_millivolts().await?.0;
+        }
+
+        Ok(Millivolts(sum / self.samples as i32))
+    }
+
+    /// Measure the moisture of the soil.
+    pub async fn read(&mut self) -> Result<MoistureReading, A::Error> {
+        let voltage = self.read_millivolts().await?;
+        Ok(MoistureReading {
+            voltage,
+            percent: self.calibration.percent(voltage),
+        })
+    }
+
+    /// Release the ADC channel
+    pub fn release(self) -> A {
+        self.adc
+    }
+}
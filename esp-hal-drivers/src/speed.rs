@@ -0,0 +1,216 @@
+//! # speed
+//!
+//! ## Overview
+//!
+//! This module controls the speed of a DC motor in closed loop, combining an
+//! [Encoder] on the shaft of the motor with a [Motor] from `esp-hal-pwm`.
+//!
+//! - At a fixed rate, the speed is measured from the encoder and a PID loop
+//!   computes the speed percentage applied to the motor.
+//! - The PID loop runs in fixed-point integer math. Gains are given in
+//!   thousandths, with the error in positions/s and the output in percent.
+//! - The integral stops accumulating while the output is saturated, so the
+//!   motor does not overshoot after being stalled.
+//! - The encoder keeps counting while waiting for the next period and while
+//!   the motor ramps, so [SpeedController::run] should run in a dedicated
+//!   task.
+//!
+//! The target speed is read from an atomic at every period, so it can be
+//! changed from any task.
+//!
+//! This module requires the `motor` feature.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! static TARGET: AtomicI32 = AtomicI32::new(0);
+//!
+//! type Controller = SpeedController<'static, Input<'static>, Input<'static>, GpioPin<4>, GpioPin<5>>;
+//!
+//! #[embassy_executor::task]
+//! async fn control(mut controller: Controller) {
+//!     let error = controller.run(&TARGET).await;
+//!     println!("Speed control stopped: {:?}", error);
+//! }
+//!
+//! // Without a glitch filter, the encoder does not miss edges between periods
+//! let encoder = Encoder::new(a, b, Decoding::X4)?.with_filter(Duration::from_ticks(0));
+//! let motor = Motor::new(in1, in2, 20_000)?;
+//! let controller = SpeedController::new(encoder, motor, Gains::new(50, 200, 0))
+//!     .with_rate(200);
+//! spawner.spawn(control(controller)).ok();
+//!
+//! TARGET.store(1_200, Ordering::Relaxed);
+//! ```
+
+use core::sync::atomic::{AtomicI32, Ordering};
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Ticker};
+use embedded_hal::digital::InputPin;
+use embedded_hal_async::digital::Wait;
+use esp_hal::{gpio::OutputPin, peripheral::Peripheral};
+use esp_hal_pwm::motor::Motor;
+
+use crate::encoder::{self, Encoder};
+
+/// Default rate of the control loop in Hz
+const DEFAULT_RATE: u32 = 100;
+
+/// Scale of the gains, which are given in thousandths
+const GAIN_SCALE: i64 = 1_000;
+
+/// Number of µs in a second
+const MICROS_PER_SECOND: i64 = 1_000_000;
+
+/// Gains of a PID loop, in thousandths
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Gains {
+    kp: i32,
+    ki: i32,
+    kd: i32,
+}
+
+impl Gains {
+    /// Create new gains.
+    ///
+    /// # Arguments
+    ///
+    /// - `kp`: The proportional gain, in thousandths of percent per
+    ///   position/s of error.
+    /// - `ki`: The integral gain, in thousandths of percent per position of
+    ///   accumulated error.
+    /// - `kd`: The derivative gain, in thousandths of percent per position/s²
+    ///   of acceleration.
+    pub const fn new(kp: i32, ki: i32, kd: i32) -> Self {
+        Self { kp, ki, kd }
+    }
+}
+
+/// A DC motor whose speed is controlled from an encoder
+pub struct SpeedController<'a, A, B, IN1: OutputPin, IN2: OutputPin> {
+    encoder: Encoder<A, B>,
+    motor: Motor<'a, IN1, IN2>,
+    gains: Gains,
+    period: Duration,
+    /// Last measured speed in positions/s
+    speed: i32,
+}
+
+impl<'a, A, B, IN1, IN2> SpeedController<'a, A, B, IN1, IN2>
+where
+    A: InputPin + Wait,
+    B: InputPin + Wait,
+    IN1: OutputPin + Peripheral<P = IN1>,
+    IN2: OutputPin + Peripheral<P = IN2>,
+{
+    /// Create a new controller running at 100 Hz.
+    pub fn new(encoder: Encoder<A, B>, motor: Motor<'a, IN1, IN2>, gains: Gains) -> Self {
+        Self {
+            encoder,
+            motor,
+            gains,
+            period: Duration::from_hz(DEFAULT_RATE as u64),
+            speed: 0,
+        }
+    }
+
+    /// Set the rate of the control loop in Hz.
+    pub fn with_rate(mut self, rate: u32) -> Self {
+        self.period = Duration::from_hz(rate.max(1) as u64);
+        self
+    }
+
+    /// Set the gains, e.g. while tuning the loop.
+    pub fn set_gains(&mut self, gains: Gains) {
+        self.gains = gains;
+    }
+
+    /// Get the last measured speed in positions/s
+    pub fn speed(&self) -> i32 {
+        self.speed
+    }
+
+    /// Release the underlying encoder and motor
+    pub fn release(self) -> (Encoder<A, B>, Motor<'a, IN1, IN2>) {
+        (self.encoder, self.motor)
+    }
+
+    /// Control the speed of the motor towards a target in positions/s,
+    /// negative backwards.
+    ///
+    /// Only returns if an error occurs.
+    pub async fn run(&mut self, target: &AtomicI32) -> Result<(), Error> {
+        let mut ticker = Ticker::every(self.period);
+        let mut last_position = self.encoder.position();
+        let mut last_time = Instant::now();
+        let mut integral = 0i64;
+
+        loop {
+            if let Either::Second(error) = select(ticker.next(), count(&mut self.encoder)).await {
+                return Err(error);
+            }
+
+            // Measure the speed over the time actually elapsed, as periods
+            // can be late while the motor ramps
+            let now = Instant::now();
+            let elapsed = ((now - last_time).as_micros() as i64).max(1);
+            let position = self.encoder.position();
+            let speed = (position - last_position) as i64 * MICROS_PER_SECOND / elapsed;
+            let acceleration = (speed - self.speed as i64) * MICROS_PER_SECOND / elapsed;
+            last_position = position;
+            last_time = now;
+            self.speed = speed as i32;
+
+            let error = target.load(Ordering::Relaxed) as i64 - speed;
+            let accumulated = integral + error * elapsed;
+            let output = (self.gains.kp as i64 * error
+                + self.gains.ki as i64 * accumulated / MICROS_PER_SECOND
+                - self.gains.kd as i64 * acceleration)
+                / GAIN_SCALE;
+
+            // Only integrate while the output is not saturated
+            if (-100..=100).contains(&output) {
+                integral = accumulated;
+            }
+
+            let speed = output.clamp(-100, 100) as i8;
+            match select(self.motor.set_speed(speed), count(&mut self.encoder)).await {
+                Either::First(result) => result?,
+                Either::Second(error) => return Err(error),
+            }
+        }
+    }
+}
+
+/// Count the positions of an encoder until an error occurs.
+async fn count<A: InputPin + Wait, B: InputPin + Wait>(encoder: &mut Encoder<A, B>) -> Error {
+    loop {
+        if let Err(error) = encoder.wait_for_change().await {
+            return error.into();
+        }
+    }
+}
+
+/// All possible errors in this module
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Error of the encoder
+    Encoder(encoder::Error),
+    /// Error of the motor
+    Motor(esp_hal_pwm::Error),
+}
+
+impl From<encoder::Error> for Error {
+    fn from(error: encoder::Error) -> Self {
+        Error::Encoder(error)
+    }
+}
+
+impl From<esp_hal_pwm::Error> for Error {
+    fn from(error: esp_hal_pwm::Error) -> Self {
+        Error::Motor(error)
+    }
+}
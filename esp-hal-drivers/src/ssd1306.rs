@@ -0,0 +1,372 @@
+//! # ssd1306
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with SSD1306 monochrome
+//! OLED displays over I2C.
+//!
+//! Drawing happens in a framebuffer held by the driver, and only the region
+//! that changed since the last [Ssd1306::flush] is sent to the display, so
+//! small updates of a status UI only take a short transfer.
+//!
+//! With the `graphics` feature, the driver implements the
+//! [embedded-graphics](https://docs.rs/embedded-graphics) `DrawTarget`
+//! trait, so text, shapes and images can be drawn directly.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut display = Ssd1306::new(i2c, ADDRESS_PRIMARY, DisplaySize::Size128x64).await?;
+//!
+//! let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+//! Text::new("Hello, world!", Point::new(0, 10), style).draw(&mut display)?;
+//! display.flush().await?;
+//!
+//! // Only the changed pixels are sent on the next flush
+//! display.set_pixel(127, 63, true);
+//! display.flush().await?;
+//! ```
+
+use embedded_hal_async::i2c::{I2c, Operation};
+
+/// I2C address of the display when D/C is tied to ground
+pub const ADDRESS_PRIMARY: u8 = 0x3C;
+
+/// I2C address of the display when D/C is tied to the supply
+pub const ADDRESS_SECONDARY: u8 = 0x3D;
+
+/// Control byte preceding a stream of commands
+const CONTROL_COMMAND: u8 = 0x00;
+
+/// Control byte preceding a stream of display data
+const CONTROL_DATA: u8 = 0x40;
+
+/// Size of the framebuffer of the largest display
+const BUFFER_SIZE: usize = 128 * 64 / 8;
+
+/// Commands of the display. See datasheet section 9 for more details.
+struct Command;
+
+impl Command {
+    const SET_CONTRAST: u8 = 0x81;
+    const DISPLAY_RAM: u8 = 0xA4;
+    const NORMAL: u8 = 0xA6;
+    const INVERTED: u8 = 0xA7;
+    const DISPLAY_OFF: u8 = 0xAE;
+    const DISPLAY_ON: u8 = 0xAF;
+    const ADDRESSING_MODE: u8 = 0x20;
+    const COLUMN_ADDRESS: u8 = 0x21;
+    const PAGE_ADDRESS: u8 = 0x22;
+    const START_LINE: u8 = 0x40;
+    const SEGMENT_REMAP: u8 = 0xA1;
+    const MULTIPLEX_RATIO: u8 = 0xA8;
+    const COM_SCAN_DECREMENTING: u8 = 0xC8;
+    const DISPLAY_OFFSET: u8 = 0xD3;
+    const COM_PINS: u8 = 0xDA;
+    const CLOCK_DIVIDER: u8 = 0xD5;
+    const PRECHARGE: u8 = 0xD9;
+    const VCOMH_DESELECT: u8 = 0xDB;
+    const CHARGE_PUMP: u8 = 0x8D;
+    const DEACTIVATE_SCROLL: u8 = 0x2E;
+}
+
+/// Resolution of the display
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DisplaySize {
+    Size128x64,
+    Size128x32,
+    Size96x16,
+}
+
+impl DisplaySize {
+    pub fn width(&self) -> u8 {
+        match self {
+            DisplaySize::Size128x64 | DisplaySize::Size128x32 => 128,
+            DisplaySize::Size96x16 => 96,
+        }
+    }
+
+    pub fn height(&self) -> u8 {
+        match self {
+            DisplaySize::Size128x64 => 64,
+            DisplaySize::Size128x32 => 32,
+            DisplaySize::Size96x16 => 16,
+        }
+    }
+
+    /// Number of pages of 8 rows
+    fn pages(&self) -> u8 {
+        self.height() / 8
+    }
+
+    /// Value of the COM pins configuration
+    fn com_pins(&self) -> u8 {
+        match self {
+            DisplaySize::Size128x64 => 0x12,
+            DisplaySize::Size128x32 | DisplaySize::Size96x16 => 0x02,
+        }
+    }
+}
+
+/// Region of the framebuffer changed since the last flush, in columns and
+/// pages
+#[derive(Debug, Copy, Clone)]
+struct Dirty {
+    first_column: u8,
+    last_column: u8,
+    first_page: u8,
+    last_page: u8,
+}
+
+impl Dirty {
+    fn include(region: Option<Self>, column: u8, page: u8) -> Self {
+        match region {
+            Some(region) => Self {
+                first_column: region.first_column.min(column),
+                last_column: region.last_column.max(column),
+                first_page: region.first_page.min(page),
+                last_page: region.last_page.max(page),
+            },
+            None => Self {
+                first_column: column,
+                last_column: column,
+                first_page: page,
+                last_page: page,
+            },
+        }
+    }
+}
+
+/// An SSD1306 display on an I2C bus
+pub struct Ssd1306<I2C> {
+    address: u8,
+    i2c: I2C,
+    size: DisplaySize,
+    /// Pixels of the display, one byte per column of 8 rows of a page
+    buffer: [u8; BUFFER_SIZE],
+    dirty: Option<Dirty>,
+}
+
+impl<I2C: I2c> Ssd1306<I2C> {
+    /// Create a new display, initialize it and clear it.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the display is on.
+    /// - `address`: The I2C address of the display, e.g. [ADDRESS_PRIMARY].
+    /// - `size`: The resolution of the display.
+    pub async fn new(i2c: I2C, address: u8, size: DisplaySize) -> Result<Self, Error<I2C::Error>> {
+        let mut display = Self {
+            address,
+            i2c,
+            size,
+            buffer: [0; BUFFER_SIZE],
+            dirty: None,
+        };
+
+        // See the application note of the datasheet for more details
+        display
+            .send_commands(&[
+                Command::DISPLAY_OFF,
+                Command::CLOCK_DIVIDER,
+                0x80,
+                Command::MULTIPLEX_RATIO,
+                size.height() - 1,
+                Command::DISPLAY_OFFSET,
+                0x00,
+                Command::START_LINE,
+                Command::CHARGE_PUMP,
+                0x14,
+                Command::ADDRESSING_MODE,
+                0x00,
+                Command::SEGMENT_REMAP,
+                Command::COM_SCAN_DECREMENTING,
+                Command::COM_PINS,
+                size.com_pins(),
+                Command::SET_CONTRAST,
+                0x8F,
+                Command::PRECHARGE,
+                0xF1,
+                Command::VCOMH_DESELECT,
+                0x40,
+                Command::DISPLAY_RAM,
+                Command::NORMAL,
+                Command::DEACTIVATE_SCROLL,
+            ])
+            .await?;
+
+        display.flush_all().await?;
+        display.send_commands(&[Command::DISPLAY_ON]).await?;
+        Ok(display)
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    pub fn display_size(&self) -> DisplaySize {
+        self.size
+    }
+
+    /// Set a pixel of the framebuffer. Pixels outside of the display are
+    /// ignored.
+    pub fn set_pixel(&mut self, x: u8, y: u8, on: bool) {
+        if x >= self.size.width() || y >= self.size.height() {
+            return;
+        }
+
+        let page = y / 8;
+        let index = page as usize * self.size.width() as usize + x as usize;
+        let mask = 1 << (y % 8);
+        let byte = if on {
+            self.buffer[index] | mask
+        } else {
+            self.buffer[index] & !mask
+        };
+
+        if byte != self.buffer[index] {
+            self.buffer[index] = byte;
+            self.dirty = Some(Dirty::include(self.dirty, x, page));
+        }
+    }
+
+    /// Clear the framebuffer.
+    pub fn clear(&mut self) {
+        let len = self.size.width() as usize * self.size.pages() as usize;
+        self.buffer[..len].fill(0);
+        self.dirty = Some(Dirty {
+            first_column: 0,
+            last_column: self.size.width() - 1,
+            first_page: 0,
+            last_page: self.size.pages() - 1,
+        });
+    }
+
+    /// Send the region of the framebuffer changed since the last flush to
+    /// the display.
+    pub async fn flush(&mut self) -> Result<(), Error<I2C::Error>> {
+        let Some(dirty) = self.dirty else {
+            return Ok(());
+        };
+
+        self.send_commands(&[
+            Command::COLUMN_ADDRESS,
+            dirty.first_column,
+            dirty.last_column,
+            Command::PAGE_ADDRESS,
+            dirty.first_page,
+            dirty.last_page,
+        ])
+        .await?;
+
+        // The display wraps to the next page at the end of the column range
+        let width = self.size.width() as usize;
+        for page in dirty.first_page..=dirty.last_page {
+            let start = page as usize * width;
+            let columns = start + dirty.first_column as usize..=start + dirty.last_column as usize;
+            self.i2c
+                .transaction(
+                    self.address,
+                    &mut [
+                        Operation::Write(&[CONTROL_DATA]),
+                        Operation::Write(&self.buffer[columns]),
+                    ],
+                )
+                .await
+                .map_err(Error::I2c)?;
+        }
+
+        self.dirty = None;
+        Ok(())
+    }
+
+    /// Send the whole framebuffer to the display.
+    pub async fn flush_all(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.dirty = Some(Dirty {
+            first_column: 0,
+            last_column: self.size.width() - 1,
+            first_page: 0,
+            last_page: self.size.pages() - 1,
+        });
+        self.flush().await
+    }
+
+    /// Set the contrast of the display, from 0 to 255.
+    pub async fn set_contrast(&mut self, contrast: u8) -> Result<(), Error<I2C::Error>> {
+        self.send_commands(&[Command::SET_CONTRAST, contrast]).await
+    }
+
+    /// Invert the pixels of the display, without changing the framebuffer.
+    pub async fn set_inverted(&mut self, inverted: bool) -> Result<(), Error<I2C::Error>> {
+        let command = if inverted {
+            Command::INVERTED
+        } else {
+            Command::NORMAL
+        };
+        self.send_commands(&[command]).await
+    }
+
+    /// Turn the display on or off. The framebuffer is kept while off.
+    pub async fn set_on(&mut self, on: bool) -> Result<(), Error<I2C::Error>> {
+        let command = if on {
+            Command::DISPLAY_ON
+        } else {
+            Command::DISPLAY_OFF
+        };
+        self.send_commands(&[command]).await
+    }
+
+    async fn send_commands(&mut self, commands: &[u8]) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .transaction(
+                self.address,
+                &mut [
+                    Operation::Write(&[CONTROL_COMMAND]),
+                    Operation::Write(commands),
+                ],
+            )
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<I2C: I2c> embedded_graphics_core::draw_target::DrawTarget for Ssd1306<I2C> {
+    type Color = embedded_graphics_core::pixelcolor::BinaryColor;
+    type Error = core::convert::Infallible;
+
+    /// Draw pixels to the framebuffer. Call [Ssd1306::flush] to show them.
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = embedded_graphics_core::Pixel<Self::Color>>,
+    {
+        for embedded_graphics_core::Pixel(point, color) in pixels {
+            if let (Ok(x), Ok(y)) = (u8::try_from(point.x), u8::try_from(point.y)) {
+                self.set_pixel(x, y, color.is_on());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<I2C: I2c> embedded_graphics_core::geometry::OriginDimensions for Ssd1306<I2C> {
+    fn size(&self) -> embedded_graphics_core::geometry::Size {
+        embedded_graphics_core::geometry::Size::new(
+            self.size.width() as u32,
+            self.size.height() as u32,
+        )
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+}
@@ -0,0 +1,259 @@
+//! # stepper
+//!
+//! ## Overview
+//!
+//! This driver moves stepper motors through step/direction drivers, such as
+//! the Allegro A4988, the TI DRV8825 or the Trinamic TMC2209 in stand-alone
+//! mode.
+//!
+//! - Moves follow a trapezoidal profile: the motor accelerates up to its
+//!   maximum speed, cruises, then decelerates to stop on the target. The
+//!   speed of every step is computed in integer math.
+//! - Steps are timed with the timer of the executor, so other tasks run
+//!   between steps.
+//! - An emergency-stop input, such as a limit switch, stops the motor at
+//!   once when pulled low.
+//!
+//! The position is counted in steps, or microsteps if the driver is set to
+//! microstepping, and kept up to date even if a move is cancelled.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let step = Output::new(peripherals.GPIO6, Level::Low);
+//! let dir = Output::new(peripherals.GPIO7, Level::Low);
+//! let limit = Input::new(peripherals.GPIO10, Pull::Up);
+//! let mut stepper = Stepper::new(step, dir)
+//!     .with_max_speed(2_000)
+//!     .with_acceleration(8_000)
+//!     .with_stop_pin(limit);
+//!
+//! stepper.move_to(3_200).await?;
+//! stepper.move_by(-1_600).await?;
+//! println!("At {}", stepper.position());
+//! ```
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{block_for, Duration, Instant, Timer};
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal_async::digital::Wait;
+
+/// Default maximum speed in steps/s
+const DEFAULT_MAX_SPEED: u32 = 1_000;
+
+/// Default acceleration in steps/s²
+const DEFAULT_ACCELERATION: u32 = 2_000;
+
+/// Length of the step pulse, long enough for the slowest drivers
+const STEP_PULSE: Duration = Duration::from_micros(2);
+
+/// Time for the driver to latch a new direction before a step
+const DIR_SETUP: Duration = Duration::from_micros(1);
+
+/// A stepper motor on a step/direction driver
+///
+/// `STOP` is the emergency-stop pin, or [NoStopPin].
+pub struct Stepper<STEP, DIR, STOP = NoStopPin> {
+    step: STEP,
+    dir: DIR,
+    stop: STOP,
+    /// Position in steps
+    position: i32,
+    max_speed: u32,
+    acceleration: u32,
+    invert_direction: bool,
+}
+
+impl<STEP: OutputPin, DIR: OutputPin> Stepper<STEP, DIR> {
+    /// Create a new motor at position 0.
+    pub fn new(step: STEP, dir: DIR) -> Self {
+        Self {
+            step,
+            dir,
+            stop: NoStopPin,
+            position: 0,
+            max_speed: DEFAULT_MAX_SPEED,
+            acceleration: DEFAULT_ACCELERATION,
+            invert_direction: false,
+        }
+    }
+
+    /// Set the emergency-stop pin, active low.
+    pub fn with_stop_pin<P: InputPin + Wait>(self, stop: P) -> Stepper<STEP, DIR, P> {
+        Stepper {
+            step: self.step,
+            dir: self.dir,
+            stop,
+            position: self.position,
+            max_speed: self.max_speed,
+            acceleration: self.acceleration,
+            invert_direction: self.invert_direction,
+        }
+    }
+
+    /// Move to a position and wait for the motor to stop there.
+    pub async fn move_to(&mut self, target: i32) -> Result<(), Error> {
+        self.split().0.run(target).await
+    }
+
+    /// Move by a number of steps from the current position.
+    pub async fn move_by(&mut self, steps: i32) -> Result<(), Error> {
+        self.move_to(self.position.saturating_add(steps)).await
+    }
+}
+
+impl<STEP: OutputPin, DIR: OutputPin, P: InputPin + Wait> Stepper<STEP, DIR, P> {
+    /// Move to a position and wait for the motor to stop there.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Stopped` if the emergency-stop pin is or gets pulled
+    /// low, in which case the motor stops at once.
+    pub async fn move_to(&mut self, target: i32) -> Result<(), Error> {
+        let (mut motion, stop) = self.split();
+        if stop.is_low().map_err(|_| Error::Pin)? {
+            return Err(Error::Stopped);
+        }
+
+        match select(motion.run(target), stop.wait_for_low()).await {
+            Either::First(result) => result,
+            Either::Second(Ok(())) => Err(Error::Stopped),
+            Either::Second(Err(_)) => Err(Error::Pin),
+        }
+    }
+
+    /// Move by a number of steps from the current position.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Stopped` if the emergency-stop pin is or gets pulled
+    /// low, in which case the motor stops at once.
+    pub async fn move_by(&mut self, steps: i32) -> Result<(), Error> {
+        self.move_to(self.position.saturating_add(steps)).await
+    }
+
+    /// Release the underlying step, direction and emergency-stop pins
+    pub fn release_with_stop_pin(self) -> (STEP, DIR, P) {
+        (self.step, self.dir, self.stop)
+    }
+}
+
+impl<STEP: OutputPin, DIR: OutputPin, STOP> Stepper<STEP, DIR, STOP> {
+    /// Set the maximum speed in steps/s.
+    pub fn with_max_speed(mut self, max_speed: u32) -> Self {
+        self.max_speed = max_speed.max(1);
+        self
+    }
+
+    /// Set the acceleration and deceleration in steps/s².
+    pub fn with_acceleration(mut self, acceleration: u32) -> Self {
+        self.acceleration = acceleration.max(1);
+        self
+    }
+
+    /// Invert the direction of the motor, e.g. to match its wiring.
+    pub fn with_invert_direction(mut self, invert_direction: bool) -> Self {
+        self.invert_direction = invert_direction;
+        self
+    }
+
+    /// Release the underlying step and direction pins
+    pub fn release(self) -> (STEP, DIR) {
+        (self.step, self.dir)
+    }
+
+    /// Get the position in steps
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Set the position without moving, e.g. after homing.
+    pub fn set_position(&mut self, position: i32) {
+        self.position = position;
+    }
+
+    /// Set the maximum speed in steps/s of the next moves.
+    pub fn set_max_speed(&mut self, max_speed: u32) {
+        self.max_speed = max_speed.max(1);
+    }
+
+    /// Set the acceleration in steps/s² of the next moves.
+    pub fn set_acceleration(&mut self, acceleration: u32) {
+        self.acceleration = acceleration.max(1);
+    }
+
+    /// Borrow the motion apart from the emergency-stop pin.
+    fn split(&mut self) -> (Motion<'_, STEP, DIR>, &mut STOP) {
+        let motion = Motion {
+            step: &mut self.step,
+            dir: &mut self.dir,
+            position: &mut self.position,
+            max_speed: self.max_speed,
+            acceleration: self.acceleration,
+            invert_direction: self.invert_direction,
+        };
+        (motion, &mut self.stop)
+    }
+}
+
+/// Placeholder of a motor without an emergency-stop pin
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NoStopPin;
+
+/// The pins and settings of a motor during a move
+struct Motion<'a, STEP, DIR> {
+    step: &'a mut STEP,
+    dir: &'a mut DIR,
+    position: &'a mut i32,
+    max_speed: u32,
+    acceleration: u32,
+    invert_direction: bool,
+}
+
+impl<STEP: OutputPin, DIR: OutputPin> Motion<'_, STEP, DIR> {
+    /// Move to a position along a trapezoidal profile.
+    async fn run(&mut self, target: i32) -> Result<(), Error> {
+        let forward = target > *self.position;
+        let steps = target.abs_diff(*self.position) as u64;
+        if steps == 0 {
+            return Ok(());
+        }
+
+        self.dir
+            .set_state((forward != self.invert_direction).into())
+            .map_err(|_| Error::Pin)?;
+        block_for(DIR_SETUP);
+
+        // After k steps from rest, the speed is sqrt(2·a·k)
+        let two_a = 2 * self.acceleration as u64;
+        let mut next = Instant::now();
+        for k in 0..steps {
+            Timer::at(next).await;
+
+            self.step.set_high().map_err(|_| Error::Pin)?;
+            block_for(STEP_PULSE);
+            self.step.set_low().map_err(|_| Error::Pin)?;
+            *self.position += if forward { 1 } else { -1 };
+
+            let accelerating = (two_a * (k + 1)).isqrt();
+            let decelerating = (two_a * (steps - k)).isqrt();
+            let speed = accelerating
+                .min(decelerating)
+                .min(self.max_speed as u64)
+                .max(1);
+            next += Duration::from_micros(1_000_000 / speed);
+        }
+        Ok(())
+    }
+}
+
+/// All possible errors in this driver
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Error of a pin
+    Pin,
+    /// The emergency-stop pin was pulled low.
+    Stopped,
+}
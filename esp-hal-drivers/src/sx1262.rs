@@ -0,0 +1,585 @@
+//! # sx1262
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Semtech SX1262
+//! LoRa radio over SPI, as found on the Ebyte E22 and Seeed Wio-SX1262
+//! modules.
+//!
+//! - The radio is configured with a [Config] shared with the other LoRa
+//!   radios, and implements the [Radio] trait.
+//! - The radio holds its BUSY pin high while it processes a command, so the
+//!   driver waits for the pin before every command.
+//! - The radio raises its DIO1 pin when a packet is sent or received, so the
+//!   driver waits for the pin instead of polling the radio.
+//! - The wiring of the module, such as a TCXO or an RF switch driven by DIO2,
+//!   is described by a [Board].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let config = Config::new(868_100_000).with_tx_power(22);
+//! let board = Board::new().with_tcxo(TcxoVoltage::V1_8);
+//! let mut radio = Sx1262::new(spi, busy, dio1, &config, &board).await?;
+//!
+//! radio.transmit(b"hello").await?;
+//!
+//! let mut buf = [0u8; 255];
+//! let (len, status) = radio.receive(&mut buf).await?;
+//! println!("{:?} at {} dBm, SNR {} dB", &buf[..len], status.rssi, status.snr);
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::{
+    digital::Wait,
+    spi::{Operation, SpiDevice},
+};
+
+use crate::lora::{Bandwidth, Config, PacketStatus, Radio};
+
+/// Frequency of the crystal of the radio in Hz
+const CRYSTAL_HZ: u64 = 32_000_000;
+
+/// Maximum time taken by the radio to process a command, calibrations
+/// included
+const BUSY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Time given to the radio to send a packet, on top of its time on air
+const TX_TIMEOUT_MARGIN: Duration = Duration::from_millis(100);
+
+/// Time taken by a TCXO to start, given to the radio
+const TCXO_STARTUP: Duration = Duration::from_millis(5);
+
+/// Largest packet sent by the radio
+const MAX_PACKET_LEN: usize = 255;
+
+/// Commands of the radio. See datasheet section 11 for more details.
+struct Command;
+
+impl Command {
+    const CLEAR_IRQ_STATUS: u8 = 0x02;
+    const SET_DIO_IRQ_PARAMS: u8 = 0x08;
+    const WRITE_REGISTER: u8 = 0x0D;
+    const WRITE_BUFFER: u8 = 0x0E;
+    const GET_IRQ_STATUS: u8 = 0x12;
+    const GET_RX_BUFFER_STATUS: u8 = 0x13;
+    const GET_PACKET_STATUS: u8 = 0x14;
+    const READ_BUFFER: u8 = 0x1E;
+    const SET_STANDBY: u8 = 0x80;
+    const SET_RX: u8 = 0x82;
+    const SET_TX: u8 = 0x83;
+    const SET_SLEEP: u8 = 0x84;
+    const SET_RF_FREQUENCY: u8 = 0x86;
+    const CALIBRATE: u8 = 0x89;
+    const SET_PACKET_TYPE: u8 = 0x8A;
+    const SET_MODULATION_PARAMS: u8 = 0x8B;
+    const SET_PACKET_PARAMS: u8 = 0x8C;
+    const SET_TX_PARAMS: u8 = 0x8E;
+    const SET_BUFFER_BASE_ADDRESS: u8 = 0x8F;
+    const SET_PA_CONFIG: u8 = 0x95;
+    const SET_REGULATOR_MODE: u8 = 0x96;
+    const SET_DIO3_AS_TCXO_CTRL: u8 = 0x97;
+    const CALIBRATE_IMAGE: u8 = 0x98;
+    const SET_DIO2_AS_RF_SWITCH_CTRL: u8 = 0x9D;
+    const GET_STATUS: u8 = 0xC0;
+}
+
+/// Registers of the radio. See datasheet section 12 for more details.
+struct Register;
+
+impl Register {
+    const LORA_SYNC_WORD_MSB: u16 = 0x0740;
+    const OCP_CONFIGURATION: u16 = 0x08E7;
+}
+
+/// Standby mode running on the RC oscillator
+const STANDBY_RC: u8 = 0x00;
+
+/// Sleep mode keeping the configuration
+const SLEEP_WARM_START: u8 = 0x04;
+
+/// Packet type of LoRa
+const PACKET_TYPE_LORA: u8 = 0x01;
+
+/// Regulator mode using the DC-DC converter
+const REGULATOR_DC_DC: u8 = 0x01;
+
+/// Calibration of all blocks
+const CALIBRATE_ALL: u8 = 0x7F;
+
+/// PA configuration of the SX1262 reaching +22 dBm
+const PA_CONFIG_22_DBM: [u8; 4] = [0x04, 0x07, 0x00, 0x01];
+
+/// Ramp time of the PA of 200 µs
+const RAMP_200_US: u8 = 0x04;
+
+/// Over current protection at 140 mA, in steps of 2.5 mA
+const OCP_140_MA: u8 = 0x38;
+
+/// Header type of explicit headers
+const HEADER_EXPLICIT: u8 = 0x00;
+
+/// Timeouts of SetTx and SetRx disabling the timeout, or receiving
+/// continuously
+const TIMEOUT_NONE: [u8; 3] = [0x00, 0x00, 0x00];
+const RX_CONTINUOUS: [u8; 3] = [0xFF, 0xFF, 0xFF];
+
+/// Flags of the IRQ status
+const IRQ_TX_DONE: u16 = 1 << 0;
+const IRQ_RX_DONE: u16 = 1 << 1;
+const IRQ_HEADER_ERROR: u16 = 1 << 5;
+const IRQ_CRC_ERROR: u16 = 1 << 6;
+const IRQ_TIMEOUT: u16 = 1 << 9;
+const IRQ_ALL: u16 = 0x03FF;
+
+/// Voltage supplied by DIO3 to a TCXO
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TcxoVoltage {
+    V1_6 = 0x00,
+    V1_7 = 0x01,
+    V1_8 = 0x02,
+    V2_2 = 0x03,
+    V2_4 = 0x04,
+    V2_7 = 0x05,
+    V3_0 = 0x06,
+    V3_3 = 0x07,
+}
+
+impl TcxoVoltage {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// Wiring of the radio on its module
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Board {
+    tcxo: Option<TcxoVoltage>,
+    dio2_as_rf_switch: bool,
+    dc_dc: bool,
+}
+
+impl Default for Board {
+    /// Module with a crystal, DIO2 driving the RF switch and the DC-DC
+    /// converter, like most modules
+    fn default() -> Self {
+        Self {
+            tcxo: None,
+            dio2_as_rf_switch: true,
+            dc_dc: true,
+        }
+    }
+}
+
+impl Board {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the voltage DIO3 supplies to the TCXO of the module, if any.
+    pub fn with_tcxo(mut self, voltage: TcxoVoltage) -> Self {
+        self.tcxo = Some(voltage);
+        self
+    }
+
+    /// Set whether DIO2 drives the RF switch of the module.
+    pub fn with_dio2_as_rf_switch(mut self, enabled: bool) -> Self {
+        self.dio2_as_rf_switch = enabled;
+        self
+    }
+
+    /// Set whether the module has the inductor of the DC-DC converter, or
+    /// uses the LDO.
+    pub fn with_dc_dc(mut self, enabled: bool) -> Self {
+        self.dc_dc = enabled;
+        self
+    }
+}
+
+/// A SX1262 on a SPI device
+///
+/// `BUSY` and `DIO1` are the pins the BUSY and DIO1 outputs of the radio are
+/// connected to.
+pub struct Sx1262<SPI, BUSY, DIO1> {
+    spi: SPI,
+    busy: BUSY,
+    dio1: DIO1,
+    config: Config,
+    /// Whether the radio sleeps, holding its BUSY pin high
+    sleeping: bool,
+}
+
+impl<SPI: SpiDevice, BUSY: Wait, DIO1: Wait> Sx1262<SPI, BUSY, DIO1> {
+    /// Create a new radio, set it up for its module and configure it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Timeout` if the radio stays busy, e.g. because it is
+    /// not connected.
+    pub async fn new(
+        spi: SPI,
+        busy: BUSY,
+        dio1: DIO1,
+        config: &Config,
+        board: &Board,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let mut radio = Self {
+            spi,
+            busy,
+            dio1,
+            config: *config,
+            sleeping: false,
+        };
+
+        radio.command(Command::SET_STANDBY, &[STANDBY_RC]).await?;
+        if let Some(voltage) = board.tcxo {
+            // The delay is in steps of 15.625 µs
+            let delay = (TCXO_STARTUP.as_micros() * 64 / 1000) as u32;
+            let delay = delay.to_be_bytes();
+            radio
+                .command(
+                    Command::SET_DIO3_AS_TCXO_CTRL,
+                    &[voltage.bits(), delay[1], delay[2], delay[3]],
+                )
+                .await?;
+        }
+        if board.dc_dc {
+            radio
+                .command(Command::SET_REGULATOR_MODE, &[REGULATOR_DC_DC])
+                .await?;
+        }
+        radio.command(Command::CALIBRATE, &[CALIBRATE_ALL]).await?;
+        if board.dio2_as_rf_switch {
+            radio
+                .command(Command::SET_DIO2_AS_RF_SWITCH_CTRL, &[1])
+                .await?;
+        }
+
+        radio
+            .command(Command::SET_PACKET_TYPE, &[PACKET_TYPE_LORA])
+            .await?;
+        radio
+            .command(Command::SET_BUFFER_BASE_ADDRESS, &[0, 0])
+            .await?;
+        radio.configure(config).await?;
+        Ok(radio)
+    }
+
+    /// Release the underlying SPI device, BUSY pin and DIO1 pin
+    pub fn release(self) -> (SPI, BUSY, DIO1) {
+        (self.spi, self.busy, self.dio1)
+    }
+
+    /// Configure the radio, leaving it in standby.
+    pub async fn configure(&mut self, config: &Config) -> Result<(), Error<SPI::Error>> {
+        self.config = *config;
+        self.standby().await?;
+
+        // The image is calibrated for the band of the frequency
+        let band = match config.frequency / 1_000_000 {
+            902.. => [0xE1, 0xE9],
+            863.. => [0xD7, 0xDB],
+            779.. => [0xC1, 0xC5],
+            470.. => [0x75, 0x81],
+            _ => [0x6B, 0x6F],
+        };
+        self.command(Command::CALIBRATE_IMAGE, &band).await?;
+
+        let frf = ((config.frequency as u64) << 25) / CRYSTAL_HZ;
+        self.command(Command::SET_RF_FREQUENCY, &(frf as u32).to_be_bytes())
+            .await?;
+
+        self.command(
+            Command::SET_MODULATION_PARAMS,
+            &[
+                config.spreading_factor.bits(),
+                bandwidth_bits(config.bandwidth),
+                config.coding_rate.bits(),
+                config.low_data_rate_optimize() as u8,
+            ],
+        )
+        .await?;
+
+        // The sync word is spread over two registers, e.g. 0x12 as 0x1424
+        let sync_word = config.sync_word;
+        self.write_registers(
+            Register::LORA_SYNC_WORD_MSB,
+            &[(sync_word & 0xF0) | 0x04, (sync_word << 4) | 0x04],
+        )
+        .await?;
+
+        self.command(Command::SET_PA_CONFIG, &PA_CONFIG_22_DBM)
+            .await?;
+        let tx_power = config.tx_power.clamp(-9, 22);
+        self.command(Command::SET_TX_PARAMS, &[tx_power as u8, RAMP_200_US])
+            .await?;
+        self.write_registers(Register::OCP_CONFIGURATION, &[OCP_140_MA])
+            .await?;
+
+        self.command(
+            Command::SET_DIO_IRQ_PARAMS,
+            &[
+                (IRQ_ALL >> 8) as u8,
+                IRQ_ALL as u8,
+                ((IRQ_TX_DONE | IRQ_RX_DONE | IRQ_TIMEOUT) >> 8) as u8,
+                (IRQ_TX_DONE | IRQ_RX_DONE | IRQ_TIMEOUT) as u8,
+                0,
+                0,
+                0,
+                0,
+            ],
+        )
+        .await
+    }
+
+    /// Put the radio in standby, aborting any transmission or reception.
+    pub async fn standby(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.command(Command::SET_STANDBY, &[STANDBY_RC]).await
+    }
+
+    /// Put the radio to sleep, its lowest power mode. The configuration is
+    /// kept, and the next command wakes the radio up.
+    pub async fn sleep(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.command(Command::SET_SLEEP, &[SLEEP_WARM_START])
+            .await?;
+        self.sleeping = true;
+        Ok(())
+    }
+
+    /// Send a packet and wait for it to be sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::PacketTooLong` if the packet is longer than 255 bytes.
+    pub async fn transmit(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        if data.len() > MAX_PACKET_LEN {
+            return Err(Error::PacketTooLong);
+        }
+
+        self.standby().await?;
+        self.set_packet_params(data.len() as u8).await?;
+        self.write_buffer(data).await?;
+        self.clear_irq_status().await?;
+        self.command(Command::SET_TX, &TIMEOUT_NONE).await?;
+
+        let timeout = self.config.time_on_air(data.len()) + TX_TIMEOUT_MARGIN;
+        with_timeout(timeout, self.dio1.wait_for_high())
+            .await
+            .map_err(|_| Error::Timeout)?
+            .map_err(|_| Error::Pin)?;
+
+        let irq = self.irq_status().await?;
+        self.clear_irq_status().await?;
+        if irq & IRQ_TX_DONE == 0 {
+            return Err(Error::Timeout);
+        }
+        Ok(())
+    }
+
+    /// Wait for a packet, read it into a buffer, and return its length and
+    /// signal quality. The radio listens until a packet is received.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Crc` if the packet is corrupted, and
+    /// `Error::BufferTooSmall` if it does not fit in the buffer.
+    pub async fn receive(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(usize, PacketStatus), Error<SPI::Error>> {
+        self.standby().await?;
+        self.set_packet_params(MAX_PACKET_LEN as u8).await?;
+        self.clear_irq_status().await?;
+        self.command(Command::SET_RX, &RX_CONTINUOUS).await?;
+
+        loop {
+            self.dio1.wait_for_high().await.map_err(|_| Error::Pin)?;
+            let irq = self.irq_status().await?;
+            self.clear_irq_status().await?;
+            if irq & IRQ_RX_DONE != 0 {
+                if irq & (IRQ_CRC_ERROR | IRQ_HEADER_ERROR) != 0 {
+                    self.standby().await?;
+                    return Err(Error::Crc);
+                }
+                break;
+            }
+        }
+        self.standby().await?;
+
+        // Payload length and start in the buffer
+        let mut status = [0u8; 2];
+        self.read_command(Command::GET_RX_BUFFER_STATUS, &mut status)
+            .await?;
+        let [len, start] = status;
+        let data = buf.get_mut(..len as usize).ok_or(Error::BufferTooSmall)?;
+        self.read_buffer(start, data).await?;
+
+        // RSSI of the packet in -0.5 dBm, SNR in quarters of a dB
+        let mut status = [0u8; 3];
+        self.read_command(Command::GET_PACKET_STATUS, &mut status)
+            .await?;
+        Ok((
+            len as usize,
+            PacketStatus {
+                rssi: -(status[0] as i16) / 2,
+                snr: status[1] as i8 as f32 / 4.0,
+            },
+        ))
+    }
+
+    async fn set_packet_params(&mut self, len: u8) -> Result<(), Error<SPI::Error>> {
+        let [preamble_msb, preamble_lsb] = self.config.preamble_len.to_be_bytes();
+        let params = [
+            preamble_msb,
+            preamble_lsb,
+            HEADER_EXPLICIT,
+            len,
+            self.config.crc as u8,
+            // Standard IQ
+            0,
+        ];
+        self.command(Command::SET_PACKET_PARAMS, &params).await
+    }
+
+    async fn irq_status(&mut self) -> Result<u16, Error<SPI::Error>> {
+        let mut irq = [0u8; 2];
+        self.read_command(Command::GET_IRQ_STATUS, &mut irq).await?;
+        Ok(u16::from_be_bytes(irq))
+    }
+
+    async fn clear_irq_status(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.command(Command::CLEAR_IRQ_STATUS, &IRQ_ALL.to_be_bytes())
+            .await
+    }
+
+    async fn write_buffer(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.wait_busy().await?;
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Command::WRITE_BUFFER, 0]),
+                Operation::Write(data),
+            ])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn read_buffer(&mut self, offset: u8, data: &mut [u8]) -> Result<(), Error<SPI::Error>> {
+        self.wait_busy().await?;
+        // The status is returned in place of the offset
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Command::READ_BUFFER, offset, 0]),
+                Operation::Read(data),
+            ])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn write_registers(
+        &mut self,
+        address: u16,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_busy().await?;
+        let [msb, lsb] = address.to_be_bytes();
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Command::WRITE_REGISTER, msb, lsb]),
+                Operation::Write(data),
+            ])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Send a command with its parameters.
+    async fn command(&mut self, command: u8, params: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.wait_busy().await?;
+        self.spi
+            .transaction(&mut [Operation::Write(&[command]), Operation::Write(params)])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Send a command and read its answer, after the status byte.
+    async fn read_command(
+        &mut self,
+        command: u8,
+        data: &mut [u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_busy().await?;
+        self.spi
+            .transaction(&mut [Operation::Write(&[command, 0]), Operation::Read(data)])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Wait for the radio to be ready for a command.
+    async fn wait_busy(&mut self) -> Result<(), Error<SPI::Error>> {
+        // Selecting the radio wakes it up
+        if self.sleeping {
+            self.spi
+                .write(&[Command::GET_STATUS, 0])
+                .await
+                .map_err(Error::Spi)?;
+            self.sleeping = false;
+        }
+
+        // The BUSY pin only rises a few hundred ns after a command
+        Timer::after_micros(1).await;
+        with_timeout(BUSY_TIMEOUT, self.busy.wait_for_low())
+            .await
+            .map_err(|_| Error::Timeout)?
+            .map_err(|_| Error::Pin)
+    }
+}
+
+impl<SPI: SpiDevice, BUSY: Wait, DIO1: Wait> Radio for Sx1262<SPI, BUSY, DIO1> {
+    type Error = Error<SPI::Error>;
+
+    async fn transmit(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        Sx1262::transmit(self, data).await
+    }
+
+    async fn receive(&mut self, buf: &mut [u8]) -> Result<(usize, PacketStatus), Self::Error> {
+        Sx1262::receive(self, buf).await
+    }
+}
+
+/// Bits of a bandwidth in the modulation parameters
+fn bandwidth_bits(bandwidth: Bandwidth) -> u8 {
+    match bandwidth {
+        Bandwidth::Khz7_8 => 0x00,
+        Bandwidth::Khz10_4 => 0x08,
+        Bandwidth::Khz15_6 => 0x01,
+        Bandwidth::Khz20_8 => 0x09,
+        Bandwidth::Khz31_25 => 0x02,
+        Bandwidth::Khz41_7 => 0x0A,
+        Bandwidth::Khz62_5 => 0x03,
+        Bandwidth::Khz125 => 0x04,
+        Bandwidth::Khz250 => 0x05,
+        Bandwidth::Khz500 => 0x06,
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying SPI device.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI bus error
+    Spi(E),
+    /// Error of the BUSY or DIO1 pin
+    Pin,
+    /// The radio stayed busy, or did not send the packet in time.
+    Timeout,
+    /// The packet is longer than 255 bytes.
+    PacketTooLong,
+    /// The received packet is corrupted.
+    Crc,
+    /// The received packet does not fit in the buffer.
+    BufferTooSmall,
+}
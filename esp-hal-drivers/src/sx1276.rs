@@ -0,0 +1,439 @@
+//! # sx1276
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Semtech SX1276
+//! LoRa radio over SPI, as found on the HopeRF RFM95W and most 868/915 MHz
+//! modules. The SX1277, SX1278 and SX1279 share its registers and work as
+//! well.
+//!
+//! - The radio is configured with a [Config] shared with the other LoRa
+//!   radios, and implements the [Radio] trait.
+//! - The radio raises its DIO0 pin when a packet is sent or received, so the
+//!   driver waits for the pin instead of polling the radio.
+//! - The output goes through the PA_BOOST pin, from 2 to 20 dBm.
+//!
+//! Spreading factor 5 is not supported, and spreading factor 6 requires
+//! implicit headers, which this driver does not use.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let config = Config::new(868_100_000).with_spreading_factor(SpreadingFactor::Sf9);
+//! let mut radio = Sx1276::new(spi, dio0, &config).await?;
+//!
+//! radio.transmit(b"hello").await?;
+//!
+//! let mut buf = [0u8; 255];
+//! let (len, status) = radio.receive(&mut buf).await?;
+//! println!("{:?} at {} dBm, SNR {} dB", &buf[..len], status.rssi, status.snr);
+//! ```
+
+use embassy_time::{with_timeout, Duration};
+use embedded_hal_async::{
+    digital::Wait,
+    spi::{Operation, SpiDevice},
+};
+
+use crate::lora::{Bandwidth, Config, PacketStatus, Radio, SpreadingFactor};
+
+/// Value of the version register of the SX1276
+const VERSION: u8 = 0x12;
+
+/// Frequency of the crystal of the radio in Hz
+const CRYSTAL_HZ: u64 = 32_000_000;
+
+/// Highest frequency of the low frequency port in Hz
+const LOW_FREQUENCY_MAX_HZ: u32 = 525_000_000;
+
+/// Time given to the radio to send a packet, on top of its time on air
+const TX_TIMEOUT_MARGIN: Duration = Duration::from_millis(100);
+
+/// Largest packet sent by the radio
+const MAX_PACKET_LEN: usize = 255;
+
+/// Registers of the radio in LoRa mode. See datasheet section 6.4 for more
+/// details.
+struct Register;
+
+impl Register {
+    const FIFO: u8 = 0x00;
+    const OP_MODE: u8 = 0x01;
+    const FRF_MSB: u8 = 0x06;
+    const PA_CONFIG: u8 = 0x09;
+    const OCP: u8 = 0x0B;
+    const LNA: u8 = 0x0C;
+    const FIFO_ADDR_PTR: u8 = 0x0D;
+    const FIFO_TX_BASE_ADDR: u8 = 0x0E;
+    const FIFO_RX_BASE_ADDR: u8 = 0x0F;
+    const FIFO_RX_CURRENT_ADDR: u8 = 0x10;
+    const IRQ_FLAGS: u8 = 0x12;
+    const RX_NB_BYTES: u8 = 0x13;
+    const PKT_SNR_VALUE: u8 = 0x19;
+    const PKT_RSSI_VALUE: u8 = 0x1A;
+    const MODEM_CONFIG_1: u8 = 0x1D;
+    const MODEM_CONFIG_2: u8 = 0x1E;
+    const PREAMBLE_MSB: u8 = 0x20;
+    const PAYLOAD_LENGTH: u8 = 0x22;
+    const MODEM_CONFIG_3: u8 = 0x26;
+    const DETECTION_OPTIMIZE: u8 = 0x31;
+    const DETECTION_THRESHOLD: u8 = 0x37;
+    const SYNC_WORD: u8 = 0x39;
+    const DIO_MAPPING_1: u8 = 0x40;
+    const VERSION: u8 = 0x42;
+    const PA_DAC: u8 = 0x4D;
+}
+
+/// Flag of the address byte of a write
+const WRITE: u8 = 0x80;
+
+/// Modes of the operating mode register, in LoRa mode
+const MODE_LONG_RANGE: u8 = 0x80;
+const MODE_LOW_FREQUENCY: u8 = 0x08;
+const MODE_SLEEP: u8 = 0x00;
+const MODE_STANDBY: u8 = 0x01;
+const MODE_TX: u8 = 0x03;
+const MODE_RX_CONTINUOUS: u8 = 0x05;
+
+/// Flags of the IRQ flags register
+const IRQ_RX_DONE: u8 = 1 << 6;
+const IRQ_PAYLOAD_CRC_ERROR: u8 = 1 << 5;
+const IRQ_TX_DONE: u8 = 1 << 3;
+
+/// Mappings of the DIO0 pin
+const DIO0_RX_DONE: u8 = 0b00 << 6;
+const DIO0_TX_DONE: u8 = 0b01 << 6;
+
+/// PA configuration selecting the PA_BOOST pin with the highest maximum
+/// power
+const PA_BOOST: u8 = 0x80 | 0x70;
+
+/// PA DAC settings of the normal and +20 dBm outputs
+const PA_DAC_NORMAL: u8 = 0x84;
+const PA_DAC_HIGH_POWER: u8 = 0x87;
+
+/// Over current protection enabled at 100 mA and 140 mA
+const OCP_100_MA: u8 = 0x20 | 0x0B;
+const OCP_140_MA: u8 = 0x20 | 0x11;
+
+/// Highest gain of the LNA, with its boost
+const LNA_MAX_GAIN: u8 = 0x20 | 0x03;
+
+/// Flags of the third modem configuration register
+const LOW_DATA_RATE_OPTIMIZE: u8 = 1 << 3;
+const AGC_AUTO_ON: u8 = 1 << 2;
+
+/// Flag of the second modem configuration register enabling CRCs
+const RX_PAYLOAD_CRC_ON: u8 = 1 << 2;
+
+/// Detection settings of spreading factors 7 to 12
+const DETECTION_OPTIMIZE_SF7_12: u8 = 0xC3;
+const DETECTION_THRESHOLD_SF7_12: u8 = 0x0A;
+
+/// Offsets of the RSSI of a packet on the high and low frequency ports
+const RSSI_OFFSET_HF: i16 = -157;
+const RSSI_OFFSET_LF: i16 = -164;
+
+/// A SX1276 on a SPI device
+///
+/// `DIO0` is the pin the DIO0 output of the radio is connected to.
+pub struct Sx1276<SPI, DIO0> {
+    spi: SPI,
+    dio0: DIO0,
+    config: Config,
+}
+
+impl<SPI: SpiDevice, DIO0: Wait> Sx1276<SPI, DIO0> {
+    /// Create a new radio and configure it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidVersion` if the device does not identify as a
+    /// SX1276.
+    pub async fn new(spi: SPI, dio0: DIO0, config: &Config) -> Result<Self, Error<SPI::Error>> {
+        let mut radio = Self {
+            spi,
+            dio0,
+            config: *config,
+        };
+
+        let version = radio.read_register(Register::VERSION).await?;
+        if version != VERSION {
+            return Err(Error::InvalidVersion(version));
+        }
+
+        radio.configure(config).await?;
+        Ok(radio)
+    }
+
+    /// Release the underlying SPI device and DIO0 pin
+    pub fn release(self) -> (SPI, DIO0) {
+        (self.spi, self.dio0)
+    }
+
+    /// Configure the radio, leaving it in standby.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Unsupported` if the spreading factor is not supported.
+    pub async fn configure(&mut self, config: &Config) -> Result<(), Error<SPI::Error>> {
+        if config.spreading_factor.bits() < SpreadingFactor::Sf7.bits() {
+            return Err(Error::Unsupported);
+        }
+        self.config = *config;
+
+        // The modem can only be changed to LoRa while sleeping
+        self.set_mode(MODE_SLEEP).await?;
+
+        let frf = ((config.frequency as u64) << 19) / CRYSTAL_HZ;
+        let frf = (frf as u32).to_be_bytes();
+        self.write_registers(Register::FRF_MSB, &frf[1..]).await?;
+
+        self.write_register(Register::FIFO_TX_BASE_ADDR, 0).await?;
+        self.write_register(Register::FIFO_RX_BASE_ADDR, 0).await?;
+        self.write_register(Register::LNA, LNA_MAX_GAIN).await?;
+
+        // Explicit header
+        let config_1 = bandwidth_bits(config.bandwidth) << 4 | config.coding_rate.bits() << 1;
+        self.write_register(Register::MODEM_CONFIG_1, config_1)
+            .await?;
+        let mut config_2 = config.spreading_factor.bits() << 4;
+        if config.crc {
+            config_2 |= RX_PAYLOAD_CRC_ON;
+        }
+        self.write_register(Register::MODEM_CONFIG_2, config_2)
+            .await?;
+        let mut config_3 = AGC_AUTO_ON;
+        if config.low_data_rate_optimize() {
+            config_3 |= LOW_DATA_RATE_OPTIMIZE;
+        }
+        self.write_register(Register::MODEM_CONFIG_3, config_3)
+            .await?;
+        self.write_register(Register::DETECTION_OPTIMIZE, DETECTION_OPTIMIZE_SF7_12)
+            .await?;
+        self.write_register(Register::DETECTION_THRESHOLD, DETECTION_THRESHOLD_SF7_12)
+            .await?;
+
+        self.write_registers(Register::PREAMBLE_MSB, &config.preamble_len.to_be_bytes())
+            .await?;
+        self.write_register(Register::SYNC_WORD, config.sync_word)
+            .await?;
+
+        // The +20 dBm output needs the high power DAC and more current
+        let tx_power = config.tx_power.clamp(2, 20);
+        let (pa_config, pa_dac, ocp) = if tx_power > 17 {
+            (
+                PA_BOOST | (tx_power - 5) as u8,
+                PA_DAC_HIGH_POWER,
+                OCP_140_MA,
+            )
+        } else {
+            (PA_BOOST | (tx_power - 2) as u8, PA_DAC_NORMAL, OCP_100_MA)
+        };
+        self.write_register(Register::PA_CONFIG, pa_config).await?;
+        self.write_register(Register::PA_DAC, pa_dac).await?;
+        self.write_register(Register::OCP, ocp).await?;
+
+        self.standby().await
+    }
+
+    /// Put the radio in standby, aborting any transmission or reception.
+    pub async fn standby(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.set_mode(MODE_STANDBY).await
+    }
+
+    /// Put the radio to sleep, its lowest power mode. The configuration is
+    /// kept.
+    pub async fn sleep(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.set_mode(MODE_SLEEP).await
+    }
+
+    /// Send a packet and wait for it to be sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::PacketTooLong` if the packet is longer than 255 bytes.
+    pub async fn transmit(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        if data.len() > MAX_PACKET_LEN {
+            return Err(Error::PacketTooLong);
+        }
+
+        self.standby().await?;
+        self.write_register(Register::FIFO_ADDR_PTR, 0).await?;
+        self.write_registers(Register::FIFO, data).await?;
+        self.write_register(Register::PAYLOAD_LENGTH, data.len() as u8)
+            .await?;
+        self.write_register(Register::DIO_MAPPING_1, DIO0_TX_DONE)
+            .await?;
+        self.write_register(Register::IRQ_FLAGS, 0xFF).await?;
+        self.set_mode(MODE_TX).await?;
+
+        let timeout = self.config.time_on_air(data.len()) + TX_TIMEOUT_MARGIN;
+        with_timeout(timeout, self.dio0.wait_for_high())
+            .await
+            .map_err(|_| Error::Timeout)?
+            .map_err(|_| Error::Pin)?;
+
+        let flags = self.read_register(Register::IRQ_FLAGS).await?;
+        self.write_register(Register::IRQ_FLAGS, flags).await?;
+        if flags & IRQ_TX_DONE == 0 {
+            return Err(Error::Timeout);
+        }
+        Ok(())
+    }
+
+    /// Wait for a packet, read it into a buffer, and return its length and
+    /// signal quality. The radio listens until a packet is received.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Crc` if the packet is corrupted, and
+    /// `Error::BufferTooSmall` if it does not fit in the buffer.
+    pub async fn receive(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(usize, PacketStatus), Error<SPI::Error>> {
+        self.standby().await?;
+        self.write_register(Register::FIFO_ADDR_PTR, 0).await?;
+        self.write_register(Register::DIO_MAPPING_1, DIO0_RX_DONE)
+            .await?;
+        self.write_register(Register::IRQ_FLAGS, 0xFF).await?;
+        self.set_mode(MODE_RX_CONTINUOUS).await?;
+
+        loop {
+            self.dio0.wait_for_high().await.map_err(|_| Error::Pin)?;
+            let flags = self.read_register(Register::IRQ_FLAGS).await?;
+            self.write_register(Register::IRQ_FLAGS, flags).await?;
+            if flags & IRQ_RX_DONE != 0 {
+                if flags & IRQ_PAYLOAD_CRC_ERROR != 0 {
+                    self.standby().await?;
+                    return Err(Error::Crc);
+                }
+                break;
+            }
+        }
+        self.standby().await?;
+
+        let len = self.read_register(Register::RX_NB_BYTES).await? as usize;
+        let current = self.read_register(Register::FIFO_RX_CURRENT_ADDR).await?;
+        self.write_register(Register::FIFO_ADDR_PTR, current)
+            .await?;
+        let data = buf.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        self.read_registers(Register::FIFO, data).await?;
+
+        // The SNR is in quarters of a dB, and lowers the RSSI below the noise
+        // floor
+        let snr = self.read_register(Register::PKT_SNR_VALUE).await? as i8;
+        let rssi = self.read_register(Register::PKT_RSSI_VALUE).await? as i16;
+        let offset = if self.config.frequency > LOW_FREQUENCY_MAX_HZ {
+            RSSI_OFFSET_HF
+        } else {
+            RSSI_OFFSET_LF
+        };
+        let rssi = offset + rssi + (snr.min(0) / 4) as i16;
+
+        Ok((
+            len,
+            PacketStatus {
+                rssi,
+                snr: snr as f32 / 4.0,
+            },
+        ))
+    }
+
+    async fn set_mode(&mut self, mode: u8) -> Result<(), Error<SPI::Error>> {
+        let mut value = MODE_LONG_RANGE | mode;
+        if self.config.frequency <= LOW_FREQUENCY_MAX_HZ {
+            value |= MODE_LOW_FREQUENCY;
+        }
+        self.write_register(Register::OP_MODE, value).await
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<SPI::Error>> {
+        let mut value = [0u8];
+        self.read_registers(register, &mut value).await?;
+        Ok(value[0])
+    }
+
+    async fn read_registers(
+        &mut self,
+        register: u8,
+        data: &mut [u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.spi
+            .transaction(&mut [Operation::Write(&[register]), Operation::Read(data)])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<SPI::Error>> {
+        self.write_registers(register, &[value]).await
+    }
+
+    /// Write consecutive registers, or the FIFO.
+    async fn write_registers(
+        &mut self,
+        register: u8,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[WRITE | register]),
+                Operation::Write(data),
+            ])
+            .await
+            .map_err(Error::Spi)
+    }
+}
+
+impl<SPI: SpiDevice, DIO0: Wait> Radio for Sx1276<SPI, DIO0> {
+    type Error = Error<SPI::Error>;
+
+    async fn transmit(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        Sx1276::transmit(self, data).await
+    }
+
+    async fn receive(&mut self, buf: &mut [u8]) -> Result<(usize, PacketStatus), Self::Error> {
+        Sx1276::receive(self, buf).await
+    }
+}
+
+/// Bits of a bandwidth in the first modem configuration register
+fn bandwidth_bits(bandwidth: Bandwidth) -> u8 {
+    match bandwidth {
+        Bandwidth::Khz7_8 => 0,
+        Bandwidth::Khz10_4 => 1,
+        Bandwidth::Khz15_6 => 2,
+        Bandwidth::Khz20_8 => 3,
+        Bandwidth::Khz31_25 => 4,
+        Bandwidth::Khz41_7 => 5,
+        Bandwidth::Khz62_5 => 6,
+        Bandwidth::Khz125 => 7,
+        Bandwidth::Khz250 => 8,
+        Bandwidth::Khz500 => 9,
+    }
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying SPI device.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI bus error
+    Spi(E),
+    /// Error of the DIO0 pin
+    Pin,
+    /// The device does not identify as a SX1276.
+    InvalidVersion(u8),
+    /// The setting is not supported by the radio.
+    Unsupported,
+    /// The packet is longer than 255 bytes.
+    PacketTooLong,
+    /// The packet was not sent in time.
+    Timeout,
+    /// The received packet is corrupted.
+    Crc,
+    /// The received packet does not fit in the buffer.
+    BufferTooSmall,
+}
@@ -0,0 +1,118 @@
+//! # temperature
+//!
+//! ## Overview
+//!
+//! This driver reads the internal temperature sensor of the ESP32-C3.
+//!
+//! The sensor measures the temperature of the die, which is usually a few
+//! degrees above the ambient temperature. It is configured for its most
+//! accurate range, from -10 °C to 80 °C, where the error is below 1 °C.
+//!
+//! The sensor is part of the APB_SARADC peripheral, which the driver takes
+//! ownership of. The internal SAR ADC can still be used alongside it, as it
+//! is driven through the ADC1 peripheral.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let peripherals = esp_hal::init(esp_hal::Config::default());
+//! let mut sensor = TemperatureSensor::new(peripherals.APB_SARADC);
+//!
+//! let temperature = sensor.read_celsius().await;
+//! println!("Chip temperature: {} °C", temperature.0);
+//! ```
+
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::{
+    peripheral::{Peripheral, PeripheralRef},
+    peripherals::{APB_SARADC, SYSTEM},
+};
+
+use crate::units::Celsius;
+
+/// Time for the sensor to settle after being powered up
+const SETTLE_TIME: Duration = Duration::from_micros(300);
+
+/// Divider of the sensor clock, as recommended by the technical reference
+/// manual
+const CLOCK_DIVIDER: u8 = 6;
+
+/// Conversion factor from the raw output in °C per LSB
+const ADC_FACTOR: f32 = 0.4386;
+
+/// Conversion factor of the DAC offset in °C per step
+const DAC_FACTOR: f32 = 27.88;
+
+/// Constant offset of the conversion in °C
+const OFFSET_FACTOR: f32 = 20.52;
+
+/// DAC offset of the -10 °C to 80 °C range, which is the power-on default
+const DAC_OFFSET: f32 = 0.0;
+
+/// The internal temperature sensor
+///
+/// The sensor is powered down when dropped.
+pub struct TemperatureSensor<'d> {
+    _apb_saradc: PeripheralRef<'d, APB_SARADC>,
+    ready_at: Instant,
+}
+
+impl<'d> TemperatureSensor<'d> {
+    /// Power up the temperature sensor.
+    ///
+    /// # Arguments
+    ///
+    /// - `apb_saradc`: The APB_SARADC peripheral, which contains the sensor.
+    pub fn new(apb_saradc: impl Peripheral<P = APB_SARADC> + 'd) -> Self {
+        let system = SYSTEM::regs();
+        // The registers of the sensor are on the bus of the SAR ADC
+        system
+            .perip_clk_en0()
+            .modify(|_, w| w.apb_saradc_clk_en().set_bit());
+        system
+            .perip_rst_en0()
+            .modify(|_, w| w.apb_saradc_rst().clear_bit());
+        system
+            .perip_clk_en1()
+            .modify(|_, w| w.tsens_clk_en().set_bit());
+
+        let regs = APB_SARADC::regs();
+        // Clock the sensor from the XTAL clock
+        regs.tsens_ctrl2()
+            .modify(|_, w| w.tsens_clk_sel().set_bit());
+        regs.tsens_ctrl().modify(|_, w| unsafe {
+            w.tsens_clk_div().bits(CLOCK_DIVIDER);
+            w.tsens_pu().set_bit()
+        });
+
+        Self {
+            _apb_saradc: apb_saradc.into_ref(),
+            ready_at: Instant::now() + SETTLE_TIME,
+        }
+    }
+
+    /// Read the raw output of the sensor.
+    pub async fn read_raw(&mut self) -> u8 {
+        Timer::at(self.ready_at).await;
+        APB_SARADC::regs().tsens_ctrl().read().tsens_out().bits()
+    }
+
+    /// Read the temperature of the chip.
+    pub async fn read_celsius(&mut self) -> Celsius {
+        let raw = self.read_raw().await;
+        Celsius(ADC_FACTOR * raw as f32 - DAC_FACTOR * DAC_OFFSET - OFFSET_FACTOR)
+    }
+}
+
+impl Drop for TemperatureSensor<'_> {
+    /// Power down the sensor and gate its clock. The bus clock is left
+    /// enabled, as the SAR ADC may still use it.
+    fn drop(&mut self) {
+        APB_SARADC::regs()
+            .tsens_ctrl()
+            .modify(|_, w| w.tsens_pu().clear_bit());
+        SYSTEM::regs()
+            .perip_clk_en1()
+            .modify(|_, w| w.tsens_clk_en().clear_bit());
+    }
+}
@@ -0,0 +1,142 @@
+//! # thermistor
+//!
+//! ## Overview
+//!
+//! This module converts the voltage measured across an NTC thermistor voltage
+//! divider into a temperature, e.g. from a [crate::mcp3428] reading.
+//!
+//! The thermistor can be described either by its Beta model or by its
+//! Steinhart–Hart coefficients, and placed on either side of the divider.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! // 10 kΩ NTC (B = 3950) to ground, 10 kΩ pull-up to 3.3 V
+//! let thermistor = Thermistor::new(
+//!     Model::Beta {
+//!         beta: 3950.0,
+//!         r0_ohms: 10_000.0,
+//!         t0_celsius: 25.0,
+//!     },
+//!     Divider::LowSide {
+//!         fixed_ohms: 10_000.0,
+//!     },
+//!     Millivolts(3_300),
+//! );
+//!
+//! let voltage = adc.one_shot_measurement().await?;
+//! let temperature = thermistor.celsius(voltage)?;
+//! ```
+
+use crate::units::{Celsius, Millivolts};
+
+/// Offset between the Celsius and Kelvin scales
+const KELVIN_OFFSET: f32 = 273.15;
+
+/// Thermistor resistance to temperature model
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Model {
+    /// Beta model, from the datasheet B constant and the nominal resistance
+    /// `r0_ohms` at `t0_celsius` (usually 25 °C).
+    Beta {
+        beta: f32,
+        r0_ohms: f32,
+        t0_celsius: f32,
+    },
+    /// Steinhart–Hart equation `1/T = a + b ln(R) + c ln(R)^3`, with `T` in
+    /// kelvin and `R` in ohms.
+    SteinhartHart { a: f32, b: f32, c: f32 },
+}
+
+/// Position of the thermistor in the voltage divider
+///
+/// The measured voltage is the voltage across the bottom resistor of the
+/// divider, relative to ground.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Divider {
+    /// The thermistor is between the supply and the measured node, with a
+    /// fixed resistor to ground.
+    HighSide { fixed_ohms: f32 },
+    /// The thermistor is between the measured node and ground, with a fixed
+    /// resistor to the supply.
+    LowSide { fixed_ohms: f32 },
+}
+
+/// All possible errors in this module
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The measured voltage is outside of the range of the divider, which
+    /// usually means the thermistor is open or shorted.
+    OutOfRange,
+}
+
+/// An NTC thermistor in a voltage divider
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Thermistor {
+    model: Model,
+    divider: Divider,
+    supply: Millivolts,
+}
+
+impl Thermistor {
+    /// Create a new thermistor helper.
+    ///
+    /// # Arguments
+    ///
+    /// - `model`: The resistance to temperature model of the thermistor.
+    /// - `divider`: The topology of the voltage divider.
+    /// - `supply`: The voltage across the whole divider.
+    pub fn new(model: Model, divider: Divider, supply: Millivolts) -> Self {
+        Self {
+            model,
+            divider,
+            supply,
+        }
+    }
+
+    /// Compute the resistance of the thermistor in ohms from the measured
+    /// voltage.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::OutOfRange] if the voltage is not strictly between 0 V
+    /// and the supply voltage.
+    pub fn resistance_ohms(&self, voltage: Millivolts) -> Result<f32, Error> {
+        if voltage.0 <= 0 || voltage >= self.supply {
+            return Err(Error::OutOfRange);
+        }
+
+        let measured = voltage.0 as f32;
+        let remaining = (self.supply - voltage).0 as f32;
+        let resistance = match self.divider {
+            Divider::HighSide { fixed_ohms } => fixed_ohms * remaining / measured,
+            Divider::LowSide { fixed_ohms } => fixed_ohms * measured / remaining,
+        };
+        Ok(resistance)
+    }
+
+    /// Compute the temperature from the measured voltage.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::OutOfRange] if the voltage is not strictly between 0 V
+    /// and the supply voltage.
+    pub fn celsius(&self, voltage: Millivolts) -> Result<Celsius, Error> {
+        let ln_r = libm::logf(self.resistance_ohms(voltage)?);
+
+        let inverse_kelvin = match self.model {
+            Model::Beta {
+                beta,
+                r0_ohms,
+                t0_celsius,
+            } => 1.0 / (t0_celsius + KELVIN_OFFSET) + (ln_r - libm::logf(r0_ohms)) / beta,
+            Model::SteinhartHart { a, b, c } => a + b * ln_r + c * ln_r * ln_r * ln_r,
+        };
+
+        Ok(Celsius(1.0 / inverse_kelvin - KELVIN_OFFSET))
+    }
+}
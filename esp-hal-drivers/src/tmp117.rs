@@ -0,0 +1,421 @@
+//! # tmp117
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Texas Instruments
+//! TMP117 high-accuracy temperature sensor over I2C.
+//!
+//! - Conversions can be averaged on the sensor to reduce noise.
+//! - The sensor either converts continuously or on demand, in which case it
+//!   shuts down between conversions to save power.
+//! - A temperature offset can be applied to the readings, and optionally stored
+//!   in the EEPROM of the sensor so that it survives a power cycle.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let config = Config::new().with_averaging(Averaging::X8);
+//! let mut tmp117 = Tmp117::new(i2c, ADDRESS_GND, config).await?;
+//!
+//! // Take a single measurement
+//! let temperature = tmp117.one_shot_measurement().await?;
+//! println!("{} °C", temperature.0);
+//!
+//! // Or let the sensor convert continuously
+//! tmp117.start_continuous().await?;
+//! let temperature = tmp117.get_measurement().await?;
+//!
+//! // Calibrate the sensor against a reference and persist the offset
+//! tmp117.store_offset(Celsius(-0.12)).await?;
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use crate::units::Celsius;
+
+/// I2C address of the sensor when ADD0 is tied to ground
+pub const ADDRESS_GND: u8 = 0x48;
+
+/// I2C address of the sensor when ADD0 is tied to the supply
+pub const ADDRESS_VCC: u8 = 0x49;
+
+/// I2C address of the sensor when ADD0 is tied to SDA
+pub const ADDRESS_SDA: u8 = 0x4A;
+
+/// I2C address of the sensor when ADD0 is tied to SCL
+pub const ADDRESS_SCL: u8 = 0x4B;
+
+/// Value of the device ID register of the TMP117, ignoring the revision
+const DEVICE_ID: u16 = 0x0117;
+
+/// Resolution of the temperature and offset registers in °C per LSB
+const CELSIUS_PER_LSB: f32 = 0.007_812_5;
+
+/// Maximum time taken by an EEPROM write
+const EEPROM_WRITE_TIME: Duration = Duration::from_millis(7);
+
+/// Extra time allowed for an operation on top of its nominal duration before
+/// giving up
+const TIMEOUT_MARGIN: Duration = Duration::from_millis(100);
+
+/// Time between two polls of the status flags
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Registers of the sensor. See datasheet section 7.6 for more details.
+struct Register;
+
+impl Register {
+    const TEMPERATURE: u8 = 0x00;
+    const CONFIGURATION: u8 = 0x01;
+    const EEPROM_UNLOCK: u8 = 0x04;
+    const TEMPERATURE_OFFSET: u8 = 0x07;
+    const DEVICE_ID: u8 = 0x0F;
+}
+
+/// Flags of the configuration register
+const CONFIG_DATA_READY: u16 = 1 << 13;
+const CONFIG_EEPROM_BUSY: u16 = 1 << 12;
+const CONFIG_SOFT_RESET: u16 = 1 << 1;
+
+/// Flag of the EEPROM unlock register enabling EEPROM programming
+const EEPROM_UNLOCK: u16 = 1 << 15;
+
+/// Configuration of the sensor
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    averaging: Averaging,
+    cycle: ConversionCycle,
+}
+
+impl Config {
+    /// Create a new configuration with the power-on defaults of the sensor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_averaging(mut self, averaging: Averaging) -> Self {
+        self.averaging = averaging;
+        self
+    }
+
+    pub fn with_cycle(mut self, cycle: ConversionCycle) -> Self {
+        self.cycle = cycle;
+        self
+    }
+
+    /// Value of the configuration register in a given mode
+    fn bits(&self, mode: Mode) -> u16 {
+        ((mode.bits() as u16) << 10)
+            | ((self.cycle.bits() as u16) << 7)
+            | ((self.averaging.bits() as u16) << 5)
+    }
+
+    /// Time taken by a conversion, including the averaging
+    fn conversion_time(&self) -> Duration {
+        self.averaging.conversion_time()
+    }
+
+    /// Time between two conversions in continuous mode, which cannot be
+    /// shorter than a conversion
+    fn cycle_time(&self) -> Duration {
+        self.cycle.duration().max(self.conversion_time())
+    }
+}
+
+/// Number of conversions averaged into each measurement
+///
+/// Defaults to `X8`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Averaging {
+    /// Takes 15.5 ms.
+    None = 0b00,
+    /// Takes 125 ms.
+    #[default]
+    X8 = 0b01,
+    /// Takes 500 ms.
+    X32 = 0b10,
+    /// Takes 1 s.
+    X64 = 0b11,
+}
+
+impl Averaging {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    fn conversion_time(&self) -> Duration {
+        match self {
+            Averaging::None => Duration::from_micros(15_500),
+            Averaging::X8 => Duration::from_millis(125),
+            Averaging::X32 => Duration::from_millis(500),
+            Averaging::X64 => Duration::from_millis(1_000),
+        }
+    }
+}
+
+/// Time between the start of two conversions in continuous mode
+///
+/// The cycle is stretched to the conversion time when the averaging takes
+/// longer. Defaults to `Ms1000`.
+#[allow(unused, dead_code)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConversionCycle {
+    Ms15_5 = 0b000,
+    Ms125 = 0b001,
+    Ms250 = 0b010,
+    Ms500 = 0b011,
+    #[default]
+    Ms1000 = 0b100,
+    Ms4000 = 0b101,
+    Ms8000 = 0b110,
+    Ms16000 = 0b111,
+}
+
+impl ConversionCycle {
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    fn duration(&self) -> Duration {
+        match self {
+            ConversionCycle::Ms15_5 => Duration::from_micros(15_500),
+            ConversionCycle::Ms125 => Duration::from_millis(125),
+            ConversionCycle::Ms250 => Duration::from_millis(250),
+            ConversionCycle::Ms500 => Duration::from_millis(500),
+            ConversionCycle::Ms1000 => Duration::from_millis(1_000),
+            ConversionCycle::Ms4000 => Duration::from_millis(4_000),
+            ConversionCycle::Ms8000 => Duration::from_millis(8_000),
+            ConversionCycle::Ms16000 => Duration::from_millis(16_000),
+        }
+    }
+}
+
+/// Conversion mode of the sensor
+#[allow(unused, dead_code)]
+#[derive(Debug, Copy, Clone)]
+enum Mode {
+    Continuous = 0b00,
+    Shutdown = 0b01,
+    OneShot = 0b11,
+}
+
+impl Mode {
+    fn bits(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// A TMP117 sensor on an I2C bus
+pub struct Tmp117<I2C> {
+    address: u8,
+    i2c: I2C,
+    config: Config,
+}
+
+impl<I2C: I2c> Tmp117<I2C> {
+    /// Create a new sensor and configure it. The sensor is left shut down
+    /// until a conversion is requested.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the sensor is on.
+    /// - `address`: The I2C address of the sensor, e.g. [ADDRESS_GND].
+    /// - `config`: The configuration of the sensor.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidDeviceId` if the device is not a TMP117.
+    pub async fn new(i2c: I2C, address: u8, config: Config) -> Result<Self, Error<I2C::Error>> {
+        let mut tmp117 = Self {
+            address,
+            i2c,
+            config,
+        };
+
+        // The upper bits of the ID hold the revision of the device
+        let id = tmp117.read_register(Register::DEVICE_ID).await?;
+        if id & 0x0FFF != DEVICE_ID {
+            return Err(Error::InvalidDeviceId(id));
+        }
+
+        tmp117.write_config(Mode::Shutdown).await?;
+        Ok(tmp117)
+    }
+
+    /// Get the current configuration of the sensor
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Set the configuration of the sensor. The sensor is shut down until a
+    /// conversion is requested.
+    pub async fn set_config(&mut self, config: Config) -> Result<(), Error<I2C::Error>> {
+        self.config = config;
+        self.write_config(Mode::Shutdown).await
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Start a single conversion and wait for its result. The sensor shuts
+    /// down once the conversion is complete.
+    pub async fn one_shot_measurement(&mut self) -> Result<Celsius, Error<I2C::Error>> {
+        self.write_config(Mode::OneShot).await?;
+        Timer::after(self.config.conversion_time()).await;
+        self.get_measurement().await
+    }
+
+    /// Let the sensor convert continuously, once per conversion cycle.
+    pub async fn start_continuous(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write_config(Mode::Continuous).await
+    }
+
+    /// Shut the sensor down between conversions.
+    pub async fn shutdown(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write_config(Mode::Shutdown).await
+    }
+
+    /// Wait for a new conversion to complete and return its result.
+    ///
+    /// The data-ready flag is cleared when read, so every conversion is only
+    /// returned once.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Timeout` if no conversion completes within a conversion
+    /// cycle, e.g. because the sensor is shut down.
+    pub async fn get_measurement(&mut self) -> Result<Celsius, Error<I2C::Error>> {
+        let timeout = self.config.cycle_time() + TIMEOUT_MARGIN;
+        self.poll_config(timeout, |config| config & CONFIG_DATA_READY != 0)
+            .await?;
+        self.read_temperature().await
+    }
+
+    /// Read the result of the last conversion without waiting for a new one.
+    pub async fn read_temperature(&mut self) -> Result<Celsius, Error<I2C::Error>> {
+        let raw = self.read_register(Register::TEMPERATURE).await? as i16;
+        Ok(Celsius(raw as f32 * CELSIUS_PER_LSB))
+    }
+
+    /// Get the offset added to the conversions by the sensor.
+    pub async fn offset(&mut self) -> Result<Celsius, Error<I2C::Error>> {
+        let raw = self.read_register(Register::TEMPERATURE_OFFSET).await? as i16;
+        Ok(Celsius(raw as f32 * CELSIUS_PER_LSB))
+    }
+
+    /// Set the offset added to the conversions by the sensor. The offset is
+    /// lost on reset; use [Tmp117::store_offset] to persist it.
+    pub async fn set_offset(&mut self, offset: Celsius) -> Result<(), Error<I2C::Error>> {
+        self.write_register(Register::TEMPERATURE_OFFSET, offset_bits(offset))
+            .await
+    }
+
+    /// Set the offset added to the conversions by the sensor and program it
+    /// in the EEPROM, so that it is loaded on every power-up.
+    ///
+    /// The EEPROM endures a limited number of writes, so only store an
+    /// offset when calibrating the sensor.
+    pub async fn store_offset(&mut self, offset: Celsius) -> Result<(), Error<I2C::Error>> {
+        self.write_register(Register::EEPROM_UNLOCK, EEPROM_UNLOCK)
+            .await?;
+
+        // Writing a register while the EEPROM is unlocked programs it
+        let programmed = async {
+            self.write_register(Register::TEMPERATURE_OFFSET, offset_bits(offset))
+                .await?;
+            Timer::after(EEPROM_WRITE_TIME).await;
+            self.poll_config(TIMEOUT_MARGIN, |config| config & CONFIG_EEPROM_BUSY == 0)
+                .await
+        }
+        .await;
+
+        // Always lock the EEPROM again, even if programming failed
+        self.write_register(Register::EEPROM_UNLOCK, 0).await?;
+        programmed
+    }
+
+    /// Soft reset the sensor, which reloads the configuration and the offset
+    /// stored in its EEPROM, then apply the configuration of the driver
+    /// again.
+    pub async fn reset(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write_register(Register::CONFIGURATION, CONFIG_SOFT_RESET)
+            .await?;
+        // The EEPROM is read during the reset
+        Timer::after(Duration::from_millis(2)).await;
+        self.poll_config(TIMEOUT_MARGIN, |config| config & CONFIG_EEPROM_BUSY == 0)
+            .await?;
+        self.write_config(Mode::Shutdown).await
+    }
+
+    /// Poll the configuration register until a condition on its flags holds.
+    ///
+    /// Reading the register clears its data-ready and alert flags.
+    async fn poll_config(
+        &mut self,
+        timeout: Duration,
+        done: impl Fn(u16) -> bool,
+    ) -> Result<(), Error<I2C::Error>> {
+        let poll = async {
+            loop {
+                let config = self.read_register(Register::CONFIGURATION).await?;
+                if done(config) {
+                    return Ok(());
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        };
+        with_timeout(timeout, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    async fn write_config(&mut self, mode: Mode) -> Result<(), Error<I2C::Error>> {
+        let bits = self.config.bits(mode);
+        self.write_register(Register::CONFIGURATION, bits).await
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u16, Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.i2c
+            .write_read(self.address, &[register], &mut buf)
+            .await
+            .map_err(Error::I2c)?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    async fn write_register(&mut self, register: u8, value: u16) -> Result<(), Error<I2C::Error>> {
+        let [msb, lsb] = value.to_be_bytes();
+        self.i2c
+            .write(self.address, &[register, msb, lsb])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+/// Value of the offset register for a given offset, saturated to its range
+fn offset_bits(offset: Celsius) -> u16 {
+    let raw = (offset.0 / CELSIUS_PER_LSB).clamp(i16::MIN as f32, i16::MAX as f32);
+    raw as i16 as u16
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The device answered with an unexpected device ID, so it is not a
+    /// TMP117.
+    InvalidDeviceId(u16),
+    /// The sensor did not complete an operation in time.
+    Timeout,
+}
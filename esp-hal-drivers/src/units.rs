@@ -0,0 +1,148 @@
+//! # units
+//!
+//! ## Overview
+//!
+//! Strongly-typed physical quantities returned by the drivers of this crate,
+//! so that values in different units cannot be mixed up.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let voltage: Millivolts = adc.one_shot_measurement().await?;
+//! let precise: Microvolts = voltage.into();
+//! println!("{} mV / {} µV / {} V", voltage.0, precise.0, voltage.volts());
+//! ```
+
+use core::ops::{Add, Sub};
+
+/// A voltage in mV
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Millivolts(pub i32);
+
+impl Millivolts {
+    /// Return the voltage in µV.
+    pub fn microvolts(&self) -> Microvolts {
+        Microvolts(self.0.saturating_mul(1000))
+    }
+
+    /// Return the voltage in V.
+    pub fn volts(&self) -> f32 {
+        self.0 as f32 / 1000.0
+    }
+}
+
+/// A voltage in µV
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Microvolts(pub i32);
+
+impl Microvolts {
+    /// Return the voltage in mV, truncated towards zero.
+    pub fn millivolts(&self) -> Millivolts {
+        Millivolts(self.0 / 1000)
+    }
+
+    /// Return the voltage in V.
+    pub fn volts(&self) -> f32 {
+        self.0 as f32 / 1_000_000.0
+    }
+}
+
+impl From<Millivolts> for Microvolts {
+    fn from(value: Millivolts) -> Self {
+        value.microvolts()
+    }
+}
+
+impl From<Microvolts> for Millivolts {
+    fn from(value: Microvolts) -> Self {
+        value.millivolts()
+    }
+}
+
+impl Add for Millivolts {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Millivolts {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Add for Microvolts {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Microvolts {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+/// A temperature in °C
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Celsius(pub f32);
+
+impl Celsius {
+    /// Return the temperature in °F.
+    pub fn fahrenheit(&self) -> f32 {
+        self.0 * 9.0 / 5.0 + 32.0
+    }
+
+    /// Return the temperature in K.
+    pub fn kelvin(&self) -> f32 {
+        self.0 + 273.15
+    }
+}
+
+/// A relative humidity in %RH
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RelativeHumidity(pub f32);
+
+/// A pressure in hPa
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Hectopascals(pub f32);
+
+/// A flow rate in L/min
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LitersPerMinute(pub f32);
+
+/// A current in mA
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Milliamps(pub f32);
+
+/// A power in mW
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Milliwatts(pub f32);
+
+/// A distance in mm
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Millimeters(pub u32);
+
+impl Millimeters {
+    /// Return the distance in m.
+    pub fn meters(&self) -> f32 {
+        self.0 as f32 / 1000.0
+    }
+}
@@ -0,0 +1,831 @@
+//! # vl53l0x
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the ST VL53L0X
+//! time-of-flight distance sensor over I2C, ranging up to about 2 m.
+//!
+//! ST only documents the sensor through its C API, so the initialization
+//! follows the sequence of the API: loading the tuning settings, selecting
+//! the reference SPADs and running the reference calibrations.
+//!
+//! - Ranging is either single or continuous, back-to-back or at a fixed
+//!   period.
+//! - The timing budget trades speed for accuracy, from 20 ms to over 200 ms
+//!   per measurement.
+//! - Each measurement comes with a [RangeStatus] and the signal and ambient
+//!   rates, to discard unreliable distances.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut vl53l0x = Vl53l0x::new(i2c, ADDRESS).await?;
+//! vl53l0x.set_timing_budget(Duration::from_millis(200)).await?;
+//!
+//! // Take a single measurement
+//! let measurement = vl53l0x.measure().await?;
+//! if measurement.status == RangeStatus::Valid {
+//!     println!("{} mm", measurement.distance.0);
+//! }
+//!
+//! // Or range continuously every 250 ms
+//! vl53l0x.start_continuous(Some(Duration::from_millis(250))).await?;
+//! let measurement = vl53l0x.get_measurement().await?;
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use crate::units::Millimeters;
+
+/// Default I2C address of the sensor
+pub const ADDRESS: u8 = 0x29;
+
+/// Value of the model ID register
+const MODEL_ID: u8 = 0xEE;
+
+/// Minimum timing budget accepted by the sensor
+const MIN_TIMING_BUDGET_US: u32 = 20_000;
+
+/// Time between two polls of the status flags
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Extra time allowed for an operation on top of its nominal duration before
+/// giving up
+const TIMEOUT_MARGIN: Duration = Duration::from_millis(100);
+
+/// Registers of the sensor. See the register map of the API for more
+/// details.
+struct Register;
+
+impl Register {
+    const SYSRANGE_START: u8 = 0x00;
+    const SYSTEM_SEQUENCE_CONFIG: u8 = 0x01;
+    const SYSTEM_INTERMEASUREMENT_PERIOD: u8 = 0x04;
+    const SYSTEM_INTERRUPT_CONFIG_GPIO: u8 = 0x0A;
+    const SYSTEM_INTERRUPT_CLEAR: u8 = 0x0B;
+    const RESULT_INTERRUPT_STATUS: u8 = 0x13;
+    const RESULT_RANGE_STATUS: u8 = 0x14;
+    const FINAL_RANGE_CONFIG_MIN_COUNT_RATE_RTN_LIMIT: u8 = 0x44;
+    const MSRC_CONFIG_TIMEOUT_MACROP: u8 = 0x46;
+    const DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD: u8 = 0x4E;
+    const DYNAMIC_SPAD_REF_EN_START_OFFSET: u8 = 0x4F;
+    const PRE_RANGE_CONFIG_VCSEL_PERIOD: u8 = 0x50;
+    const PRE_RANGE_CONFIG_TIMEOUT_MACROP_HI: u8 = 0x51;
+    const MSRC_CONFIG_CONTROL: u8 = 0x60;
+    const FINAL_RANGE_CONFIG_VCSEL_PERIOD: u8 = 0x70;
+    const FINAL_RANGE_CONFIG_TIMEOUT_MACROP_HI: u8 = 0x71;
+    const GPIO_HV_MUX_ACTIVE_HIGH: u8 = 0x84;
+    const VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV: u8 = 0x89;
+    const I2C_SLAVE_DEVICE_ADDRESS: u8 = 0x8A;
+    const GLOBAL_CONFIG_SPAD_ENABLES_REF_0: u8 = 0xB0;
+    const GLOBAL_CONFIG_REF_EN_START_SELECT: u8 = 0xB6;
+    const IDENTIFICATION_MODEL_ID: u8 = 0xC0;
+    const OSC_CALIBRATE_VAL: u8 = 0xF8;
+}
+
+/// Values of the SYSRANGE_START register
+struct RangeMode;
+
+impl RangeMode {
+    const SINGLE_SHOT: u8 = 0x01;
+    const BACK_TO_BACK: u8 = 0x02;
+    const TIMED: u8 = 0x04;
+}
+
+/// Default tuning settings of the API, written as register and value pairs.
+/// Register 0xFF selects the page of the following registers.
+const TUNING_SETTINGS: [(u8, u8); 80] = [
+    (0xFF, 0x01),
+    (0x00, 0x00),
+    (0xFF, 0x00),
+    (0x09, 0x00),
+    (0x10, 0x00),
+    (0x11, 0x00),
+    (0x24, 0x01),
+    (0x25, 0xFF),
+    (0x75, 0x00),
+    (0xFF, 0x01),
+    (0x4E, 0x2C),
+    (0x48, 0x00),
+    (0x30, 0x20),
+    (0xFF, 0x00),
+    (0x30, 0x09),
+    (0x54, 0x00),
+    (0x31, 0x04),
+    (0x32, 0x03),
+    (0x40, 0x83),
+    (0x46, 0x25),
+    (0x60, 0x00),
+    (0x27, 0x00),
+    (0x50, 0x06),
+    (0x51, 0x00),
+    (0x52, 0x96),
+    (0x56, 0x08),
+    (0x57, 0x30),
+    (0x61, 0x00),
+    (0x62, 0x00),
+    (0x64, 0x00),
+    (0x65, 0x00),
+    (0x66, 0xA0),
+    (0xFF, 0x01),
+    (0x22, 0x32),
+    (0x47, 0x14),
+    (0x49, 0xFF),
+    (0x4A, 0x00),
+    (0xFF, 0x00),
+    (0x7A, 0x0A),
+    (0x7B, 0x00),
+    (0x78, 0x21),
+    (0xFF, 0x01),
+    (0x23, 0x34),
+    (0x42, 0x00),
+    (0x44, 0xFF),
+    (0x45, 0x26),
+    (0x46, 0x05),
+    (0x40, 0x40),
+    (0x0E, 0x06),
+    (0x20, 0x1A),
+    (0x43, 0x40),
+    (0xFF, 0x00),
+    (0x34, 0x03),
+    (0x35, 0x44),
+    (0xFF, 0x01),
+    (0x31, 0x04),
+    (0x4B, 0x09),
+    (0x4C, 0x05),
+    (0x4D, 0x04),
+    (0xFF, 0x00),
+    (0x44, 0x00),
+    (0x45, 0x20),
+    (0x47, 0x08),
+    (0x48, 0x28),
+    (0x67, 0x00),
+    (0x70, 0x04),
+    (0x71, 0x01),
+    (0x72, 0xFE),
+    (0x76, 0x00),
+    (0x77, 0x00),
+    (0xFF, 0x01),
+    (0x0D, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x01),
+    (0x01, 0xF8),
+    (0xFF, 0x01),
+    (0x8E, 0x01),
+    (0x00, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x00),
+];
+
+/// Quality of a measurement, as reported by the sensor
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RangeStatus {
+    Valid,
+    /// The returned signal was too weak for the distance to be reliable,
+    /// e.g. with a target out of range or of low reflectance.
+    SignalFail,
+    /// The target is too close, or the cover glass reflects too much.
+    MinRangeFail,
+    /// The phase of the returned signal is out of bounds, usually because of
+    /// a target beyond the range of the sensor.
+    PhaseFail,
+    /// The laser or the receiver failed.
+    HardwareFail,
+}
+
+impl RangeStatus {
+    /// Decode the device range status, bits 6:3 of the range status
+    /// register.
+    fn from_bits(bits: u8) -> Self {
+        match (bits >> 3) & 0x0F {
+            1..=3 => RangeStatus::HardwareFail,
+            4 => RangeStatus::SignalFail,
+            6 | 9 => RangeStatus::PhaseFail,
+            8 | 10 => RangeStatus::MinRangeFail,
+            _ => RangeStatus::Valid,
+        }
+    }
+}
+
+/// A measurement of the sensor
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    pub distance: Millimeters,
+    pub status: RangeStatus,
+    /// Rate of returned photons from the target, in million counts per
+    /// second
+    pub signal_rate: f32,
+    /// Rate of photons from ambient light, in million counts per second
+    pub ambient_rate: f32,
+}
+
+/// Steps of the ranging sequence and their timeouts, in macro periods and µs
+#[derive(Debug, Default, Copy, Clone)]
+struct Sequence {
+    tcc: bool,
+    dss: bool,
+    msrc: bool,
+    pre_range: bool,
+    final_range: bool,
+    msrc_dss_tcc_us: u32,
+    pre_range_mclks: u32,
+    pre_range_us: u32,
+    final_range_vcsel_pclks: u8,
+    final_range_us: u32,
+}
+
+/// A VL53L0X on an I2C bus
+pub struct Vl53l0x<I2C> {
+    address: u8,
+    i2c: I2C,
+    /// Value read from the sensor on initialization, written back before
+    /// every ranging
+    stop_variable: u8,
+    timing_budget_us: u32,
+    /// Period of the continuous ranging, zero for back-to-back
+    period: Duration,
+}
+
+impl<I2C: I2c> Vl53l0x<I2C> {
+    /// Create a new sensor, initialize and calibrate it.
+    ///
+    /// # Arguments
+    ///
+    /// - `i2c`: The I2C bus the sensor is on.
+    /// - `address`: The I2C address of the sensor, [ADDRESS] after a
+    ///   power-up.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidModelId` if the device does not identify as a
+    /// VL53L0X, and `Error::Timeout` if a calibration did not complete.
+    pub async fn new(i2c: I2C, address: u8) -> Result<Self, Error<I2C::Error>> {
+        let mut vl53l0x = Self {
+            address,
+            i2c,
+            stop_variable: 0,
+            timing_budget_us: 0,
+            period: Duration::from_ticks(0),
+        };
+
+        let model_id = vl53l0x
+            .read_register(Register::IDENTIFICATION_MODEL_ID)
+            .await?;
+        if model_id != MODEL_ID {
+            return Err(Error::InvalidModelId(model_id));
+        }
+
+        vl53l0x.data_init().await?;
+        vl53l0x.static_init().await?;
+        vl53l0x.ref_calibration().await?;
+        Ok(vl53l0x)
+    }
+
+    /// Release the underlying I2C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Change the I2C address of the sensor, e.g. to put several sensors on
+    /// the same bus by enabling them one at a time. The address is lost when
+    /// the sensor is powered off.
+    pub async fn set_address(&mut self, address: u8) -> Result<(), Error<I2C::Error>> {
+        self.write_register(Register::I2C_SLAVE_DEVICE_ADDRESS, address & 0x7F)
+            .await?;
+        self.address = address;
+        Ok(())
+    }
+
+    /// Set the minimum rate of returned signal for a measurement to be
+    /// valid, in million counts per second. Lower limits extend the range but
+    /// make far measurements less accurate.
+    ///
+    /// Defaults to 0.25 MCPS.
+    pub async fn set_signal_rate_limit(&mut self, limit: f32) -> Result<(), Error<I2C::Error>> {
+        // Fixed point with 7 fractional bits
+        let bits = (limit.clamp(0.0, 511.99) * (1 << 7) as f32) as u16;
+        self.write_register16(Register::FINAL_RANGE_CONFIG_MIN_COUNT_RATE_RTN_LIMIT, bits)
+            .await
+    }
+
+    /// Get the time allowed for a measurement.
+    pub fn timing_budget(&self) -> Duration {
+        Duration::from_micros(self.timing_budget_us as u64)
+    }
+
+    /// Set the time allowed for a measurement. Longer budgets give more
+    /// accurate measurements.
+    ///
+    /// Defaults to about 33 ms.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidTimingBudget` if the budget is shorter than
+    /// 20 ms or than the steps of the ranging sequence before the final
+    /// range.
+    pub async fn set_timing_budget(&mut self, budget: Duration) -> Result<(), Error<I2C::Error>> {
+        const START_OVERHEAD: u32 = 1320;
+        const END_OVERHEAD: u32 = 960;
+        const FINAL_RANGE_OVERHEAD: u32 = 550;
+
+        let budget_us = budget.as_micros().min(u32::MAX as u64) as u32;
+        if budget_us < MIN_TIMING_BUDGET_US {
+            return Err(Error::InvalidTimingBudget);
+        }
+
+        let sequence = self.sequence().await?;
+        if !sequence.final_range {
+            return Ok(());
+        }
+
+        let used_us = START_OVERHEAD
+            + END_OVERHEAD
+            + FINAL_RANGE_OVERHEAD
+            + sequence.steps_before_final_range_us();
+        if used_us > budget_us {
+            return Err(Error::InvalidTimingBudget);
+        }
+
+        // The final range timeout includes the pre-range
+        let mut final_range_mclks =
+            timeout_us_to_mclks(budget_us - used_us, sequence.final_range_vcsel_pclks);
+        if sequence.pre_range {
+            final_range_mclks += sequence.pre_range_mclks;
+        }
+        self.write_register16(
+            Register::FINAL_RANGE_CONFIG_TIMEOUT_MACROP_HI,
+            encode_timeout(final_range_mclks),
+        )
+        .await?;
+
+        self.timing_budget_us = budget_us;
+        Ok(())
+    }
+
+    /// Take a single measurement.
+    pub async fn measure(&mut self) -> Result<Measurement, Error<I2C::Error>> {
+        self.restore_stop_variable().await?;
+        self.write_register(Register::SYSRANGE_START, RangeMode::SINGLE_SHOT)
+            .await?;
+
+        // The start bit is cleared once the ranging started
+        let timeout = self.timing_budget() + TIMEOUT_MARGIN;
+        self.poll_register(timeout, Register::SYSRANGE_START, |start| start & 0x01 == 0)
+            .await?;
+        self.read_measurement(timeout).await
+    }
+
+    /// Start ranging continuously.
+    ///
+    /// # Arguments
+    ///
+    /// - `period`: The time between the start of two measurements, or
+    ///   `None` to range back-to-back. A period shorter than the timing
+    ///   budget ranges back-to-back.
+    pub async fn start_continuous(
+        &mut self,
+        period: Option<Duration>,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.restore_stop_variable().await?;
+
+        match period {
+            Some(period) => {
+                // The period is counted by the internal oscillator
+                let mut bits = period.as_millis() as u32;
+                let calibration = self.read_register16(Register::OSC_CALIBRATE_VAL).await?;
+                if calibration != 0 {
+                    bits = bits.saturating_mul(calibration as u32);
+                }
+                self.write_register32(Register::SYSTEM_INTERMEASUREMENT_PERIOD, bits)
+                    .await?;
+                self.write_register(Register::SYSRANGE_START, RangeMode::TIMED)
+                    .await?;
+                self.period = period;
+            }
+            None => {
+                self.write_register(Register::SYSRANGE_START, RangeMode::BACK_TO_BACK)
+                    .await?;
+                self.period = Duration::from_ticks(0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop ranging continuously.
+    pub async fn stop_continuous(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write_register(Register::SYSRANGE_START, RangeMode::SINGLE_SHOT)
+            .await?;
+        self.write_register(0xFF, 0x01).await?;
+        self.write_register(0x00, 0x00).await?;
+        self.write_register(0x91, 0x00).await?;
+        self.write_register(0x00, 0x01).await?;
+        self.write_register(0xFF, 0x00).await
+    }
+
+    /// Wait for the next continuous measurement and return it.
+    pub async fn get_measurement(&mut self) -> Result<Measurement, Error<I2C::Error>> {
+        let timeout = self.period + self.timing_budget() + TIMEOUT_MARGIN;
+        self.read_measurement(timeout).await
+    }
+
+    /// Wait for a measurement to complete, read it and clear its interrupt.
+    async fn read_measurement(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Measurement, Error<I2C::Error>> {
+        self.poll_register(timeout, Register::RESULT_INTERRUPT_STATUS, |status| {
+            status & 0x07 != 0
+        })
+        .await?;
+
+        let mut buf = [0u8; 12];
+        self.read_registers(Register::RESULT_RANGE_STATUS, &mut buf)
+            .await?;
+        self.write_register(Register::SYSTEM_INTERRUPT_CLEAR, 0x01)
+            .await?;
+
+        // The rates are fixed point with 7 fractional bits
+        let rate = |bytes: [u8; 2]| u16::from_be_bytes(bytes) as f32 / (1 << 7) as f32;
+        Ok(Measurement {
+            distance: Millimeters(u16::from_be_bytes([buf[10], buf[11]]) as u32),
+            status: RangeStatus::from_bits(buf[0]),
+            signal_rate: rate([buf[6], buf[7]]),
+            ambient_rate: rate([buf[8], buf[9]]),
+        })
+    }
+
+    /// First part of the initialization, `VL53L0X_DataInit` in the API.
+    async fn data_init(&mut self) -> Result<(), Error<I2C::Error>> {
+        // Switch the I/O from 1.8 V to 2.8 V
+        let extsup = self
+            .read_register(Register::VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV)
+            .await?;
+        self.write_register(Register::VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV, extsup | 0x01)
+            .await?;
+
+        // Set the I2C standard mode
+        self.write_register(0x88, 0x00).await?;
+
+        self.write_register(0x80, 0x01).await?;
+        self.write_register(0xFF, 0x01).await?;
+        self.write_register(0x00, 0x00).await?;
+        self.stop_variable = self.read_register(0x91).await?;
+        self.write_register(0x00, 0x01).await?;
+        self.write_register(0xFF, 0x00).await?;
+        self.write_register(0x80, 0x00).await?;
+
+        // Disable the minimum signal rate checks of the MSRC and pre-range
+        // steps
+        let control = self.read_register(Register::MSRC_CONFIG_CONTROL).await?;
+        self.write_register(Register::MSRC_CONFIG_CONTROL, control | 0x12)
+            .await?;
+        self.set_signal_rate_limit(0.25).await?;
+
+        self.write_register(Register::SYSTEM_SEQUENCE_CONFIG, 0xFF)
+            .await
+    }
+
+    /// Second part of the initialization, `VL53L0X_StaticInit` in the API.
+    async fn static_init(&mut self) -> Result<(), Error<I2C::Error>> {
+        let (spad_count, aperture) = self.spad_info().await?;
+
+        // Enable the requested number of reference SPADs from the map stored
+        // in the sensor, starting from the first aperture SPAD if needed
+        let mut spad_map = [0u8; 6];
+        self.read_registers(Register::GLOBAL_CONFIG_SPAD_ENABLES_REF_0, &mut spad_map)
+            .await?;
+        self.write_register(0xFF, 0x01).await?;
+        self.write_register(Register::DYNAMIC_SPAD_REF_EN_START_OFFSET, 0x00)
+            .await?;
+        self.write_register(Register::DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD, 0x2C)
+            .await?;
+        self.write_register(0xFF, 0x00).await?;
+        self.write_register(Register::GLOBAL_CONFIG_REF_EN_START_SELECT, 0xB4)
+            .await?;
+
+        let first_spad = if aperture { 12 } else { 0 };
+        let mut enabled = 0;
+        for i in 0..48 {
+            let mask = 1 << (i % 8);
+            if i < first_spad || enabled == spad_count {
+                spad_map[i / 8] &= !mask;
+            } else if spad_map[i / 8] & mask != 0 {
+                enabled += 1;
+            }
+        }
+        let mut buf = [0u8; 7];
+        buf[0] = Register::GLOBAL_CONFIG_SPAD_ENABLES_REF_0;
+        buf[1..].copy_from_slice(&spad_map);
+        self.i2c
+            .write(self.address, &buf)
+            .await
+            .map_err(Error::I2c)?;
+
+        for (register, value) in TUNING_SETTINGS {
+            self.write_register(register, value).await?;
+        }
+
+        // Assert the interrupt, active low, when a measurement is ready
+        self.write_register(Register::SYSTEM_INTERRUPT_CONFIG_GPIO, 0x04)
+            .await?;
+        let mux = self
+            .read_register(Register::GPIO_HV_MUX_ACTIVE_HIGH)
+            .await?;
+        self.write_register(Register::GPIO_HV_MUX_ACTIVE_HIGH, mux & !0x10)
+            .await?;
+        self.write_register(Register::SYSTEM_INTERRUPT_CLEAR, 0x01)
+            .await?;
+
+        // Disable the MSRC and TCC steps, then recompute the final range
+        // timeout without them
+        self.timing_budget_us = self.sequence().await?.timing_budget_us();
+        self.write_register(Register::SYSTEM_SEQUENCE_CONFIG, 0xE8)
+            .await?;
+        self.set_timing_budget(self.timing_budget()).await
+    }
+
+    /// Run the VHV and phase calibrations, `VL53L0X_PerformRefCalibration`
+    /// in the API.
+    async fn ref_calibration(&mut self) -> Result<(), Error<I2C::Error>> {
+        for (sequence, vhv_init) in [(0x01, 0x40), (0x02, 0x00)] {
+            self.write_register(Register::SYSTEM_SEQUENCE_CONFIG, sequence)
+                .await?;
+            self.write_register(Register::SYSRANGE_START, RangeMode::SINGLE_SHOT | vhv_init)
+                .await?;
+            self.poll_register(
+                TIMEOUT_MARGIN,
+                Register::RESULT_INTERRUPT_STATUS,
+                |status| status & 0x07 != 0,
+            )
+            .await?;
+            self.write_register(Register::SYSTEM_INTERRUPT_CLEAR, 0x01)
+                .await?;
+            self.write_register(Register::SYSRANGE_START, 0x00).await?;
+        }
+
+        // Restore the sequence
+        self.write_register(Register::SYSTEM_SEQUENCE_CONFIG, 0xE8)
+            .await
+    }
+
+    /// Read the number and type of the reference SPADs from the
+    /// non-volatile memory of the sensor.
+    async fn spad_info(&mut self) -> Result<(u8, bool), Error<I2C::Error>> {
+        self.write_register(0x80, 0x01).await?;
+        self.write_register(0xFF, 0x01).await?;
+        self.write_register(0x00, 0x00).await?;
+        self.write_register(0xFF, 0x06).await?;
+        let value = self.read_register(0x83).await?;
+        self.write_register(0x83, value | 0x04).await?;
+        self.write_register(0xFF, 0x07).await?;
+        self.write_register(0x81, 0x01).await?;
+        self.write_register(0x80, 0x01).await?;
+        self.write_register(0x94, 0x6B).await?;
+        self.write_register(0x83, 0x00).await?;
+
+        self.poll_register(TIMEOUT_MARGIN, 0x83, |value| value != 0x00)
+            .await?;
+        self.write_register(0x83, 0x01).await?;
+        let info = self.read_register(0x92).await?;
+
+        self.write_register(0x81, 0x00).await?;
+        self.write_register(0xFF, 0x06).await?;
+        let value = self.read_register(0x83).await?;
+        self.write_register(0x83, value & !0x04).await?;
+        self.write_register(0xFF, 0x01).await?;
+        self.write_register(0x00, 0x01).await?;
+        self.write_register(0xFF, 0x00).await?;
+        self.write_register(0x80, 0x00).await?;
+
+        Ok((info & 0x7F, info & 0x80 != 0))
+    }
+
+    /// Read the enabled steps of the ranging sequence and their timeouts.
+    async fn sequence(&mut self) -> Result<Sequence, Error<I2C::Error>> {
+        let config = self.read_register(Register::SYSTEM_SEQUENCE_CONFIG).await?;
+        let pre_range_vcsel_pclks = decode_vcsel_period(
+            self.read_register(Register::PRE_RANGE_CONFIG_VCSEL_PERIOD)
+                .await?,
+        );
+        let final_range_vcsel_pclks = decode_vcsel_period(
+            self.read_register(Register::FINAL_RANGE_CONFIG_VCSEL_PERIOD)
+                .await?,
+        );
+
+        let msrc_dss_tcc_mclks = self
+            .read_register(Register::MSRC_CONFIG_TIMEOUT_MACROP)
+            .await? as u32
+            + 1;
+        let pre_range_mclks = decode_timeout(
+            self.read_register16(Register::PRE_RANGE_CONFIG_TIMEOUT_MACROP_HI)
+                .await?,
+        );
+        let mut final_range_mclks = decode_timeout(
+            self.read_register16(Register::FINAL_RANGE_CONFIG_TIMEOUT_MACROP_HI)
+                .await?,
+        );
+
+        let pre_range = config & (1 << 6) != 0;
+        if pre_range {
+            final_range_mclks = final_range_mclks.saturating_sub(pre_range_mclks);
+        }
+
+        Ok(Sequence {
+            tcc: config & (1 << 4) != 0,
+            dss: config & (1 << 3) != 0,
+            msrc: config & (1 << 2) != 0,
+            pre_range,
+            final_range: config & (1 << 7) != 0,
+            msrc_dss_tcc_us: timeout_mclks_to_us(msrc_dss_tcc_mclks, pre_range_vcsel_pclks),
+            pre_range_mclks,
+            pre_range_us: timeout_mclks_to_us(pre_range_mclks, pre_range_vcsel_pclks),
+            final_range_vcsel_pclks,
+            final_range_us: timeout_mclks_to_us(final_range_mclks, final_range_vcsel_pclks),
+        })
+    }
+
+    /// Write back the stop variable read on initialization, as the API does
+    /// before starting a ranging.
+    async fn restore_stop_variable(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write_register(0x80, 0x01).await?;
+        self.write_register(0xFF, 0x01).await?;
+        self.write_register(0x00, 0x00).await?;
+        self.write_register(0x91, self.stop_variable).await?;
+        self.write_register(0x00, 0x01).await?;
+        self.write_register(0xFF, 0x00).await?;
+        self.write_register(0x80, 0x00).await
+    }
+
+    /// Poll a register until a condition on its value holds.
+    async fn poll_register(
+        &mut self,
+        timeout: Duration,
+        register: u8,
+        done: impl Fn(u8) -> bool,
+    ) -> Result<(), Error<I2C::Error>> {
+        let poll = async {
+            loop {
+                let value = self.read_register(register).await?;
+                if done(value) {
+                    return Ok(());
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        };
+        with_timeout(timeout, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    async fn read_registers(
+        &mut self,
+        register: u8,
+        buf: &mut [u8],
+    ) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write_read(self.address, &[register], buf)
+            .await
+            .map_err(Error::I2c)
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<I2C::Error>> {
+        let mut buf = [0u8; 1];
+        self.read_registers(register, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn read_register16(&mut self, register: u8) -> Result<u16, Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        self.read_registers(register, &mut buf).await?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .await
+            .map_err(Error::I2c)
+    }
+
+    async fn write_register16(
+        &mut self,
+        register: u8,
+        value: u16,
+    ) -> Result<(), Error<I2C::Error>> {
+        let [msb, lsb] = value.to_be_bytes();
+        self.i2c
+            .write(self.address, &[register, msb, lsb])
+            .await
+            .map_err(Error::I2c)
+    }
+
+    async fn write_register32(
+        &mut self,
+        register: u8,
+        value: u32,
+    ) -> Result<(), Error<I2C::Error>> {
+        let [b0, b1, b2, b3] = value.to_be_bytes();
+        self.i2c
+            .write(self.address, &[register, b0, b1, b2, b3])
+            .await
+            .map_err(Error::I2c)
+    }
+}
+
+impl Sequence {
+    /// Time taken by the enabled steps before the final range, overheads
+    /// included
+    fn steps_before_final_range_us(&self) -> u32 {
+        const MSRC_OVERHEAD: u32 = 660;
+        const TCC_OVERHEAD: u32 = 590;
+        const DSS_OVERHEAD: u32 = 690;
+        const PRE_RANGE_OVERHEAD: u32 = 660;
+
+        let mut used_us = 0;
+        if self.tcc {
+            used_us += self.msrc_dss_tcc_us + TCC_OVERHEAD;
+        }
+        if self.dss {
+            used_us += 2 * (self.msrc_dss_tcc_us + DSS_OVERHEAD);
+        } else if self.msrc {
+            used_us += self.msrc_dss_tcc_us + MSRC_OVERHEAD;
+        }
+        if self.pre_range {
+            used_us += self.pre_range_us + PRE_RANGE_OVERHEAD;
+        }
+        used_us
+    }
+
+    /// Timing budget of the sequence as configured on the sensor
+    fn timing_budget_us(&self) -> u32 {
+        const START_OVERHEAD: u32 = 1910;
+        const END_OVERHEAD: u32 = 960;
+        const FINAL_RANGE_OVERHEAD: u32 = 550;
+
+        let mut budget_us = START_OVERHEAD + END_OVERHEAD + self.steps_before_final_range_us();
+        if self.final_range {
+            budget_us += self.final_range_us + FINAL_RANGE_OVERHEAD;
+        }
+        budget_us
+    }
+}
+
+/// Period of the VCSEL in PLL clocks from its register value
+fn decode_vcsel_period(bits: u8) -> u8 {
+    (bits + 1) << 1
+}
+
+/// Macro period in ns for a VCSEL period in PLL clocks
+fn macro_period_ns(vcsel_pclks: u8) -> u32 {
+    (2304 * vcsel_pclks as u32 * 1655 + 500) / 1000
+}
+
+fn timeout_mclks_to_us(mclks: u32, vcsel_pclks: u8) -> u32 {
+    let macro_period_ns = macro_period_ns(vcsel_pclks);
+    (mclks * macro_period_ns + 500) / 1000
+}
+
+fn timeout_us_to_mclks(us: u32, vcsel_pclks: u8) -> u32 {
+    let macro_period_ns = macro_period_ns(vcsel_pclks);
+    (us * 1000 + macro_period_ns / 2) / macro_period_ns
+}
+
+/// Timeout in macro periods from its register value, a mantissa in the low
+/// byte shifted by the exponent in the high byte
+fn decode_timeout(bits: u16) -> u32 {
+    (((bits & 0xFF) as u32) << (bits >> 8)) + 1
+}
+
+/// Register value of a timeout in macro periods
+fn encode_timeout(mclks: u32) -> u16 {
+    if mclks == 0 {
+        return 0;
+    }
+
+    let mut mantissa = mclks - 1;
+    let mut exponent = 0;
+    while mantissa > 0xFF {
+        mantissa >>= 1;
+        exponent += 1;
+    }
+    (exponent << 8) | mantissa as u16
+}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying I2C bus.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    I2c(E),
+    /// The device does not identify as a VL53L0X.
+    InvalidModelId(u8),
+    /// The timing budget is too short.
+    InvalidTimingBudget,
+    /// An operation did not complete in time.
+    Timeout,
+}
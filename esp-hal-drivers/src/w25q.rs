@@ -0,0 +1,445 @@
+//! # w25q
+//!
+//! ## Overview
+//!
+//! This driver provides an abstraction to interact with the Winbond W25Qxx
+//! serial NOR flash memories over SPI, from the 1 MiB W25Q80 to the 16 MiB
+//! W25Q128, e.g. to log data beyond the internal flash of the ESP32-C3.
+//!
+//! - The size of the flash is read from its JEDEC ID.
+//! - Erasing picks the largest of the 4 KiB sectors, 32 KiB blocks and
+//!   64 KiB blocks that fits the range, or the whole chip.
+//! - Programs are split along the 256 bytes pages of the flash, since a
+//!   program wraps around within its page.
+//! - Reads use the fast read command, in a single transaction.
+//! - The flash implements the `embedded-storage-async`
+//!   [NorFlash](embedded_storage_async::nor_flash::NorFlash) traits, so that
+//!   it can back any storage built on them.
+//!
+//! As with any NOR flash, programming can only clear bits, so a range must
+//! be erased to `0xFF` before being programmed again.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let spi = SpiDevice::new(spi_bus, cs);
+//! let mut flash = W25q::new(spi).await?;
+//!
+//! flash.erase_sector(0).await?;
+//! flash.program(0, b"hello").await?;
+//! let mut buf = [0u8; 5];
+//! flash.read(0, &mut buf).await?;
+//! ```
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_hal_async::spi::{Operation, SpiDevice};
+use embedded_storage_async::nor_flash::{
+    ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+/// Size of a page, the largest unit of a program
+pub const PAGE_SIZE: usize = 256;
+
+/// Size of a sector, the smallest unit of an erase
+pub const SECTOR_SIZE: usize = 4096;
+
+/// Size of a small block
+pub const BLOCK_32K_SIZE: usize = 32 * 1024;
+
+/// Size of a large block
+pub const BLOCK_64K_SIZE: usize = 64 * 1024;
+
+/// Capacity codes of the JEDEC ID supported by the driver, from the W25Q80 to
+/// the W25Q128. Larger flashes need 4-byte addresses.
+const MIN_CAPACITY_CODE: u8 = 0x14;
+const MAX_CAPACITY_CODE: u8 = 0x18;
+
+/// Time between two polls of the flash during a program
+const PROGRAM_POLL_INTERVAL: Duration = Duration::from_micros(100);
+
+/// Time between two polls of the flash during an erase
+const ERASE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Maximum times taken by the operations. See datasheet section 9.6 for more
+/// details.
+const PROGRAM_TIMEOUT: Duration = Duration::from_millis(3);
+const SECTOR_ERASE_TIMEOUT: Duration = Duration::from_millis(400);
+const BLOCK_32K_ERASE_TIMEOUT: Duration = Duration::from_millis(1600);
+const BLOCK_64K_ERASE_TIMEOUT: Duration = Duration::from_millis(2000);
+const CHIP_ERASE_TIMEOUT: Duration = Duration::from_secs(200);
+
+/// Commands of the flash. See datasheet section 8.1 for more details.
+struct Command;
+
+impl Command {
+    const WRITE_ENABLE: u8 = 0x06;
+    const READ_STATUS_1: u8 = 0x05;
+    const PAGE_PROGRAM: u8 = 0x02;
+    const FAST_READ: u8 = 0x0B;
+    const SECTOR_ERASE: u8 = 0x20;
+    const BLOCK_32K_ERASE: u8 = 0x52;
+    const BLOCK_64K_ERASE: u8 = 0xD8;
+    const CHIP_ERASE: u8 = 0xC7;
+    const JEDEC_ID: u8 = 0x9F;
+}
+
+/// Flag of the status register set during a program or an erase
+const STATUS_BUSY: u8 = 1 << 0;
+
+/// JEDEC ID of the flash
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct JedecId {
+    /// Manufacturer, `0xEF` for Winbond
+    pub manufacturer: u8,
+    /// Memory type, e.g. `0x40` for the W25QxxJV-IQ
+    pub memory_type: u8,
+    /// Capacity, the base 2 logarithm of the size in bytes
+    pub capacity: u8,
+}
+
+/// A W25Qxx flash on an SPI bus
+pub struct W25q<SPI> {
+    spi: SPI,
+    id: JedecId,
+}
+
+impl<SPI: SpiDevice> W25q<SPI> {
+    /// Create a new flash and read its JEDEC ID.
+    ///
+    /// # Arguments
+    ///
+    /// - `spi`: The SPI device of the flash, in mode 0 and up to 133 MHz.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnsupportedCapacity` if the flash is not found or is
+    /// larger than 16 MiB.
+    pub async fn new(spi: SPI) -> Result<Self, Error<SPI::Error>> {
+        let mut flash = Self {
+            spi,
+            id: JedecId {
+                manufacturer: 0,
+                memory_type: 0,
+                capacity: 0,
+            },
+        };
+
+        let mut buf = [0u8; 3];
+        flash
+            .spi
+            .transaction(&mut [
+                Operation::Write(&[Command::JEDEC_ID]),
+                Operation::Read(&mut buf),
+            ])
+            .await
+            .map_err(Error::Spi)?;
+        flash.id = JedecId {
+            manufacturer: buf[0],
+            memory_type: buf[1],
+            capacity: buf[2],
+        };
+
+        if !(MIN_CAPACITY_CODE..=MAX_CAPACITY_CODE).contains(&flash.id.capacity) {
+            return Err(Error::UnsupportedCapacity(flash.id.capacity));
+        }
+
+        Ok(flash)
+    }
+
+    /// Get the JEDEC ID of the flash
+    pub fn jedec_id(&self) -> JedecId {
+        self.id
+    }
+
+    /// Get the size of the flash in bytes
+    pub fn capacity(&self) -> usize {
+        1 << self.id.capacity
+    }
+
+    /// Release the underlying SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Read bytes starting at an address.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::OutOfBounds` if the bytes do not fit in the flash.
+    pub async fn read(&mut self, address: u32, buf: &mut [u8]) -> Result<(), Error<SPI::Error>> {
+        self.check_bounds(address, buf.len())?;
+
+        // The fast read command is followed by a dummy byte
+        let [_, a2, a1, a0] = address.to_be_bytes();
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Command::FAST_READ, a2, a1, a0, 0]),
+                Operation::Read(buf),
+            ])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Program bytes starting at an address, one page at a time, and wait for
+    /// the programs to complete.
+    ///
+    /// The bytes must have been erased beforehand, since a program can only
+    /// clear bits.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::OutOfBounds` if the bytes do not fit in the flash,
+    /// and `Error::Timeout` if a program does not complete.
+    pub async fn program(&mut self, address: u32, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        self.check_bounds(address, data.len())?;
+
+        let mut address = address as usize;
+        let mut data = data;
+        while !data.is_empty() {
+            let len = data.len().min(PAGE_SIZE - address % PAGE_SIZE);
+            let (chunk, rest) = data.split_at(len);
+
+            self.write_enable().await?;
+            let [_, a2, a1, a0] = (address as u32).to_be_bytes();
+            self.spi
+                .transaction(&mut [
+                    Operation::Write(&[Command::PAGE_PROGRAM, a2, a1, a0]),
+                    Operation::Write(chunk),
+                ])
+                .await
+                .map_err(Error::Spi)?;
+            self.wait_ready(PROGRAM_TIMEOUT, PROGRAM_POLL_INTERVAL)
+                .await?;
+
+            address += len;
+            data = rest;
+        }
+        Ok(())
+    }
+
+    /// Erase the 4 KiB sector at an address.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotAligned` if the address is not at the start of a
+    /// sector.
+    pub async fn erase_sector(&mut self, address: u32) -> Result<(), Error<SPI::Error>> {
+        self.erase_unit(
+            address,
+            SECTOR_SIZE,
+            Command::SECTOR_ERASE,
+            SECTOR_ERASE_TIMEOUT,
+        )
+        .await
+    }
+
+    /// Erase the 32 KiB block at an address.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotAligned` if the address is not at the start of a
+    /// block.
+    pub async fn erase_block_32k(&mut self, address: u32) -> Result<(), Error<SPI::Error>> {
+        self.erase_unit(
+            address,
+            BLOCK_32K_SIZE,
+            Command::BLOCK_32K_ERASE,
+            BLOCK_32K_ERASE_TIMEOUT,
+        )
+        .await
+    }
+
+    /// Erase the 64 KiB block at an address.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotAligned` if the address is not at the start of a
+    /// block.
+    pub async fn erase_block_64k(&mut self, address: u32) -> Result<(), Error<SPI::Error>> {
+        self.erase_unit(
+            address,
+            BLOCK_64K_SIZE,
+            Command::BLOCK_64K_ERASE,
+            BLOCK_64K_ERASE_TIMEOUT,
+        )
+        .await
+    }
+
+    /// Erase the whole flash, which can take well over a minute on the larger
+    /// flashes.
+    pub async fn erase_chip(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.write_enable().await?;
+        self.spi
+            .write(&[Command::CHIP_ERASE])
+            .await
+            .map_err(Error::Spi)?;
+        self.wait_ready(CHIP_ERASE_TIMEOUT, ERASE_POLL_INTERVAL)
+            .await
+    }
+
+    /// Erase the sectors from an address up to another, using the largest
+    /// erase units that fit.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotAligned` if the addresses are not at the start of a
+    /// sector, and `Error::OutOfBounds` if the range does not fit in the
+    /// flash.
+    pub async fn erase(&mut self, from: u32, to: u32) -> Result<(), Error<SPI::Error>> {
+        if from > to {
+            return Err(Error::OutOfBounds);
+        }
+        self.check_bounds(from, (to - from) as usize)?;
+        if !(from as usize).is_multiple_of(SECTOR_SIZE)
+            || !(to as usize).is_multiple_of(SECTOR_SIZE)
+        {
+            return Err(Error::NotAligned);
+        }
+
+        if from == 0 && to as usize == self.capacity() {
+            return self.erase_chip().await;
+        }
+
+        let mut address = from as usize;
+        let end = to as usize;
+        while address < end {
+            let remaining = end - address;
+            if address.is_multiple_of(BLOCK_64K_SIZE) && remaining >= BLOCK_64K_SIZE {
+                self.erase_block_64k(address as u32).await?;
+                address += BLOCK_64K_SIZE;
+            } else if address.is_multiple_of(BLOCK_32K_SIZE) && remaining >= BLOCK_32K_SIZE {
+                self.erase_block_32k(address as u32).await?;
+                address += BLOCK_32K_SIZE;
+            } else {
+                self.erase_sector(address as u32).await?;
+                address += SECTOR_SIZE;
+            }
+        }
+        Ok(())
+    }
+
+    /// Erase a single sector or block and wait for the erase to complete.
+    async fn erase_unit(
+        &mut self,
+        address: u32,
+        size: usize,
+        command: u8,
+        timeout: Duration,
+    ) -> Result<(), Error<SPI::Error>> {
+        if !(address as usize).is_multiple_of(size) {
+            return Err(Error::NotAligned);
+        }
+        self.check_bounds(address, size)?;
+
+        self.write_enable().await?;
+        let [_, a2, a1, a0] = address.to_be_bytes();
+        self.spi
+            .write(&[command, a2, a1, a0])
+            .await
+            .map_err(Error::Spi)?;
+        self.wait_ready(timeout, ERASE_POLL_INTERVAL).await
+    }
+
+    /// Allow the next program or erase.
+    async fn write_enable(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.spi
+            .write(&[Command::WRITE_ENABLE])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Poll the status register until the flash is no longer busy.
+    async fn wait_ready(
+        &mut self,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<(), Error<SPI::Error>> {
+        let poll = async {
+            loop {
+                let mut status = [0u8];
+                self.spi
+                    .transaction(&mut [
+                        Operation::Write(&[Command::READ_STATUS_1]),
+                        Operation::Read(&mut status),
+                    ])
+                    .await
+                    .map_err(Error::Spi)?;
+                if status[0] & STATUS_BUSY == 0 {
+                    return Ok(());
+                }
+                Timer::after(interval).await;
+            }
+        };
+        with_timeout(timeout, poll)
+            .await
+            .unwrap_or(Err(Error::Timeout))
+    }
+
+    fn check_bounds(&self, address: u32, len: usize) -> Result<(), Error<SPI::Error>> {
+        match (address as usize).checked_add(len) {
+            Some(end) if end <= self.capacity() => Ok(()),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+}
+
+impl<SPI: SpiDevice> ErrorType for W25q<SPI> {
+    type Error = Error<SPI::Error>;
+}
+
+impl<SPI: SpiDevice> ReadNorFlash for W25q<SPI> {
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        W25q::read(self, offset, bytes).await
+    }
+
+    fn capacity(&self) -> usize {
+        W25q::capacity(self)
+    }
+}
+
+impl<SPI: SpiDevice> NorFlash for W25q<SPI> {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = SECTOR_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        W25q::erase(self, from, to).await
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.program(offset, bytes).await
+    }
+}
+
+impl<SPI: SpiDevice> MultiwriteNorFlash for W25q<SPI> {}
+
+/// All possible errors in this driver
+///
+/// `E` is the error type of the underlying SPI device.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// SPI bus error
+    Spi(E),
+    /// The capacity code of the JEDEC ID is not supported, or no flash
+    /// answered.
+    UnsupportedCapacity(u8),
+    /// The erase is not aligned on its unit.
+    NotAligned,
+    /// The access does not fit in the flash.
+    OutOfBounds,
+    /// A program or an erase did not complete in time.
+    Timeout,
+}
+
+impl<E: core::fmt::Debug> NorFlashError for Error<E> {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Error::NotAligned => NorFlashErrorKind::NotAligned,
+            Error::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
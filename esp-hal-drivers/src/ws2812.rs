@@ -0,0 +1,206 @@
+//! # ws2812
+//!
+//! ## Overview
+//!
+//! This driver sends colors to WS2812B (NeoPixel) addressable LEDs with a
+//! transmit channel of the RMT peripheral of the ESP32-C3.
+//!
+//! The RMT generates the pulses of the one-wire protocol of the LEDs in
+//! hardware, and the driver awaits the end of each transmission instead of
+//! busy-waiting. Colors are gamma-corrected and scaled by a global brightness
+//! before they are sent.
+//!
+//! Asynchronous transmissions must fit in the memory of the channel, so the
+//! strip is sent in chunks of a few LEDs: 3 LEDs with 2 memory blocks, the
+//! minimum, and 7 LEDs with 4 memory blocks. Between two chunks, the line
+//! stays low for the time taken to wake the task after a transmission, in
+//! the tens of µs on an idle executor. If it stays low for longer than the
+//! reset time of the LEDs, the LEDs latch early and the rest of the frame is
+//! lost or shown one frame late. Recent WS2812B (V5) only latch after 280 µs,
+//! but older ones latch after 50 µs, which other tasks and interrupts can
+//! easily exceed. With those, run the driver from a high-priority executor,
+//! such as an `InterruptExecutor`, or keep the strip within a single chunk.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let rmt = Rmt::new(peripherals.RMT, 80.MHz()).unwrap().into_async();
+//! let channel = rmt
+//!     .channel0
+//!     .configure(peripherals.GPIO8, ws2812::channel_config(4))
+//!     .unwrap();
+//! let mut strip = Ws2812::new(channel, 4)?.with_brightness(64);
+//!
+//! strip.write(&[Rgb8::new(255, 0, 0), Rgb8::new(0, 255, 0)]).await?;
+//!
+//! // Or keep the colors of the strip in a buffer
+//! let mut pixels = PixelBuffer::<16>::new();
+//! pixels.fill(Rgb8::new(0, 0, 255));
+//! pixels.show(&mut strip).await?;
+//! ```
+
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::rmt::{Error, TxChannelAsync, TxChannelConfig};
+
+use crate::led::{LedStrip, Rgb8};
+
+/// Words of RMT memory in a block of a channel
+const BLOCK_WORDS: usize = 48;
+
+/// Minimum number of memory blocks of the channel, to send more than a
+/// single LED per chunk
+const MIN_BLOCKS: usize = 2;
+
+/// Maximum number of memory blocks of a transmit channel
+const MAX_BLOCKS: usize = 4;
+
+/// RMT words sent per LED, one per bit
+const WORDS_PER_LED: usize = 24;
+
+/// Pulse lengths in ticks of 12.5 ns, with the RMT clocked at 80 MHz. See
+/// datasheet section "Data transfer time" for more details.
+const T0H: u16 = 32;
+const T0L: u16 = 68;
+const T1H: u16 = 64;
+const T1L: u16 = 36;
+
+/// Time the line must stay low for the LEDs to latch their colors. Recent
+/// WS2812B need more than 280 µs.
+const RESET_TIME: Duration = Duration::from_micros(300);
+
+/// Configuration of the transmit channel expected by the driver
+///
+/// # Arguments
+///
+/// - `memsize`: The number of memory blocks of the channel, from 2 to 4.
+///   Blocks are taken from the following channels.
+pub fn channel_config(memsize: u8) -> TxChannelConfig {
+    TxChannelConfig {
+        clk_divider: 1,
+        idle_output_level: false,
+        idle_output: true,
+        memsize,
+        ..TxChannelConfig::default()
+    }
+}
+
+/// A RMT word, made of two pulses. See the technical reference manual
+/// section 35.3.1 for more details.
+const fn pulse(level0: bool, length0: u16, level1: bool, length1: u16) -> u32 {
+    (length0 as u32 & 0x7FFF)
+        | ((level0 as u32) << 15)
+        | ((length1 as u32 & 0x7FFF) << 16)
+        | ((level1 as u32) << 31)
+}
+
+const ZERO: u32 = pulse(true, T0H, false, T0L);
+const ONE: u32 = pulse(true, T1H, false, T1L);
+
+/// A strip of WS2812B LEDs on a RMT channel
+pub struct Ws2812<TX> {
+    channel: TX,
+    /// Number of LEDs sent per transmission
+    chunk: usize,
+    brightness: u8,
+    gamma: bool,
+    latched_at: Instant,
+}
+
+impl<TX: TxChannelAsync> Ws2812<TX> {
+    /// Create a new strip.
+    ///
+    /// # Arguments
+    ///
+    /// - `channel`: The transmit channel, configured with [channel_config].
+    /// - `memsize`: The number of memory blocks the channel was configured
+    ///   with, from 2 to 4.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if `memsize` is not between 2 and 4.
+    pub fn new(channel: TX, memsize: u8) -> Result<Self, Error> {
+        let memsize = memsize as usize;
+        if !(MIN_BLOCKS..=MAX_BLOCKS).contains(&memsize) {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(Self {
+            channel,
+            // Keep a word for the end marker
+            chunk: (BLOCK_WORDS * memsize - 1) / WORDS_PER_LED,
+            brightness: 255,
+            gamma: true,
+            latched_at: Instant::now(),
+        })
+    }
+
+    /// Set the global brightness, where 255 is full brightness.
+    pub fn with_brightness(mut self, brightness: u8) -> Self {
+        self.brightness = brightness;
+        self
+    }
+
+    /// Enable or disable gamma correction, enabled by default.
+    pub fn with_gamma(mut self, gamma: bool) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// Release the underlying channel
+    pub fn release(self) -> TX {
+        self.channel
+    }
+
+    /// Send colors to the strip, starting from its first LED.
+    pub async fn write(&mut self, pixels: &[Rgb8]) -> Result<(), Error> {
+        // The previous colors must be latched before new ones are sent
+        Timer::at(self.latched_at).await;
+
+        let mut words = [0u32; BLOCK_WORDS * MAX_BLOCKS];
+        for chunk in pixels.chunks(self.chunk) {
+            let mut len = 0;
+            for pixel in chunk {
+                len += self.encode(*pixel, &mut words[len..]);
+            }
+            // End marker
+            words[len] = 0;
+            self.channel.transmit(&words[..=len]).await?;
+        }
+
+        self.latched_at = Instant::now() + RESET_TIME;
+        Ok(())
+    }
+
+    /// Encode a color in RMT words, returning the number of words written.
+    fn encode(&self, pixel: Rgb8, words: &mut [u32]) -> usize {
+        let pixel = if self.gamma {
+            pixel.gamma_corrected()
+        } else {
+            pixel
+        };
+        let pixel = pixel.scaled(self.brightness);
+
+        // The LEDs expect green first, most significant bit first
+        let bits = ((pixel.g as u32) << 16) | ((pixel.r as u32) << 8) | pixel.b as u32;
+        for (i, word) in words[..WORDS_PER_LED].iter_mut().enumerate() {
+            *word = if bits & (1 << (23 - i)) != 0 {
+                ONE
+            } else {
+                ZERO
+            };
+        }
+        WORDS_PER_LED
+    }
+}
+
+impl<TX: TxChannelAsync> LedStrip for Ws2812<TX> {
+    type Error = Error;
+
+    async fn write(&mut self, pixels: &[Rgb8]) -> Result<(), Self::Error> {
+        Ws2812::write(self, pixels).await
+    }
+}
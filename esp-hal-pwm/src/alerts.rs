@@ -0,0 +1,49 @@
+//! # Alerts
+//!
+//! ## Overview
+//!
+//! A library of predefined alert sounds for device user experience, so they
+//! don't need to be authored for each project.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! player.play_song(alerts::CONFIRMATION).await.unwrap();
+//! player
+//!     .play_song_looped(alerts::SIREN, Repeat::Times(5))
+//!     .await
+//!     .unwrap();
+//! ```
+
+use crate::{song, song::Song};
+
+/// A single short beep
+pub const SHORT_BEEP: &Song = song!(120, [A5: 16]);
+
+/// Two short beeps
+pub const DOUBLE_BEEP: &Song = song!(120, [A5: 16, Rest: 16, A5: 16]);
+
+/// Morse code SOS (`... --- ...`)
+pub const SOS: &Song = song!(
+    180,
+    [
+        A5: 8, Rest: 8, A5: 8, Rest: 8, A5: 8, Rest: 4,
+        A5: 4, Rest: 8, A5: 4, Rest: 8, A5: 4, Rest: 4,
+        A5: 8, Rest: 8, A5: 8, Rest: 8, A5: 8, Rest: 2
+    ]
+);
+
+/// A quick rising chirp, for a successful connection or a device waking up
+pub const RISING_CHIRP: &Song = song!(240, [C6: 32, E6: 32, G6: 32, C7: 32]);
+
+/// A quick falling chirp, for a disconnection or a device going to sleep
+pub const FALLING_CHIRP: &Song = song!(240, [C7: 32, G6: 32, E6: 32, C6: 32]);
+
+/// A two-tone siren, meant to be looped
+pub const SIREN: &Song = song!(60, [B5: 4, E5: 4]);
+
+/// A short confirmation melody
+pub const CONFIRMATION: &Song = song!(160, [C5: 16, E5: 16, G5: 16, C6: 8]);
+
+/// A short error melody
+pub const ERROR: &Song = song!(160, [E5: 8, Rest: 16, C5: 4]);
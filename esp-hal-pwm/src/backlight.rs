@@ -0,0 +1,131 @@
+//! # Backlight
+//!
+//! ## Overview
+//!
+//! A display backlight dimmer built on top of [Pwm].
+//!
+//! - The PWM frequency is enforced to be at least 1 kHz to avoid visible
+//!   flicker.
+//! - The brightness is mapped through the CIE 1931 lightness curve so that
+//!   brightness steps look uniform to the human eye.
+//! - With the `embassy` feature, brightness changes can be smoothly
+//!   transitioned asynchronously.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let pwm = Pwm::new(
+//!     &ledc,
+//!     timer::Number::Timer0,
+//!     channel::Number::Channel1,
+//!     io.pins.gpio6,
+//! );
+//! let mut backlight = Backlight::new(pwm, 5_000).unwrap();
+//!
+//! backlight.set_brightness(20).unwrap();
+//! backlight
+//!     .fade_to(80, Duration::from_millis(500))
+//!     .await
+//!     .unwrap();
+//! ```
+
+#[cfg(feature = "embassy")]
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::{gpio::OutputPin, peripheral::Peripheral};
+
+use crate::{Error, Pwm};
+
+/// Minimum PWM frequency in Hz that does not produce visible flicker
+pub const MIN_FREQUENCY_HZ: u32 = 1_000;
+
+/// Interval between two brightness updates during a transition in ms
+#[cfg(feature = "embassy")]
+const TRANSITION_STEP_MS: u64 = 10;
+
+/// Duty cycle percentage for each perceived brightness percentage.
+///
+/// Computed with the CIE 1931 lightness formula. Non-zero brightness levels
+/// always map to a non-zero duty cycle.
+const CIE_LIGHTNESS: [u8; 101] = [
+    0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 6, 6, 7,
+    7, 8, 8, 8, 9, 10, 10, 11, 11, 12, 12, 13, 14, 15, 15, 16, 17, 18, 18, 19, 20, 21, 22, 23, 24,
+    25, 26, 27, 28, 29, 30, 32, 33, 34, 35, 37, 38, 39, 41, 42, 44, 45, 47, 48, 50, 52, 53, 55, 57,
+    58, 60, 62, 64, 66, 68, 70, 72, 74, 76, 78, 81, 83, 85, 88, 90, 92, 95, 97, 100,
+];
+
+/// A flicker-free display backlight
+pub struct Backlight<'a, O: OutputPin> {
+    pwm: Pwm<'a, O>,
+    brightness: u8,
+}
+
+impl<'a, O: OutputPin + Peripheral<P = O>> Backlight<'a, O> {
+    /// Create a new backlight driven at `frequency` Hz.
+    ///
+    /// The backlight starts turned off.
+    ///
+    /// # Errors
+    /// Returns [Error::FrequencyTooLow] if `frequency` is below
+    /// [MIN_FREQUENCY_HZ].
+    pub fn new(mut pwm: Pwm<'a, O>, frequency: u32) -> Result<Self, Error> {
+        if frequency < MIN_FREQUENCY_HZ {
+            return Err(Error::FrequencyTooLow);
+        }
+
+        pwm.set_frequency_hz(frequency)?;
+        pwm.start(0)?;
+
+        Ok(Self { pwm, brightness: 0 })
+    }
+
+    /// Set the perceived brightness percentage (0-100).
+    pub fn set_brightness(&mut self, brightness: u8) -> Result<(), Error> {
+        if brightness > 100 {
+            return Err(Error::BrightnessOutOfRange);
+        }
+
+        self.pwm.start(CIE_LIGHTNESS[brightness as usize])?;
+        self.brightness = brightness;
+
+        Ok(())
+    }
+
+    /// Get the perceived brightness percentage (0-100).
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Smoothly transition from the current brightness to `brightness` over
+    /// `duration`.
+    #[cfg(feature = "embassy")]
+    pub async fn fade_to(&mut self, brightness: u8, duration: Duration) -> Result<(), Error> {
+        if brightness > 100 {
+            return Err(Error::BrightnessOutOfRange);
+        }
+
+        let from = self.brightness as i64;
+        let to = brightness as i64;
+        let total_ms = duration.as_millis() as i64;
+        let start = Instant::now();
+
+        loop {
+            let elapsed_ms = start.elapsed().as_millis() as i64;
+            if elapsed_ms >= total_ms {
+                break;
+            }
+
+            // Interpolate linearly in the perceived brightness space
+            let level = from + (to - from) * elapsed_ms / total_ms;
+            self.set_brightness(level as u8)?;
+
+            Timer::after(Duration::from_millis(TRANSITION_STEP_MS)).await;
+        }
+
+        self.set_brightness(brightness)
+    }
+
+    /// Release the underlying [Pwm].
+    pub fn release(self) -> Pwm<'a, O> {
+        self.pwm
+    }
+}
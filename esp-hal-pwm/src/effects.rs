@@ -0,0 +1,214 @@
+//! # Effects
+//!
+//! ## Overview
+//!
+//! Pitch and amplitude effects applied to notes played by [crate::player::Player]. Effects
+//! are implemented as small periodic frequency adjustments during the note.
+//!
+//! - [Vibrato] periodically modulates the pitch around the note.
+//! - [PitchBend] ramps the pitch from the note to an offset over the note
+//!   duration.
+//!
+//! Pitch offsets are expressed in cents, where 100 cents is a semitone.
+//!
+//! An [Envelope] shapes the amplitude of notes with attack, decay, sustain and
+//! release phases so notes don't start and stop with harsh clicks. The
+//! amplitude is mapped onto the duty cycle.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let effects = Effects::new()
+//!     .with_vibrato(Vibrato {
+//!         depth_cents: 30,
+//!         rate_hz: 6,
+//!     })
+//!     .with_pitch_bend(PitchBend { cents: -200 });
+//!
+//! player.play_note(Note::A4, 1_000, &effects).await.unwrap();
+//!
+//! player.set_envelope(Some(Envelope {
+//!     attack_ms: 20,
+//!     decay_ms: 50,
+//!     sustain_pct: 60,
+//!     release_ms: 30,
+//! }));
+//! ```
+
+/// Interval between two frequency adjustments in ms
+pub(crate) const EFFECT_STEP_MS: u32 = 10;
+
+/// ln(2) / 1200 in Q16 fixed point, scaled by 1000
+const LN2_OVER_1200_Q16_X1000: i64 = 37_854;
+
+/// A periodic pitch modulation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Vibrato {
+    /// Maximum pitch deviation from the note in cents
+    pub depth_cents: u16,
+
+    /// Number of modulation periods per second
+    pub rate_hz: u16,
+}
+
+/// A linear pitch ramp over the duration of a note
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PitchBend {
+    /// Pitch offset in cents reached at the end of the note
+    pub cents: i16,
+}
+
+/// Pitch effects applied to a note
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Effects {
+    /// Vibrato applied during the note
+    pub vibrato: Option<Vibrato>,
+
+    /// Pitch bend applied during the note
+    pub pitch_bend: Option<PitchBend>,
+}
+
+impl Effects {
+    /// Create an empty set of effects
+    pub const fn new() -> Self {
+        Self {
+            vibrato: None,
+            pitch_bend: None,
+        }
+    }
+
+    /// Add a vibrato
+    pub const fn with_vibrato(mut self, vibrato: Vibrato) -> Self {
+        self.vibrato = Some(vibrato);
+        self
+    }
+
+    /// Add a pitch bend
+    pub const fn with_pitch_bend(mut self, pitch_bend: PitchBend) -> Self {
+        self.pitch_bend = Some(pitch_bend);
+        self
+    }
+
+    /// Whether any effect modifies the pitch
+    pub fn is_active(&self) -> bool {
+        self.vibrato
+            .is_some_and(|vibrato| vibrato.depth_cents != 0 && vibrato.rate_hz != 0)
+            || self.pitch_bend.is_some_and(|bend| bend.cents != 0)
+    }
+
+    /// Get the frequency in Hz of a note at `frequency` Hz, `elapsed_ms` into a
+    /// note lasting `duration_ms`.
+    pub fn frequency_hz(&self, frequency: u32, elapsed_ms: u32, duration_ms: u32) -> u32 {
+        let mut cents: i32 = 0;
+
+        if let Some(bend) = self.pitch_bend {
+            if duration_ms != 0 {
+                cents += bend.cents as i32 * elapsed_ms as i32 / duration_ms as i32;
+            }
+        }
+
+        if let Some(vibrato) = self.vibrato {
+            if vibrato.rate_hz != 0 {
+                // Triangle wave between -1000 and 1000
+                let period_ms = (1_000 / vibrato.rate_hz as i32).max(1);
+                let phase = (elapsed_ms as i32) % period_ms;
+                let triangle = if phase < period_ms / 2 {
+                    -1_000 + 4_000 * phase / period_ms
+                } else {
+                    3_000 - 4_000 * phase / period_ms
+                };
+                cents += vibrato.depth_cents as i32 * triangle / 1_000;
+            }
+        }
+
+        shift_cents(frequency, cents)
+    }
+}
+
+/// An attack, decay, sustain and release (ADSR) amplitude envelope
+///
+/// The phases are shortened to fit notes shorter than the envelope, the
+/// release taking precedence over the attack, and the attack over the decay.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Envelope {
+    /// Duration of the rise from silence to the peak amplitude in ms
+    pub attack_ms: u16,
+
+    /// Duration of the fall from the peak to the sustain amplitude in ms
+    pub decay_ms: u16,
+
+    /// Amplitude held until the release as a percentage of the peak (0-100)
+    pub sustain_pct: u8,
+
+    /// Duration of the fall from the sustain amplitude to silence in ms
+    pub release_ms: u16,
+}
+
+impl Envelope {
+    /// Get the sustain duty cycle for a peak duty cycle
+    pub fn sustain_level(&self, peak: u8) -> u8 {
+        (peak as u32 * self.sustain_pct.min(100) as u32 / 100) as u8
+    }
+
+    /// Get the duration of the attack, decay and release phases of a note
+    /// sounding for `sound_ms`.
+    pub fn phases(&self, sound_ms: u32) -> (u32, u32, u32) {
+        let release = (self.release_ms as u32).min(sound_ms);
+        let attack = (self.attack_ms as u32).min(sound_ms - release);
+        let decay = (self.decay_ms as u32).min(sound_ms - release - attack);
+        (attack, decay, release)
+    }
+
+    /// Get the duty cycle `elapsed_ms` into a note sounding for `sound_ms`
+    /// whose peak duty cycle is `peak`.
+    pub fn level(&self, peak: u8, elapsed_ms: u32, sound_ms: u32) -> u8 {
+        let (attack, decay, release) = self.phases(sound_ms);
+        let sustain = self.sustain_level(peak) as u32;
+        let peak = peak as u32;
+
+        let level = if elapsed_ms < attack {
+            peak * elapsed_ms / attack
+        } else if elapsed_ms < attack + decay {
+            peak - (peak - sustain) * (elapsed_ms - attack) / decay
+        } else if elapsed_ms < sound_ms - release {
+            sustain
+        } else {
+            sustain * sound_ms.saturating_sub(elapsed_ms) / release.max(1)
+        };
+
+        level as u8
+    }
+}
+
+/// Shift a frequency by a number of cents.
+///
+/// Computes `frequency * 2^(cents / 1200)` in fixed point. Whole octaves are
+/// applied as bit shifts, and the remaining ±600 cents with the fourth order
+/// Taylor expansion of the exponential, which is accurate to 0.2 cent before
+/// the result is rounded down to a whole Hz.
+pub fn shift_cents(frequency: u32, cents: i32) -> u32 {
+    const ONE: i64 = 1 << 16;
+
+    // Split the shift into whole octaves and a remainder within ±600 cents
+    let octaves = (cents as i64 + 600).div_euclid(1200);
+    let cents = cents as i64 - octaves * 1200;
+
+    // x = cents * ln(2) / 1200 in Q16
+    let x = cents * LN2_OVER_1200_Q16_X1000 / 1_000;
+    let x2 = x * x / ONE;
+    let x3 = x2 * x / ONE;
+    let x4 = x3 * x / ONE;
+    let ratio = ONE + x + x2 / 2 + x3 / 6 + x4 / 24;
+
+    let frequency = (frequency as i64 * ratio / ONE) as i128;
+    let frequency = if octaves >= 0 {
+        frequency << octaves.min(64)
+    } else {
+        frequency >> (-octaves).min(64)
+    };
+    frequency.clamp(0, u32::MAX as i128) as u32
+}
@@ -0,0 +1,171 @@
+//! # Fan
+//!
+//! ## Overview
+//!
+//! A 4-pin PC fan driven with the standard 25 kHz PWM signal of the Intel
+//! fan specification, built on top of [Pwm].
+//!
+//! - The speed is set as a percentage of the duty cycle.
+//! - The speed is read in RPM from the tach line, which pulses twice per
+//!   revolution on most fans.
+//! - A stall is detected when the fan is driven but the tach line stays
+//!   still, or turns slower than a minimum speed.
+//!
+//! The ESP32-C3 has no pulse counter peripheral, so the tach pulses are timed
+//! with edge interrupts and embassy timestamps. The tach line is open
+//! collector and needs a pull-up.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let pwm = Pwm::new(
+//!     &ledc,
+//!     timer::Number::Timer0,
+//!     channel::Number::Channel0,
+//!     peripherals.GPIO6,
+//! );
+//! let tach = Input::new(peripherals.GPIO7, Pull::Up);
+//! let mut fan = Fan::new(pwm, tach)?.with_min_rpm(300);
+//!
+//! fan.set_speed_pct(60)?;
+//! println!("{} RPM", fan.rpm().await);
+//!
+//! fan.wait_for_stall().await;
+//! println!("Fan stalled");
+//! ```
+
+use embassy_time::{with_timeout, Duration, Instant};
+use esp_hal::{
+    gpio::{Input, OutputPin},
+    peripheral::Peripheral,
+};
+
+use crate::{Error, Pwm};
+
+/// Frequency of the PWM signal in Hz, set by the Intel fan specification
+pub const FREQUENCY_HZ: u32 = 25_000;
+
+/// Default number of tach pulses per revolution
+const DEFAULT_PULSES_PER_REVOLUTION: u32 = 2;
+
+/// Default time without tach pulse after which the fan is stopped
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A 4-pin PC fan
+pub struct Fan<'a, O: OutputPin> {
+    pwm: Pwm<'a, O>,
+    tach: Input<'a>,
+    speed_pct: u8,
+    pulses_per_revolution: u32,
+    min_rpm: u32,
+    stall_timeout: Duration,
+}
+
+impl<'a, O: OutputPin + Peripheral<P = O>> Fan<'a, O> {
+    /// Create a new fan.
+    ///
+    /// The fan starts at a duty cycle of 0%. Most fans keep turning slowly at
+    /// 0% and only stop if they support it.
+    pub fn new(mut pwm: Pwm<'a, O>, tach: Input<'a>) -> Result<Self, Error> {
+        pwm.set_frequency_hz(FREQUENCY_HZ)?;
+        pwm.start(0)?;
+
+        Ok(Self {
+            pwm,
+            tach,
+            speed_pct: 0,
+            pulses_per_revolution: DEFAULT_PULSES_PER_REVOLUTION,
+            min_rpm: 0,
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+        })
+    }
+
+    /// Set the number of tach pulses per revolution.
+    ///
+    /// Defaults to 2.
+    pub fn with_pulses_per_revolution(mut self, pulses_per_revolution: u32) -> Self {
+        self.pulses_per_revolution = pulses_per_revolution.max(1);
+        self
+    }
+
+    /// Set the speed in RPM below which a driven fan is stalled.
+    ///
+    /// Defaults to 0, which only detects fans that stopped.
+    pub fn with_min_rpm(mut self, min_rpm: u32) -> Self {
+        self.min_rpm = min_rpm;
+        self
+    }
+
+    /// Set the time without tach pulse after which the fan is stopped.
+    ///
+    /// Defaults to 500ms, which is enough for speeds down to 60 RPM.
+    pub fn with_stall_timeout(mut self, stall_timeout: Duration) -> Self {
+        self.stall_timeout = stall_timeout;
+        self
+    }
+
+    /// Set the speed percentage (0-100).
+    pub fn set_speed_pct(&mut self, speed_pct: u8) -> Result<(), Error> {
+        if speed_pct > 100 {
+            return Err(Error::SpeedOutOfRange);
+        }
+
+        self.pwm.start(speed_pct)?;
+        self.speed_pct = speed_pct;
+
+        Ok(())
+    }
+
+    /// Get the speed percentage (0-100).
+    pub fn speed_pct(&self) -> u8 {
+        self.speed_pct
+    }
+
+    /// Measure the speed of the fan in RPM over one revolution.
+    ///
+    /// Returns 0 if the tach line does not pulse before the stall timeout.
+    pub async fn rpm(&mut self) -> u32 {
+        // Synchronize on a pulse
+        if !self.pulse().await {
+            return 0;
+        }
+        let start = Instant::now();
+
+        for _ in 0..self.pulses_per_revolution {
+            if !self.pulse().await {
+                return 0;
+            }
+        }
+        let revolution_us = (Instant::now() - start).as_micros().max(1);
+
+        (60_000_000 / revolution_us) as u32
+    }
+
+    /// Wait for the fan to stall, turning slower than the minimum speed
+    /// while driven.
+    ///
+    /// Never returns while the speed percentage is 0.
+    pub async fn wait_for_stall(&mut self) {
+        if self.speed_pct == 0 {
+            core::future::pending::<()>().await;
+        }
+
+        loop {
+            if self.rpm().await <= self.min_rpm {
+                return;
+            }
+        }
+    }
+
+    /// Release the underlying [Pwm] and tach pin.
+    pub fn release(self) -> (Pwm<'a, O>, Input<'a>) {
+        (self.pwm, self.tach)
+    }
+
+    /// Wait for a tach pulse, returning false after the stall timeout.
+    async fn pulse(&mut self) -> bool {
+        with_timeout(self.stall_timeout, self.tach.wait_for_falling_edge())
+            .await
+            .is_ok()
+    }
+}
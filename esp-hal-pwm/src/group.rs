@@ -0,0 +1,201 @@
+//! # PWM group
+//!
+//! ## Overview
+//!
+//! A group of LEDC channels sharing the same timer whose duty cycles can be
+//! updated together.
+//!
+//! Duty cycle changes are latched by the LEDC at the end of the current PWM
+//! period. [PwmGroup::set_duties] writes the duty of every channel first and
+//! only then requests the update of all channels at once, so the new duty
+//! cycles take effect on the same PWM period. This avoids visible color
+//! tearing on RGB LEDs.
+//!
+//! A duty cycle of 100% drives the output high like [crate::Pwm::start], so
+//! it takes effect at once instead of at the end of the period.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let peripherals = esp_hal::init(esp_hal::Config::default());
+//! let mut ledc = Ledc::new(peripherals.LEDC);
+//! ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+//!
+//! let mut rgb = PwmGroup::new(&ledc, timer::Number::Timer0, 1_000)?
+//!     .with_channel(channel::Number::Channel0, peripherals.GPIO3)?
+//!     .with_channel(channel::Number::Channel1, peripherals.GPIO4)?
+//!     .with_channel(channel::Number::Channel2, peripherals.GPIO5)?;
+//!
+//! rgb.set_duties(&[
+//!     (channel::Number::Channel0, 100),
+//!     (channel::Number::Channel1, 50),
+//!     (channel::Number::Channel2, 0),
+//! ])?;
+//! ```
+
+use esp_hal::{
+    clock::Clocks,
+    gpio::{AnyPin, OutputPin},
+    ledc::{
+        channel::{self, Channel, ChannelIFace},
+        timer::{self, Timer, TimerIFace},
+        Ledc, LowSpeed,
+    },
+    peripheral::{Peripheral, PeripheralRef},
+    time::RateExtU32,
+};
+
+use crate::{full_on, latch_duty, max_duty_resolution, write_raw_duty, Error};
+
+/// Number of low speed channels of the LEDC
+const CHANNEL_COUNT: usize = 6;
+
+/// A group of PWM channels driven by the same Ledc timer
+pub struct PwmGroup<'a> {
+    timer: Timer<'a, LowSpeed>,
+    duty_bits: u32,
+    /// Output pins of the channels in the group, by channel number
+    pins: [Option<PeripheralRef<'a, AnyPin>>; CHANNEL_COUNT],
+    /// Channels whose output is driven high for a duty cycle of 100%
+    full_on: [bool; CHANNEL_COUNT],
+    derating: Option<&'a mut dyn FnMut(u8) -> u8>,
+}
+
+impl<'a> PwmGroup<'a> {
+    /// Create a new group whose channels will run at `frequency` Hz.
+    pub fn new(ledc: &'a Ledc, timer_number: timer::Number, frequency: u32) -> Result<Self, Error> {
+        if frequency == 0 {
+            return Err(Error::FrequencyNotConfigured);
+        }
+
+        let duty_bits = max_duty_resolution(Clocks::get().apb_clock.to_Hz(), frequency);
+
+        let mut timer = ledc.timer::<LowSpeed>(timer_number);
+        timer.configure(timer::config::Config {
+            duty: timer::config::Duty::try_from(duty_bits).unwrap(),
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: frequency.Hz(),
+        })?;
+
+        Ok(Self {
+            timer,
+            duty_bits,
+            pins: [const { None }; CHANNEL_COUNT],
+            full_on: [false; CHANNEL_COUNT],
+            derating: None,
+        })
+    }
+
+    /// Add a derating callback applied on every duty cycle write.
+    ///
+    /// See [crate::Pwm::with_derating].
+    pub fn with_derating(mut self, derating: &'a mut dyn FnMut(u8) -> u8) -> Self {
+        self.derating = Some(derating);
+        self
+    }
+
+    /// Remove the derating callback.
+    pub fn clear_derating(&mut self) {
+        self.derating = None;
+    }
+
+    /// Add a channel to the group.
+    ///
+    /// The channel is connected to `output_pin` and starts with a duty cycle of 0%.
+    pub fn with_channel<O: OutputPin + Peripheral<P = O> + Into<AnyPin>>(
+        mut self,
+        channel_number: channel::Number,
+        output_pin: impl Peripheral<P = O> + 'a,
+    ) -> Result<Self, Error> {
+        let mut output_pin = output_pin.into_ref().map_into::<AnyPin>();
+        connect(&self.timer, channel_number, output_pin.reborrow())?;
+
+        self.pins[channel_number as usize] = Some(output_pin);
+        Ok(self)
+    }
+
+    /// Update the duty cycle of multiple channels so that all changes take
+    /// effect on the same PWM period.
+    ///
+    /// # Arguments
+    /// - `duties` - Pairs of channel and duty cycle percentage (0-100).
+    pub fn set_duties(&mut self, duties: &[(channel::Number, u8)]) -> Result<(), Error> {
+        // Validate everything before touching the hardware
+        for (channel_number, duty_pct) in duties {
+            if self.pins[*channel_number as usize].is_none() {
+                return Err(Error::ChannelNotInGroup);
+            }
+            if *duty_pct > 100 {
+                return Err(Error::Channel(channel::Error::Duty));
+            }
+        }
+
+        let max_duty = 1u32 << self.duty_bits;
+        let mut full_on_now = [false; CHANNEL_COUNT];
+
+        // Write the new duty values. They are only applied once `para_up` is set.
+        for (channel_number, duty_pct) in duties {
+            let index = *channel_number as usize;
+            let duty_pct = match self.derating {
+                Some(ref mut derating) => derating(*duty_pct).min(100),
+                None => *duty_pct,
+            };
+
+            if duty_pct == 100 {
+                full_on_now[index] = true;
+                continue;
+            }
+            full_on_now[index] = false;
+
+            // An output driven high must be connected back to its channel
+            if self.full_on[index] {
+                if let Some(output_pin) = self.pins[index].as_mut() {
+                    connect(&self.timer, *channel_number, output_pin.reborrow())?;
+                }
+                self.full_on[index] = false;
+            }
+
+            write_raw_duty(*channel_number, max_duty * duty_pct as u32 / 100);
+        }
+
+        // Latch all channels as close together as possible so that they are
+        // updated at the same timer overflow
+        critical_section::with(|_| {
+            for (channel_number, _) in duties {
+                if !full_on_now[*channel_number as usize] {
+                    latch_duty(*channel_number);
+                }
+            }
+        });
+
+        for (index, output_pin) in self.pins.iter_mut().enumerate() {
+            if let (true, Some(output_pin)) = (full_on_now[index], output_pin.as_mut()) {
+                full_on(output_pin.reborrow());
+                self.full_on[index] = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the frequency of the group.
+    pub fn get_frequency_hz(&self) -> u32 {
+        self.timer.frequency()
+    }
+}
+
+/// Connect an output pin to its channel, starting with a duty cycle of 0%.
+fn connect<'d>(
+    timer: &'d Timer<'_, LowSpeed>,
+    channel_number: channel::Number,
+    output_pin: PeripheralRef<'d, AnyPin>,
+) -> Result<(), Error> {
+    let mut channel = Channel::new(channel_number, output_pin);
+    channel.configure(channel::config::Config {
+        timer,
+        duty_pct: 0,
+        pin_config: channel::config::PinConfig::PushPull,
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! # PWM input
+//!
+//! ## Overview
+//!
+//! Measures the frequency and duty cycle of an external PWM signal with a
+//! receive channel of the RMT peripheral.
+//!
+//! The RMT records the length of every level in hardware until the memory of
+//! the channel is full, so the measurement does not depend on the interrupt
+//! latency. Each memory block holds 48 words of a high and a low level, and
+//! the complete periods of the recording are averaged. A measurement
+//! therefore lasts about 48 periods per block: 2ms at 25 kHz, or 1s for the
+//! 50 Hz frames of a RC receiver.
+//!
+//! The range is set by the divider of the 80 MHz RMT clock, as every level
+//! must last longer than the glitch filter (100ns) and shorter than the
+//! longest level the RMT can record (32 767 ticks):
+//!
+//! - With a divider of 1, levels last up to 409µs, so signals above about
+//!   1.2 kHz are measured at 50% duty. A 25 kHz signal is measured with 3 200
+//!   ticks per period, for duty cycles between 0.25% and 99.75%.
+//! - With a divider of 80, levels last up to 32ms, which covers RC receiver
+//!   channels. The duty cycle of a 25 kHz signal is then only resolved to
+//!   2.5%.
+//!
+//! The driver expects the RMT to be clocked at 80 MHz.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let rmt = Rmt::new(peripherals.RMT, 80.MHz()).unwrap().into_async();
+//! let channel = rmt
+//!     .channel2
+//!     .configure(peripherals.GPIO7, input::rx_channel_config(1, 1))
+//!     .unwrap();
+//!
+//! let mut pwm_input = PwmInput::new(channel, 1, 1);
+//! let (frequency, duty) = pwm_input.measure().await?;
+//! ```
+
+use embassy_time::{with_timeout, Duration};
+use esp_hal::{
+    rmt::{self, RxChannelAsync, RxChannelConfig},
+    Async,
+};
+
+use crate::Error;
+
+/// Frequency of the RMT clock in Hz
+const RMT_CLK_HZ: u64 = 80_000_000;
+
+/// Start of the RMT memory. See the technical reference manual section 35.3.1
+/// for more details.
+const RMT_RAM_START: usize = 0x6001_6400;
+
+/// Words of RMT memory in a block of a channel
+const BLOCK_WORDS: usize = 48;
+
+/// Maximum number of memory blocks of a receive channel
+const MAX_BLOCKS: usize = 2;
+
+/// Longest level recorded, in ticks. A longer level ends the recording.
+const IDLE_THRESHOLD: u16 = 0x7FFF;
+
+/// Glitches filtered out, in cycles of the 80 MHz clock
+const FILTER_THRESHOLD: u8 = 8;
+
+/// Configuration of the receive channel expected by [PwmInput]
+///
+/// # Arguments
+///
+/// - `clk_divider`: The divider of the 80 MHz RMT clock, setting the range
+///   of the measurement.
+/// - `memsize`: The number of memory blocks of the channel, 1 or 2. Blocks
+///   are taken from the following channel.
+pub fn rx_channel_config(clk_divider: u8, memsize: u8) -> RxChannelConfig {
+    RxChannelConfig {
+        clk_divider,
+        idle_threshold: IDLE_THRESHOLD,
+        filter_threshold: FILTER_THRESHOLD,
+        memsize,
+        ..RxChannelConfig::default()
+    }
+}
+
+/// Measures an external PWM signal on a RMT receive channel
+pub struct PwmInput<const CH: u8> {
+    channel: rmt::Channel<Async, CH>,
+    /// Frequency of the ticks of the channel in Hz
+    tick_hz: u64,
+    /// Number of words recorded per measurement
+    words: usize,
+    timeout: Duration,
+}
+
+impl<const CH: u8> PwmInput<CH>
+where
+    rmt::Channel<Async, CH>: RxChannelAsync,
+{
+    /// Create a new PWM input.
+    ///
+    /// # Arguments
+    ///
+    /// - `channel`: The receive channel, configured with
+    ///   [rx_channel_config].
+    /// - `clk_divider`: The divider the channel was configured with.
+    /// - `memsize`: The number of memory blocks the channel was configured
+    ///   with.
+    pub fn new(channel: rmt::Channel<Async, CH>, clk_divider: u8, memsize: u8) -> Self {
+        Self {
+            channel,
+            tick_hz: RMT_CLK_HZ / clk_divider.max(1) as u64,
+            words: BLOCK_WORDS * (memsize as usize).clamp(1, MAX_BLOCKS),
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    /// Set the maximum time to wait for a recording.
+    ///
+    /// It must cover about 48 periods of the signal per memory block.
+    /// Defaults to 2s, enough for a 50 Hz signal on two blocks.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Release the underlying channel
+    pub fn release(self) -> rmt::Channel<Async, CH> {
+        self.channel
+    }
+
+    /// Measure the signal.
+    ///
+    /// Returns `(frequency_hz, duty_pct)`.
+    ///
+    /// # Errors
+    /// Returns [Error::NoSignal] if no complete period is recorded before the
+    /// timeout, for example when the signal is stuck high or low.
+    pub async fn measure(&mut self) -> Result<(u32, u8), Error> {
+        let mut words = [0u32; BLOCK_WORDS * MAX_BLOCKS];
+        let words = &mut words[..self.words];
+
+        match with_timeout(self.timeout, self.channel.receive(words)).await {
+            Err(_) => return Err(Error::NoSignal),
+            // A continuous signal never idles, so the recording ends when the
+            // memory is full, which the RMT reports as an error
+            Ok(Err(rmt::Error::ReceiverError)) => {}
+            Ok(result) => result?,
+        }
+
+        // esp-hal only copies the recording when the signal idled, so it is
+        // read back from the memory of the channel in both cases
+        let ram = (RMT_RAM_START + CH as usize * BLOCK_WORDS * 4) as *const u32;
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = unsafe { ram.add(i).read_volatile() };
+        }
+
+        let (high, total, cycles) = periods(words);
+        if cycles == 0 {
+            return Err(Error::NoSignal);
+        }
+
+        let frequency = (cycles as u64 * self.tick_hz / total) as u32;
+        let duty = (high * 100 / total).min(100) as u8;
+
+        Ok((frequency, duty))
+    }
+}
+
+/// Sum the complete periods of a recording, each starting on a rising edge.
+///
+/// Returns the ticks spent high, the total ticks and the number of periods.
+fn periods(words: &[u32]) -> (u64, u64, u32) {
+    // Each word holds two levels, and a zero length ends the recording. The
+    // first level started before the recording, so it is incomplete.
+    let mut levels = words
+        .iter()
+        .flat_map(|&word| [word as u16, (word >> 16) as u16])
+        .map(|level| (level & 0x8000 != 0, (level & 0x7FFF) as u64))
+        .take_while(|&(_, length)| length != 0)
+        .skip(1)
+        .skip_while(|&(high, _)| !high);
+
+    let mut high = 0;
+    let mut total = 0;
+    let mut cycles = 0;
+    while let (Some((true, high_ticks)), Some((false, low_ticks))) = (levels.next(), levels.next())
+    {
+        // The last low level is cut when the signal idles
+        if low_ticks >= IDLE_THRESHOLD as u64 {
+            break;
+        }
+        high += high_ticks;
+        total += high_ticks + low_ticks;
+        cycles += 1;
+    }
+
+    (high, total, cycles)
+}
@@ -5,6 +5,33 @@
 //! This driver provides an abstraction over LEDC to drive a PWM signal
 //! through a user-friendly API.
 //!
+//! The [group] module contains [group::PwmGroup] to update the duty cycle of
+//! multiple channels on the same PWM period.
+//!
+//! The [backlight] module contains [backlight::Backlight], a flicker-free
+//! display backlight dimmer.
+//!
+//! The [note] module contains [note::Note] to write songs symbolically.
+//!
+//! The [song] module contains [song::Song], a melody played at a given tempo.
+//! Songs are played asynchronously by the [player] module, which requires the
+//! `embassy` feature. The [mml] module parses songs written in the Music Macro
+//! Language. The [polyphony] module plays simultaneous notes on multiple
+//! channels. The [effects] module adds vibrato, pitch bends and amplitude
+//! envelopes to notes. The [service] module spawns a task playing sounds
+//! requested from anywhere in the application. The [alerts] module contains
+//! predefined alert sounds. The [pcm] module plays 8-bit PCM samples such as
+//! voice prompts. The [metronome] module ticks at a given tempo.
+//!
+//! The [input] module contains [input::PwmInput] to measure an external PWM
+//! signal with the RMT. It requires the `embassy` feature.
+//!
+//! The [fan] module contains [fan::Fan] to drive a 4-pin PC fan and read its
+//! speed. It requires the `embassy` feature.
+//!
+//! The [motor] module contains [motor::Motor] to drive a DC motor through an
+//! H-bridge. It requires the `embassy` feature.
+//!
 //! ## Example
 //!
 //! ```rust,ignore
@@ -20,11 +47,31 @@
 //!     channel::Number::Channel1,
 //!     io.pins.gpio6,
 //! );
-//! pwm.set_frequency_hz(60).ok();
+//! let frequency = pwm.set_frequency(25.kHz()).unwrap();
 //!
 //! pwm.start(50).ok();
 //! ```
 //!
+//! To keep the PWM running while the chip is in light sleep (e.g. a status
+//! LED), build it with [Pwm::low_power] before setting the frequency:
+//!
+//! ```rust,ignore
+//! let mut pwm = Pwm::new(
+//!     &ledc,
+//!     timer::Number::Timer0,
+//!     channel::Number::Channel1,
+//!     io.pins.gpio6,
+//! )
+//! .low_power();
+//! pwm.set_frequency_hz(1_000).ok();
+//! ```
+//!
+//! With the `embassy` feature, tones can be played asynchronously:
+//!
+//! ```rust,ignore
+//! pwm.play_tone(440, 500).await.unwrap();
+//! ```
+//!
 //! ## Features
 //!
 //! - `defmt`: Implement `defmt::Format` on certain types.
@@ -32,6 +79,30 @@
 //! - `esp32c3`: Target the ESP32-C3.
 
 #![no_std]
+pub mod alerts;
+pub mod backlight;
+pub mod effects;
+#[cfg(feature = "embassy")]
+pub mod fan;
+pub mod group;
+#[cfg(feature = "embassy")]
+pub mod input;
+#[cfg(feature = "embassy")]
+pub mod metronome;
+pub mod mml;
+#[cfg(feature = "embassy")]
+pub mod motor;
+pub mod note;
+#[cfg(feature = "embassy")]
+pub mod pcm;
+#[cfg(feature = "embassy")]
+pub mod player;
+#[cfg(feature = "embassy")]
+pub mod polyphony;
+#[cfg(feature = "embassy")]
+pub mod service;
+pub mod song;
+
 use core::{fmt::Debug, ops::DerefMut};
 
 use esp_hal::{
@@ -43,8 +114,13 @@ use esp_hal::{
         Ledc, LowSpeed,
     },
     peripheral::{Peripheral, PeripheralRef},
+    rmt,
     time::RateExtU32,
 };
+use fugit::{HertzU32, Rate};
+
+/// Nominal frequency of the RC_FAST clock in Hz
+const RC_FAST_CLK_HZ: u32 = 17_500_000;
 
 /// Errors from PWM
 #[derive(Debug)]
@@ -57,6 +133,45 @@ pub enum Error {
     Timer(timer::Error),
 
     FrequencyNotConfigured,
+
+    /// The channel is not part of the [group::PwmGroup]
+    ChannelNotInGroup,
+
+    /// No edge was detected on the input before the timeout
+    NoSignal,
+
+    /// The frequency is too low for the requested use
+    FrequencyTooLow,
+
+    /// The brightness is not between 0 and 100
+    BrightnessOutOfRange,
+
+    /// The articulation is not between 1 and 100
+    ArticulationOutOfRange,
+
+    /// The volume is not between 0 and 100
+    VolumeOutOfRange,
+
+    /// There are more simultaneous notes than voices
+    TooManyNotes,
+
+    /// A transposed note is outside of the range of [note::Note]
+    NoteOutOfRange,
+
+    /// The tempo multiplier is 0
+    TempoOutOfRange,
+
+    /// Too many sounds are already queued
+    QueueFull,
+
+    /// The speed is not between -100 and 100, or 0 and 100 for a fan
+    SpeedOutOfRange,
+
+    /// The sample rate of the PCM samples is 0
+    InvalidSampleRate,
+
+    /// Errors from [rmt::Error]
+    Rmt(rmt::Error),
 }
 
 /// Converts [channel::Error] into [self::Error]
@@ -73,11 +188,20 @@ impl From<timer::Error> for Error {
     }
 }
 
+/// Converts [rmt::Error] into [self::Error]
+impl From<rmt::Error> for Error {
+    fn from(error: rmt::Error) -> Self {
+        Error::Rmt(error)
+    }
+}
+
 /// A PWM instance driven by Ledc
 pub struct Pwm<'a, O: OutputPin> {
     timer: Timer<'a, LowSpeed>,
     channel_number: channel::Number,
     output_pin: PeripheralRef<'a, O>,
+    low_power: bool,
+    derating: Option<&'a mut dyn FnMut(u8) -> u8>,
 }
 
 impl<'a, O: OutputPin + Peripheral<P = O>> Pwm<'a, O> {
@@ -91,9 +215,82 @@ impl<'a, O: OutputPin + Peripheral<P = O>> Pwm<'a, O> {
             timer: ledc.timer::<LowSpeed>(timer_number),
             channel_number,
             output_pin: output_pin.into_ref(),
+            low_power: false,
+            derating: None,
+        }
+    }
+
+    /// Add a derating callback applied on every duty cycle write.
+    ///
+    /// The callback receives the requested duty cycle percentage and returns
+    /// the allowed one, which is clamped to 100. This allows, for instance, a
+    /// temperature monitoring task to throttle a heater or a motor globally.
+    ///
+    /// The callback applies to [Pwm::start], [Pwm::start_duty_fade] and the
+    /// samples of the PCM player. Braking a motor is deliberately exempt, as a
+    /// derated brake would let the motor coast instead of stopping it.
+    ///
+    /// # Examples
+    /// Limit the duty cycle to half of the requested one when overheating
+    /// ```rust,ignore
+    /// static OVERHEATING: AtomicBool = AtomicBool::new(false);
+    ///
+    /// let mut derating = |duty: u8| {
+    ///     if OVERHEATING.load(Ordering::Relaxed) { duty / 2 } else { duty }
+    /// };
+    /// let mut pwm = Pwm::new(&ledc, timer_number, channel_number, pin)
+    ///     .with_derating(&mut derating);
+    /// ```
+    pub fn with_derating(mut self, derating: &'a mut dyn FnMut(u8) -> u8) -> Self {
+        self.derating = Some(derating);
+        self
+    }
+
+    /// Remove the derating callback.
+    pub fn clear_derating(&mut self) {
+        self.derating = None;
+    }
+
+    /// Apply the derating callback to a duty cycle percentage.
+    fn derate(&mut self, duty_cycle: u8) -> u8 {
+        match self.derating {
+            Some(ref mut derating) => derating(duty_cycle).min(100),
+            None => duty_cycle,
         }
     }
 
+    /// Apply the derating callback to a raw duty value out of `max_duty`.
+    ///
+    /// The value keeps its full resolution unless the callback lowers its
+    /// percentage.
+    pub(crate) fn derate_raw(&mut self, duty: u32, max_duty: u32) -> u32 {
+        if self.derating.is_none() || max_duty == 0 {
+            return duty;
+        }
+
+        let duty_cycle = (duty * 100 / max_duty).min(100) as u8;
+        let allowed = self.derate(duty_cycle);
+        if allowed >= duty_cycle {
+            duty
+        } else {
+            max_duty * allowed as u32 / 100
+        }
+    }
+
+    /// Keep the PWM running while the chip is in light sleep.
+    ///
+    /// The LEDC clock is switched from the APB clock, which is gated in light
+    /// sleep, to the RC_FAST clock, which is kept powered during sleep. The
+    /// configuration is applied on the next call to [Pwm::set_frequency_hz].
+    ///
+    /// Note: The LEDC clock source is global, so every timer of the [Ledc]
+    /// instance will run from RC_FAST. The RC_FAST clock is less accurate than
+    /// the APB clock.
+    pub fn low_power(mut self) -> Self {
+        self.low_power = true;
+        self
+    }
+
     /// Start the PWM.
     ///
     /// # Arguments
@@ -103,11 +300,14 @@ impl<'a, O: OutputPin + Peripheral<P = O>> Pwm<'a, O> {
             return Err(Error::FrequencyNotConfigured);
         }
 
-        // BUG: There is a bug that prevents the duty cycle from being set to 100%.
-        // When setting it to 100%, the duty cycle is set to 0% instead.
-        // As a workaround, set the output pin to high.
+        // Make sure the duty cycle is within bounds
+        if duty_cycle > 100 {
+            return Err(Error::Channel(channel::Error::Duty));
+        }
+        let duty_cycle = self.derate(duty_cycle);
+
         if duty_cycle == 100 {
-            let _ = Output::new(self.output_pin.reborrow(), Level::High);
+            self.full_on();
             return Ok(());
         }
 
@@ -121,6 +321,14 @@ impl<'a, O: OutputPin + Peripheral<P = O>> Pwm<'a, O> {
         Ok(())
     }
 
+    /// Drive the output high at once, bypassing the derating callback.
+    ///
+    /// Only meant for outputs that must not be derated, such as the brake of
+    /// a motor.
+    pub(crate) fn full_on(&mut self) {
+        full_on(self.output_pin.reborrow());
+    }
+
     /// Start a duty cycle fade from `start` to `end` over `duration` milliseconds.
     ///
     /// # Arguments
@@ -136,6 +344,8 @@ impl<'a, O: OutputPin + Peripheral<P = O>> Pwm<'a, O> {
         if start > 100 || end > 100 {
             return Err(Error::Channel(channel::Error::Duty));
         }
+        let start = self.derate(start);
+        let end = self.derate(end);
 
         let mut channel = Channel::new(self.channel_number, self.output_pin.deref_mut());
         channel.configure(channel::config::Config {
@@ -167,35 +377,92 @@ impl<'a, O: OutputPin + Peripheral<P = O>> Pwm<'a, O> {
         Ok(())
     }
 
+    /// Play a tone through the PWM.
+    ///
+    /// Sets the frequency, starts the PWM at a 50% duty cycle, waits for
+    /// `duration_ms` and then silences the channel.
+    ///
+    /// # Arguments
+    /// - `frequency` - The frequency of the tone in Hz. Use 0 for a silent tone.
+    /// - `duration_ms` - The duration of the tone in milliseconds.
+    #[cfg(feature = "embassy")]
+    pub async fn play_tone(&mut self, frequency: u32, duration_ms: u32) -> Result<(), Error> {
+        if frequency != 0 {
+            self.set_frequency_hz(frequency)?;
+            self.start(50)?;
+        }
+
+        embassy_time::Timer::after(embassy_time::Duration::from_millis(duration_ms as u64)).await;
+
+        if self.timer.is_configured() {
+            self.stop()?;
+        }
+
+        Ok(())
+    }
+
     /// Set the frequency of the PWM.
     ///
+    /// Returns the frequency actually achieved by the timer, which can differ
+    /// from the requested one because of the clock divider rounding.
+    ///
     /// # Arguments
-    /// - `frequency` - The frequency in Hz.
-    pub fn set_frequency_hz(&mut self, frequency: u32) -> Result<(), Error> {
+    /// - `frequency` - The frequency as a [fugit::Rate] (e.g. `60.Hz()` or `25.kHz()`).
+    pub fn set_frequency<const NOM: u32, const DENOM: u32>(
+        &mut self,
+        frequency: Rate<u32, NOM, DENOM>,
+    ) -> Result<HertzU32, Error> {
+        let frequency = frequency.to_Hz();
+
         // If the frequency is 0, stop the PWM
         if frequency == 0 {
-            return self.stop();
+            self.stop()?;
+            return Ok(0.Hz());
         }
 
         // Max duty resolution for a frequency:
         // Integer(log2(LEDC_APB_CKL / frequency))
         // Source: https://github.com/esp-rs/esp-hal-community
-        let mut result = 0;
-        let mut value = (Clocks::get().apb_clock / frequency).raw();
+        let apb_clock = Clocks::get().apb_clock.to_Hz();
+        let source_clock = if self.low_power {
+            RC_FAST_CLK_HZ
+        } else {
+            apb_clock
+        };
 
-        // Limit duty resolution to 14 bits
-        while value > 1 && result < 14 {
-            value >>= 1;
-            result += 1;
-        }
+        let result = max_duty_resolution(source_clock, frequency);
+
+        // The timer divider is computed by esp-hal from the APB clock.
+        // When running from RC_FAST, scale the requested frequency so that the
+        // resulting divider matches the RC_FAST clock.
+        let timer_frequency = if self.low_power {
+            (frequency as u64 * apb_clock as u64 / RC_FAST_CLK_HZ as u64) as u32
+        } else {
+            frequency
+        };
 
         self.timer.configure(timer::config::Config {
             duty: timer::config::Duty::try_from(result).unwrap(),
             clock_source: timer::LSClockSource::APBClk,
-            frequency: frequency.Hz(),
+            frequency: timer_frequency.Hz(),
         })?;
 
-        Ok(())
+        if self.low_power {
+            enable_sleep_clock();
+        }
+
+        Ok(achieved_frequency(apb_clock, source_clock, timer_frequency, result).Hz())
+    }
+
+    /// Set the frequency of the PWM.
+    ///
+    /// Returns the frequency in Hz actually achieved by the timer.
+    /// See [Pwm::set_frequency].
+    ///
+    /// # Arguments
+    /// - `frequency` - The frequency in Hz.
+    pub fn set_frequency_hz(&mut self, frequency: u32) -> Result<u32, Error> {
+        Ok(self.set_frequency(frequency.Hz())?.raw())
     }
 
     /// Get the frequency of the PWM.
@@ -203,6 +470,101 @@ impl<'a, O: OutputPin + Peripheral<P = O>> Pwm<'a, O> {
         if !self.timer.is_configured() {
             return Err(Error::FrequencyNotConfigured);
         }
+
+        if self.low_power {
+            let apb_clock = Clocks::get().apb_clock.to_Hz();
+            return Ok(
+                (self.timer.frequency() as u64 * RC_FAST_CLK_HZ as u64 / apb_clock as u64) as u32,
+            );
+        }
+
         Ok(self.timer.frequency())
     }
 }
+
+/// Max duty resolution in bits for a frequency, limited to 14 bits.
+///
+/// Integer(log2(source_clock / frequency))
+pub(crate) fn max_duty_resolution(source_clock: u32, frequency: u32) -> u32 {
+    let mut result = 0;
+    let mut value = source_clock / frequency;
+
+    // Limit duty resolution to 14 bits
+    while value > 1 && result < 14 {
+        value >>= 1;
+        result += 1;
+    }
+
+    result
+}
+
+/// Frequency in Hz output by the timer after the divider rounding.
+///
+/// The LEDC divider is a fixed point number with 8 fractional bits computed
+/// from the APB clock: `divider = (apb_clock << 8) / (frequency << duty_bits)`.
+fn achieved_frequency(
+    apb_clock: u32,
+    source_clock: u32,
+    timer_frequency: u32,
+    duty_bits: u32,
+) -> u32 {
+    let precision = 1u64 << duty_bits;
+    let divider = ((apb_clock as u64) << 8) / timer_frequency as u64 / precision;
+    (((source_clock as u64) << 8) / (divider * precision)) as u32
+}
+
+/// Drive the output of a channel high at once, for a duty cycle of 100%.
+///
+/// The output is connected back to its channel when the channel is
+/// configured again.
+pub(crate) fn full_on<O: OutputPin>(output_pin: impl Peripheral<P = O>) {
+    // BUG: There is a bug that prevents the duty cycle from being set to 100%.
+    // When setting it to 100%, the duty cycle is set to 0% instead.
+    // As a workaround, set the output pin to high.
+    let _ = Output::new(output_pin, Level::High);
+}
+
+/// Write the raw duty value of a channel.
+///
+/// The value is only applied by the hardware after [latch_duty] is called.
+pub(crate) fn write_raw_duty(channel_number: channel::Number, duty: u32) {
+    let ch = esp_hal::peripherals::LEDC::regs().ch(channel_number as usize);
+
+    ch.hpoint().write(|w| unsafe { w.hpoint().bits(0) });
+    // The duty register has 4 fractional bits
+    ch.duty().write(|w| unsafe { w.duty().bits(duty << 4) });
+    ch.conf1().write(|w| unsafe {
+        w.duty_start().set_bit();
+        w.duty_inc().set_bit();
+        w.duty_num().bits(1);
+        w.duty_cycle().bits(1);
+        w.duty_scale().bits(0)
+    });
+}
+
+/// Apply the duty value written with [write_raw_duty] at the next overflow
+/// of the channel timer.
+pub(crate) fn latch_duty(channel_number: channel::Number) {
+    esp_hal::peripherals::LEDC::regs()
+        .ch(channel_number as usize)
+        .conf0()
+        .modify(|_, w| w.para_up().set_bit());
+}
+
+/// Switch the LEDC clock to RC_FAST and keep it running during light sleep.
+fn enable_sleep_clock() {
+    // Keep RC_FAST powered and ungated, including during light sleep
+    esp_hal::peripherals::RTC_CNTL::regs()
+        .clk_conf()
+        .modify(|_, w| {
+            w.enb_ck8m().clear_bit();
+            w.dig_clk8m_en().set_bit();
+            w.ck8m_force_pu().set_bit();
+            w.ck8m_force_nogating().set_bit()
+        });
+
+    // LEDC_APB_CLK_SEL: 1 = APB_CLK, 2 = RC_FAST_CLK, 3 = XTAL_CLK
+    esp_hal::peripherals::LEDC::regs()
+        .conf()
+        .modify(|_, w| unsafe { w.apb_clk_sel().bits(2) });
+}
@@ -0,0 +1,135 @@
+//! # Metronome
+//!
+//! ## Overview
+//!
+//! An asynchronous metronome emitting ticks at a given tempo through a [Pwm]
+//! driving a buzzer. The first beat of each bar is accented with a higher
+//! pitched click. An optional callback is called on every beat, for instance
+//! to blink an LED in sync.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut on_beat = |beat: Beat| {
+//!     if beat.accent {
+//!         led.toggle();
+//!     }
+//! };
+//! let mut metronome = Metronome::new(pwm, 90)
+//!     .with_beats_per_bar(3)
+//!     .with_callback(&mut on_beat);
+//!
+//! // Play 4 bars
+//! metronome.run_for(12).await.unwrap();
+//! ```
+
+use embassy_time::{Duration, Ticker};
+use esp_hal::{gpio::OutputPin, peripheral::Peripheral};
+
+use crate::{Error, Pwm};
+
+/// Frequency of the click of accented beats in Hz
+const ACCENT_FREQUENCY_HZ: u32 = 2_000;
+
+/// Frequency of the click of regular beats in Hz
+const BEAT_FREQUENCY_HZ: u32 = 1_000;
+
+/// Duration of a click in ms
+const CLICK_MS: u32 = 20;
+
+/// A beat emitted by the [Metronome]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Beat {
+    /// Number of beats since the metronome started
+    pub index: u32,
+
+    /// Whether the beat is the first of a bar
+    pub accent: bool,
+}
+
+/// An asynchronous metronome
+pub struct Metronome<'a, O: OutputPin> {
+    pwm: Pwm<'a, O>,
+    bpm: u32,
+    beats_per_bar: u32,
+    callback: Option<&'a mut dyn FnMut(Beat)>,
+}
+
+impl<'a, O: OutputPin + Peripheral<P = O>> Metronome<'a, O> {
+    /// Create a new metronome ticking at `bpm` beats per minute.
+    ///
+    /// Defaults to 4 beats per bar.
+    pub fn new(pwm: Pwm<'a, O>, bpm: u32) -> Self {
+        Self {
+            pwm,
+            bpm: bpm.max(1),
+            beats_per_bar: 4,
+            callback: None,
+        }
+    }
+
+    /// Set the number of beats per bar. The first beat of each bar is
+    /// accented. Use 0 to disable the accents.
+    pub fn with_beats_per_bar(mut self, beats_per_bar: u32) -> Self {
+        self.beats_per_bar = beats_per_bar;
+        self
+    }
+
+    /// Add a callback called on every beat.
+    pub fn with_callback(mut self, callback: &'a mut dyn FnMut(Beat)) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    /// Set the tempo in beats per minute.
+    ///
+    /// The new tempo is used on the next call to [Metronome::run] or
+    /// [Metronome::run_for].
+    pub fn set_bpm(&mut self, bpm: u32) {
+        self.bpm = bpm.max(1);
+    }
+
+    /// Run the metronome until the future is dropped or an error occurs.
+    pub async fn run(&mut self) -> Result<(), Error> {
+        self.ticks(None).await
+    }
+
+    /// Run the metronome for a number of beats.
+    pub async fn run_for(&mut self, beats: u32) -> Result<(), Error> {
+        self.ticks(Some(beats)).await
+    }
+
+    async fn ticks(&mut self, beats: Option<u32>) -> Result<(), Error> {
+        let mut ticker = Ticker::every(Duration::from_micros(60_000_000 / self.bpm as u64));
+        let mut index = 0;
+
+        while beats.is_none_or(|beats| index < beats) {
+            let beat = Beat {
+                index,
+                accent: self.beats_per_bar != 0 && index % self.beats_per_bar == 0,
+            };
+
+            if let Some(ref mut callback) = self.callback {
+                callback(beat);
+            }
+
+            let frequency = if beat.accent {
+                ACCENT_FREQUENCY_HZ
+            } else {
+                BEAT_FREQUENCY_HZ
+            };
+            self.pwm.play_tone(frequency, CLICK_MS).await?;
+
+            index += 1;
+            ticker.next().await;
+        }
+
+        Ok(())
+    }
+
+    /// Release the underlying [Pwm].
+    pub fn release(self) -> Pwm<'a, O> {
+        self.pwm
+    }
+}
@@ -0,0 +1,229 @@
+//! # MML
+//!
+//! ## Overview
+//!
+//! Parser for the Music Macro Language (MML). The parsed melody is stored in
+//! a user-provided buffer and returned as a [Song].
+//!
+//! The following commands are supported (case-insensitive, whitespace is
+//! ignored):
+//!
+//! - `c`, `d`, `e`, `f`, `g`, `a`, `b`: A note, optionally followed by `+` or
+//!   `#` (sharp) or `-` (flat), by a length (`1`, `2`, `4`, `8`, `16` or
+//!   `32`) and by a dot `.` for a dotted note.
+//! - `r`: A rest, optionally followed by a length and a dot.
+//! - `o<n>`: Set the octave (0-8). Defaults to 4.
+//! - `<` / `>`: Go one octave down / up.
+//! - `l<n>`: Set the default note length, optionally dotted. Defaults to 4.
+//! - `t<n>`: Set the tempo in BPM. Defaults to 120.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let mut buffer = [(Note::C0, NoteLength::Quarter); 32];
+//! let song = mml::parse("t140 o4 l8 cdefgab>c r4 c.", &mut buffer).unwrap();
+//! player.play_song(&song).await.unwrap();
+//! ```
+
+use crate::{
+    note::Note,
+    song::{NoteLength, Song},
+};
+
+/// Default tempo of MML songs in BPM
+const DEFAULT_BPM: u32 = 120;
+
+/// Default octave of MML songs
+const DEFAULT_OCTAVE: u8 = 4;
+
+/// Errors from the MML parser
+///
+/// Each error contains the byte position in the MML string where it occurred.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A character is not a supported MML command
+    UnexpectedCharacter(usize),
+
+    /// A length is not 1, 2, 4, 8, 16 or 32
+    InvalidLength(usize),
+
+    /// An octave is not between 0 and 8
+    InvalidOctave(usize),
+
+    /// A tempo is 0 or missing
+    InvalidTempo(usize),
+
+    /// A note is outside of the range of [Note]
+    NoteOutOfRange(usize),
+
+    /// The buffer is too small to hold every note of the song
+    BufferFull(usize),
+}
+
+/// Parse an MML string into a [Song] stored in `buffer`.
+///
+/// # Errors
+/// Returns an [Error] if the string is not valid MML or if the buffer is too
+/// small.
+pub fn parse<'a>(mml: &str, buffer: &'a mut [(Note, NoteLength)]) -> Result<Song<'a>, Error> {
+    let mut parser = Parser {
+        bytes: mml.as_bytes(),
+        position: 0,
+    };
+
+    let mut bpm = DEFAULT_BPM;
+    let mut octave = DEFAULT_OCTAVE;
+    let mut default_length = NoteLength::Quarter;
+    let mut count = 0;
+
+    while let Some(command) = parser.bump() {
+        let position = parser.position - 1;
+        match command.to_ascii_lowercase() {
+            b' ' | b'\t' | b'\n' | b'\r' => {}
+            b'o' => {
+                octave = match parser.number() {
+                    Some(value @ 0..=8) => value as u8,
+                    _ => return Err(Error::InvalidOctave(position)),
+                }
+            }
+            b'<' => {
+                octave = octave
+                    .checked_sub(1)
+                    .ok_or(Error::InvalidOctave(position))?
+            }
+            b'>' => {
+                octave += 1;
+                if octave > 8 {
+                    return Err(Error::InvalidOctave(position));
+                }
+            }
+            b'l' => {
+                default_length = parser
+                    .number()
+                    .and_then(length_from_number)
+                    .ok_or(Error::InvalidLength(position))?;
+                if parser.dot() {
+                    default_length = default_length.dotted();
+                }
+            }
+            b't' => {
+                bpm = match parser.number() {
+                    Some(value) if value > 0 => value,
+                    _ => return Err(Error::InvalidTempo(position)),
+                }
+            }
+            b'r' => {
+                let length = parser.length(default_length, position)?;
+
+                let slot = buffer.get_mut(count).ok_or(Error::BufferFull(position))?;
+                *slot = (Note::Rest, length);
+                count += 1;
+            }
+            note @ b'a'..=b'g' => {
+                let semitone: i16 = match note {
+                    b'c' => 0,
+                    b'd' => 2,
+                    b'e' => 4,
+                    b'f' => 5,
+                    b'g' => 7,
+                    b'a' => 9,
+                    _ => 11,
+                };
+                let accidental: i16 = match parser.peek() {
+                    Some(b'+') | Some(b'#') => {
+                        parser.position += 1;
+                        1
+                    }
+                    Some(b'-') => {
+                        parser.position += 1;
+                        -1
+                    }
+                    _ => 0,
+                };
+                let length = parser.length(default_length, position)?;
+
+                let midi = 12 * (octave as i16 + 1) + semitone + accidental;
+                let note = u8::try_from(midi)
+                    .ok()
+                    .and_then(Note::from_midi)
+                    .ok_or(Error::NoteOutOfRange(position))?;
+
+                let slot = buffer.get_mut(count).ok_or(Error::BufferFull(position))?;
+                *slot = (note, length);
+                count += 1;
+            }
+            _ => return Err(Error::UnexpectedCharacter(position)),
+        }
+    }
+
+    Ok(Song::new(&buffer[..count], bpm))
+}
+
+/// Convert an MML length number into a [NoteLength]
+fn length_from_number(value: u32) -> Option<NoteLength> {
+    match value {
+        1 => Some(NoteLength::Whole),
+        2 => Some(NoteLength::Half),
+        4 => Some(NoteLength::Quarter),
+        8 => Some(NoteLength::Eighth),
+        16 => Some(NoteLength::Sixteenth),
+        32 => Some(NoteLength::ThirtySecond),
+        _ => None,
+    }
+}
+
+/// A cursor over the bytes of an MML string
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    /// Parse the optional length and dot following a note or a rest
+    fn length(&mut self, default: NoteLength, position: usize) -> Result<NoteLength, Error> {
+        let length = match self.number() {
+            Some(value) => length_from_number(value).ok_or(Error::InvalidLength(position))?,
+            None => default,
+        };
+
+        if self.dot() {
+            return Ok(length.dotted());
+        }
+        Ok(length)
+    }
+
+    /// Consume a dot if one is at the current position
+    fn dot(&mut self) -> bool {
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    /// Parse a decimal number if one starts at the current position
+    fn number(&mut self) -> Option<u32> {
+        let mut value: Option<u32> = None;
+        while let Some(digit @ b'0'..=b'9') = self.peek() {
+            self.position += 1;
+            value = Some(
+                value
+                    .unwrap_or(0)
+                    .saturating_mul(10)
+                    .saturating_add((digit - b'0') as u32),
+            );
+        }
+        value
+    }
+}
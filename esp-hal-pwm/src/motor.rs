@@ -0,0 +1,196 @@
+//! # Motor
+//!
+//! ## Overview
+//!
+//! A brushed DC motor driven through an H-bridge, such as the DRV8833 or the
+//! L298, built on top of two [Pwm] channels, one on each input of the bridge.
+//!
+//! - The speed is a signed percentage, negative values turning the motor
+//!   backwards.
+//! - A stopped motor either brakes, shorting its terminals, or coasts,
+//!   leaving them floating.
+//! - Speed changes can be limited to a slew rate to reduce current spikes
+//!   and mechanical stress.
+//! - When reversing, the bridge coasts for a dead-time so that the motor is
+//!   not driven against its own back-EMF.
+//!
+//! Both channels can share the same Ledc timer.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let in1 = Pwm::new(
+//!     &ledc,
+//!     timer::Number::Timer0,
+//!     channel::Number::Channel0,
+//!     peripherals.GPIO4,
+//! );
+//! let in2 = Pwm::new(
+//!     &ledc,
+//!     timer::Number::Timer0,
+//!     channel::Number::Channel1,
+//!     peripherals.GPIO5,
+//! );
+//! let mut motor = Motor::new(in1, in2, 20_000)?
+//!     .with_slew_rate(200)
+//!     .with_dead_time(Duration::from_millis(50));
+//!
+//! motor.set_speed(80).await?;
+//! motor.set_speed(-80).await?;
+//! motor.brake()?;
+//! ```
+
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::{gpio::OutputPin, peripheral::Peripheral};
+
+use crate::{Error, Pwm};
+
+/// Interval between two speed updates during a ramp in ms
+const RAMP_STEP_MS: u64 = 10;
+
+/// Default time during which the bridge coasts when reversing
+const DEFAULT_DEAD_TIME: Duration = Duration::from_millis(10);
+
+/// A DC motor driven by an H-bridge
+pub struct Motor<'a, A: OutputPin, B: OutputPin> {
+    in1: Pwm<'a, A>,
+    in2: Pwm<'a, B>,
+    speed: i8,
+    slew_rate: u16,
+    dead_time: Duration,
+}
+
+impl<'a, A, B> Motor<'a, A, B>
+where
+    A: OutputPin + Peripheral<P = A>,
+    B: OutputPin + Peripheral<P = B>,
+{
+    /// Create a new motor driven at `frequency` Hz.
+    ///
+    /// The motor starts coasting. Frequencies above 20 kHz keep the motor
+    /// silent.
+    ///
+    /// # Arguments
+    /// - `in1` - The channel driving the motor forwards.
+    /// - `in2` - The channel driving the motor backwards.
+    /// - `frequency` - The PWM frequency in Hz.
+    pub fn new(mut in1: Pwm<'a, A>, mut in2: Pwm<'a, B>, frequency: u32) -> Result<Self, Error> {
+        in1.set_frequency_hz(frequency)?;
+        in2.set_frequency_hz(frequency)?;
+        in1.start(0)?;
+        in2.start(0)?;
+
+        Ok(Self {
+            in1,
+            in2,
+            speed: 0,
+            slew_rate: 0,
+            dead_time: DEFAULT_DEAD_TIME,
+        })
+    }
+
+    /// Limit the rate of speed changes in percent per second.
+    ///
+    /// Defaults to 0, which applies speed changes at once.
+    pub fn with_slew_rate(mut self, slew_rate: u16) -> Self {
+        self.slew_rate = slew_rate;
+        self
+    }
+
+    /// Set the time during which the bridge coasts when reversing.
+    ///
+    /// Defaults to 10ms.
+    pub fn with_dead_time(mut self, dead_time: Duration) -> Self {
+        self.dead_time = dead_time;
+        self
+    }
+
+    /// Get the speed percentage (-100 to 100).
+    pub fn speed(&self) -> i8 {
+        self.speed
+    }
+
+    /// Change the speed of the motor, following the slew rate.
+    ///
+    /// Returns once the speed is reached. When the direction changes, the
+    /// motor first slows down to 0 and coasts for the dead-time.
+    ///
+    /// # Arguments
+    /// - `speed` - The speed percentage (-100 to 100), negative backwards.
+    pub async fn set_speed(&mut self, speed: i8) -> Result<(), Error> {
+        if !(-100..=100).contains(&speed) {
+            return Err(Error::SpeedOutOfRange);
+        }
+
+        if self.speed != 0 && speed != 0 && self.speed.signum() != speed.signum() {
+            self.ramp_to(0).await?;
+            Timer::after(self.dead_time).await;
+        }
+
+        self.ramp_to(speed).await
+    }
+
+    /// Stop the motor at once by shorting its terminals.
+    ///
+    /// The slew rate and the derating callbacks of the PWMs are not applied,
+    /// so the motor is always fully braked.
+    pub fn brake(&mut self) -> Result<(), Error> {
+        self.in1.full_on();
+        self.in2.full_on();
+        self.speed = 0;
+
+        Ok(())
+    }
+
+    /// Let the motor spin down freely by leaving its terminals floating.
+    ///
+    /// The slew rate is not applied.
+    pub fn coast(&mut self) -> Result<(), Error> {
+        self.apply(0)
+    }
+
+    /// Release the underlying [Pwm] channels.
+    pub fn release(self) -> (Pwm<'a, A>, Pwm<'a, B>) {
+        (self.in1, self.in2)
+    }
+
+    /// Change the speed without changing direction, following the slew rate.
+    async fn ramp_to(&mut self, speed: i8) -> Result<(), Error> {
+        if self.slew_rate == 0 {
+            return self.apply(speed);
+        }
+
+        let from = self.speed as i64;
+        let to = speed as i64;
+        let start = Instant::now();
+
+        while self.speed != speed {
+            Timer::after(Duration::from_millis(RAMP_STEP_MS)).await;
+
+            let change = self.slew_rate as i64 * start.elapsed().as_millis() as i64 / 1000;
+            let level = if to > from {
+                (from + change).min(to)
+            } else {
+                (from - change).max(to)
+            };
+            self.apply(level as i8)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drive the bridge at a speed, coasting at 0.
+    fn apply(&mut self, speed: i8) -> Result<(), Error> {
+        // Turn off the other side first so the bridge never brakes
+        if speed >= 0 {
+            self.in2.start(0)?;
+            self.in1.start(speed as u8)?;
+        } else {
+            self.in1.start(0)?;
+            self.in2.start(speed.unsigned_abs())?;
+        }
+        self.speed = speed;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! # Notes
+//!
+//! ## Overview
+//!
+//! Musical notes from C0 to B8 in twelve-tone equal temperament with A4 tuned
+//! to 440 Hz, so that songs can be written symbolically instead of with raw
+//! frequencies.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! assert_eq!(Note::A4.frequency_hz(), 440);
+//! assert_eq!(Note::from_midi(69), Some(Note::A4));
+//! ```
+
+/// Frequencies of the notes of the 8th octave in mHz, from C8 to B8.
+///
+/// Lower octaves are obtained by halving the frequency for each octave.
+const OCTAVE_8_MILLIHZ: [u32; 12] = [
+    4186009, 4434922, 4698636, 4978032, 5274041, 5587652, 5919911, 6271927, 6644875, 7040000,
+    7458620, 7902133,
+];
+
+/// MIDI number of [Note::C0]
+const MIDI_C0: u8 = 12;
+
+/// MIDI number of [Note::B8]
+const MIDI_B8: u8 = 119;
+
+/// A musical note from C0 to B8, or a rest
+///
+/// The `s` suffix stands for sharp (e.g. [Note::Cs4] is C♯4). The
+/// discriminant of each note is its MIDI number.
+#[allow(missing_docs)]
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Note {
+    /// A silent note
+    Rest = 0,
+    C0 = 12,
+    Cs0 = 13,
+    D0 = 14,
+    Ds0 = 15,
+    E0 = 16,
+    F0 = 17,
+    Fs0 = 18,
+    G0 = 19,
+    Gs0 = 20,
+    A0 = 21,
+    As0 = 22,
+    B0 = 23,
+    C1 = 24,
+    Cs1 = 25,
+    D1 = 26,
+    Ds1 = 27,
+    E1 = 28,
+    F1 = 29,
+    Fs1 = 30,
+    G1 = 31,
+    Gs1 = 32,
+    A1 = 33,
+    As1 = 34,
+    B1 = 35,
+    C2 = 36,
+    Cs2 = 37,
+    D2 = 38,
+    Ds2 = 39,
+    E2 = 40,
+    F2 = 41,
+    Fs2 = 42,
+    G2 = 43,
+    Gs2 = 44,
+    A2 = 45,
+    As2 = 46,
+    B2 = 47,
+    C3 = 48,
+    Cs3 = 49,
+    D3 = 50,
+    Ds3 = 51,
+    E3 = 52,
+    F3 = 53,
+    Fs3 = 54,
+    G3 = 55,
+    Gs3 = 56,
+    A3 = 57,
+    As3 = 58,
+    B3 = 59,
+    C4 = 60,
+    Cs4 = 61,
+    D4 = 62,
+    Ds4 = 63,
+    E4 = 64,
+    F4 = 65,
+    Fs4 = 66,
+    G4 = 67,
+    Gs4 = 68,
+    A4 = 69,
+    As4 = 70,
+    B4 = 71,
+    C5 = 72,
+    Cs5 = 73,
+    D5 = 74,
+    Ds5 = 75,
+    E5 = 76,
+    F5 = 77,
+    Fs5 = 78,
+    G5 = 79,
+    Gs5 = 80,
+    A5 = 81,
+    As5 = 82,
+    B5 = 83,
+    C6 = 84,
+    Cs6 = 85,
+    D6 = 86,
+    Ds6 = 87,
+    E6 = 88,
+    F6 = 89,
+    Fs6 = 90,
+    G6 = 91,
+    Gs6 = 92,
+    A6 = 93,
+    As6 = 94,
+    B6 = 95,
+    C7 = 96,
+    Cs7 = 97,
+    D7 = 98,
+    Ds7 = 99,
+    E7 = 100,
+    F7 = 101,
+    Fs7 = 102,
+    G7 = 103,
+    Gs7 = 104,
+    A7 = 105,
+    As7 = 106,
+    B7 = 107,
+    C8 = 108,
+    Cs8 = 109,
+    D8 = 110,
+    Ds8 = 111,
+    E8 = 112,
+    F8 = 113,
+    Fs8 = 114,
+    G8 = 115,
+    Gs8 = 116,
+    A8 = 117,
+    As8 = 118,
+    B8 = 119,
+}
+
+impl Note {
+    /// Get the note for a MIDI number.
+    ///
+    /// Returns `None` if the MIDI number is outside of C0 (12) to B8 (119).
+    pub fn from_midi(midi: u8) -> Option<Self> {
+        if !(MIDI_C0..=MIDI_B8).contains(&midi) {
+            return None;
+        }
+
+        // Safety: The enum is `repr(u8)` with contiguous discriminants from
+        // MIDI_C0 to MIDI_B8, and `midi` was checked to be within that range.
+        Some(unsafe { core::mem::transmute::<u8, Note>(midi) })
+    }
+
+    /// Whether the note is a rest
+    pub fn is_rest(&self) -> bool {
+        *self == Note::Rest
+    }
+
+    /// Shift the note by a number of semitones.
+    ///
+    /// Returns `None` if the resulting note is outside of C0 to B8. A
+    /// [Note::Rest] is returned unchanged.
+    pub fn transposed(&self, semitones: i8) -> Option<Self> {
+        if self.is_rest() {
+            return Some(Note::Rest);
+        }
+
+        let midi = self.midi() as i16 + semitones as i16;
+        u8::try_from(midi).ok().and_then(Note::from_midi)
+    }
+
+    /// Get the MIDI number of the note.
+    ///
+    /// Returns 0 for [Note::Rest].
+    pub fn midi(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Get the octave of the note (0-8).
+    ///
+    /// Returns 0 for [Note::Rest].
+    pub fn octave(&self) -> u8 {
+        (self.midi() / 12).saturating_sub(1)
+    }
+
+    /// Get the frequency of the note rounded to the nearest Hz.
+    ///
+    /// Returns 0 for [Note::Rest].
+    pub fn frequency_hz(&self) -> u32 {
+        if self.is_rest() {
+            return 0;
+        }
+
+        let semitone = (self.midi() % 12) as usize;
+        let millihz = OCTAVE_8_MILLIHZ[semitone] >> (8 - self.octave());
+        (millihz + 500) / 1000
+    }
+}
@@ -0,0 +1,81 @@
+//! # PCM
+//!
+//! ## Overview
+//!
+//! Plays short 8-bit unsigned PCM samples, such as voice prompts stored in
+//! flash, through a [Pwm].
+//!
+//! The PWM runs at a carrier frequency well above the audible range with an
+//! 8-bit duty resolution, and its duty cycle is updated at the sample rate.
+//! The output should be filtered by an RC low-pass filter (or the inertia of
+//! the speaker) to recover the audio signal.
+//!
+//! The samples are paced by an embassy ticker, so sample rates above 8 kHz
+//! may suffer from timing jitter. The derating callback of the [Pwm], if any,
+//! is applied to every sample.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! static VOICE_PROMPT: &[u8] = include_bytes!("prompt.u8");
+//!
+//! let mut pcm = PcmPlayer::new(pwm).unwrap();
+//! pcm.play(VOICE_PROMPT, 8_000).await.unwrap();
+//! ```
+
+use embassy_time::{Duration, Ticker};
+use esp_hal::{gpio::OutputPin, peripheral::Peripheral};
+
+use crate::{latch_duty, write_raw_duty, Error, Pwm};
+
+/// Frequency of the PWM carrier in Hz.
+///
+/// The APB clock divided by 256, giving an 8-bit duty resolution.
+const CARRIER_HZ: u32 = 312_500;
+
+/// Duty value of a full duty cycle at the 8-bit resolution of the carrier
+const MAX_DUTY: u32 = 256;
+
+/// A PCM sample player
+pub struct PcmPlayer<'a, O: OutputPin> {
+    pwm: Pwm<'a, O>,
+}
+
+impl<'a, O: OutputPin + Peripheral<P = O>> PcmPlayer<'a, O> {
+    /// Create a new PCM player for the given PWM
+    pub fn new(mut pwm: Pwm<'a, O>) -> Result<Self, Error> {
+        pwm.set_frequency_hz(CARRIER_HZ)?;
+        Ok(Self { pwm })
+    }
+
+    /// Play 8-bit unsigned PCM samples at `sample_rate` Hz.
+    ///
+    /// The PWM is stopped once the last sample has been played.
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::InvalidSampleRate] if `sample_rate` is 0.
+    pub async fn play(&mut self, samples: &[u8], sample_rate: u32) -> Result<(), Error> {
+        if sample_rate == 0 {
+            return Err(Error::InvalidSampleRate);
+        }
+
+        self.pwm.start(50)?;
+        let channel_number = self.pwm.channel_number;
+
+        let mut ticker = Ticker::every(Duration::from_hz(sample_rate as u64));
+        for sample in samples {
+            let duty = self.pwm.derate_raw(*sample as u32, MAX_DUTY);
+            write_raw_duty(channel_number, duty);
+            latch_duty(channel_number);
+            ticker.next().await;
+        }
+
+        self.pwm.stop()
+    }
+
+    /// Release the underlying [Pwm].
+    pub fn release(self) -> Pwm<'a, O> {
+        self.pwm
+    }
+}
@@ -0,0 +1,663 @@
+//! # Player
+//!
+//! ## Overview
+//!
+//! Plays [Song]s asynchronously through a [Pwm] driving a passive buzzer.
+//!
+//! Songs played with [Player::play_song_with_handle] can be paused, resumed,
+//! stopped or skipped from another task through a [PlaybackHandle].
+//!
+//! The [Progress] of the songs can be followed from another task through an
+//! `embassy_sync` watch set with [Player::with_progress].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let pwm = Pwm::new(
+//!     &ledc,
+//!     timer::Number::Timer0,
+//!     channel::Number::Channel1,
+//!     io.pins.gpio6,
+//! );
+//! let mut player = Player::new(pwm);
+//!
+//! player.play_song(&MELODY).await.unwrap();
+//! // Play the same melody staccato
+//! player.set_articulation(50).unwrap();
+//! player.play_song(&MELODY).await.unwrap();
+//! // Play the same melody quieter
+//! player.set_volume(20).unwrap();
+//! player.play_song(&MELODY).await.unwrap();
+//! // Play the same melody three times
+//! player.play_song_looped(&MELODY, Repeat::Times(3)).await.unwrap();
+//! // Play the same melody faster
+//! player.play_song(&MELODY.with_bpm(180)).await.unwrap();
+//! // Play the same melody an octave higher and 50% faster
+//! player.set_tempo_pct(150).unwrap();
+//! player.play_song(&MELODY.transposed(12)).await.unwrap();
+//! ```
+
+use embassy_futures::{
+    select::{select, Either},
+    yield_now,
+};
+use embassy_sync::{
+    blocking_mutex::raw::{NoopRawMutex, RawMutex},
+    channel::Channel,
+    watch::Watch,
+};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::{gpio::OutputPin, peripheral::Peripheral};
+
+use crate::{
+    effects::{Effects, Envelope, EFFECT_STEP_MS},
+    note::Note,
+    song::Song,
+    Error, Pwm,
+};
+
+/// Number of playback commands that can be queued
+const PLAYBACK_QUEUE_SIZE: usize = 4;
+
+/// A command sent to a song being played
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PlaybackCommand {
+    /// Silence the song until it is resumed
+    Pause,
+    /// Resume a paused song
+    Resume,
+    /// Stop the song
+    Stop,
+    /// Skip to the next note
+    Skip,
+}
+
+/// A handle to control a song being played from another task
+///
+/// # Example
+///
+/// ```rust,ignore
+/// static PLAYBACK: PlaybackHandle<CriticalSectionRawMutex> = PlaybackHandle::new();
+///
+/// // In the player task
+/// player.play_song_with_handle(&MELODY, &PLAYBACK).await.unwrap();
+///
+/// // In another task
+/// PLAYBACK.pause().await;
+/// PLAYBACK.resume().await;
+/// PLAYBACK.stop().await;
+/// ```
+pub struct PlaybackHandle<M: RawMutex> {
+    commands: Channel<M, PlaybackCommand, PLAYBACK_QUEUE_SIZE>,
+}
+
+impl<M: RawMutex> PlaybackHandle<M> {
+    /// Create a new playback handle
+    pub const fn new() -> Self {
+        Self {
+            commands: Channel::new(),
+        }
+    }
+
+    /// Send a command to the song being played
+    pub async fn send(&self, command: PlaybackCommand) {
+        self.commands.send(command).await;
+    }
+
+    /// Pause the song being played
+    pub async fn pause(&self) {
+        self.send(PlaybackCommand::Pause).await;
+    }
+
+    /// Resume the song being played
+    pub async fn resume(&self) {
+        self.send(PlaybackCommand::Resume).await;
+    }
+
+    /// Stop the song being played
+    pub async fn stop(&self) {
+        self.send(PlaybackCommand::Stop).await;
+    }
+
+    /// Skip to the next note of the song being played
+    pub async fn skip(&self) {
+        self.send(PlaybackCommand::Skip).await;
+    }
+
+    /// Discard the pending commands and stop the song being played, without
+    /// waiting for room in the queue.
+    pub(crate) fn stop_now(&self) {
+        self.commands.clear();
+        self.commands.try_send(PlaybackCommand::Stop).ok();
+    }
+}
+
+impl<M: RawMutex> Default for PlaybackHandle<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Position of the player in the song being played
+///
+/// A progress is emitted when each note starts, and once more when the song
+/// ends with `note_index` equal to the number of notes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Progress {
+    /// Index of the note being played
+    pub note_index: usize,
+
+    /// Position in the song in ms, excluding pauses
+    pub elapsed_ms: u32,
+
+    /// Total duration of the song in ms
+    pub duration_ms: u32,
+}
+
+/// Destination of the [Progress] notifications
+trait ProgressSink {
+    fn notify(&self, progress: Progress);
+}
+
+impl<M: RawMutex, const N: usize> ProgressSink for Watch<M, Progress, N> {
+    fn notify(&self, progress: Progress) {
+        self.sender().send(progress);
+    }
+}
+
+/// Number of times a song is played
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Repeat {
+    /// Play the song until it is stopped
+    Forever,
+    /// Play the song the given number of times
+    Times(u32),
+}
+
+/// What to do after waiting during playback
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    Skip,
+    Stop,
+}
+
+/// Default percentage of each note duration during which the note sounds
+const DEFAULT_ARTICULATION: u8 = 90;
+
+/// Default volume percentage
+const DEFAULT_VOLUME: u8 = 100;
+
+/// Duty cycle percentage at the maximum volume.
+///
+/// A 50% duty cycle gives the loudest sound on a passive piezo buzzer.
+const MAX_VOLUME_DUTY: u32 = 50;
+
+/// An asynchronous song player
+pub struct Player<'a, O: OutputPin> {
+    pwm: Pwm<'a, O>,
+    articulation: u8,
+    volume: u8,
+    effects: Effects,
+    envelope: Option<Envelope>,
+    progress: Option<&'a dyn ProgressSink>,
+    tempo_pct: u16,
+}
+
+impl<'a, O: OutputPin + Peripheral<P = O>> Player<'a, O> {
+    /// Create a new player for the given PWM
+    pub fn new(pwm: Pwm<'a, O>) -> Self {
+        Self {
+            pwm,
+            articulation: DEFAULT_ARTICULATION,
+            volume: DEFAULT_VOLUME,
+            effects: Effects::new(),
+            envelope: None,
+            progress: None,
+            tempo_pct: 100,
+        }
+    }
+
+    /// Set the tempo multiplier as a percentage of the tempo of the songs.
+    ///
+    /// For instance, 200 plays songs twice as fast and 50 twice as slow.
+    /// Defaults to 100.
+    pub fn set_tempo_pct(&mut self, tempo_pct: u16) -> Result<(), Error> {
+        if tempo_pct == 0 {
+            return Err(Error::TempoOutOfRange);
+        }
+        self.tempo_pct = tempo_pct;
+        Ok(())
+    }
+
+    /// Emit the [Progress] of the songs played through `watch`, so that
+    /// another task can display the progress or synchronize with the music.
+    ///
+    /// # Examples
+    /// ```rust,ignore
+    /// static PROGRESS: Watch<CriticalSectionRawMutex, Progress, 1> = Watch::new();
+    ///
+    /// let mut player = Player::new(pwm).with_progress(&PROGRESS);
+    ///
+    /// // In another task
+    /// let mut receiver = PROGRESS.receiver().unwrap();
+    /// loop {
+    ///     let progress = receiver.changed().await;
+    /// }
+    /// ```
+    pub fn with_progress<M: RawMutex, const N: usize>(
+        mut self,
+        watch: &'a Watch<M, Progress, N>,
+    ) -> Self {
+        self.progress = Some(watch);
+        self
+    }
+
+    /// Set the amplitude envelope applied to every note played.
+    ///
+    /// The envelope is scaled to the volume of the player.
+    pub fn set_envelope(&mut self, envelope: Option<Envelope>) {
+        self.envelope = envelope;
+    }
+
+    /// Set the pitch effects applied to every note of the songs played.
+    pub fn set_effects(&mut self, effects: Effects) {
+        self.effects = effects;
+    }
+
+    /// Set the volume percentage (0-100).
+    ///
+    /// The volume is mapped onto the duty cycle, from 1% for the lowest volume
+    /// to 50% for the highest. A volume of 0 mutes the player.
+    /// Defaults to 100.
+    pub fn set_volume(&mut self, volume: u8) -> Result<(), Error> {
+        if volume > 100 {
+            return Err(Error::VolumeOutOfRange);
+        }
+        self.volume = volume;
+        Ok(())
+    }
+
+    /// Get the volume percentage (0-100).
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// Duty cycle percentage for the current volume
+    fn duty(&self) -> u8 {
+        match self.volume {
+            0 => 0,
+            volume => (1 + (volume as u32 - 1) * (MAX_VOLUME_DUTY - 1) / 99) as u8,
+        }
+    }
+
+    /// Set the percentage (1-100) of each note duration during which the note
+    /// sounds. The rest of the duration is silent.
+    ///
+    /// Lower values give a staccato articulation and 100 gives a legato one
+    /// where consecutive identical notes merge into a single tone.
+    /// Defaults to 90.
+    pub fn set_articulation(&mut self, articulation: u8) -> Result<(), Error> {
+        if !(1..=100).contains(&articulation) {
+            return Err(Error::ArticulationOutOfRange);
+        }
+        self.articulation = articulation;
+        Ok(())
+    }
+
+    /// Play a song.
+    ///
+    /// # Errors
+    /// This function returns an [Error] in case of an error.
+    /// An error can occur when a note cannot be output by the PWM.
+    pub async fn play_song(&mut self, song: &Song<'_>) -> Result<(), Error> {
+        self.play(song, None::<&PlaybackHandle<NoopRawMutex>>)
+            .await?;
+        Ok(())
+    }
+
+    /// Play a song that can be paused, resumed, stopped or skipped through
+    /// `handle`.
+    ///
+    /// Commands sent before the song starts are discarded.
+    ///
+    /// # Errors
+    /// This function returns an [Error] in case of an error.
+    /// An error can occur when a note cannot be output by the PWM.
+    pub async fn play_song_with_handle<M: RawMutex>(
+        &mut self,
+        song: &Song<'_>,
+        handle: &PlaybackHandle<M>,
+    ) -> Result<(), Error> {
+        handle.commands.clear();
+        self.play(song, Some(handle)).await?;
+        Ok(())
+    }
+
+    /// Play a song repeatedly.
+    ///
+    /// With [Repeat::Forever], this function only returns on error, unless the
+    /// future is dropped. A song without notes returns at once.
+    ///
+    /// # Errors
+    /// This function returns an [Error] in case of an error.
+    /// An error can occur when a note cannot be output by the PWM.
+    pub async fn play_song_looped(&mut self, song: &Song<'_>, repeat: Repeat) -> Result<(), Error> {
+        self.play_looped(song, repeat, None::<&PlaybackHandle<NoopRawMutex>>)
+            .await
+    }
+
+    /// Play a song repeatedly. The playback can be paused, resumed, stopped or
+    /// skipped through `handle`.
+    ///
+    /// Stopping the playback stops all the remaining repetitions.
+    ///
+    /// # Errors
+    /// This function returns an [Error] in case of an error.
+    /// An error can occur when a note cannot be output by the PWM.
+    pub async fn play_song_looped_with_handle<M: RawMutex>(
+        &mut self,
+        song: &Song<'_>,
+        repeat: Repeat,
+        handle: &PlaybackHandle<M>,
+    ) -> Result<(), Error> {
+        handle.commands.clear();
+        self.play_looped(song, repeat, Some(handle)).await
+    }
+
+    async fn play_looped<M: RawMutex>(
+        &mut self,
+        song: &Song<'_>,
+        repeat: Repeat,
+        handle: Option<&PlaybackHandle<M>>,
+    ) -> Result<(), Error> {
+        if song.notes.is_empty() {
+            return Ok(());
+        }
+
+        let mut played = 0;
+        loop {
+            if let Repeat::Times(times) = repeat {
+                if played >= times {
+                    return Ok(());
+                }
+            }
+
+            if self.play(song, handle).await? == Flow::Stop {
+                return Ok(());
+            }
+            played += 1;
+
+            // A song of zero-length notes never awaits, so let the other tasks
+            // run between two repetitions
+            yield_now().await;
+        }
+    }
+
+    async fn play<M: RawMutex>(
+        &mut self,
+        song: &Song<'_>,
+        handle: Option<&PlaybackHandle<M>>,
+    ) -> Result<Flow, Error> {
+        let effects = self.effects;
+        let bpm = (song.bpm as u64 * self.tempo_pct as u64 / 100).max(1) as u32;
+        let song_ms = self
+            .progress
+            .map_or(0, |_| song.with_bpm(bpm).duration_ms());
+        let mut elapsed_ms = 0;
+
+        for (note_index, (note, length)) in song.notes.iter().enumerate() {
+            let note = note
+                .transposed(song.transpose)
+                .ok_or(Error::NoteOutOfRange)?;
+            let duration_ms = length.duration_ms(bpm);
+            self.notify(note_index, elapsed_ms, song_ms);
+
+            let flow = self.note(note, duration_ms, &effects, handle).await?;
+            if flow == Flow::Stop {
+                return Ok(Flow::Stop);
+            }
+            elapsed_ms += duration_ms;
+        }
+
+        self.notify(song.notes.len(), song_ms, song_ms);
+        Ok(Flow::Continue)
+    }
+
+    /// Emit the progress if a watch is set
+    fn notify(&self, note_index: usize, elapsed_ms: u32, duration_ms: u32) {
+        if let Some(progress) = self.progress {
+            progress.notify(Progress {
+                note_index,
+                elapsed_ms,
+                duration_ms,
+            });
+        }
+    }
+
+    /// Play a single note with the given effects.
+    ///
+    /// The articulation and volume of the player are applied.
+    ///
+    /// # Errors
+    /// This function returns an [Error] in case of an error.
+    /// An error can occur when a note cannot be output by the PWM.
+    pub async fn play_note(
+        &mut self,
+        note: Note,
+        duration_ms: u32,
+        effects: &Effects,
+    ) -> Result<(), Error> {
+        self.note(
+            note,
+            duration_ms,
+            effects,
+            None::<&PlaybackHandle<NoopRawMutex>>,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn note<M: RawMutex>(
+        &mut self,
+        note: Note,
+        duration_ms: u32,
+        effects: &Effects,
+        handle: Option<&PlaybackHandle<M>>,
+    ) -> Result<Flow, Error> {
+        let sound_ms = if note.is_rest() {
+            0
+        } else {
+            duration_ms * self.articulation as u32 / 100
+        };
+
+        let frequency = note.frequency_hz();
+        let sounding = frequency != 0 && self.duty() != 0 && sound_ms != 0;
+        let flow = if !sounding {
+            self.wait(sound_ms, 0, false, handle).await
+        } else if effects.is_active() {
+            self.sound_with_effects(frequency, sound_ms, effects, handle)
+                .await
+        } else if let Some(envelope) = self.envelope {
+            self.sound_with_envelope(frequency, sound_ms, &envelope, handle)
+                .await
+        } else {
+            self.tone_on(frequency)?;
+            self.wait(sound_ms, frequency, true, handle).await
+        };
+        self.tone_off(sounding)?;
+
+        match flow? {
+            Flow::Continue => self.wait(duration_ms - sound_ms, 0, false, handle).await,
+            flow => Ok(flow),
+        }
+    }
+
+    /// Keep a tone sounding for `sound_ms` while periodically adjusting its
+    /// frequency for the effects, and its duty cycle for the envelope.
+    async fn sound_with_effects<M: RawMutex>(
+        &mut self,
+        frequency: u32,
+        sound_ms: u32,
+        effects: &Effects,
+        handle: Option<&PlaybackHandle<M>>,
+    ) -> Result<Flow, Error> {
+        let peak = self.duty();
+        let mut elapsed_ms = 0;
+        while elapsed_ms < sound_ms {
+            let step_ms = EFFECT_STEP_MS.min(sound_ms - elapsed_ms);
+            let current = effects.frequency_hz(frequency, elapsed_ms, sound_ms);
+            let duty = self
+                .envelope
+                .map_or(peak, |envelope| envelope.level(peak, elapsed_ms, sound_ms));
+            self.tone(current, duty)?;
+
+            let flow = self.wait(step_ms, current, true, handle).await?;
+            if flow != Flow::Continue {
+                return Ok(flow);
+            }
+            elapsed_ms += step_ms;
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Keep a tone sounding for `sound_ms` while shaping its amplitude with
+    /// hardware duty fades.
+    async fn sound_with_envelope<M: RawMutex>(
+        &mut self,
+        frequency: u32,
+        sound_ms: u32,
+        envelope: &Envelope,
+        handle: Option<&PlaybackHandle<M>>,
+    ) -> Result<Flow, Error> {
+        let peak = self.duty();
+        let sustain = envelope.sustain_level(peak);
+        let (attack_ms, decay_ms, release_ms) = envelope.phases(sound_ms);
+        let hold_ms = sound_ms - attack_ms - decay_ms - release_ms;
+
+        self.pwm.set_frequency_hz(frequency)?;
+
+        let phases = [
+            (0, peak, attack_ms),
+            (peak, sustain, decay_ms),
+            (sustain, sustain, hold_ms),
+            (sustain, 0, release_ms),
+        ];
+        for (from, to, phase_ms) in phases {
+            if phase_ms == 0 {
+                continue;
+            }
+
+            if from == to {
+                self.pwm.start(to)?;
+            } else {
+                self.pwm
+                    .start_duty_fade(from, to, phase_ms.min(u16::MAX as u32) as u16)?;
+            }
+
+            let flow = self.wait(phase_ms, frequency, true, handle).await?;
+            if flow != Flow::Continue {
+                return Ok(flow);
+            }
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Play a tone at the current volume, then silence it.
+    ///
+    /// # Arguments
+    /// - `frequency` - The frequency of the tone in Hz. Use 0 for a silent tone.
+    /// - `duration_ms` - The duration of the tone in milliseconds.
+    pub async fn play_tone(&mut self, frequency: u32, duration_ms: u32) -> Result<(), Error> {
+        let sounding = self.tone_on(frequency)?;
+        Timer::after(Duration::from_millis(duration_ms as u64)).await;
+        self.tone_off(sounding)
+    }
+
+    /// Start a tone at the current volume.
+    ///
+    /// Returns whether the tone is audible.
+    fn tone_on(&mut self, frequency: u32) -> Result<bool, Error> {
+        self.tone(frequency, self.duty())
+    }
+
+    /// Start a tone at the given duty cycle.
+    ///
+    /// Returns whether the tone is audible.
+    fn tone(&mut self, frequency: u32, duty: u8) -> Result<bool, Error> {
+        if frequency == 0 || duty == 0 {
+            return Ok(false);
+        }
+
+        self.pwm.set_frequency_hz(frequency)?;
+        self.pwm.start(duty)?;
+        Ok(true)
+    }
+
+    /// Stop a tone started with [Player::tone_on]
+    fn tone_off(&mut self, sounding: bool) -> Result<(), Error> {
+        if sounding {
+            self.pwm.stop()?;
+        }
+        Ok(())
+    }
+
+    /// Wait for `duration_ms` while handling the playback commands.
+    ///
+    /// The tone at `frequency` is silenced while paused and restarted when
+    /// resumed if it is `sounding`.
+    async fn wait<M: RawMutex>(
+        &mut self,
+        duration_ms: u32,
+        frequency: u32,
+        sounding: bool,
+        handle: Option<&PlaybackHandle<M>>,
+    ) -> Result<Flow, Error> {
+        let Some(handle) = handle else {
+            Timer::after(Duration::from_millis(duration_ms as u64)).await;
+            return Ok(Flow::Continue);
+        };
+
+        let mut remaining = Duration::from_millis(duration_ms as u64);
+        loop {
+            let start = Instant::now();
+            let command = match select(Timer::after(remaining), handle.commands.receive()).await {
+                Either::First(_) => return Ok(Flow::Continue),
+                Either::Second(command) => command,
+            };
+            remaining = remaining
+                .checked_sub(start.elapsed())
+                .unwrap_or(Duration::MIN);
+
+            match command {
+                PlaybackCommand::Pause => {
+                    self.tone_off(sounding)?;
+                    loop {
+                        match handle.commands.receive().await {
+                            PlaybackCommand::Resume => break,
+                            PlaybackCommand::Pause => {}
+                            PlaybackCommand::Skip => return Ok(Flow::Skip),
+                            PlaybackCommand::Stop => return Ok(Flow::Stop),
+                        }
+                    }
+                    if sounding {
+                        self.tone_on(frequency)?;
+                    }
+                }
+                PlaybackCommand::Resume => {}
+                PlaybackCommand::Skip => return Ok(Flow::Skip),
+                PlaybackCommand::Stop => return Ok(Flow::Stop),
+            }
+        }
+    }
+
+    /// Release the underlying [Pwm].
+    pub fn release(self) -> Pwm<'a, O> {
+        self.pwm
+    }
+}
@@ -0,0 +1,140 @@
+//! # Polyphony
+//!
+//! ## Overview
+//!
+//! Plays simultaneous notes by distributing them across multiple [Pwm]
+//! channels, each driving its own piezo buzzer. This allows simple two or
+//! three voice chords and melodies.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let voice_1 = Pwm::new(
+//!     &ledc,
+//!     timer::Number::Timer0,
+//!     channel::Number::Channel0,
+//!     peripherals.GPIO5.degrade(),
+//! );
+//! let voice_2 = Pwm::new(
+//!     &ledc,
+//!     timer::Number::Timer1,
+//!     channel::Number::Channel1,
+//!     peripherals.GPIO6.degrade(),
+//! );
+//! let mut polyphony = Polyphony::new([voice_1, voice_2]);
+//!
+//! // Play a chord
+//! polyphony.play_chord(&[Note::C4, Note::G4], 500).await.unwrap();
+//! // Play two voices at once
+//! polyphony.play_songs(&[MELODY, BASS]).await.unwrap();
+//! ```
+//!
+//! Note: Each voice must use its own LEDC timer since each voice has its own
+//! frequency.
+
+use embassy_futures::join::join_array;
+use embassy_time::{Duration, Timer};
+use esp_hal::gpio::AnyPin;
+
+use crate::{note::Note, song::Song, Error, Pwm};
+
+/// Percentage of each note duration during which the note sounds
+const ARTICULATION: u32 = 90;
+
+/// A polyphonic player made of `N` voices
+pub struct Polyphony<'a, const N: usize> {
+    voices: [Pwm<'a, AnyPin>; N],
+}
+
+impl<'a, const N: usize> Polyphony<'a, N> {
+    /// Create a new polyphonic player where each [Pwm] is a voice
+    pub fn new(voices: [Pwm<'a, AnyPin>; N]) -> Self {
+        Self { voices }
+    }
+
+    /// Play up to `N` notes simultaneously for `duration_ms`.
+    ///
+    /// The `i`th note is played by the `i`th voice. Voices without a note are
+    /// silent.
+    ///
+    /// # Errors
+    /// Returns [Error::TooManyNotes] if there are more notes than voices. If
+    /// a voice cannot be started, the voices already started are stopped
+    /// before its error is returned.
+    pub async fn play_chord(&mut self, notes: &[Note], duration_ms: u32) -> Result<(), Error> {
+        if notes.len() > N {
+            return Err(Error::TooManyNotes);
+        }
+
+        for (index, (voice, note)) in self.voices.iter_mut().zip(notes).enumerate() {
+            if note.is_rest() {
+                continue;
+            }
+
+            let started = voice
+                .set_frequency_hz(note.frequency_hz())
+                .and_then(|_| voice.start(50));
+            if let Err(error) = started {
+                // The failed voice may have been reconfigured, so stop it too
+                self.stop_voices(&notes[..=index]).ok();
+                return Err(error);
+            }
+        }
+
+        Timer::after(Duration::from_millis(duration_ms as u64)).await;
+
+        self.stop_voices(notes)
+    }
+
+    /// Play `N` songs simultaneously, one per voice.
+    ///
+    /// # Errors
+    /// This function returns the first [Error] that occurred on a voice.
+    pub async fn play_songs(&mut self, songs: &[Song<'_>; N]) -> Result<(), Error> {
+        let mut index = 0;
+        let voices = self.voices.each_mut().map(|voice| {
+            let song = &songs[index];
+            index += 1;
+            play_voice(voice, song)
+        });
+
+        join_array(voices).await.into_iter().collect()
+    }
+
+    /// Stop the voices playing `notes`, returning the first error.
+    fn stop_voices(&mut self, notes: &[Note]) -> Result<(), Error> {
+        let mut result = Ok(());
+        for (voice, note) in self.voices.iter_mut().zip(notes) {
+            if !note.is_rest() {
+                result = result.and(voice.stop());
+            }
+        }
+
+        result
+    }
+
+    /// Release the underlying [Pwm]s.
+    pub fn release(self) -> [Pwm<'a, AnyPin>; N] {
+        self.voices
+    }
+}
+
+/// Play a song on a single voice
+async fn play_voice(voice: &mut Pwm<'_, AnyPin>, song: &Song<'_>) -> Result<(), Error> {
+    for (note, length) in song.notes {
+        let note = note
+            .transposed(song.transpose)
+            .ok_or(Error::NoteOutOfRange)?;
+        let duration_ms = length.duration_ms(song.bpm);
+        let sound_ms = if note.is_rest() {
+            0
+        } else {
+            duration_ms * ARTICULATION / 100
+        };
+
+        voice.play_tone(note.frequency_hz(), sound_ms).await?;
+        Timer::after(Duration::from_millis((duration_ms - sound_ms) as u64)).await;
+    }
+
+    Ok(())
+}
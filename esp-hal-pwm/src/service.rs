@@ -0,0 +1,136 @@
+//! # Sound service
+//!
+//! ## Overview
+//!
+//! Spawns a dedicated embassy task owning a [Player], so that application
+//! code can trigger sounds from anywhere without holding the driver. Play
+//! requests are queued through a static [Sounds] instance.
+//!
+//! Requests are played in order. A request stops the song being played only
+//! through [Sounds::stop].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! static SOUNDS: Sounds = Sounds::new();
+//!
+//! let pwm = Pwm::new(
+//!     ledc,
+//!     timer::Number::Timer0,
+//!     channel::Number::Channel1,
+//!     peripherals.GPIO6.degrade(),
+//! );
+//! service::spawn(&spawner, Player::new(pwm), &SOUNDS).unwrap();
+//!
+//! // From anywhere
+//! SOUNDS.play_tone(2_000, 100).ok();
+//! SOUNDS.play_song(MELODY).ok();
+//! SOUNDS.stop();
+//! ```
+
+use embassy_executor::{SpawnError, Spawner};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use esp_hal::gpio::AnyPin;
+
+use crate::{
+    player::{PlaybackHandle, Player},
+    song::Song,
+    Error,
+};
+
+/// Number of play requests that can be queued
+const REQUEST_QUEUE_SIZE: usize = 4;
+
+/// A sound to play
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PlayRequest {
+    /// Play a tone at `frequency` Hz for `duration_ms` ms
+    Tone { frequency: u32, duration_ms: u32 },
+    /// Play a song
+    Song(Song<'static>),
+}
+
+/// Queue of sounds played by the task spawned with [spawn]
+pub struct Sounds {
+    requests: Channel<CriticalSectionRawMutex, PlayRequest, REQUEST_QUEUE_SIZE>,
+    playback: PlaybackHandle<CriticalSectionRawMutex>,
+}
+
+impl Sounds {
+    /// Create a new sound queue
+    pub const fn new() -> Self {
+        Self {
+            requests: Channel::new(),
+            playback: PlaybackHandle::new(),
+        }
+    }
+
+    /// Queue a request without waiting.
+    ///
+    /// # Errors
+    /// Returns [Error::QueueFull] if too many requests are already queued.
+    pub fn play(&self, request: PlayRequest) -> Result<(), Error> {
+        self.requests
+            .try_send(request)
+            .map_err(|_| Error::QueueFull)
+    }
+
+    /// Queue a tone without waiting. See [Sounds::play].
+    pub fn play_tone(&self, frequency: u32, duration_ms: u32) -> Result<(), Error> {
+        self.play(PlayRequest::Tone {
+            frequency,
+            duration_ms,
+        })
+    }
+
+    /// Queue a song without waiting. See [Sounds::play].
+    pub fn play_song(&self, song: Song<'static>) -> Result<(), Error> {
+        self.play(PlayRequest::Song(song))
+    }
+
+    /// Stop the song being played and discard the queued requests.
+    ///
+    /// The pending playback commands are discarded too, so this never waits.
+    pub fn stop(&self) {
+        self.requests.clear();
+        self.playback.stop_now();
+    }
+
+    /// Get the handle controlling the song being played
+    pub fn playback(&self) -> &PlaybackHandle<CriticalSectionRawMutex> {
+        &self.playback
+    }
+}
+
+impl Default for Sounds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Spawn the task playing the sounds queued in `sounds` through `player`.
+pub fn spawn(
+    spawner: &Spawner,
+    player: Player<'static, AnyPin>,
+    sounds: &'static Sounds,
+) -> Result<(), SpawnError> {
+    spawner.spawn(sound_task(player, sounds))
+}
+
+#[embassy_executor::task]
+async fn sound_task(mut player: Player<'static, AnyPin>, sounds: &'static Sounds) {
+    loop {
+        // Errors are not fatal for the task, the next request is played anyway
+        match sounds.requests.receive().await {
+            PlayRequest::Tone {
+                frequency,
+                duration_ms,
+            } => player.play_tone(frequency, duration_ms).await.ok(),
+            PlayRequest::Song(song) => player
+                .play_song_with_handle(&song, &sounds.playback)
+                .await
+                .ok(),
+        };
+    }
+}
@@ -0,0 +1,223 @@
+//! # Songs
+//!
+//! ## Overview
+//!
+//! A [Song] is a list of [Note]s with their [NoteLength], played at a given
+//! tempo in beats per minute (BPM). Songs can be played with
+//! [crate::player::Player].
+//!
+//! Note durations are computed from the tempo in integer math, so songs are
+//! written with musical lengths instead of durations in ms.
+//!
+//! Songs can be validated at compile time and stored in flash with the
+//! [crate::song!] macro.
+//!
+//! The same stored melody can be played at another tempo with
+//! [Song::with_bpm] or in another key with [Song::transposed].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! const MELODY: Song = Song::new(
+//!     &[
+//!         (Note::C4, NoteLength::Quarter),
+//!         (Note::E4, NoteLength::Quarter),
+//!         (Note::F4, NoteLength::DottedEighth),
+//!         (Note::G4, NoteLength::Half),
+//!     ],
+//!     120,
+//! );
+//! ```
+
+use crate::note::Note;
+
+/// Define a `&'static` [Song] stored in flash, validated at compile time.
+///
+/// Each note is written as `<note>: <length>`, where `<note>` is a variant of
+/// [Note] and `<length>` is the denominator of the note length (1, 2, 4, 8, 16
+/// or 32), optionally followed by `dot` for a dotted note. Invalid notes,
+/// lengths or tempos are compile time errors.
+///
+/// # Examples
+/// ```rust,ignore
+/// const MELODY: &Song = song!(120, [C4: 4, E4: 8 dot, G4: 16, Rest: 4, C5: 2]);
+/// ```
+#[macro_export]
+macro_rules! song {
+    (@dotted dot) => {
+        true
+    };
+    (@dotted) => {
+        false
+    };
+    ($bpm:expr, [$($note:ident : $length:literal $($dot:ident)?),* $(,)?]) => {{
+        const NOTES: &[($crate::note::Note, $crate::song::NoteLength)] = &[$((
+            $crate::note::Note::$note,
+            $crate::song::NoteLength::from_denominator($length, $crate::song!(@dotted $($dot)?)),
+        )),*];
+        static SONG: $crate::song::Song<'static> = {
+            assert!($bpm > 0, "the tempo must be greater than 0");
+            $crate::song::Song::new(NOTES, $bpm)
+        };
+        &SONG
+    }};
+}
+
+/// Number of ms in a whole note at 1 BPM, where a beat is a quarter note
+const WHOLE_NOTE_MS_AT_1_BPM: u32 = 4 * 60_000;
+
+/// Number of ticks in a whole note.
+///
+/// A tick is the shortest representable length, a dotted thirty-second note
+/// being 3 ticks long.
+const TICKS_PER_WHOLE_NOTE: u32 = 64;
+
+/// Length of a note relative to a beat, where a beat is a quarter note
+///
+/// A dotted note lasts one and a half times its base length.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NoteLength {
+    /// Four beats
+    Whole,
+    /// Six beats
+    DottedWhole,
+    /// Two beats
+    Half,
+    /// Three beats
+    DottedHalf,
+    /// One beat
+    Quarter,
+    /// One and a half beats
+    DottedQuarter,
+    /// Half a beat
+    Eighth,
+    /// Three quarters of a beat
+    DottedEighth,
+    /// A quarter of a beat
+    Sixteenth,
+    /// Three eighths of a beat
+    DottedSixteenth,
+    /// An eighth of a beat
+    ThirtySecond,
+    /// Three sixteenths of a beat
+    DottedThirtySecond,
+}
+
+impl NoteLength {
+    /// Get the dotted version of the note length.
+    ///
+    /// Dotted lengths are returned unchanged.
+    pub const fn dotted(self) -> Self {
+        match self {
+            NoteLength::Whole => NoteLength::DottedWhole,
+            NoteLength::Half => NoteLength::DottedHalf,
+            NoteLength::Quarter => NoteLength::DottedQuarter,
+            NoteLength::Eighth => NoteLength::DottedEighth,
+            NoteLength::Sixteenth => NoteLength::DottedSixteenth,
+            NoteLength::ThirtySecond => NoteLength::DottedThirtySecond,
+            dotted => dotted,
+        }
+    }
+
+    /// Get the note length from its denominator (1 for a whole note, 2 for a
+    /// half note, up to 32 for a thirty-second note).
+    ///
+    /// # Panics
+    /// Panics if the denominator is not 1, 2, 4, 8, 16 or 32. When used in a
+    /// const context, such as by the [crate::song!] macro, this is a compile
+    /// time error.
+    pub const fn from_denominator(denominator: u32, dotted: bool) -> Self {
+        let length = match denominator {
+            1 => NoteLength::Whole,
+            2 => NoteLength::Half,
+            4 => NoteLength::Quarter,
+            8 => NoteLength::Eighth,
+            16 => NoteLength::Sixteenth,
+            32 => NoteLength::ThirtySecond,
+            _ => panic!("note length must be 1, 2, 4, 8, 16 or 32"),
+        };
+
+        if dotted {
+            length.dotted()
+        } else {
+            length
+        }
+    }
+
+    /// Get the length of the note in ticks, where a whole note is
+    /// [TICKS_PER_WHOLE_NOTE] ticks.
+    const fn ticks(&self) -> u32 {
+        match self {
+            NoteLength::Whole => 64,
+            NoteLength::DottedWhole => 96,
+            NoteLength::Half => 32,
+            NoteLength::DottedHalf => 48,
+            NoteLength::Quarter => 16,
+            NoteLength::DottedQuarter => 24,
+            NoteLength::Eighth => 8,
+            NoteLength::DottedEighth => 12,
+            NoteLength::Sixteenth => 4,
+            NoteLength::DottedSixteenth => 6,
+            NoteLength::ThirtySecond => 2,
+            NoteLength::DottedThirtySecond => 3,
+        }
+    }
+
+    /// Get the duration of the note in ms at the given tempo.
+    ///
+    /// The duration is computed from the whole note duration so that the
+    /// rounding error does not depend on the note length.
+    pub const fn duration_ms(&self, bpm: u32) -> u32 {
+        let bpm = if bpm == 0 { 1 } else { bpm };
+        WHOLE_NOTE_MS_AT_1_BPM * self.ticks() / (TICKS_PER_WHOLE_NOTE * bpm)
+    }
+}
+
+/// A melody made of notes played at a given tempo
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Song<'a> {
+    /// Notes of the song with their length
+    pub notes: &'a [(Note, NoteLength)],
+
+    /// Tempo of the song in beats per minute
+    pub bpm: u32,
+
+    /// Number of semitones by which the notes are shifted when played
+    pub transpose: i8,
+}
+
+impl<'a> Song<'a> {
+    /// Create a new song
+    pub const fn new(notes: &'a [(Note, NoteLength)], bpm: u32) -> Self {
+        Self {
+            notes,
+            bpm,
+            transpose: 0,
+        }
+    }
+
+    /// Shift the notes of the song by a number of semitones when played.
+    ///
+    /// The transposition adds up with any previous one. The notes themselves
+    /// are left untouched so the same stored melody can be reused.
+    pub const fn transposed(mut self, semitones: i8) -> Self {
+        self.transpose = self.transpose.saturating_add(semitones);
+        self
+    }
+
+    /// Set the tempo of the song in beats per minute
+    pub const fn with_bpm(mut self, bpm: u32) -> Self {
+        self.bpm = bpm;
+        self
+    }
+
+    /// Get the total duration of the song in ms
+    pub fn duration_ms(&self) -> u32 {
+        self.notes
+            .iter()
+            .map(|(_, length)| length.duration_ms(self.bpm))
+            .sum()
+    }
+}